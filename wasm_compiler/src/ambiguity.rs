@@ -0,0 +1,137 @@
+//! Choosing what to build when a project is ambiguous: a `CMakeLists.txt` next to a
+//! `Makefile` (unless CMake generated it), several detected libraries, or several candidate
+//! main files. The command-line tool asks on the terminal; otherwise, and with
+//! `--non-interactive`, the default candidate is used with a warning, or the build fails
+//! with `--non-interactive fail` or `--strict`. Answers are remembered for the rest of the
+//! run, so matrix targets and the wasm2js fallback do not ask again.
+
+use std::collections::HashMap;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use crate::app_config::{AppConfig, NonInteractive};
+use crate::compiler::error::DetectionError;
+use crate::compiler::library_handlers::LibraryHandler;
+use crate::compiler::make_handler;
+use crate::compiler::BuildSystemHandler;
+
+/// Answers given on the terminal, by project and question
+static ANSWERS: OnceLock<Mutex<HashMap<(PathBuf, String), usize>>> = OnceLock::new();
+
+/// The build system to use for the project, if any detects it
+pub fn choose_build_system<'a>(
+    config: &'a AppConfig,
+    project_path: &Path,
+) -> Result<Option<&'a dyn BuildSystemHandler>, DetectionError> {
+    let mut handlers = config.handlers.detect_build_systems(project_path);
+    // The Makefile of an in-source CMake build (`# CMAKE generated file: DO NOT EDIT!`) is not a
+    // second build system
+    if handlers.len() > 1 && make_handler::is_cmake_generated(project_path) {
+        handlers.retain(|handler| handler.name() != "Make");
+    }
+    let names: Vec<String> = handlers.iter().map(|handler| handler.name().to_string()).collect();
+    Ok(choose(config, project_path, "build systems", &names)?.map(|index| handlers[index]))
+}
+
+/// The library handler to use for the project, if any detects it
+pub fn choose_library<'a>(
+    config: &'a AppConfig,
+    project_path: &Path,
+) -> Result<Option<&'a dyn LibraryHandler>, DetectionError> {
+    let handlers = config.handlers.detect_libraries_limited(project_path, &config.detection_limits());
+    let names: Vec<String> = handlers.iter().map(|handler| handler.library_name().to_string()).collect();
+    Ok(choose(config, project_path, "library handlers", &names)?.map(|index| handlers[index]))
+}
+
+/// The index of the candidate to use, the first being the default; `None` without candidates.
+pub fn choose(config: &AppConfig, project_path: &Path, what: &str, candidates: &[String]) -> Result<Option<usize>, DetectionError> {
+    if candidates.len() <= 1 {
+        return Ok((!candidates.is_empty()).then_some(0));
+    }
+
+    if !is_interactive(config) {
+        if config.non_interactive == Some(NonInteractive::Fail) || config.strict {
+            return Err(DetectionError::Ambiguous { what: what.to_string(), candidates: candidates.to_vec() });
+        }
+        tracing::warn!("Several {} found ({}); using {}", what, candidates.join(", "), candidates[0]);
+        return Ok(Some(0));
+    }
+
+    // Holding the lock keeps concurrent batch builds from asking at the same time
+    let mut answers = ANSWERS.get_or_init(Default::default).lock().expect("answers lock");
+    let key = (project_path.to_path_buf(), what.to_string());
+    if let Some(&index) = answers.get(&key) {
+        return Ok(Some(index));
+    }
+    let index = with_terminal(|| ask(project_path, what, candidates));
+    tracing::info!("Using {}", candidates[index]);
+    answers.insert(key, index);
+    Ok(Some(index))
+}
+
+/// Asks `question` on the terminal; `None` when the build cannot ask (see [`choose`])
+pub fn confirm(config: &AppConfig, question: &str) -> Option<bool> {
+    if !is_interactive(config) {
+        return None;
+    }
+    let _answers = ANSWERS.get_or_init(Default::default).lock().expect("answers lock");
+    Some(with_terminal(|| ask_yes_no(question)))
+}
+
+fn is_interactive(config: &AppConfig) -> bool {
+    config.interactive && config.non_interactive.is_none() && std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Asks until the answer is yes, no or empty (yes)
+fn ask_yes_no(question: &str) -> bool {
+    let mut stderr = std::io::stderr();
+    loop {
+        let _ = write!(stderr, "{} [Y/n]: ", question);
+        let _ = stderr.flush();
+        let mut answer = String::new();
+        // End of input counts as no
+        if std::io::stdin().lock().read_line(&mut answer).map_or(true, |read| read == 0) {
+            return false;
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "" | "y" | "yes" => return true,
+            "n" | "no" => return false,
+            _ => {}
+        }
+    }
+}
+
+/// Asks until the answer is a candidate's number or empty (the default)
+fn ask(project_path: &Path, what: &str, candidates: &[String]) -> usize {
+    let mut stderr = std::io::stderr();
+    let _ = writeln!(stderr, "Several {} found in {}:", what, project_path.display());
+    for (index, candidate) in candidates.iter().enumerate() {
+        let _ = writeln!(stderr, "  {}) {}{}", index + 1, candidate, if index == 0 { " (default)" } else { "" });
+    }
+    loop {
+        let _ = write!(stderr, "Which one should be used? [1-{}, Enter for 1]: ", candidates.len());
+        let _ = stderr.flush();
+        let mut answer = String::new();
+        // End of input counts as the default
+        if std::io::stdin().lock().read_line(&mut answer).map_or(true, |read| read == 0) {
+            return 0;
+        }
+        match answer.trim() {
+            "" => return 0,
+            answer => match answer.parse::<usize>() {
+                Ok(number) if (1..=candidates.len()).contains(&number) => return number - 1,
+                _ => {}
+            },
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+fn with_terminal<R>(f: impl FnOnce() -> R) -> R {
+    crate::progress::suspend(f)
+}
+
+#[cfg(not(feature = "cli"))]
+fn with_terminal<R>(f: impl FnOnce() -> R) -> R {
+    f()
+}
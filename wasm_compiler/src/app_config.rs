@@ -1,44 +1,1803 @@
-use clap::Parser;
-use std::path::PathBuf;
+#[cfg(feature = "cli")]
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::cancel::CancelHandle;
+use crate::compiler::library_handlers::DetectionLimits;
+use crate::compiler::registry::HandlerRegistry;
+use crate::diagnostics::MessageFormat;
+use crate::events::{EventSink, Events, Phase};
+use crate::project_config::{ProjectConfig, PROJECT_CONFIG_FILE_NAME};
+use crate::resource_limits;
+use crate::utils::output_name;
 
-#[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+/// The default values of the options, shared by the command-line parser and the `Default` of
+/// builds without the `cli` feature
+mod defaults {
+    use super::{CanvasSize, HtmlMode, MainLoopMode, ModuleFormat, WarningLevel, WebappMode};
+    use crate::diagnostics::MessageFormat;
+
+    pub const PROJECT_PATH: &str = ".";
+    pub const OUTPUT_DIR: &str = "dist";
+    pub const BUILD_CONFIG: &str = "Release";
+    pub const TARGET_ENV: &str = "web";
+    pub const DETECT_DEPTH: u32 = 3;
+    pub const DETECT_MAX_FILE_SIZE_KIB: u64 = 1024;
+    pub const MODULE_FORMAT: ModuleFormat = ModuleFormat::Esm;
+    pub const WARN: WarningLevel = WarningLevel::Default;
+    pub const MESSAGE_FORMAT: MessageFormat = MessageFormat::Human;
+    pub const RETRIES: u32 = 2;
+    pub const WEBAPP: WebappMode = WebappMode::Auto;
+    pub const MAIN_LOOP: MainLoopMode = MainLoopMode::Auto;
+    pub const HTML: HtmlMode = HtmlMode::Full;
+    pub const CANVAS_SIZE: CanvasSize = CanvasSize { width: 1280, height: 720 };
+}
+
+/// Style of the generated `index.html` for GUI applications
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum HtmlMode {
+    /// Styled page with loading screen, controls and debug log
+    Full,
+    /// Bare canvas plus module import, intended for embedding in an existing site
+    Minimal,
+}
+
+/// Whether to generate the browser webapp around the build output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum WebappMode {
+    /// Generate it when the project looks like a GUI application
+    Auto,
+    /// Always generate it
+    Always,
+    /// Never generate it
+    Never,
+}
+
+/// Kind of application a project is (`--ui`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum UiKind {
+    /// Draws to a canvas: SDL, GLFW, OpenGL/WebGL, ImGui or a GUI toolkit
+    Graphical,
+    /// Uses stdin and stdout only, or is a library
+    Console,
+}
+
+/// Major version of SDL a build links (`--sdl-version`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum SdlVersion {
+    /// SDL2 (`-sUSE_SDL=2`)
+    #[cfg_attr(feature = "cli", value(name = "2"))]
+    Sdl2,
+    /// SDL3 (`-sUSE_SDL=3`)
+    #[cfg_attr(feature = "cli", value(name = "3"))]
+    Sdl3,
+}
+
+impl SdlVersion {
+    pub fn major(self) -> u32 {
+        match self {
+            SdlVersion::Sdl2 => 2,
+            SdlVersion::Sdl3 => 3,
+        }
+    }
+
+    /// The emcc flag linking Emscripten's SDL port of this version
+    pub fn use_flag(self) -> String {
+        format!("-sUSE_SDL={}", self.major())
+    }
+}
+
+/// Which compiler warnings to enable (`--warn`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum WarningLevel {
+    /// The compiler's default warnings
+    Default,
+    /// `-Wall`
+    All,
+    /// `-Wall -Wextra`
+    Extra,
+    /// No warnings (`-w`)
+    None,
+}
+
+impl WarningLevel {
+    /// The compile flags enabling the warnings
+    pub fn flags(self) -> &'static [&'static str] {
+        match self {
+            WarningLevel::Default => &[],
+            WarningLevel::All => &["-Wall"],
+            WarningLevel::Extra => &["-Wall", "-Wextra"],
+            WarningLevel::None => &["-w"],
+        }
+    }
+}
+
+/// The shell Makefile builds run in on Windows (`--shell`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Shell {
+    /// Run emmake directly
+    Native,
+    /// Run emmake in the default WSL distribution, with an Emscripten SDK installed there
+    Wsl,
+    /// Run emmake in an MSYS2 login shell
+    Msys2,
+}
+
+/// What to do about a render loop that blocks the browser (`--main-loop`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum MainLoopMode {
+    /// Build with -sASYNCIFY when the loop waits with SDL_Delay or emscripten_sleep, warn otherwise
+    Auto,
+    /// Only warn
+    Warn,
+    /// Do not look for blocking loops
+    Off,
+}
+
+/// Module system of the generated JS glue
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum ModuleFormat {
+    /// ES module with a default-exported factory (`import createModule from './output.js'`)
+    Esm,
+    /// CommonJS module (`const createModule = require('./output.js')`)
+    Cjs,
+    /// Universal module: CommonJS, AMD, or a `createModule` global for plain `<script>` tags
+    Umd,
+}
+
+/// Oldest browsers a web build has to run in (`--browser-baseline`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum BrowserBaseline {
+    /// Chrome 120, Firefox 121, Safari 17.2: threads, SIMD and wasm exceptions
+    Modern,
+    /// Chrome 100, Firefox 100, Safari 15.4: threads and wasm exceptions, no SIMD
+    #[cfg_attr(feature = "cli", value(name = "2022"))]
+    Baseline2022,
+    /// Chrome 85, Firefox 79, Safari 14: no threads, SIMD or wasm exceptions
+    #[cfg_attr(feature = "cli", value(name = "2020"))]
+    Baseline2020,
+    /// Safari 15.2: threads and wasm exceptions, no SIMD
+    Safari15,
+    /// Safari 14: no threads, SIMD or wasm exceptions
+    Safari14,
+}
+
+/// Arrangement of the output directory for a static hosting provider
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum DeployLayout {
+    /// GitHub Pages: relative paths, `.nojekyll`, and a `404.html`
+    GhPages,
+}
+
+/// Runtime used to smoke-test the build output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum TestRunner {
+    /// Import the module with Node.js and run main()
+    Node,
+    /// Load the generated index.html in headless Chromium and wait for the runtime to start
+    Browser,
+}
+
+/// What to do without a terminal to ask on when a project is ambiguous (several build
+/// systems, library handlers or main files)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum NonInteractive {
+    /// Use the default candidate and warn about the others
+    Default,
+    /// Fail the build, listing the candidates
+    Fail,
+}
+
+/// Output of the `completions` subcommand
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum CompletionFormat {
+    Bash,
+    Zsh,
+    Fish,
+    #[cfg_attr(feature = "cli", clap(name = "powershell"))]
+    PowerShell,
+    /// Man page in roff format
+    Man,
+}
+
+/// WebAssembly runtime used by `--run` for standalone and WASI builds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum WasmRuntime {
+    Wasmtime,
+    Wasmer,
+}
+
+/// Subcommands; without one, the project is built
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Subcommand))]
+pub enum Command {
+    /// Publish an output directory to a git branch (e.g. gh-pages)
+    Deploy(DeployArgs),
+    /// Pre-build libraries into, or clear, the Emscripten cache
+    Cache(CacheArgs),
+    /// Check the files of an output directory against its SHA256SUMS and, with --public-key,
+    /// the signature of SHA256SUMS
+    Verify(VerifyArgs),
+    /// Print the imports, exports, memories, section sizes, features, producers and build
+    /// provenance of a .wasm file
+    Inspect(InspectArgs),
+    /// Print shell completions or the man page
+    Completions(CompletionsArgs),
+    /// Write .clangd and .vscode/c_cpp_properties.json pointing editors at the Emscripten
+    /// sysroot and the include directories and defines of the build
+    IdeSetup(IdeSetupArgs),
+    /// Print where the build tools (emcc, node, Python, CMake, make, Binaryen) are, their
+    /// versions and the environment variables Emscripten reads
+    Doctor(DoctorArgs),
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Args))]
+pub struct DoctorArgs {
+    /// Project whose config file's `[tools]`, `[env]` and `path_prefix` are used
+    #[cfg_attr(feature = "cli", clap(short, long, value_name = "PATH", default_value = defaults::PROJECT_PATH))]
+    pub project_path: PathBuf,
+
+    /// Print the report as JSON
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Args))]
+pub struct IdeSetupArgs {
+    /// The C++ project to configure the editors for
+    #[cfg_attr(feature = "cli", clap(short, long, value_name = "PATH", default_value = defaults::PROJECT_PATH))]
+    pub project_path: PathBuf,
+
+    /// Include directory the build passes with -I (repeatable)
+    #[cfg_attr(feature = "cli", clap(short = 'I', long = "include-dir", value_name = "DIR"))]
+    pub include_dirs: Vec<PathBuf>,
+
+    /// Define the build passes with -D (repeatable)
+    #[cfg_attr(feature = "cli", clap(short = 'D', long = "define", value_name = "NAME[=VALUE]"))]
+    pub defines: Vec<String>,
+
+    /// emcc flag of the build, e.g. `-std=c++20` or `-sUSE_SDL=2` (repeatable)
+    #[cfg_attr(feature = "cli", clap(long = "emcc-flag", value_name = "FLAG", allow_hyphen_values = true))]
+    pub emcc_flags: Vec<String>,
+
+    /// Replace existing .clangd and c_cpp_properties.json files
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub force: bool,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Args))]
+pub struct CompletionsArgs {
+    /// What to generate
+    #[cfg_attr(feature = "cli", clap(value_enum, value_name = "FORMAT"))]
+    pub format: CompletionFormat,
+
+    /// Write the file(s) to this directory instead of printing them; the man page then comes
+    /// with one page per subcommand
+    #[cfg_attr(feature = "cli", clap(short, long, value_name = "DIR"))]
+    pub output_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Args))]
+pub struct InspectArgs {
+    /// The .wasm file to inspect
+    #[cfg_attr(feature = "cli", clap(value_parser, value_name = "FILE"))]
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Args))]
+pub struct VerifyArgs {
+    /// Output directory containing SHA256SUMS
+    #[cfg_attr(feature = "cli", clap(short, long, value_parser, default_value = defaults::OUTPUT_DIR))]
+    pub output_dir: PathBuf,
+
+    /// minisign public key file, or the base64 public key itself, to check SHA256SUMS.minisig with
+    #[cfg_attr(feature = "cli", clap(long, value_name = "KEY"))]
+    pub public_key: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Args))]
+pub struct CacheArgs {
+    #[cfg_attr(feature = "cli", clap(subcommand))]
+    pub action: CacheAction,
+
+    /// Emscripten cache directory (default: EM_CACHE, or the cache of the Emscripten installation)
+    #[cfg_attr(feature = "cli", clap(long, value_name = "DIR", global = true))]
+    pub em_cache: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Subcommand))]
+pub enum CacheAction {
+    /// Build system libraries and ports into the cache with embuilder (e.g. `libc sdl2`)
+    Warm {
+        #[cfg_attr(feature = "cli", clap(required = true, value_name = "LIBRARY"))]
+        libraries: Vec<String>,
+    },
+    /// Delete everything in the cache (emcc --clear-cache)
+    Clear,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Args))]
+pub struct DeployArgs {
+    /// Output directory to publish
+    #[cfg_attr(feature = "cli", clap(short, long, value_parser, default_value = defaults::OUTPUT_DIR))]
+    pub output_dir: PathBuf,
+
+    /// Branch to publish to (its history is replaced)
+    #[cfg_attr(feature = "cli", clap(long, default_value = "gh-pages"))]
+    pub branch: String,
+
+    /// Git remote to push to
+    #[cfg_attr(feature = "cli", clap(long, default_value = "origin"))]
+    pub remote: String,
+
+    /// Repository whose remote is used (default: current directory)
+    #[cfg_attr(feature = "cli", clap(long, value_parser))]
+    pub repo: Option<PathBuf>,
+
+    /// Commit message for the published commit
+    #[cfg_attr(feature = "cli", clap(short, long, default_value = "Deploy WebAssembly build"))]
+    pub message: String,
+}
+
+/// Canvas dimensions in pixels, parsed from `WIDTHxHEIGHT` (e.g. `1280x720`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CanvasSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Parses a `KEY=VALUE` environment variable assignment (`--env`)
+pub fn parse_env_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("Invalid environment variable '{}', expected KEY=VALUE", s)),
+    }
+}
+
+/// Time limit of the build tools, parsed from `SECONDS` (every tool) or `TOOL=SECONDS`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandTimeout {
+    /// The tool it applies to; `None` for every tool
+    pub tool: Option<String>,
+    pub limit: Duration,
+}
+
+/// Parses a `[TOOL=]SECONDS` time limit (`--command-timeout`)
+pub fn parse_command_timeout(s: &str) -> Result<CommandTimeout, String> {
+    let (tool, seconds) = match s.split_once('=') {
+        Some((tool, seconds)) if !tool.is_empty() => (Some(tool.to_string()), seconds),
+        Some(_) => return Err(format!("Invalid command timeout '{}', expected SECONDS or TOOL=SECONDS", s)),
+        None => (None, s),
+    };
+    match seconds.parse::<u64>() {
+        Ok(seconds) if seconds > 0 => Ok(CommandTimeout { tool, limit: Duration::from_secs(seconds) }),
+        _ => Err(format!("Invalid command timeout '{}', expected a number of seconds greater than 0", s)),
+    }
+}
+
+/// A host directory made available to a module run with `--run`, parsed from `HOST::GUEST`
+/// or `HOST` (mapped to the same path in the guest)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirMapping {
+    pub host: PathBuf,
+    pub guest: String,
+}
+
+impl FromStr for DirMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (host, guest) = s.split_once("::").unwrap_or((s, s));
+        if host.is_empty() || guest.is_empty() {
+            return Err(format!("Invalid directory mapping '{}', expected HOST::GUEST or HOST", s));
+        }
+        Ok(DirMapping { host: PathBuf::from(host), guest: guest.to_string() })
+    }
+}
+
+impl std::fmt::Display for CanvasSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl FromStr for CanvasSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s
+            .to_lowercase()
+            .split_once('x')
+            .map(|(w, h)| (w.trim().parse::<u32>(), h.trim().parse::<u32>()))
+            .ok_or_else(|| format!("Invalid canvas size '{}', expected WIDTHxHEIGHT (e.g. 1280x720)", s))?;
+        match (width, height) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok(CanvasSize { width, height }),
+            _ => Err(format!("Invalid canvas size '{}', expected positive WIDTHxHEIGHT (e.g. 1280x720)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+#[cfg_attr(feature = "cli", clap(author, version, about, long_about = None, args_conflicts_with_subcommands = true))]
 pub struct AppConfig {
-    /// Path to the C++ project directory
-    #[clap(short, long, value_parser)]
+    #[cfg_attr(feature = "cli", clap(subcommand))]
+    pub command: Option<Command>,
+
+    /// Path to the C++ project directory. With several paths, the projects are built in
+    /// parallel, each into <output-dir>/<project directory name>
+    #[cfg_attr(feature = "cli", clap(short = 'p', long = "project-path", value_name = "PATH", value_parser, num_args = 1.., default_value = defaults::PROJECT_PATH))]
+    pub project_paths: Vec<PathBuf>,
+
+    /// The project being built; set from `--project-path` by [`crate::run`]
+    #[cfg_attr(feature = "cli", clap(skip = PathBuf::from(".")))]
     pub project_path: PathBuf,
 
     /// Output directory for the WASM build
-    #[clap(short, long, value_parser, default_value = "dist")]
+    #[cfg_attr(feature = "cli", clap(short, long, value_parser, default_value = defaults::OUTPUT_DIR))]
     pub output_dir: PathBuf,
 
+    /// Write into a non-empty output directory that does not contain a previous build
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub force: bool,
+
+    /// Remove the files of the previous build from the output directory first (hidden
+    /// entries such as .git are kept)
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub clean_output: bool,
+
+    /// Directory for the intermediate build trees (build_wasm_cmake, build_wasm_objects, ...)
+    /// instead of the project directory, e.g. on scratch storage or for read-only checkouts
+    #[cfg_attr(feature = "cli", clap(long, value_name = "DIR"))]
+    pub build_dir: Option<PathBuf>,
+
+    /// Delete the intermediate build trees after a successful build; without --build-dir they
+    /// go to a temporary directory
+    #[cfg_attr(feature = "cli", clap(long, overrides_with = "keep_build_dir"))]
+    pub ephemeral: bool,
+
+    /// Keep the intermediate build trees (the default; overrides an earlier --ephemeral)
+    #[cfg_attr(feature = "cli", clap(long, overrides_with = "ephemeral"))]
+    pub keep_build_dir: bool,
+
     /// Build configuration (e.g., Debug, Release)
-    #[clap(short, long, value_parser, default_value = "Release")]
+    #[cfg_attr(feature = "cli", clap(short, long, value_parser, default_value = defaults::BUILD_CONFIG))]
     pub build_config: String,
 
     /// Target WASM environment (e.g., web, wasi)
-    #[clap(short, long, value_parser, default_value = "web")]
+    #[cfg_attr(feature = "cli", clap(short, long, value_parser, default_value = defaults::TARGET_ENV))]
     pub target_env: String,
 
     /// Enable support for ImGui (adds necessary WebGL/GLFW flags)
-    #[clap(long)]
+    #[cfg_attr(feature = "cli", clap(long))]
     pub with_imgui: bool,
 
-    /// Additional emcc flags (space-separated)
-    #[clap(long)]
+    /// ImGui checkout (the directory with imgui.h) the sources of ImGui projects are built
+    /// with, instead of looking for one around the project or fetching it
+    #[cfg_attr(feature = "cli", clap(long, value_name = "DIR"))]
+    pub imgui_path: Option<PathBuf>,
+
+    /// ImGui release fetched when an ImGui project comes without ImGui (default: the pinned one)
+    #[cfg_attr(feature = "cli", clap(long, value_name = "TAG"))]
+    pub imgui_version: Option<String>,
+
+    /// Fail instead of fetching ImGui when an ImGui project comes without it
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub no_imgui_fetch: bool,
+
+    /// Compile vendored libraries (ImGui) with the project every build instead of linking the
+    /// archive cached for the same emcc version, flags and sources
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub no_library_cache: bool,
+
+    /// Fetch the single-header libraries (stb, nlohmann/json, doctest, fmt) a direct build
+    /// includes but the project does not have, without asking
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub fetch_headers: bool,
+
+    /// Check out the git submodules that are not checked out before building, without asking
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub init_submodules: bool,
+
+    /// Clone the git repositories of CMake FetchContent declarations once into the cache and
+    /// configure with them, so configuring needs no network access
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub prefetch_content: bool,
+
+    /// Fail when the Emscripten version, the ports or the downloaded dependencies differ from
+    /// wasm_compiler.lock, instead of updating it
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub locked: bool,
+
+    /// Additional emcc flags, split with shell quoting rules (e.g. "-sEXPORTED_FUNCTIONS='[\"_main\", \"_foo\"]'")
+    #[cfg_attr(feature = "cli", clap(long))]
     pub emcc_flags: Option<String>,
 
+    /// A single additional emcc flag passed through without any splitting (repeatable).
+    // Once `resolve_emcc_flags` has run, this also holds the parsed `--emcc-flags`
+    #[cfg_attr(feature = "cli", clap(long = "emcc-flag", value_name = "FLAG", allow_hyphen_values = true))]
+    pub emcc_flag: Vec<String>,
+
+    /// Glob (relative to the project root) selecting the sources to compile, replacing the
+    /// automatic discovery for direct and ImGui builds, e.g. `src/**/*.cpp` (repeatable)
+    #[cfg_attr(feature = "cli", clap(long = "source", value_name = "GLOB"))]
+    pub sources: Vec<String>,
+
+    /// Without a build system, compile and link every C/C++ source found recursively (honoring
+    /// .gitignore) instead of a single top-level file. Used automatically when the project root
+    /// has no sources.
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub recursive: bool,
+
+    /// Glob (relative to the project root) of sources to leave out, e.g. `scratch_*.cpp` (repeatable)
+    #[cfg_attr(feature = "cli", clap(long = "exclude", value_name = "GLOB"))]
+    pub excludes: Vec<String>,
+
+    /// Directory levels searched for the sources that reveal a library (ImGui, Qt, ...);
+    /// 1 searches the project root only
+    #[cfg_attr(feature = "cli", clap(long, value_name = "LEVELS", default_value_t = defaults::DETECT_DEPTH, value_parser = clap::value_parser!(u32).range(1..)))]
+    pub detect_depth: u32,
+
+    /// Sources larger than this many KiB are not read for library detection
+    #[cfg_attr(feature = "cli", clap(long, value_name = "KIB", default_value_t = defaults::DETECT_MAX_FILE_SIZE_KIB))]
+    pub detect_max_file_size: u64,
+
+    /// Build a library module without main() (--no-entry). Enabled automatically when no
+    /// project source defines main()
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub no_entry: bool,
+
+    /// Function to export to JavaScript (without the leading underscore). Replaces the
+    /// automatic EMSCRIPTEN_KEEPALIVE / extern "C" scan of builds without CMake or Make, and
+    /// is the only way to add exports to CMake and Make projects (repeatable)
+    #[cfg_attr(feature = "cli", clap(long = "export", value_name = "NAME"))]
+    pub exports: Vec<String>,
+
+    /// Generate `<output>.api.js` with cwrap-based wrappers and `<output>.api.d.ts` TypeScript
+    /// declarations for the exported C functions, from their declarations in the project sources
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub typescript: bool,
+
+    /// Header declaring the C++ API bound by the project's .idl files; included before the
+    /// generated WebIDL glue (repeatable). Defaults to the header next to each .idl file
+    #[cfg_attr(feature = "cli", clap(long = "webidl-header", value_name = "PATH"))]
+    pub webidl_headers: Vec<PathBuf>,
+
+    /// Module system of the generated JS glue
+    #[cfg_attr(feature = "cli", clap(long, value_enum, default_value_t = defaults::MODULE_FORMAT))]
+    pub module_format: ModuleFormat,
+
+    /// Experimental: also wrap the module as a WebAssembly component (`<output>.component.wasm`)
+    /// using the WIT world from --wit. Builds with -sSTANDALONE_WASM=1 and needs `wasm-tools`
+    #[cfg_attr(feature = "cli", clap(long, requires = "wit", conflicts_with = "single_file"))]
+    pub component: bool,
+
+    /// WIT file or directory describing the component's world (for --component)
+    #[cfg_attr(feature = "cli", clap(long, value_name = "PATH"))]
+    pub wit: Option<PathBuf>,
+
+    /// World to use from the WIT package when it defines several (for --component)
+    #[cfg_attr(feature = "cli", clap(long, value_name = "WORLD"))]
+    pub wit_world: Option<String>,
+
+    /// WASI preview 1 adapter module (wasi_snapshot_preview1.reactor.wasm or .command.wasm)
+    /// used to target WASI preview 2 (for --component)
+    #[cfg_attr(feature = "cli", clap(long, value_name = "PATH"))]
+    pub wasi_adapter: Option<PathBuf>,
+
+    /// Additional include directory passed to the compiler as -I (repeatable)
+    #[cfg_attr(feature = "cli", clap(short = 'I', long = "include-dir", value_name = "DIR"))]
+    pub include_dirs: Vec<PathBuf>,
+
+    /// Preprocessor define passed to the compiler as -D, e.g. `-D DEBUG` or `-D VERSION=2` (repeatable)
+    #[cfg_attr(feature = "cli", clap(short = 'D', long = "define", value_name = "NAME[=VALUE]"))]
+    pub defines: Vec<String>,
+
+    /// Library to link, passed to the linker as -l (repeatable)
+    #[cfg_attr(feature = "cli", clap(short = 'l', long = "link-lib", value_name = "LIB"))]
+    pub link_libs: Vec<String>,
+
+    /// Library search directory passed to the linker as -L (repeatable)
+    #[cfg_attr(feature = "cli", clap(short = 'L', long = "lib-dir", value_name = "DIR"))]
+    pub lib_dirs: Vec<PathBuf>,
+
+    /// Replace deprecated or misspelled flags in --emcc-flags/--emcc-flag instead of only warning
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub fix_flags: bool,
+
+    /// Oldest browsers to support: sets -sMIN_CHROME/FIREFOX/SAFARI_VERSION and drops flags
+    /// enabling WebAssembly features (threads, SIMD, ...) these browsers lack
+    #[cfg_attr(feature = "cli", clap(long, value_enum, value_name = "BASELINE"))]
+    pub browser_baseline: Option<BrowserBaseline>,
+
+    /// Skip validating the linked .wasm and checking its features (threads, simd, memory64, ...)
+    /// against the target environment
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub no_wasm_validation: bool,
+
+    /// Do not write compile_commands.json (for clangd and other IDE tooling) to the project root
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub no_compile_commands: bool,
+
+    /// Do not check before running emcc that every #include resolves against the include
+    /// directories and the Emscripten sysroot
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub no_include_check: bool,
+
+    /// Compiler warnings to enable (default, all, extra, none), for every handler
+    #[cfg_attr(feature = "cli", clap(long, value_enum, value_name = "LEVEL", default_value_t = defaults::WARN))]
+    pub warn: WarningLevel,
+
+    /// Treat compiler warnings as errors (-Werror)
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub werror: bool,
+
+    /// Also build an asm.js version of the module with -sWASM=0 (`<output>.wasm2js.js`), which
+    /// the generated pages load in browsers without WebAssembly
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub wasm2js_fallback: bool,
+
+    /// Embed a `wasm_compiler.provenance` custom section in the .wasm recording the tool and
+    /// Emscripten versions, a hash of the build flags and the git commit of the project
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub provenance: bool,
+
+    /// Remove the name section from the .wasm and rename the exports only the JS glue uses to
+    /// short names, writing the original names to `<output>.export-map.json`
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub strip_names: bool,
+
+    /// After the build, start the module with this runtime and fail the build if it does not
+    /// start: `node` calls main() and expects exit status 0, `browser` loads index.html in
+    /// headless Chromium. Skipped when the output is not built for that runtime
+    #[cfg_attr(feature = "cli", clap(long, value_enum, value_name = "RUNTIME"))]
+    pub run_test: Option<TestRunner>,
+
+    /// Argument passed to main() by --run-test node (repeatable)
+    #[cfg_attr(feature = "cli", clap(long = "test-arg", value_name = "ARG", allow_hyphen_values = true))]
+    pub test_args: Vec<String>,
+
+    /// For CMake projects: the generator of the build tree (passed as -G), e.g. Ninja, whose
+    /// progress is reported while it builds (default: CMake's default, usually Unix Makefiles)
+    #[cfg_attr(feature = "cli", clap(long, value_name = "NAME"))]
+    pub cmake_generator: Option<String>,
+
+    /// For CMake projects: also build the CTest tests (BUILD_TESTING=ON) as Node.js scripts in
+    /// build_wasm_cmake-test and run them with ctest, failing the build if a test fails
+    #[cfg_attr(feature = "cli", clap(long = "test"))]
+    pub ctest: bool,
+
+    /// For CMake projects: write the targets of the project with their sources and
+    /// dependencies, as read from CMake's File API, to target-graph.json and target-graph.dot
+    /// (graphviz) in the output directory
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub target_graph: bool,
+
+    /// For CMake projects with several executables (e.g. an app and its tools): build every
+    /// executable that is not a test into its own `<output-dir>/<target>/`, each with its own
+    /// webapp, and write an index.html listing them
+    #[cfg_attr(feature = "cli", clap(long, conflicts_with = "output_name"))]
+    pub all_executables: bool,
+
+    /// Run the module after the build under a WebAssembly runtime (standalone and WASI builds,
+    /// see --target-env wasi). Arguments after `--` are passed to the program
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub run: bool,
+
+    /// Runtime for --run (default: wasmtime, or wasmer when wasmtime is not installed)
+    #[cfg_attr(feature = "cli", clap(long, value_enum, requires = "run"))]
+    pub runtime: Option<WasmRuntime>,
+
+    /// Host directory the program run with --run may access, as HOST::GUEST or HOST (repeatable)
+    #[cfg_attr(feature = "cli", clap(long = "map-dir", value_name = "HOST::GUEST", requires = "run"))]
+    pub map_dirs: Vec<DirMapping>,
+
+    /// Arguments for the program run with --run
+    #[cfg_attr(feature = "cli", clap(last = true, value_name = "ARGS", requires = "run"))]
+    pub run_args: Vec<String>,
+
     /// Optional: Path to a specific Emscripten config file (not yet implemented)
-    #[clap(long)]
+    #[cfg_attr(feature = "cli", clap(long))]
     pub emscripten_config: Option<PathBuf>,
 
-    /// Optional: Name of the final .wasm / .js file
-    #[clap(long, default_value = "output")]
+    /// Name of the final .wasm / .js files (default: the CMake or Makefile target, or the
+    /// project directory name)
+    // Empty until `resolve_output_name` has run
+    #[cfg_attr(feature = "cli", clap(long, default_value = "", hide_default_value = true))]
     pub output_name: String,
+
+    /// How compiler diagnostics are reported (human, json)
+    #[cfg_attr(feature = "cli", clap(long, value_enum, default_value_t = defaults::MESSAGE_FORMAT))]
+    pub message_format: MessageFormat,
+
+    /// Hide the progress bar and log only warnings and errors (RUST_LOG still applies)
+    #[cfg_attr(feature = "cli", clap(short, long))]
+    pub quiet: bool,
+
+    /// Record every command the build runs, with its arguments, working directory,
+    /// environment, timing and full output, in this file (whatever the log level)
+    #[cfg_attr(feature = "cli", clap(long, value_name = "PATH"))]
+    pub log_file: Option<PathBuf>,
+
+    /// Write the toolchain commands the build runs to this script, to reproduce the build
+    /// without wasm_compiler (PowerShell for a `.ps1` file, otherwise POSIX shell)
+    #[cfg_attr(feature = "cli", clap(long, value_name = "PATH"))]
+    pub emit_script: Option<PathBuf>,
+
+    /// Never ask which build system, library handler or main file to use when there are
+    /// several: use the default (`default`, the value when none is given) or fail (`fail`)
+    #[cfg_attr(feature = "cli", clap(long, value_enum, value_name = "POLICY", num_args = 0..=1, default_missing_value = "default"))]
+    pub non_interactive: Option<NonInteractive>,
+
+    /// Whether ambiguities may be resolved by asking on the terminal; set by the
+    /// command-line tool, so library builds never wait for input
+    #[cfg_attr(feature = "cli", clap(skip))]
+    pub interactive: bool,
+
+    /// Fail instead of guessing or skipping: no direct compilation of a guessed source file,
+    /// no guessed ImGui backends or candidates, deprecated emcc flags and skipped outputs
+    /// are errors
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub strict: bool,
+
+    /// Path to a project config file (default: <project-path>/wasm_compiler.toml if present)
+    #[cfg_attr(feature = "cli", clap(long, value_parser))]
+    pub config: Option<PathBuf>,
+
+    /// Emscripten cache directory (sets EM_CACHE for the build tools), e.g. a directory
+    /// shared between builds or persisted by CI
+    #[cfg_attr(feature = "cli", clap(long, value_name = "DIR"))]
+    pub em_cache: Option<PathBuf>,
+
+    /// System library or port to build into the Emscripten cache with embuilder before the
+    /// build, e.g. `libc` or `sdl2` (repeatable)
+    #[cfg_attr(feature = "cli", clap(long = "prewarm", value_name = "LIBRARY"))]
+    pub prewarm: Vec<String>,
+
+    /// Environment variable set for the build tools, e.g. `--env EM_CACHE=/ci/em-cache`
+    /// (repeatable; overrides the `[env]` table of the project config)
+    #[cfg_attr(feature = "cli", clap(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var))]
+    pub env: Vec<(String, String)>,
+
+    /// Directory put in front of PATH for the build tools (repeatable)
+    #[cfg_attr(feature = "cli", clap(long = "path-prefix", value_name = "DIR"))]
+    pub path_prefixes: Vec<PathBuf>,
+
+    /// emcc to build with, e.g. of one of several installed SDKs; em++, emcmake, emmake and
+    /// the other Emscripten tools are taken from the same directory
+    #[cfg_attr(feature = "cli", clap(long, value_name = "PATH"))]
+    pub emcc_path: Option<PathBuf>,
+
+    /// cmake to configure CMake projects with; ctest is taken from the same directory
+    #[cfg_attr(feature = "cli", clap(long, value_name = "PATH"))]
+    pub cmake_path: Option<PathBuf>,
+
+    /// make to build Makefile projects and Makefile build trees of CMake with
+    #[cfg_attr(feature = "cli", clap(long, value_name = "PATH"))]
+    pub make_path: Option<PathBuf>,
+
+    /// ninja to build Ninja build trees of CMake with
+    #[cfg_attr(feature = "cli", clap(long, value_name = "PATH"))]
+    pub ninja_path: Option<PathBuf>,
+
+    /// node to run tests and Node.js builds with
+    #[cfg_attr(feature = "cli", clap(long, value_name = "PATH"))]
+    pub node_path: Option<PathBuf>,
+
+    /// Shell to run Makefile builds in on Windows (native, wsl, msys2); when the Makefile
+    /// needs a POSIX shell and this is not given, the available ones are offered
+    #[cfg_attr(feature = "cli", clap(long, value_enum, value_name = "SHELL"))]
+    pub shell: Option<Shell>,
+
+    /// Time limit of the build tools, after which a hung command is terminated: `SECONDS` for
+    /// every tool or `TOOL=SECONDS` for one (repeatable)
+    #[cfg_attr(feature = "cli", clap(long = "command-timeout", value_name = "[TOOL=]SECONDS", value_parser = parse_command_timeout))]
+    pub command_timeouts: Vec<CommandTimeout>,
+
+    /// How often a command that failed with a network error (downloading a port, fetching
+    /// emsdk) is retried, at most 10 times
+    #[cfg_attr(feature = "cli", clap(long, value_name = "N", default_value_t = defaults::RETRIES, value_parser = clap::value_parser!(u32).range(..=crate::utils::command_runner::MAX_RETRIES as i64)))]
+    pub retries: u32,
+
+    /// Build-system plugin file to load (repeatable); see the README section on plugins
+    #[cfg_attr(feature = "cli", clap(long = "plugin", value_name = "FILE"))]
+    pub plugins: Vec<PathBuf>,
+
+    /// Build only this target of the project config's target matrix (repeatable)
+    #[cfg_attr(feature = "cli", clap(long = "target", value_name = "NAME"))]
+    pub targets: Vec<String>,
+
+    /// The matrix target being built, set by [`crate::compile_matrix`]. Also names the
+    /// separate build trees of the wasm2js fallback (see [`AppConfig::target_build_tree`])
+    #[cfg_attr(feature = "cli", clap(skip))]
+    pub matrix_target: Option<String>,
+
+    /// The shell the commands of a Makefile build run in, chosen by the Make handler from
+    /// `--shell` (see [`crate::utils::posix_shell`])
+    #[cfg_attr(feature = "cli", clap(skip))]
+    pub posix_shell: Option<Shell>,
+
+    /// Settings loaded from the project config file
+    #[cfg_attr(feature = "cli", clap(skip))]
+    pub project_config: ProjectConfig,
+
+    /// The file `project_config` was loaded from, so loading it again is skipped
+    #[cfg_attr(feature = "cli", clap(skip))]
+    pub project_config_path: Option<PathBuf>,
+
+    /// Progress event sink registered by a library user
+    #[cfg_attr(feature = "cli", clap(skip))]
+    pub events: Events,
+
+    /// Build-system and library handlers consulted for the project
+    #[cfg_attr(feature = "cli", clap(skip))]
+    pub handlers: HandlerRegistry,
+
+    /// Whether the project directory is read-only; set when the build starts (see
+    /// [`crate::build_dir`])
+    #[cfg_attr(feature = "cli", clap(skip))]
+    pub read_only_source: bool,
+
+    /// Handle that cancels the build (see [`crate::cancel`])
+    #[cfg_attr(feature = "cli", clap(skip))]
+    pub cancel: Option<CancelHandle>,
+
+    /// Cancellation token of a `compile_async` build
+    #[cfg(feature = "async")]
+    #[cfg_attr(feature = "cli", clap(skip))]
+    pub cancellation: Option<tokio_util::sync::CancellationToken>,
+
+    /// Whether to generate a webapp (auto, always, never); `auto` generates it for graphical
+    /// applications (see `--ui`)
+    #[cfg_attr(feature = "cli", clap(long, value_enum, default_value_t = defaults::WEBAPP))]
+    pub webapp: WebappMode,
+
+    /// What to do about a render loop that never returns to the browser (auto, warn, off):
+    /// `auto` builds with -sASYNCIFY when the loop waits with SDL_Delay or emscripten_sleep,
+    /// and points at the loop with a warning otherwise
+    #[cfg_attr(feature = "cli", clap(long, value_enum, value_name = "MODE", default_value_t = defaults::MAIN_LOOP))]
+    pub main_loop: MainLoopMode,
+
+    /// Kind of application (graphical, console); detected from the sources, build files and
+    /// libraries when not given
+    // Set to the detected kind once the build starts
+    #[cfg_attr(feature = "cli", clap(long, value_enum, value_name = "KIND"))]
+    pub ui: Option<UiKind>,
+
+    /// Major version of SDL (2, 3) for the ImGui backends and the -sUSE_SDL flag --with-imgui
+    /// adds; detected from the emcc flags, sources and build files when not given (default: 2)
+    // Set to the detected version once the build starts
+    #[cfg_attr(feature = "cli", clap(long, value_enum, value_name = "VERSION"))]
+    pub sdl_version: Option<SdlVersion>,
+
+    /// Style of the generated index.html (full, minimal)
+    #[cfg_attr(feature = "cli", clap(long, value_enum, default_value_t = defaults::HTML))]
+    pub html: HtmlMode,
+
+    /// Initial canvas size of the generated webapp, as WIDTHxHEIGHT
+    #[cfg_attr(feature = "cli", clap(long, default_value_t = defaults::CANVAS_SIZE))]
+    pub canvas_size: CanvasSize,
+
+    /// Title of the generated web page (defaults to the output name)
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub page_title: Option<String>,
+
+    /// Short description shown on the generated web page and in its metadata
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub app_description: Option<String>,
+
+    /// Package the webapp as an installable, offline-capable Progressive Web App
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub pwa: bool,
+
+    /// Build with -sSINGLE_FILE=1 and emit one self-contained index.html (no serve.py)
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub single_file: bool,
+
+    /// Add a content hash to output file names (output.1a2b3c4d.js) and write asset-manifest.json
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub hash_filenames: bool,
+
+    /// Write SHA256SUMS with the checksums of the build outputs
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub checksums: bool,
+
+    /// Unencrypted minisign secret key to sign SHA256SUMS with (SHA256SUMS.minisig); implies
+    /// --checksums. Overrides `key` in the `[signing]` table of the project config
+    #[cfg_attr(feature = "cli", clap(long, value_name = "PATH"))]
+    pub signing_key: Option<PathBuf>,
+
+    /// Pack the output directory into this .zip, .tar.gz or .tgz file once the build is done,
+    /// with reproducible contents
+    #[cfg_attr(feature = "cli", clap(long, value_name = "FILE"))]
+    pub archive_output: Option<PathBuf>,
+
+    /// Arrange the output directory for a static host (gh-pages)
+    #[cfg_attr(feature = "cli", clap(long, value_enum))]
+    pub deploy_layout: Option<DeployLayout>,
+
+    /// Wrap the generated webapp in a minimal Electron app (main.js, preload.js, package.json)
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub electron: bool,
+}
+
+/// The command-line defaults.
+#[cfg(feature = "cli")]
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig::parse_from(["wasm_compiler"])
+    }
+}
+
+/// The command-line defaults (see `defaults`).
+#[cfg(not(feature = "cli"))]
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            command: None,
+            project_paths: vec![PathBuf::from(defaults::PROJECT_PATH)],
+            project_path: PathBuf::from(defaults::PROJECT_PATH),
+            output_dir: PathBuf::from(defaults::OUTPUT_DIR),
+            force: false,
+            clean_output: false,
+            build_dir: None,
+            ephemeral: false,
+            keep_build_dir: false,
+            build_config: defaults::BUILD_CONFIG.to_string(),
+            target_env: defaults::TARGET_ENV.to_string(),
+            with_imgui: false,
+            imgui_path: None,
+            imgui_version: None,
+            no_imgui_fetch: false,
+            no_library_cache: false,
+            fetch_headers: false,
+            init_submodules: false,
+            prefetch_content: false,
+            locked: false,
+            emcc_flags: None,
+            emcc_flag: Vec::new(),
+            sources: Vec::new(),
+            recursive: false,
+            excludes: Vec::new(),
+            detect_depth: defaults::DETECT_DEPTH,
+            detect_max_file_size: defaults::DETECT_MAX_FILE_SIZE_KIB,
+            no_entry: false,
+            exports: Vec::new(),
+            typescript: false,
+            webidl_headers: Vec::new(),
+            module_format: defaults::MODULE_FORMAT,
+            component: false,
+            wit: None,
+            wit_world: None,
+            wasi_adapter: None,
+            include_dirs: Vec::new(),
+            defines: Vec::new(),
+            link_libs: Vec::new(),
+            lib_dirs: Vec::new(),
+            fix_flags: false,
+            browser_baseline: None,
+            no_wasm_validation: false,
+            no_compile_commands: false,
+            no_include_check: false,
+            warn: defaults::WARN,
+            werror: false,
+            wasm2js_fallback: false,
+            provenance: false,
+            strip_names: false,
+            run_test: None,
+            test_args: Vec::new(),
+            cmake_generator: None,
+            ctest: false,
+            target_graph: false,
+            all_executables: false,
+            run: false,
+            runtime: None,
+            map_dirs: Vec::new(),
+            run_args: Vec::new(),
+            emscripten_config: None,
+            output_name: String::new(),
+            message_format: defaults::MESSAGE_FORMAT,
+            quiet: false,
+            log_file: None,
+            emit_script: None,
+            non_interactive: None,
+            interactive: false,
+            strict: false,
+            config: None,
+            em_cache: None,
+            prewarm: Vec::new(),
+            env: Vec::new(),
+            path_prefixes: Vec::new(),
+            emcc_path: None,
+            cmake_path: None,
+            make_path: None,
+            ninja_path: None,
+            node_path: None,
+            shell: None,
+            command_timeouts: Vec::new(),
+            retries: defaults::RETRIES,
+            plugins: Vec::new(),
+            targets: Vec::new(),
+            matrix_target: None,
+            posix_shell: None,
+            project_config: ProjectConfig::default(),
+            project_config_path: None,
+            events: Events::default(),
+            handlers: HandlerRegistry::default(),
+            read_only_source: false,
+            cancel: None,
+            #[cfg(feature = "async")]
+            cancellation: None,
+            webapp: defaults::WEBAPP,
+            main_loop: defaults::MAIN_LOOP,
+            ui: None,
+            sdl_version: None,
+            html: defaults::HTML,
+            canvas_size: defaults::CANVAS_SIZE,
+            page_title: None,
+            app_description: None,
+            pwa: false,
+            single_file: false,
+            hash_filenames: false,
+            checksums: false,
+            signing_key: None,
+            archive_output: None,
+            deploy_layout: None,
+            electron: false,
+        }
+    }
 }
 
 impl AppConfig {
+    /// Parses the configuration from the command-line arguments.
+    #[cfg(feature = "cli")]
     pub fn new() -> Self {
         AppConfig::parse()
     }
+
+    /// Starts building a configuration in code, with the same defaults as the command line.
+    pub fn builder() -> CompileOptions {
+        CompileOptions {
+            config: AppConfig::default(),
+        }
+    }
+
+    /// Compiler flags from `--include-dir` and `--define`. Directories are made absolute since
+    /// the build tools do not run in the current directory.
+    pub fn compile_flags(&self) -> Vec<String> {
+        let includes = self.include_dirs.iter().map(|dir| format!("-I{}", absolute_path(dir).display()));
+        let defines = self.defines.iter().map(|define| format!("-D{}", define));
+        includes.chain(defines).collect()
+    }
+
+    /// How much of the project library detection reads, from `--detect-depth` and
+    /// `--detect-max-file-size`
+    pub fn detection_limits(&self) -> DetectionLimits {
+        DetectionLimits {
+            max_depth: self.detect_depth.max(1) as usize,
+            max_file_size: self.detect_max_file_size.saturating_mul(1024),
+        }
+    }
+
+    /// The directory of the intermediate build trees (`build_wasm_cmake`, ...) of the project
+    /// at `project_path`: `--build-dir`, or the project
+    pub fn build_root(&self, project_path: &Path) -> PathBuf {
+        match &self.build_dir {
+            Some(build_dir) => absolute_path(build_dir),
+            None => project_path.to_path_buf(),
+        }
+    }
+
+    /// The intermediate build tree `name` of the project at `project_path` (see
+    /// [`AppConfig::build_root`])
+    pub fn build_tree(&self, project_path: &Path, name: &str) -> PathBuf {
+        self.build_root(project_path).join(name)
+    }
+
+    /// The build tree `name` of the matrix target or wasm2js fallback being built, which each
+    /// keep their own (`<name>-<target>`), so switching between them neither reuses objects
+    /// built with other flags nor redoes the configure step
+    pub fn target_build_tree(&self, project_path: &Path, name: &str) -> PathBuf {
+        match &self.matrix_target {
+            Some(target) => self.build_tree(project_path, &format!("{}-{}", name, target)),
+            None => self.build_tree(project_path, name),
+        }
+    }
+
+    /// Linker search directories from `--lib-dir`
+    pub fn lib_dir_flags(&self) -> Vec<String> {
+        self.lib_dirs.iter().map(|dir| format!("-L{}", absolute_path(dir).display())).collect()
+    }
+
+    /// Libraries from `--link-lib`
+    pub fn link_lib_flags(&self) -> Vec<String> {
+        self.link_libs.iter().map(|lib| format!("-l{}", lib)).collect()
+    }
+
+    /// Splits `--emcc-flags` with shell quoting rules and merges the result in front of the
+    /// `--emcc-flag` values, so handlers only need to look at `emcc_flag`.
+    pub fn resolve_emcc_flags(&mut self) -> Result<(), String> {
+        if let Some(flags) = self.emcc_flags.take() {
+            let mut parsed = shell_words::split(&flags)
+                .map_err(|e| format!("Invalid --emcc-flags {:?}: {}", flags, e))?;
+            parsed.append(&mut self.emcc_flag);
+            self.emcc_flag = parsed;
+        }
+        Ok(())
+    }
+
+    /// Loads the project config file given with `--config`, or `wasm_compiler.toml` from the
+    /// project root if it exists. Does nothing if that file is already loaded, e.g. into the
+    /// configuration a matrix target was derived from.
+    pub fn load_project_config(&mut self) -> Result<(), String> {
+        let path = match &self.config {
+            Some(path) => path.clone(),
+            None => {
+                let default_path = self.project_path.join(PROJECT_CONFIG_FILE_NAME);
+                if !default_path.is_file() {
+                    return Ok(());
+                }
+                default_path
+            }
+        };
+        if self.project_config_path.as_ref() == Some(&path) {
+            return Ok(());
+        }
+        tracing::info!("Loading project config from {:?}", path);
+        self.project_config = ProjectConfig::load(&path)?;
+        self.project_config_path = Some(path);
+        Ok(())
+    }
+
+    /// Derives the output name from the project when `--output-name` was not given.
+    pub fn resolve_output_name(&mut self, project_path_abs: &Path) {
+        if self.output_name.is_empty() {
+            self.output_name = output_name::default_output_name(project_path_abs);
+            tracing::info!("Using output name '{}'", self.output_name);
+        }
+    }
+
+    /// Environment of the build tools on top of the inherited one: the `[env]` table of the
+    /// project config, then `--env`, `EM_CACHE` from `--em-cache`, and PATH with the
+    /// `--path-prefix` and `path_prefix` directories in front.
+    pub fn tool_env(&self) -> Vec<(OsString, OsString)> {
+        let mut env: Vec<(OsString, OsString)> = self.project_config.env.iter()
+            .chain(self.env.iter().map(|(key, value)| (key, value)))
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect();
+        if let Some(em_cache) = &self.em_cache {
+            env.push(("EM_CACHE".into(), absolute_path(em_cache).into()));
+        }
+
+        let prefixes: Vec<PathBuf> = self.path_prefixes.iter()
+            .chain(&self.project_config.path_prefix)
+            .map(|dir| absolute_path(dir))
+            .collect();
+        if !prefixes.is_empty() {
+            let path = env.iter().rev()
+                .find(|(key, _)| key == "PATH")
+                .map(|(_, value)| value.clone())
+                .or_else(|| std::env::var_os("PATH"))
+                .unwrap_or_default();
+            let dirs = prefixes.into_iter().chain(std::env::split_paths(&path));
+            match std::env::join_paths(dirs) {
+                Ok(path) => env.push(("PATH".into(), path)),
+                Err(e) => tracing::warn!("Ignoring path prefixes: {}", e),
+            }
+        }
+        // Temporary files count towards the disk cap of the phase (see resource_limits)
+        if let Some(temp_dir) = resource_limits::temp_dir() {
+            for variable in ["TMPDIR", "TMP", "TEMP"] {
+                env.push((variable.into(), temp_dir.clone().into()));
+            }
+        }
+        env
+    }
+
+    /// Reports the start of `phase` to the event sink and puts its `[limits]` in force until
+    /// the returned guard is dropped
+    pub(crate) fn enter_phase(&self, phase: Phase) -> PhaseGuard {
+        let span = self.events.enter_phase(phase);
+        PhaseGuard { _limits: resource_limits::enter(self, phase), _span: span }
+    }
+
+    /// The file `tool` is pinned to with `--emcc-path`, `--cmake-path`, `--make-path`,
+    /// `--ninja-path`, `--node-path` or the `[tools]` table of the project config, instead of
+    /// looking it up in PATH. Tools next to a pinned one (em++ next to emcc, ctest next to
+    /// cmake) are pinned too.
+    pub fn tool_path(&self, tool: &str) -> Option<PathBuf> {
+        let tools = &self.project_config.tools;
+        let pinned = |option: &Option<PathBuf>, table: &Option<PathBuf>| option.clone().or_else(|| table.clone());
+        // Emscripten tools are named `emcmake.bat` on Windows
+        let tool = tool.strip_suffix(".bat").unwrap_or(tool);
+        let path = match tool {
+            "emcc" => pinned(&self.emcc_path, &tools.emcc),
+            "em++" | "emcmake" | "emmake" | "emar" | "emranlib" | "emlink" | "emsize" | "emstrip" | "embuilder" => {
+                pinned(&self.emcc_path, &tools.emcc).map(|emcc| sibling(&emcc, tool))
+            }
+            "cmake" => pinned(&self.cmake_path, &tools.cmake),
+            "ctest" => pinned(&self.cmake_path, &tools.cmake).map(|cmake| sibling(&cmake, tool)),
+            "make" => pinned(&self.make_path, &tools.make),
+            "ninja" => pinned(&self.ninja_path, &tools.ninja),
+            "node" => pinned(&self.node_path, &tools.node),
+            _ => None,
+        };
+        path.map(|path| absolute_path(&path))
+    }
+
+    /// What to run for `tool`: its pinned path (see [`Self::tool_path`]), or its name to be
+    /// looked up in PATH. For tools started by another tool, such as the `cmake` emcmake runs.
+    pub fn tool_program(&self, tool: &str) -> String {
+        self.tool_path(tool).map_or_else(|| tool.to_string(), |path| path.to_string_lossy().into_owned())
+    }
+
+    /// Time limit of `tool`: its own `--command-timeout`, or the one for every tool
+    pub fn command_timeout(&self, tool: &str) -> Option<Duration> {
+        let limit = |tool: Option<&str>| self.command_timeouts.iter().rev().find(|timeout| timeout.tool.as_deref() == tool);
+        limit(Some(tool)).or_else(|| limit(None)).map(|timeout| timeout.limit)
+    }
+
+    /// Title used for the generated web page, falling back to the output name
+    pub fn page_title(&self) -> &str {
+        self.page_title.as_deref().unwrap_or(&self.output_name)
+    }
+
+    /// The key SHA256SUMS is signed with: `--signing-key`, or the one in the project config
+    pub fn signing_key_path(&self) -> Option<&Path> {
+        self.signing_key.as_deref().or(self.project_config.signing.key.as_deref())
+    }
+}
+
+/// A build phase in progress (see [`AppConfig::enter_phase`])
+pub(crate) struct PhaseGuard {
+    _limits: resource_limits::LimitsGuard,
+    _span: tracing::span::EnteredSpan,
+}
+
+/// Builder for an [`AppConfig`] used when `wasm_compiler` is driven as a library rather than
+/// from the command line. Options not set keep their command-line defaults.
+///
+/// ```no_run
+/// let artifacts = wasm_compiler::compile(
+///     wasm_compiler::app_config::AppConfig::builder()
+///         .project_path("examples/hello")
+///         .output_dir("dist/hello")
+///         .define("NDEBUG"),
+/// )?;
+/// println!("{}", artifacts.js.path.display());
+/// # Ok::<(), wasm_compiler::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct CompileOptions {
+    config: AppConfig,
+}
+
+impl CompileOptions {
+    /// Path to the C++ project directory
+    pub fn project_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.project_path = path.into();
+        self
+    }
+
+    /// Directory receiving the build output
+    pub fn output_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.output_dir = path.into();
+        self
+    }
+
+    /// Allows writing into a non-empty directory without a previous build (see `--force`)
+    pub fn force(mut self, enabled: bool) -> Self {
+        self.config.force = enabled;
+        self
+    }
+
+    /// Removes the previous build from the output directory first (see `--clean-output`)
+    pub fn clean_output(mut self, enabled: bool) -> Self {
+        self.config.clean_output = enabled;
+        self
+    }
+
+    /// Directory for the intermediate build trees instead of the project (see `--build-dir`)
+    pub fn build_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.build_dir = Some(path.into());
+        self
+    }
+
+    /// Deletes the intermediate build trees after a successful build (see `--ephemeral`)
+    pub fn ephemeral(mut self, enabled: bool) -> Self {
+        self.config.ephemeral = enabled;
+        self.config.keep_build_dir = !enabled;
+        self
+    }
+
+    /// Base name of the `.js` / `.wasm` output (default: derived from the project)
+    pub fn output_name(mut self, name: impl Into<String>) -> Self {
+        self.config.output_name = name.into();
+        self
+    }
+
+    /// Build configuration (Debug, Release, ...)
+    pub fn build_config(mut self, build_config: impl Into<String>) -> Self {
+        self.config.build_config = build_config.into();
+        self
+    }
+
+    /// Target environment (web, node)
+    pub fn target_env(mut self, target_env: impl Into<String>) -> Self {
+        self.config.target_env = target_env.into();
+        self
+    }
+
+    pub fn with_imgui(mut self, enabled: bool) -> Self {
+        self.config.with_imgui = enabled;
+        self
+    }
+
+    /// ImGui checkout to build ImGui projects with (see `--imgui-path`)
+    pub fn imgui_path(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.imgui_path = Some(dir.into());
+        self
+    }
+
+    /// ImGui release fetched for ImGui projects that come without it (see `--imgui-version`)
+    pub fn imgui_version(mut self, tag: impl Into<String>) -> Self {
+        self.config.imgui_version = Some(tag.into());
+        self
+    }
+
+    /// Never fetch ImGui (see `--no-imgui-fetch`)
+    pub fn no_imgui_fetch(mut self, enabled: bool) -> Self {
+        self.config.no_imgui_fetch = enabled;
+        self
+    }
+
+    /// Compile vendored libraries with the project instead of linking cached archives (see
+    /// `--no-library-cache`)
+    pub fn no_library_cache(mut self, enabled: bool) -> Self {
+        self.config.no_library_cache = enabled;
+        self
+    }
+
+    /// Fetch missing single-header libraries without asking (see `--fetch-headers`)
+    pub fn fetch_headers(mut self, enabled: bool) -> Self {
+        self.config.fetch_headers = enabled;
+        self
+    }
+
+    /// Check out missing submodules without asking (see `--init-submodules`)
+    pub fn init_submodules(mut self, enabled: bool) -> Self {
+        self.config.init_submodules = enabled;
+        self
+    }
+
+    /// Clone FetchContent dependencies into the cache (see `--prefetch-content`)
+    pub fn prefetch_content(mut self, enabled: bool) -> Self {
+        self.config.prefetch_content = enabled;
+        self
+    }
+
+    /// Verify the build against wasm_compiler.lock instead of updating it (see `--locked`)
+    pub fn locked(mut self, enabled: bool) -> Self {
+        self.config.locked = enabled;
+        self
+    }
+
+    /// Adds one emcc flag, passed through without splitting
+    pub fn emcc_flag(mut self, flag: impl Into<String>) -> Self {
+        self.config.emcc_flag.push(flag.into());
+        self
+    }
+
+    pub fn include_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.include_dirs.push(dir.into());
+        self
+    }
+
+    /// Adds a preprocessor define (`NAME` or `NAME=VALUE`)
+    pub fn define(mut self, define: impl Into<String>) -> Self {
+        self.config.defines.push(define.into());
+        self
+    }
+
+    pub fn link_lib(mut self, lib: impl Into<String>) -> Self {
+        self.config.link_libs.push(lib.into());
+        self
+    }
+
+    pub fn lib_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.lib_dirs.push(dir.into());
+        self
+    }
+
+    /// Adds a glob selecting sources to compile (see `--source`)
+    pub fn source(mut self, glob: impl Into<String>) -> Self {
+        self.config.sources.push(glob.into());
+        self
+    }
+
+    /// Adds a glob of sources to leave out (see `--exclude`)
+    pub fn exclude(mut self, glob: impl Into<String>) -> Self {
+        self.config.excludes.push(glob.into());
+        self
+    }
+
+    /// Directory levels searched by library detection (see `--detect-depth`)
+    pub fn detect_depth(mut self, levels: u32) -> Self {
+        self.config.detect_depth = levels.max(1);
+        self
+    }
+
+    /// Size in KiB above which sources are not read for library detection (see
+    /// `--detect-max-file-size`)
+    pub fn detect_max_file_size(mut self, kib: u64) -> Self {
+        self.config.detect_max_file_size = kib;
+        self
+    }
+
+    /// Adds a function to export to JavaScript (see `--export`)
+    pub fn export(mut self, name: impl Into<String>) -> Self {
+        self.config.exports.push(name.into());
+        self
+    }
+
+    pub fn no_entry(mut self, enabled: bool) -> Self {
+        self.config.no_entry = enabled;
+        self
+    }
+
+    pub fn module_format(mut self, format: ModuleFormat) -> Self {
+        self.config.module_format = format;
+        self
+    }
+
+    pub fn webapp(mut self, mode: WebappMode) -> Self {
+        self.config.webapp = mode;
+        self
+    }
+
+    /// What to do about a blocking render loop (see `--main-loop`)
+    pub fn main_loop(mut self, mode: MainLoopMode) -> Self {
+        self.config.main_loop = mode;
+        self
+    }
+
+    /// Sets the kind of application instead of detecting it (see `--ui`)
+    pub fn ui(mut self, kind: UiKind) -> Self {
+        self.config.ui = Some(kind);
+        self
+    }
+
+    /// Sets the SDL version instead of detecting it (see `--sdl-version`)
+    pub fn sdl_version(mut self, version: SdlVersion) -> Self {
+        self.config.sdl_version = Some(version);
+        self
+    }
+
+    pub fn html(mut self, mode: HtmlMode) -> Self {
+        self.config.html = mode;
+        self
+    }
+
+    pub fn single_file(mut self, enabled: bool) -> Self {
+        self.config.single_file = enabled;
+        self
+    }
+
+    pub fn hash_filenames(mut self, enabled: bool) -> Self {
+        self.config.hash_filenames = enabled;
+        self
+    }
+
+    /// Writes SHA256SUMS for the build outputs (see `--checksums`)
+    pub fn checksums(mut self, enabled: bool) -> Self {
+        self.config.checksums = enabled;
+        self
+    }
+
+    /// Signs SHA256SUMS with the minisign secret key at `path` (see `--signing-key`)
+    pub fn signing_key(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.signing_key = Some(path.into());
+        self
+    }
+
+    /// Packs the output directory into the archive `path` (see `--archive-output`)
+    pub fn archive_output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.archive_output = Some(path.into());
+        self
+    }
+
+    pub fn typescript(mut self, enabled: bool) -> Self {
+        self.config.typescript = enabled;
+        self
+    }
+
+    /// Targets the browsers of `baseline` (see `--browser-baseline`)
+    pub fn browser_baseline(mut self, baseline: BrowserBaseline) -> Self {
+        self.config.browser_baseline = Some(baseline);
+        self
+    }
+
+    /// Skips validating the linked module (see `--no-wasm-validation`)
+    pub fn no_wasm_validation(mut self, enabled: bool) -> Self {
+        self.config.no_wasm_validation = enabled;
+        self
+    }
+
+    /// Skips writing `compile_commands.json` to the project root (see `--no-compile-commands`)
+    pub fn no_compile_commands(mut self, enabled: bool) -> Self {
+        self.config.no_compile_commands = enabled;
+        self
+    }
+
+    /// Skips checking that the includes resolve before running emcc (see `--no-include-check`)
+    pub fn no_include_check(mut self, enabled: bool) -> Self {
+        self.config.no_include_check = enabled;
+        self
+    }
+
+    /// Sets the compiler warnings to enable (see `--warn`)
+    pub fn warn(mut self, level: WarningLevel) -> Self {
+        self.config.warn = level;
+        self
+    }
+
+    /// Treats compiler warnings as errors (see `--werror`)
+    pub fn werror(mut self, enabled: bool) -> Self {
+        self.config.werror = enabled;
+        self
+    }
+
+    /// Embeds the build provenance in the module (see `--provenance`)
+    pub fn provenance(mut self, enabled: bool) -> Self {
+        self.config.provenance = enabled;
+        self
+    }
+
+    /// Strips function names from the module (see `--strip-names`)
+    pub fn strip_names(mut self, enabled: bool) -> Self {
+        self.config.strip_names = enabled;
+        self
+    }
+
+    /// Also builds the asm.js fallback (see `--wasm2js-fallback`)
+    pub fn wasm2js_fallback(mut self, enabled: bool) -> Self {
+        self.config.wasm2js_fallback = enabled;
+        self
+    }
+
+    /// Smoke-tests the output with `runner` after the build (see `--run-test`)
+    pub fn run_test(mut self, runner: TestRunner) -> Self {
+        self.config.run_test = Some(runner);
+        self
+    }
+
+    /// Adds an argument passed to main() by the smoke test (see `--test-arg`)
+    pub fn test_arg(mut self, arg: impl Into<String>) -> Self {
+        self.config.test_args.push(arg.into());
+        self
+    }
+
+    /// Builds and runs the project's CTest tests after the build (see `--test`)
+    pub fn ctest(mut self, enabled: bool) -> Self {
+        self.config.ctest = enabled;
+        self
+    }
+
+    /// Configures CMake projects with `generator`, e.g. `Ninja` (see `--cmake-generator`)
+    pub fn cmake_generator(mut self, generator: impl Into<String>) -> Self {
+        self.config.cmake_generator = Some(generator.into());
+        self
+    }
+
+    /// What to do when the project is ambiguous (see `--non-interactive`); library builds
+    /// never ask, so without this the default candidate is used
+    pub fn non_interactive(mut self, policy: NonInteractive) -> Self {
+        self.config.non_interactive = Some(policy);
+        self
+    }
+
+    /// Records the commands of the build in a file (see `--log-file`)
+    pub fn log_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.log_file = Some(path.into());
+        self
+    }
+
+    /// Writes the toolchain commands of the build to a script (see `--emit-script`)
+    pub fn emit_script(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.emit_script = Some(path.into());
+        self
+    }
+
+    /// Fails the build instead of taking fallbacks (see `--strict`)
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.config.strict = enabled;
+        self
+    }
+
+    /// Writes the CMake target graph to the output directory (see `--target-graph`)
+    pub fn target_graph(mut self, enabled: bool) -> Self {
+        self.config.target_graph = enabled;
+        self
+    }
+
+    /// Builds every executable of a CMake project into its own output subdirectory with
+    /// [`crate::compile_executables`] (see `--all-executables`)
+    pub fn all_executables(mut self, enabled: bool) -> Self {
+        self.config.all_executables = enabled;
+        self
+    }
+
+    /// Runs the module under a WebAssembly runtime after the build (see `--run`)
+    pub fn run(mut self, enabled: bool) -> Self {
+        self.config.run = enabled;
+        self
+    }
+
+    pub fn runtime(mut self, runtime: WasmRuntime) -> Self {
+        self.config.runtime = Some(runtime);
+        self
+    }
+
+    /// Gives the program run with `--run` access to a host directory (see `--map-dir`)
+    pub fn map_dir(mut self, host: impl Into<PathBuf>, guest: impl Into<String>) -> Self {
+        self.config.map_dirs.push(DirMapping { host: host.into(), guest: guest.into() });
+        self
+    }
+
+    /// Adds an argument for the program run with `--run`
+    pub fn run_arg(mut self, arg: impl Into<String>) -> Self {
+        self.config.run_args.push(arg.into());
+        self
+    }
+
+    /// Emscripten cache directory (see `--em-cache`)
+    pub fn em_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.em_cache = Some(dir.into());
+        self
+    }
+
+    /// Builds a library into the Emscripten cache before the build (see `--prewarm`)
+    pub fn prewarm(mut self, library: impl Into<String>) -> Self {
+        self.config.prewarm.push(library.into());
+        self
+    }
+
+    /// Sets an environment variable for the build tools (see `--env`)
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Puts a directory in front of PATH for the build tools (see `--path-prefix`)
+    pub fn path_prefix(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.path_prefixes.push(dir.into());
+        self
+    }
+
+    /// Terminates `tool` (every tool with `None`) when it runs longer than `limit` (see
+    /// `--command-timeout`)
+    pub fn command_timeout(mut self, tool: Option<&str>, limit: Duration) -> Self {
+        self.config.command_timeouts.push(CommandTimeout { tool: tool.map(str::to_string), limit });
+        self
+    }
+
+    /// Retries commands failing with a network error up to `retries` times, at most
+    /// [`MAX_RETRIES`](crate::utils::command_runner::MAX_RETRIES) (see `--retries`)
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.config.retries = retries;
+        self
+    }
+
+    /// Builds with this emcc, and the Emscripten tools next to it (see `--emcc-path`)
+    pub fn emcc_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.emcc_path = Some(path.into());
+        self
+    }
+
+    /// Configures CMake projects with this cmake (see `--cmake-path`)
+    pub fn cmake_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.cmake_path = Some(path.into());
+        self
+    }
+
+    /// Builds Makefiles with this make (see `--make-path`)
+    pub fn make_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.make_path = Some(path.into());
+        self
+    }
+
+    /// Builds Ninja build trees with this ninja (see `--ninja-path`)
+    pub fn ninja_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.ninja_path = Some(path.into());
+        self
+    }
+
+    /// Runs tests and Node.js builds with this node (see `--node-path`)
+    pub fn node_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.node_path = Some(path.into());
+        self
+    }
+
+    /// Runs Makefile builds in this shell on Windows (see `--shell`)
+    pub fn shell(mut self, shell: Shell) -> Self {
+        self.config.shell = Some(shell);
+        self
+    }
+
+    /// Limits [`crate::compile_matrix`] to this target of the project config (see `--target`)
+    pub fn target(mut self, name: impl Into<String>) -> Self {
+        self.config.targets.push(name.into());
+        self
+    }
+
+    /// Replaces the build-system and library handlers, e.g. with a registry that has custom
+    /// handlers added to [`HandlerRegistry::default`]
+    pub fn handlers(mut self, registry: HandlerRegistry) -> Self {
+        self.config.handlers = registry;
+        self
+    }
+
+    /// Registers a sink for progress events (phases, commands, stderr lines, artifacts)
+    pub fn event_sink(mut self, sink: impl EventSink + 'static) -> Self {
+        self.config.events = Events::new(Arc::new(sink));
+        self
+    }
+
+    /// Cancels the build when `handle` is cancelled, e.g. from another thread (see
+    /// [`crate::cancel`])
+    pub fn cancel_handle(mut self, handle: CancelHandle) -> Self {
+        self.config.cancel = Some(handle);
+        self
+    }
+
+    /// Finishes the configuration
+    pub fn build(self) -> AppConfig {
+        self.config
+    }
+}
+
+impl From<AppConfig> for CompileOptions {
+    fn from(config: AppConfig) -> Self {
+        CompileOptions { config }
+    }
+}
+
+fn absolute_path(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// The tool `name` in the directory of `path`, with the same extension (`em++.bat` next to
+/// `emcc.bat`)
+fn sibling(path: &Path, name: &str) -> PathBuf {
+    let sibling = path.with_file_name(name);
+    match path.extension() {
+        Some(extension) => sibling.with_extension(extension),
+        None => sibling,
+    }
 }
@@ -0,0 +1,131 @@
+//! Async compilation with cancellation (`async` feature). The build runs on tokio's
+//! blocking thread pool; its external commands run as `tokio::process` children that are
+//! terminated as soon as the [`CancellationToken`] is cancelled.
+
+use std::process::{Command, Output};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Child;
+use crate::app_config::CompileOptions;
+use crate::events::Events;
+use crate::resource_limits::CommandLimits;
+use crate::utils::command_runner::{CommandError, TERMINATE_GRACE};
+use crate::{compile, BuildArtifacts, Error};
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Async variant of [`compile`]. When `cancel` is cancelled, the running emcc/cmake/make
+/// process (and the tools it started) is terminated and the build resolves to
+/// [`Error::Cancelled`]. Must be called from within a tokio runtime.
+pub async fn compile_async(options: CompileOptions, cancel: CancellationToken) -> Result<BuildArtifacts, Error> {
+    let mut config = options.build();
+    config.cancellation = Some(cancel.clone());
+
+    let build = tokio::task::spawn_blocking(move || compile(CompileOptions::from(config)));
+    let result = match build.await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(_) => return Err(Error::Cancelled),
+    };
+
+    // Steps between commands are not interrupted, so a build cancelled late may still finish
+    if cancel.is_cancelled() {
+        tracing::info!("Build cancelled");
+        return Err(Error::Cancelled);
+    }
+    result
+}
+
+/// Runs `cmd` as a tokio child process, streaming its output to `events`, and terminates it
+/// when `token` is cancelled or it runs out of time (see [`CommandLimits`]; the disk caps of
+/// phases are only watched in blocking builds). Called from the blocking thread
+/// of [`compile_async`].
+pub(crate) fn output_cancellable(
+    mut cmd: Command,
+    command_name: &str,
+    events: &Events,
+    token: &CancellationToken,
+    limits: &CommandLimits,
+) -> Result<Output, CommandError> {
+    let cancelled = || CommandError::Cancelled { tool: command_name.to_string() };
+    if token.is_cancelled() {
+        return Err(cancelled());
+    }
+
+    // emcc, emcmake and emmake start further tools; give them a process group to signal
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+
+    let run = async {
+        let mut child = tokio::process::Command::from(cmd).kill_on_drop(true).spawn()?;
+        let mut stdout_lines = BufReader::new(child.stdout.take().expect("stdout is piped")).lines();
+        let mut stderr_lines = BufReader::new(child.stderr.take().expect("stderr is piped")).lines();
+
+        let io = async {
+            let read_stdout = async {
+                let mut stdout = Vec::new();
+                while let Some(line) = stdout_lines.next_line().await? {
+                    events.stdout_line(&line);
+                    stdout.extend_from_slice(line.as_bytes());
+                    stdout.push(b'\n');
+                }
+                Ok::<_, std::io::Error>(stdout)
+            };
+            let read_stderr = async {
+                let mut stderr = Vec::new();
+                while let Some(line) = stderr_lines.next_line().await? {
+                    events.stderr_line(command_name, &line);
+                    stderr.extend_from_slice(line.as_bytes());
+                    stderr.push(b'\n');
+                }
+                Ok::<_, std::io::Error>(stderr)
+            };
+            let (stdout, stderr) = tokio::try_join!(read_stdout, read_stderr)?;
+            let status = child.wait().await?;
+            Ok::<_, std::io::Error>(Output { status, stdout, stderr })
+        };
+        let deadline = async {
+            match limits.remaining() {
+                Some(limit) => tokio::time::sleep(limit).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let output = tokio::select! {
+            output = io => Ok(output?),
+            _ = token.cancelled() => Err(cancelled()),
+            _ = deadline => Err(limits.timed_out(command_name)),
+        };
+        if let Err(e) = &output {
+            tracing::info!("Terminating '{}': {}", command_name, e);
+            terminate(&mut child).await;
+        }
+        Ok::<_, std::io::Error>(output)
+    };
+
+    let output = match tokio::runtime::Handle::try_current() {
+        Ok(handle) => handle.block_on(run),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .and_then(|runtime| runtime.block_on(run)),
+    };
+    output.map_err(|e| CommandError::Spawn { tool: command_name.to_string(), source: e })?
+}
+
+/// Asks the process group of `child` to exit and kills it if it does not within
+/// [`TERMINATE_GRACE`].
+async fn terminate(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        let group = -(pid as libc::pid_t);
+        // SAFETY: kill(2) has no memory-safety preconditions
+        unsafe { libc::kill(group, libc::SIGTERM) };
+        let exited = tokio::time::timeout(TERMINATE_GRACE, child.wait()).await.is_ok();
+        // Also reaches tools that ignored SIGTERM after their parent exited
+        unsafe { libc::kill(group, libc::SIGKILL) };
+        if exited {
+            return;
+        }
+    }
+    child.kill().await.ok();
+}
@@ -0,0 +1,146 @@
+//! Building several independent projects in one invocation. Projects are compiled in
+//! parallel (one per available CPU) and their results are collected into a [`BatchReport`].
+
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::app_config::{AppConfig, CompileOptions};
+use crate::{compile, BuildArtifacts, Error};
+
+/// Outcome of one project of a batch
+#[derive(Debug)]
+pub struct ProjectReport {
+    pub project_path: PathBuf,
+    pub output_dir: PathBuf,
+    pub duration: Duration,
+    pub result: Result<BuildArtifacts, Error>,
+}
+
+/// Outcome of [`compile_all`], in the order the projects were given
+#[derive(Debug)]
+pub struct BatchReport {
+    pub projects: Vec<ProjectReport>,
+}
+
+impl BatchReport {
+    pub fn failed(&self) -> usize {
+        self.projects.iter().filter(|project| project.result.is_err()).count()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+
+    /// One line per project, followed by a totals line.
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for project in &self.projects {
+            let seconds = project.duration.as_secs_f64();
+            match &project.result {
+                Ok(_) => summary.push_str(&format!(
+                    "  ok      {} -> {} ({:.1}s)\n",
+                    project.project_path.display(), project.output_dir.display(), seconds
+                )),
+                Err(e) => summary.push_str(&format!(
+                    "  FAILED  {} ({:.1}s): {}\n",
+                    project.project_path.display(), seconds, first_line(&e.to_string())
+                )),
+            }
+        }
+        summary.push_str(&format!(
+            "{} project(s): {} succeeded, {} failed",
+            self.projects.len(), self.projects.len() - self.failed(), self.failed()
+        ));
+        summary
+    }
+
+    /// The report as a `batch-finished` event for `--message-format json`: one entry per
+    /// project and the totals
+    pub fn to_json(&self) -> serde_json::Value {
+        let projects: Vec<serde_json::Value> = self
+            .projects
+            .iter()
+            .map(|project| {
+                serde_json::json!({
+                    "project_path": project.project_path,
+                    "output_dir": project.output_dir,
+                    "duration_ms": project.duration.as_millis() as u64,
+                    "success": project.result.is_ok(),
+                    "error": project.result.as_ref().err().map(|e| e.to_string()),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "reason": "batch-finished",
+            "projects": projects,
+            "succeeded": self.projects.len() - self.failed(),
+            "failed": self.failed(),
+        })
+    }
+}
+
+/// Builds every project with its own options, in parallel. A failing project does not stop
+/// the others.
+pub fn compile_all(options: Vec<CompileOptions>) -> BatchReport {
+    let total = options.len();
+    let jobs = std::thread::available_parallelism().map_or(1, |n| n.get()).min(total.max(1));
+    let queue = Mutex::new(options.into_iter().enumerate().collect::<VecDeque<_>>());
+    let reports = Mutex::new(Vec::with_capacity(total));
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let Some((index, options)) = queue.lock().unwrap().pop_front() else { break };
+                let config = options.build();
+                let project_path = config.project_path.clone();
+                let output_dir = config.output_dir.clone();
+                let start = Instant::now();
+                let result = compile(CompileOptions::from(config));
+                let report = ProjectReport { project_path, output_dir, duration: start.elapsed(), result };
+                reports.lock().unwrap().push((index, report));
+            });
+        }
+    });
+
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by_key(|(index, _)| *index);
+    BatchReport { projects: reports.into_iter().map(|(_, report)| report).collect() }
+}
+
+/// One configuration per `--project-path`, each writing to a subdirectory of the output
+/// directory (and of `--build-dir`) named after the project directory (made unique with a
+/// numeric suffix).
+pub fn split_projects(config: &AppConfig) -> Vec<CompileOptions> {
+    let mut used_names = HashSet::new();
+    config
+        .project_paths
+        .iter()
+        .map(|project_path| {
+            let base = project_name(project_path);
+            let mut name = base.clone();
+            let mut suffix = 2;
+            while !used_names.insert(name.clone()) {
+                name = format!("{}-{}", base, suffix);
+                suffix += 1;
+            }
+            let mut project = config.clone();
+            project.project_path = project_path.clone();
+            project.output_dir = config.output_dir.join(&name);
+            project.build_dir = config.build_dir.as_ref().map(|build_dir| build_dir.join(&name));
+            project.archive_output = None;
+            CompileOptions::from(project)
+        })
+        .collect()
+}
+
+fn project_name(project_path: &Path) -> String {
+    std::path::absolute(project_path)
+        .ok()
+        .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "project".to_string())
+}
+
+fn first_line(message: &str) -> &str {
+    message.lines().next().unwrap_or_default()
+}
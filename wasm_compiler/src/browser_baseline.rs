@@ -0,0 +1,124 @@
+//! Browser-compatibility presets (`--browser-baseline`). A baseline sets Emscripten's
+//! `MIN_CHROME_VERSION` / `MIN_FIREFOX_VERSION` / `MIN_SAFARI_VERSION`, so the JS glue avoids
+//! language features the oldest supported browsers lack, and removes the flags enabling
+//! WebAssembly features they cannot run (threads, SIMD, ...) with a warning. Features that a
+//! CMake or Make project enables itself are caught by the validation of the linked module.
+
+use crate::app_config::{AppConfig, BrowserBaseline};
+use crate::wasm_validation::WasmFeature;
+
+/// Minimum browser versions of a baseline; `None` keeps Emscripten's default
+struct MinVersions {
+    chrome: Option<u32>,
+    firefox: Option<u32>,
+    /// In Emscripten's `MMmmpp` form, e.g. 140000 for Safari 14.0
+    safari: Option<u32>,
+}
+
+/// User flags enabling each feature. Entries ending in `*` match as prefixes.
+const FEATURE_FLAGS: &[(WasmFeature, &[&str])] = &[
+    (WasmFeature::Threads, &["-pthread", "-sUSE_PTHREADS*", "-sSHARED_MEMORY*", "-sWASM_WORKERS*"]),
+    (WasmFeature::Simd, &["-msimd128", "-mrelaxed-simd", "-msse*", "-mavx*"]),
+    (WasmFeature::Memory64, &["-sMEMORY64*", "-mwasm64"]),
+    (WasmFeature::Exceptions, &["-fwasm-exceptions"]),
+    (WasmFeature::TailCall, &["-mtail-call"]),
+];
+
+impl BrowserBaseline {
+    pub fn name(self) -> &'static str {
+        match self {
+            BrowserBaseline::Modern => "modern",
+            BrowserBaseline::Baseline2022 => "2022",
+            BrowserBaseline::Baseline2020 => "2020",
+            BrowserBaseline::Safari15 => "safari15",
+            BrowserBaseline::Safari14 => "safari14",
+        }
+    }
+
+    fn min_versions(self) -> MinVersions {
+        match self {
+            BrowserBaseline::Modern => MinVersions { chrome: Some(120), firefox: Some(121), safari: Some(170200) },
+            BrowserBaseline::Baseline2022 => MinVersions { chrome: Some(100), firefox: Some(100), safari: Some(150400) },
+            BrowserBaseline::Baseline2020 => MinVersions { chrome: Some(85), firefox: Some(79), safari: Some(140000) },
+            BrowserBaseline::Safari15 => MinVersions { chrome: None, firefox: None, safari: Some(150200) },
+            BrowserBaseline::Safari14 => MinVersions { chrome: None, firefox: None, safari: Some(140000) },
+        }
+    }
+
+    /// The WebAssembly features every browser of the baseline supports
+    pub fn supported_features(self) -> &'static [WasmFeature] {
+        match self {
+            BrowserBaseline::Modern => &[WasmFeature::Threads, WasmFeature::Simd, WasmFeature::Exceptions],
+            BrowserBaseline::Baseline2022 | BrowserBaseline::Safari15 => &[WasmFeature::Threads, WasmFeature::Exceptions],
+            BrowserBaseline::Baseline2020 | BrowserBaseline::Safari14 => &[],
+        }
+    }
+
+    pub fn supports(self, feature: WasmFeature) -> bool {
+        self.supported_features().contains(&feature)
+    }
+}
+
+/// Whether the build emits native wasm exceptions (`-fwasm-exceptions`); otherwise the build
+/// uses Emscripten's JavaScript-based exceptions (`-fexceptions`), as asm.js builds must.
+pub fn native_exceptions(config: &AppConfig) -> bool {
+    !crate::wasm2js::is_wasm2js(config)
+        && active_baseline(config).is_none_or(|baseline| baseline.supports(WasmFeature::Exceptions))
+}
+
+/// The `--browser-baseline`, unless the build does not target browsers
+fn active_baseline(config: &AppConfig) -> Option<BrowserBaseline> {
+    match config.target_env.to_lowercase().as_str() {
+        "node" | "wasi" => None,
+        _ => config.browser_baseline,
+    }
+}
+
+/// Adds the minimum browser versions of `--browser-baseline` to the emcc flags (unless
+/// already given) and drops the flags enabling features the baseline does not support.
+pub fn apply_browser_baseline(config: &mut AppConfig) -> Result<(), String> {
+    let Some(baseline) = active_baseline(config) else {
+        if config.browser_baseline.is_some() {
+            crate::strict::fallback(config, format!("--browser-baseline has no effect for --target-env {}", config.target_env))?;
+        }
+        return Ok(());
+    };
+
+    let versions = baseline.min_versions();
+    let settings = [
+        ("MIN_CHROME_VERSION", versions.chrome),
+        ("MIN_FIREFOX_VERSION", versions.firefox),
+        ("MIN_SAFARI_VERSION", versions.safari),
+    ];
+    for (setting, version) in settings {
+        let Some(version) = version else { continue };
+        if config.emcc_flag.iter().any(|flag| flag.starts_with(&format!("-s{}=", setting))) {
+            tracing::info!("Keeping the {} given in the emcc flags over --browser-baseline", setting);
+        } else {
+            config.emcc_flag.push(format!("-s{}={}", setting, version));
+        }
+    }
+
+    drop_feature_flags(config, |feature| baseline.supports(feature), &format!("browser baseline '{}'", baseline.name()));
+    Ok(())
+}
+
+/// Drops the user flags enabling features for which `supported` is false, with a warning
+/// naming `target` as what lacks the feature.
+pub fn drop_feature_flags(config: &mut AppConfig, supported: impl Fn(WasmFeature) -> bool, target: &str) {
+    for (feature, patterns) in FEATURE_FLAGS {
+        if supported(*feature) {
+            continue;
+        }
+        config.emcc_flag.retain(|flag| {
+            let enables = patterns.iter().any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => flag.starts_with(prefix),
+                None => flag == pattern,
+            });
+            if enables {
+                tracing::warn!("Dropping {}: {} is not supported by {}", flag, feature, target);
+            }
+            !enables
+        });
+    }
+}
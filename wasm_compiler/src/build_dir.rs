@@ -0,0 +1,180 @@
+//! Where the intermediate build trees of a project go (`build_wasm_cmake`,
+//! `build_wasm_objects`, ...): by default into the project directory, with `--build-dir` into
+//! another directory, e.g. on fast scratch storage or a tmpfs, so read-only checkouts can be
+//! built. With `--ephemeral` the trees are deleted once the build succeeded; without
+//! `--build-dir` they then go to a fresh directory in the system temporary directory, so the
+//! build leaves nothing behind. The trees of a failed build are kept for inspection.
+//!
+//! A read-only project (in a package cache, on a mounted volume) is detected before the build
+//! starts and gets a build directory in the cache directory; nothing is then written into the
+//! project. Make builds next to the sources, so with a build directory it builds a copy of
+//! the project in the `build_wasm_make` tree, refreshed with the files changed or deleted
+//! since.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+use crate::app_config::AppConfig;
+use crate::utils::file_system;
+
+/// Prefix of the names of the intermediate build trees
+pub const BUILD_TREE_PREFIX: &str = "build_wasm";
+
+/// The build tree of the copy of the project Make builds
+pub const MAKE_TREE_NAME: &str = "build_wasm_make";
+
+/// The build trees of an `--ephemeral` build, deleted by [`EphemeralTrees::finish`]
+#[derive(Debug)]
+pub(crate) struct EphemeralTrees {
+    root: PathBuf,
+    /// Whether `root` was created for the build, and is removed with the trees
+    created: bool,
+}
+
+/// Applies the build directory policy to `config`: notes whether the project is read-only,
+/// and without `--build-dir` puts the build trees of a read-only project into the cache
+/// directory and those of an `--ephemeral` build into a fresh temporary directory. Returns
+/// the trees to delete after the build, unless they are kept.
+pub(crate) fn prepare(config: &mut AppConfig) -> Result<Option<EphemeralTrees>, String> {
+    let project = std::path::absolute(&config.project_path).unwrap_or_else(|_| config.project_path.clone());
+    config.read_only_source = project.is_dir() && !is_writable(&project);
+    let ephemeral = config.ephemeral && !config.keep_build_dir;
+    let created = config.build_dir.is_none() && ephemeral;
+    if config.build_dir.is_none() && (ephemeral || config.read_only_source) {
+        let key = file_system::hex_digest(project.to_string_lossy().as_bytes());
+        let name = project.file_name().map_or_else(|| "project".to_string(), |name| name.to_string_lossy().into_owned());
+        let root = match file_system::cache_dir() {
+            // Kept between builds of the read-only project, so they are incremental
+            Some(cache) if !ephemeral => cache.join("builds").join(format!("{}-{}", name, &key[..16])),
+            _ => std::env::temp_dir().join(format!("wasm_compiler-build-{}-{}", std::process::id(), &key[..12])),
+        };
+        config.build_dir = Some(root);
+    }
+    let Some(build_dir) = config.build_dir.clone() else { return Ok(None) };
+    if config.read_only_source {
+        tracing::info!("The project directory {:?} is read-only; building in {:?}", project, build_dir);
+    }
+    file_system::ensure_dir_exists(&build_dir)?;
+    Ok(ephemeral.then_some(EphemeralTrees { root: build_dir, created }))
+}
+
+/// Whether files can be created in `dir`
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".wasm_compiler-write-test-{}", std::process::id()));
+    match std::fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            true
+        }
+        // Left over from an earlier build of this process
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => true,
+        Err(_) => false,
+    }
+}
+
+/// The list of the files copied into the `build_wasm_make` tree by the last build, in the tree
+const MIRRORED_FILES: &str = ".wasm_compiler-mirrored";
+
+/// Copies the files of `project_path` that changed since the last copy into the
+/// `build_wasm_make` tree (of the matrix target or fallback being built) and returns it. The
+/// copies keep their modification times, so make only rebuilds what changed. Copies of files
+/// deleted from the project since are removed; what make built in the tree is kept.
+pub(crate) fn mirror_project(project_path: &Path, config: &AppConfig) -> Result<PathBuf, String> {
+    let mirror = config.target_build_tree(project_path, MAKE_TREE_NAME);
+    let build_root = config.build_root(project_path);
+    let output_dir = std::path::absolute(&config.output_dir).unwrap_or_else(|_| config.output_dir.clone());
+    let entries = WalkDir::new(project_path).into_iter().filter_entry(|entry| {
+        let name = entry.file_name().to_string_lossy();
+        entry.depth() == 0
+            || !(name == ".git" || name.starts_with(BUILD_TREE_PREFIX) || entry.path() == output_dir || entry.path() == build_root)
+    });
+    let mut copied = 0;
+    let mut mirrored: BTreeSet<PathBuf> = BTreeSet::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {:?}: {}", project_path, e))?;
+        let relative = entry.path().strip_prefix(project_path).unwrap_or(entry.path());
+        let target = mirror.join(relative);
+        if entry.file_type().is_dir() {
+            file_system::ensure_dir_exists(&target)?;
+            continue;
+        }
+        // Symbolic links are copied as the files they point to
+        let Ok(metadata) = std::fs::metadata(entry.path()) else { continue };
+        if !metadata.is_file() {
+            continue;
+        }
+        mirrored.insert(relative.to_path_buf());
+        let modified = metadata.modified().map_err(|e| format!("Failed to read {:?}: {}", entry.path(), e))?;
+        let unchanged = std::fs::metadata(&target).is_ok_and(|copy| copy.len() == metadata.len() && copy.modified().ok() == Some(modified));
+        if !unchanged {
+            copy_file(entry.path(), &target, modified).map_err(|e| format!("Failed to copy {:?} to {:?}: {}", entry.path(), target, e))?;
+            copied += 1;
+        }
+    }
+    let removed = remove_deleted_copies(&mirror, &mirrored)?;
+    tracing::info!("Building a copy of the project in {:?} ({} files updated, {} removed)", mirror, copied, removed);
+    Ok(mirror)
+}
+
+/// Removes the copies of the files the last build mirrored that are no longer in `mirrored`,
+/// and records `mirrored` for the next build. Returns the number of copies removed.
+fn remove_deleted_copies(mirror: &Path, mirrored: &BTreeSet<PathBuf>) -> Result<usize, String> {
+    let list = mirror.join(MIRRORED_FILES);
+    let previous = std::fs::read_to_string(&list).unwrap_or_default();
+    let mut removed = 0;
+    for deleted in previous.lines().map(PathBuf::from).filter(|path| !mirrored.contains(path)) {
+        let copy = mirror.join(&deleted);
+        match std::fs::remove_file(&copy) {
+            Ok(()) => {
+                tracing::debug!("Removed {:?}, which was deleted from the project", copy);
+                removed += 1;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to remove {:?}: {}", copy, e)),
+        }
+    }
+    let content: String = mirrored.iter().map(|path| format!("{}\n", path.to_string_lossy())).collect();
+    std::fs::write(&list, content).map_err(|e| format!("Failed to write {:?}: {}", list, e))?;
+    Ok(removed)
+}
+
+/// Copies `source` to `target`, writable even when `source` is not, and dated `modified`
+fn copy_file(source: &Path, target: &Path, modified: SystemTime) -> std::io::Result<()> {
+    let _ = std::fs::remove_file(target);
+    std::fs::copy(source, target)?;
+    let mut permissions = std::fs::metadata(target)?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    std::fs::set_permissions(target, permissions)?;
+    std::fs::OpenOptions::new().write(true).open(target)?.set_modified(modified)
+}
+
+impl EphemeralTrees {
+    /// Deletes the build trees after a successful build
+    pub(crate) fn finish(self, succeeded: bool) {
+        if !succeeded {
+            tracing::info!("Keeping the build trees of the failed build in {:?}", self.root);
+            return;
+        }
+        let Ok(entries) = std::fs::read_dir(&self.root) else { return };
+        let trees = entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(BUILD_TREE_PREFIX) && entry.path().is_dir());
+        for tree in trees {
+            tracing::debug!("Removing the build tree {:?}", tree.path());
+            if let Err(e) = std::fs::remove_dir_all(tree.path()) {
+                tracing::warn!("Failed to remove the build tree {:?}: {}", tree.path(), e);
+            }
+        }
+        if self.created {
+            let _ = std::fs::remove_dir(&self.root);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! The `build-manifest.json` file written to the output directory after every build. It
+//! describes what the build produced so tooling and consumers of the module do not have to
+//! inspect the JS glue.
+
+use serde::Serialize;
+use crate::app_config::{AppConfig, UiKind};
+use crate::exports::{self, ExportedFunction};
+use crate::output_assets::AssetNames;
+use crate::toolchain::{ToolchainSnapshot, ToolchainVersions};
+
+/// File name of the build manifest in the output directory
+pub const BUILD_MANIFEST_FILE_NAME: &str = "build-manifest.json";
+
+#[derive(Debug, Serialize)]
+pub struct BuildManifest {
+    pub output_name: String,
+    /// Output file names, relative to the output directory
+    pub files: ManifestFiles,
+    /// Whether the module was built without an entry point (`--no-entry`)
+    pub no_entry: bool,
+    /// Kind of application, detected or given with `--ui`
+    pub ui: UiKind,
+    /// Functions callable from JavaScript
+    pub exports: Vec<ExportedFunction>,
+    /// Types registered with embind, available as `Module.<name>`
+    pub embind_classes: Vec<String>,
+    /// The versions of the tools the module was built with
+    pub toolchain: ToolchainVersions,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestFiles {
+    pub js: String,
+    pub wasm: Option<String>,
+    pub data: Option<String>,
+    /// Further files loaded by the module, such as pthread worker scripts
+    pub auxiliary: Vec<String>,
+}
+
+impl BuildManifest {
+    pub fn new(config: &AppConfig, assets: &AssetNames) -> Self {
+        BuildManifest {
+            output_name: config.output_name.clone(),
+            files: ManifestFiles {
+                js: assets.js.clone(),
+                // The wasm binary is embedded into the JS glue in single-file builds
+                wasm: (!config.single_file).then(|| assets.wasm.clone()),
+                data: assets.data.clone(),
+                auxiliary: assets.auxiliary.clone(),
+            },
+            no_entry: config.no_entry,
+            ui: crate::ui_kind::resolve(config),
+            exports: exports::known_exports(config),
+            embind_classes: exports::scan_embind_classes(&config.project_path),
+            toolchain: ToolchainSnapshot::collect(config).versions(),
+        }
+    }
+}
+
+/// Writes the build manifest for the finished build to the output directory.
+pub fn write_build_manifest(config: &AppConfig, assets: &AssetNames) -> Result<(), String> {
+    let manifest = BuildManifest::new(config, assets);
+    let content = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize build manifest: {}", e))?;
+    let path = config.output_dir.join(BUILD_MANIFEST_FILE_NAME);
+    std::fs::write(&path, content)
+        .map_err(|e| format!("Failed to write build manifest {:?}: {}", path, e))?;
+    tracing::info!("Wrote build manifest to {:?}", path);
+    Ok(())
+}
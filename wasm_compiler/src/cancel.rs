@@ -0,0 +1,137 @@
+//! Cancellation of blocking builds. A [`CancelHandle`] given to
+//! [`CompileOptions::cancel_handle`](crate::app_config::CompileOptions::cancel_handle) can be
+//! cancelled from another thread: the running command is terminated together with the
+//! processes it started (cmake, ninja, the compilers, node), no further command starts, and
+//! [`compile`](crate::compile) returns [`Error::Cancelled`](crate::Error::Cancelled) after
+//! removing the files the build created in the output directory. The command-line tool cancels
+//! its build this way on Ctrl-C; a second Ctrl-C exits immediately.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use walkdir::WalkDir;
+use crate::build_dir::BUILD_TREE_PREFIX;
+
+/// Cancels the builds it is given to; clones cancel the same builds
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle(Arc<CancelState>);
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    /// Process groups of the commands running for the builds
+    running: Mutex<Vec<u32>>,
+}
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the builds; takes effect while a command runs or before the next one starts
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Notes the process group `group` of a running command until the guard is dropped, so
+    /// [`CancelHandle::kill_running`] reaches it
+    pub(crate) fn track(&self, group: u32) -> RunningGuard {
+        self.0.running.lock().unwrap_or_else(|e| e.into_inner()).push(group);
+        RunningGuard { handle: self.clone(), group }
+    }
+
+    /// Kills the process groups of the running commands at once, for an exit without clean-up
+    #[cfg(feature = "cli")]
+    fn kill_running(&self) {
+        #[cfg(unix)]
+        for group in self.0.running.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+            // SAFETY: kill(2) has no memory-safety preconditions
+            unsafe { libc::kill(-(*group as libc::pid_t), libc::SIGKILL) };
+        }
+    }
+}
+
+/// Keeps a process group in the running commands of a [`CancelHandle`]
+pub(crate) struct RunningGuard {
+    handle: CancelHandle,
+    group: u32,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        let mut running = self.handle.0.running.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = running.iter().position(|group| *group == self.group) {
+            running.swap_remove(index);
+        }
+    }
+}
+
+/// A handle cancelled by Ctrl-C. The first Ctrl-C cancels the build, the second kills the
+/// running tools, which are in process groups of their own and do not get the Ctrl-C, and
+/// exits without cleaning up, with the status of a process killed by SIGINT.
+#[cfg(feature = "cli")]
+pub(crate) fn cancel_on_ctrl_c() -> CancelHandle {
+    let handle = CancelHandle::new();
+    let cancelled = handle.clone();
+    let installed = ctrlc::set_handler(move || {
+        if cancelled.is_cancelled() {
+            cancelled.kill_running();
+            std::process::exit(130);
+        }
+        eprintln!("Cancelling the build (press Ctrl-C again to exit immediately)");
+        cancelled.cancel();
+    });
+    if let Err(e) = installed {
+        tracing::debug!("Cannot cancel builds on Ctrl-C: {}", e);
+    }
+    handle
+}
+
+/// The files and directories of the output directory before a build, to tell the ones a
+/// cancelled build created
+#[derive(Debug)]
+pub(crate) struct OutputSnapshot {
+    output_dir: PathBuf,
+    existed: HashSet<PathBuf>,
+}
+
+impl OutputSnapshot {
+    /// Records what `output_dir` holds now. Build trees and `.git` are left out: they are not
+    /// outputs, even when the output directory is the project directory.
+    pub(crate) fn take(output_dir: &Path) -> Self {
+        let existed = walk(output_dir).map(|entry| entry.into_path()).collect();
+        OutputSnapshot { output_dir: output_dir.to_path_buf(), existed }
+    }
+
+    /// Removes the files and directories the cancelled build created in the output directory.
+    /// Files that were there before are kept, even if the build overwrote them.
+    pub(crate) fn remove_new_outputs(&self) {
+        let created: Vec<walkdir::DirEntry> = walk(&self.output_dir).filter(|entry| !self.existed.contains(entry.path())).collect();
+        for entry in created.iter().filter(|entry| !entry.file_type().is_dir()) {
+            tracing::debug!("Removing {:?} of the cancelled build", entry.path());
+            if let Err(e) = std::fs::remove_file(entry.path()) {
+                tracing::warn!("Failed to remove {:?} of the cancelled build: {}", entry.path(), e);
+            }
+        }
+        // Deepest first; directories holding files the build did not create stay
+        for entry in created.iter().rev().filter(|entry| entry.file_type().is_dir()) {
+            let _ = std::fs::remove_dir(entry.path());
+        }
+    }
+}
+
+/// The entries of `output_dir`, itself included, parents before their contents
+fn walk(output_dir: &Path) -> impl Iterator<Item = walkdir::DirEntry> {
+    WalkDir::new(output_dir)
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.depth() == 0 || !(name == ".git" || name.starts_with(BUILD_TREE_PREFIX))
+        })
+        .filter_map(Result::ok)
+}
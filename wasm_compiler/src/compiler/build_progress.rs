@@ -0,0 +1,33 @@
+//! Progress of CMake builds, read from the status lines the build tool prints for every
+//! step: `[12/345] Building CXX object CMakeFiles/app.dir/src/main.cpp.o` with Ninja, and
+//! `[ 45%] Building CXX object CMakeFiles/app.dir/src/main.cpp.o` with CMake's Makefiles,
+//! whose progress is only known as a percentage.
+
+use crate::events::BuildProgress;
+
+/// Parses a status line of Ninja's default format (`[%f/%t] <description>`) or of CMake's
+/// Makefiles (`[ 45%] <description>`).
+pub fn parse_status_line(line: &str) -> Option<BuildProgress> {
+    let (counts, description) = line.strip_prefix('[')?.split_once("] ")?;
+    let (completed, total) = match counts.split_once('/') {
+        Some((completed, total)) => (completed.trim().parse().ok()?, total.trim().parse().ok()?),
+        None => (counts.trim().strip_suffix('%')?.parse().ok()?, 100),
+    };
+    Some(BuildProgress { completed, total, description: describe_step(description) })
+}
+
+/// Shortens CMake's step descriptions to e.g. `compiling main.cpp` and `linking app.js`
+fn describe_step(description: &str) -> String {
+    let file_name = |path: &str| path.rsplit('/').next().unwrap_or(path).to_string();
+    if let Some(object) = description
+        .strip_prefix("Building CXX object ")
+        .or_else(|| description.strip_prefix("Building C object "))
+    {
+        format!("compiling {}", file_name(object.trim_end_matches(".o").trim_end_matches(".obj")))
+    } else if let Some(rest) = description.strip_prefix("Linking ") {
+        // `Linking CXX executable app.js`, `Linking CXX static library libcore.a`
+        format!("linking {}", file_name(rest.rsplit(' ').next().unwrap_or(rest)))
+    } else {
+        description.trim().to_string()
+    }
+}
@@ -0,0 +1,256 @@
+//! The targets of a CMake project, read from CMake's File API. Before configuring, a query
+//! for the `codemodel-v2` object is placed in the build tree; CMake (3.14 and later) answers
+//! it with JSON files under `.cmake/api/v1/reply` describing every target: its type, its
+//! sources, the targets it depends on and the files it produces. The handler uses it to
+//! build only the executable the output belongs to and to find the files that executable
+//! produced, and `--target-graph` writes it out as JSON and as a graphviz graph.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::app_config::AppConfig;
+
+/// File name of the `--target-graph` JSON report in the output directory
+pub const GRAPH_JSON_FILE_NAME: &str = "target-graph.json";
+/// File name of the `--target-graph` graphviz graph in the output directory
+pub const GRAPH_DOT_FILE_NAME: &str = "target-graph.dot";
+
+/// Target type of executables in the codemodel
+const EXECUTABLE: &str = "EXECUTABLE";
+
+/// The targets of one build configuration
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CodeModel {
+    /// Build configuration the targets were read for, e.g. `Release`
+    pub configuration: String,
+    pub targets: Vec<Target>,
+}
+
+/// One target of the project
+#[derive(Serialize, Debug, Clone)]
+pub struct Target {
+    pub name: String,
+    /// `EXECUTABLE`, `STATIC_LIBRARY`, `SHARED_LIBRARY`, `MODULE_LIBRARY`, `OBJECT_LIBRARY`,
+    /// `INTERFACE_LIBRARY` or `UTILITY`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Source files, relative to the source directory where they are inside it
+    pub sources: Vec<String>,
+    /// Names of the targets it depends on (link dependencies and `add_dependencies`)
+    pub dependencies: Vec<String>,
+    /// Files the target produces, absolute; for executables the JS glue
+    pub artifacts: Vec<PathBuf>,
+}
+
+impl Target {
+    pub fn is_executable(&self) -> bool {
+        self.kind == EXECUTABLE
+    }
+}
+
+#[derive(Deserialize)]
+struct ReplyIndex {
+    reply: HashMap<String, ReplyObject>,
+}
+
+#[derive(Deserialize)]
+struct ReplyObject {
+    #[serde(rename = "jsonFile")]
+    json_file: String,
+}
+
+#[derive(Deserialize)]
+struct CodeModelReply {
+    paths: ReplyPaths,
+    configurations: Vec<ConfigurationReply>,
+}
+
+#[derive(Deserialize)]
+struct ReplyPaths {
+    build: PathBuf,
+    source: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct ConfigurationReply {
+    name: String,
+    targets: Vec<TargetReference>,
+}
+
+#[derive(Deserialize)]
+struct TargetReference {
+    #[serde(rename = "jsonFile")]
+    json_file: String,
+}
+
+#[derive(Deserialize)]
+struct TargetReply {
+    name: String,
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    sources: Vec<PathEntry>,
+    #[serde(default)]
+    dependencies: Vec<IdEntry>,
+    #[serde(default)]
+    artifacts: Vec<PathEntry>,
+}
+
+#[derive(Deserialize)]
+struct PathEntry {
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct IdEntry {
+    id: String,
+}
+
+fn reply_dir(build_dir: &Path) -> PathBuf {
+    build_dir.join(".cmake").join("api").join("v1").join("reply")
+}
+
+/// Asks CMake to write the codemodel at the next configure of `build_dir`.
+pub fn write_query(build_dir: &Path) -> Result<(), String> {
+    let query_dir = build_dir.join(".cmake").join("api").join("v1").join("query");
+    std::fs::create_dir_all(&query_dir)
+        .map_err(|e| format!("Failed to create CMake File API query directory {:?}: {}", query_dir, e))?;
+    let query = query_dir.join("codemodel-v2");
+    std::fs::write(&query, "").map_err(|e| format!("Failed to write CMake File API query {:?}: {}", query, e))
+}
+
+/// Reads the codemodel CMake wrote while configuring `build_dir`, for the configuration
+/// `build_type` (or the only one, for single-configuration generators).
+pub fn read_codemodel(build_dir: &Path, build_type: &str) -> Result<CodeModel, String> {
+    let reply_dir = reply_dir(build_dir);
+    // Each configure writes a new index; the newest one sorts last
+    let index_path = std::fs::read_dir(&reply_dir)
+        .map_err(|e| format!("No CMake File API reply in {:?} (CMake 3.14 or later is needed): {}", reply_dir, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("index-") && name.ends_with(".json"))
+        })
+        .max()
+        .ok_or_else(|| format!("No CMake File API reply index in {:?}", reply_dir))?;
+    let index: ReplyIndex = read_json(&index_path)?;
+    let codemodel_file = index.reply
+        .get("codemodel-v2")
+        .ok_or_else(|| format!("{:?} has no codemodel-v2 reply", index_path))?;
+    let codemodel: CodeModelReply = read_json(&reply_dir.join(&codemodel_file.json_file))?;
+
+    let configuration = codemodel.configurations
+        .iter()
+        .find(|configuration| configuration.name.eq_ignore_ascii_case(build_type))
+        .or_else(|| codemodel.configurations.first())
+        .ok_or_else(|| "The CMake codemodel has no configurations".to_string())?;
+    let replies = configuration.targets
+        .iter()
+        .map(|target| read_json::<TargetReply>(&reply_dir.join(&target.json_file)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let names: HashMap<&str, &str> = replies.iter().map(|target| (target.id.as_str(), target.name.as_str())).collect();
+    let targets = replies
+        .iter()
+        .map(|target| Target {
+            name: target.name.clone(),
+            kind: target.kind.clone(),
+            sources: target.sources
+                .iter()
+                .map(|source| {
+                    let path = source.path.strip_prefix(&codemodel.paths.source).unwrap_or(&source.path);
+                    path.to_string_lossy().replace('\\', "/")
+                })
+                .collect(),
+            dependencies: target.dependencies
+                .iter()
+                .filter_map(|dependency| names.get(dependency.id.as_str()).map(|name| name.to_string()))
+                .collect(),
+            artifacts: target.artifacts.iter().map(|artifact| codemodel.paths.build.join(&artifact.path)).collect(),
+        })
+        .collect();
+    Ok(CodeModel { configuration: configuration.name.clone(), targets })
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, String> {
+    let content = std::fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    serde_json::from_slice(&content).map_err(|e| format!("Malformed CMake File API reply {:?}: {}", path, e))
+}
+
+impl CodeModel {
+    pub fn executables(&self) -> impl Iterator<Item = &Target> {
+        self.targets.iter().filter(|target| target.is_executable())
+    }
+
+    /// The executables that are not tests; all executables when every one is a test
+    pub fn app_executables(&self) -> impl Iterator<Item = &Target> {
+        let all_tests = self.executables().all(is_test);
+        self.executables().filter(move |target| all_tests || !is_test(target))
+    }
+
+    /// The executable the output is built from: the one named like the output, otherwise the
+    /// only executable that is not a test, otherwise the first executable (an error with
+    /// `--strict`). `None` if the project has no executables.
+    pub fn select_executable(&self, config: &AppConfig) -> Result<Option<&Target>, String> {
+        if let Some(target) = self.executables().find(|target| target.name == crate::wasm2js::target_name(config)) {
+            return Ok(Some(target));
+        }
+        let candidates: Vec<&Target> = self.executables().filter(|target| !is_test(target)).collect();
+        match candidates.as_slice() {
+            [target] => Ok(Some(target)),
+            _ => {
+                let Some(target) = candidates.first().copied().or_else(|| self.executables().next()) else {
+                    return Ok(None);
+                };
+                crate::strict::fallback(
+                    config,
+                    format!(
+                        "The CMake project has several executables ({}); building {}. Pass --output-name <TARGET> to pick another, or --all-executables to build them all",
+                        self.executables().map(|target| target.name.as_str()).collect::<Vec<_>>().join(", "),
+                        target.name
+                    ),
+                )?;
+                Ok(Some(target))
+            }
+        }
+    }
+
+    /// The graph in graphviz's DOT language: executables are boxes, libraries ellipses, and
+    /// edges point from a target to its dependencies
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph targets {\n    rankdir=LR;\n");
+        for target in &self.targets {
+            let shape = if target.is_executable() { "box" } else { "ellipse" };
+            let _ = writeln!(
+                dot,
+                "    \"{}\" [shape={}, label=\"{}\\n{} ({} sources)\"];",
+                target.name, shape, target.name, target.kind.to_lowercase(), target.sources.len()
+            );
+            for dependency in &target.dependencies {
+                let _ = writeln!(dot, "    \"{}\" -> \"{}\";", target.name, dependency);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Test executables are named `*test*` or have their sources in a `test`/`tests` directory
+fn is_test(target: &Target) -> bool {
+    target.name.to_lowercase().contains("test")
+        || target.sources.iter().any(|source| source.split('/').any(|part| part == "test" || part == "tests"))
+}
+
+/// Writes the `--target-graph` report to `output_dir`.
+pub fn write_graph(codemodel: &CodeModel, output_dir: &Path) -> Result<(), String> {
+    let json_path = output_dir.join(GRAPH_JSON_FILE_NAME);
+    let json = serde_json::to_string_pretty(codemodel).map_err(|e| format!("Failed to serialize target graph: {}", e))?;
+    std::fs::write(&json_path, json).map_err(|e| format!("Failed to write {:?}: {}", json_path, e))?;
+    let dot_path = output_dir.join(GRAPH_DOT_FILE_NAME);
+    std::fs::write(&dot_path, codemodel.to_dot()).map_err(|e| format!("Failed to write {:?}: {}", dot_path, e))?;
+    tracing::info!("Wrote the graph of {} CMake target(s) to {:?} and {:?}", codemodel.targets.len(), json_path, dot_path);
+    Ok(())
+}
@@ -1,146 +1,71 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use crate::app_config::AppConfig;
+use crate::events::Phase;
 use super::BuildSystemHandler;
+use super::error::{CompileError, ConfigureError};
 use super::emscripten_runner::EmscriptenRunner; // Import EmscriptenRunner
+use super::cmake_file_api::{self, CodeModel};
+use super::compile_commands;
+use super::emcc_flags::EmccFlags;
+use super::include_check;
+use super::system_libraries;
+use super::ninja;
 use crate::utils::file_system;
+use crate::utils::flag_set::FlagSet;
 use crate::utils::command_runner::resolve_emscripten_tool;
 
 pub struct CMakeHandler;
 
 impl BuildSystemHandler for CMakeHandler {
-    fn detect(project_path: &Path) -> bool {
+    fn name(&self) -> &str {
+        "CMake"
+    }
+
+    fn detect(&self, project_path: &Path) -> bool {
         project_path.join("CMakeLists.txt").exists()
     }
 
-    fn compile(&self, project_path: &Path, config: &AppConfig) -> Result<(), String> {
-        log::info!("Compiling project with CMake at: {:?}", project_path);
-        if !Self::detect(project_path) {
-            return Err("CMakeLists.txt not found.".to_string());
+    fn compile(&self, project_path: &Path, config: &AppConfig) -> Result<(), CompileError> {
+        tracing::info!("Compiling project with CMake at: {:?}", project_path);
+        if !self.detect(project_path) {
+            return Err(CompileError::Other("CMakeLists.txt not found.".to_string()));
         }
 
         // Check if any library handler can handle this project
-        if let Some(library_handler) = super::library_handlers::detect_library_handler(project_path) {
-            log::info!("Detected {} library, delegating to specialized handler", library_handler.library_name());
+        if let Some(library_handler) = crate::ambiguity::choose_library(config, project_path)? {
+            tracing::info!("Detected {} library, delegating to specialized handler", library_handler.library_name());
+            let _phase = config.enter_phase(Phase::Build);
+            let _span = tracing::info_span!("library", handler = library_handler.library_name()).entered();
             return library_handler.compile(project_path, config);
         }
 
         // If no library handler is found, proceed with generic CMake compilation
-        log::info!("No specific library detected, proceeding with generic CMake compilation");
+        tracing::info!("No specific library detected, proceeding with generic CMake compilation");
 
-        let build_dir_name = "build_wasm_cmake"; // More specific name
-        let build_dir = project_path.join(build_dir_name);
-        file_system::ensure_dir_exists(&build_dir)?;
         file_system::ensure_dir_exists(&config.output_dir)?; // Ensure final output dir exists
-
-        // 1. Configure with emcmake
-        // `emcmake cmake <path_to_source> -B<path_to_build_dir> [options]`
-        let mut cmake_args: Vec<String> = Vec::new();
-        cmake_args.push(project_path.to_string_lossy().into_owned());
-        cmake_args.push(format!("-DCMAKE_BUILD_TYPE={}", config.build_config));
-
-        // Add Emscripten specific CMake flags. These flags are passed to CMake,
-        // which then uses them to configure the Emscripten toolchain.
-        // The actual emcc flags for compiling sources will be mostly handled by Emscripten's toolchain file.
-        // However, we might want to pass some high-level options.
-        // For example, if linking to specific libraries or setting definitions.
-        // cmake_args.push(format!("-DEMSCRIPTEN_OUTPUT_NAME={}", config.output_name));
-
-        // If using `-s` flags directly with emcmake, they might not always propagate as expected.
-        // It's usually better to set these via CMAKE_CXX_FLAGS or target_link_options in CMakeLists.txt
-        // or rely on Emscripten's toolchain defaults.
-        // However, some global `-s` flags can be passed via EMMAKEN_CFLAGS or EMMAKEN_LDFLAGS environment variables
-        // or by setting CMAKE_EXE_LINKER_FLAGS.
-
-        // Example of setting linker flags that contain Emscripten -s options:
-        // Note: This is one way; using a custom toolchain file or modifying CMakeLists.txt is often cleaner.
-        let mut emcc_link_flags = Vec::new();
-        // emcc_link_flags.push("-sALLOW_MEMORY_GROWTH=1".to_string());
-        emcc_link_flags.push(format!("-sMODULARIZE=1"));
-        emcc_link_flags.push(format!("-sEXPORT_ES6=1"));
-        emcc_link_flags.push(format!("-sENVIRONMENT={}", match config.target_env.to_lowercase().as_str() {
-            "web" => "web",
-            "node" => "node",
-            _ => "web,node" // Default
-        }));
-        emcc_link_flags.push(format!("-sEXPORTED_RUNTIME_METHODS=FS,callMain,setValue,getValue,UTF8ToString,stringToUTF8"));
-        emcc_link_flags.push(format!("-o"));
-        let output_js_in_build_dir = build_dir.join(format!("{}.js", config.output_name));
-        emcc_link_flags.push(output_js_in_build_dir.to_string_lossy().into_owned());
-        // Note: WASM_BINARY_NAME is not a valid setting, the .wasm file will be automatically named based on the .js output
-
-
-        match config.build_config.to_lowercase().as_str() {
-            "debug" => {
-                emcc_link_flags.push("-g4".to_string());
-                emcc_link_flags.push("-O0".to_string());
-                emcc_link_flags.push("-sASSERTIONS=2".to_string());
-            }
-            "release" => {
-                emcc_link_flags.push("-O3".to_string());
-                // Note: --llvm-lto is deprecated and ignored in newer Emscripten versions
-                // LTO is enabled by default in -O3 builds
-                emcc_link_flags.push("-sASSERTIONS=0".to_string());
-            }
-            _ => {
-                emcc_link_flags.push("-O2".to_string());
-                emcc_link_flags.push("-sASSERTIONS=1".to_string());
-            }
-        }
-
-        if let Some(user_flags) = &config.emcc_flags {
-            for flag in user_flags.split_whitespace() {
-                emcc_link_flags.push(flag.to_string());
+        let phase = config.enter_phase(Phase::Configure);
+        let (build_dir, codemodel) = configure(project_path, config)?;
+        // The compile database is always written to the build tree, where the include
+        // preflight reads it; --no-compile-commands only keeps it out of the project
+        if !config.no_compile_commands {
+            // A read-only project keeps it in the build tree
+            if config.read_only_source {
+                tracing::debug!("{} is in {:?}", compile_commands::FILE_NAME, build_dir);
+            } else if let Err(e) = compile_commands::copy_from_build_dir(&build_dir, project_path) {
+                tracing::warn!("{}", e);
             }
         }
-
-        // Setting CMAKE_EXE_LINKER_FLAGS to pass these flags to the linker invocation
-        // This is generally more reliable for -s flags than trying to pass them as compiler flags.
-
-        // Add ImGui specific flags if enabled
-        if config.with_imgui {
-            log::info!("ImGui support enabled for CMake, adding specific linker flags.");
-            emcc_link_flags.push("-sUSE_GLFW=3".to_string());
-            emcc_link_flags.push("-sUSE_WEBGL2=1".to_string());
-            emcc_link_flags.push("-sFULL_ES3=1".to_string());
-            emcc_link_flags.push("-sGL_ENABLE_GET_PROC_ADDRESS=1".to_string());
-            emcc_link_flags.push("-sALLOW_MEMORY_GROWTH=1".to_string());
-            if !config.emcc_flags.as_deref().unwrap_or("").contains("EXPORT_NAME") &&
-               !emcc_link_flags.iter().any(|arg| arg.contains("EXPORT_NAME")) {
-                 emcc_link_flags.push("-sEXPORT_NAME='Module'".to_string());
-            }
-            emcc_link_flags.push("-sUSE_SDL=2".to_string());
-            emcc_link_flags.push("-sINITIAL_MEMORY=67108864".to_string());
-            if config.build_config.to_lowercase().as_str() == "debug" {
-                 emcc_link_flags.push("-sGL_ASSERTIONS=1".to_string());
-            }
+        include_check::check_compile_database(project_path, &build_dir, config)?;
+        if let Some(codemodel) = codemodel.as_ref().filter(|_| config.target_graph) {
+            cmake_file_api::write_graph(codemodel, &config.output_dir)?;
         }
-
-        // Ensure user-provided emcc_flags are added (and de-duplicated if already added by ImGui)
-        if let Some(user_flags) = &config.emcc_flags {
-            for flag_str in user_flags.split_whitespace() {
-                if !emcc_link_flags.contains(&flag_str.to_string()) {
-                    emcc_link_flags.push(flag_str.to_string());
-                }
-            }
-        }
-
-        cmake_args.push(format!("-DCMAKE_EXE_LINKER_FLAGS={}", emcc_link_flags.join(" ")));
-        // Alternative: Set CMAKE_CXX_FLAGS for compiler-specific flags, CMAKE_C_FLAGS for C
-        // cmake_args.push(format!("-DCMAKE_CXX_FLAGS_INIT=\"{}\"", compiler_flags_str));
-
-        log::debug!("Running emcmake cmake with args: {:?}", cmake_args.join(" "));
-        EmscriptenRunner::run_emscripten_tool(
-            &resolve_emscripten_tool("emcmake"),
-            &[
-                "cmake".to_string()
-            ]
-            .iter()
-            .chain(cmake_args.iter())
-            .cloned()
-            .collect::<Vec<String>>(),
-            &build_dir,
-            config,
-        )?;
+        // Only the executable the output comes from is built, so other executables (tests,
+        // tools) cannot slow down or break the build; projects without executables build
+        // everything
+        let target = match &codemodel {
+            Some(codemodel) => codemodel.select_executable(config)?,
+            None => None,
+        };
 
         // 2. Build with emmake or directly with chosen generator (e.g., ninja)
         // `emmake make` or `cmake --build .` if Ninja or another generator is used
@@ -155,8 +80,8 @@ impl BuildSystemHandler for CMakeHandler {
         // For multi-config generators (like Visual Studio), `--config` in build step is used.
         // For emscripten with Makefiles/Ninja, CMAKE_BUILD_TYPE is usually sufficient.
 
-        let build_tool_args = vec!["--build".to_string(), ".".to_string(), "--config".to_string(), config.build_config.clone()];
-        log::debug!("Running cmake --build with args: {:?}", build_tool_args.join(" "));
+        let build_tool_args = ["--build".to_string(), ".".to_string(), "--config".to_string(), config.build_config.clone()];
+        tracing::debug!("Running cmake --build with args: {:?}", build_tool_args.join(" "));
         // We need to run this build command also within an emscripten environment,
         // so `emcc`/`em++` are used as compilers by `make` or `ninja`.
         // `emcmake` sets up the environment for `cmake` to generate the build files correctly.
@@ -171,52 +96,71 @@ impl BuildSystemHandler for CMakeHandler {
         // For now, stick to `emmake make` if makefiles are default, or `cmake --build .` and hope emcc is picked up.
         // Let's try `emmake make` first.
 
-        let make_args = vec!["make".to_string()]; // Add verbosity or specific targets if needed e.g. "VERBOSE=1"
-        log::debug!("Running emmake make with args: {:?}", make_args.join(" "));
+        drop(phase);
+        let _phase = config.enter_phase(Phase::Build);
+        // Ninja build trees (--cmake-generator Ninja, or CMAKE_GENERATOR) are built with ninja,
+        // which reports its progress
+        let ninja = ninja::is_ninja_build(&build_dir);
+        let mut make_args = vec![config.tool_program(if ninja { "ninja" } else { "make" })]; // Add verbosity if needed e.g. "VERBOSE=1"
+        if let Some(target) = target {
+            tracing::info!("Building CMake target {}", target.name);
+            make_args.push(target.name.clone());
+        }
+        let ninja_log_length = ninja::log_length(&build_dir);
+        tracing::debug!("Running emmake with args: {:?}", make_args.join(" "));
         EmscriptenRunner::run_emscripten_tool(
             &resolve_emscripten_tool("emmake"),
             &make_args,
             &build_dir,
             config,
         )?;
+        if ninja {
+            let slowest = ninja::slowest_steps(&build_dir, ninja_log_length, 3);
+            if !slowest.is_empty() {
+                let steps: Vec<String> = slowest
+                    .iter()
+                    .map(|(output, duration)| format!("{} ({:.1} s)", output, duration.as_secs_f64()))
+                    .collect();
+                tracing::info!("Slowest build steps: {}", steps.join(", "));
+            }
+        }
 
-        log::info!("CMake project built successfully in {:?}", build_dir);
+        tracing::info!("CMake project built successfully in {:?}", build_dir);
 
         // 3. Copy artifacts to the final output directory
-        // The output name from emcc flags was set to `build_dir/output_name.js` and `.wasm`
-        let src_js = output_js_in_build_dir.clone();
-        let src_wasm = build_dir.join(format!("{}.wasm", config.output_name));
-        // let src_html = build_dir.join(format!("{}.html", config.output_name)); // If emcc generated one
-
-        let dest_js = config.output_dir.join(format!("{}.js", config.output_name));
-        let dest_wasm = config.output_dir.join(format!("{}.wasm", config.output_name));
-        // let dest_html = config.output_dir.join(format!("{}.html", config.output_name));
-
-        if src_js.exists() {
-            std::fs::copy(&src_js, &dest_js)
-                .map_err(|e| format!("Failed to copy JS file {:?} to {:?}: {}", src_js, dest_js, e))?;
-            log::info!("Copied {:?} to {:?}", src_js, dest_js);
-        } else {
-            return Err(format!("Expected JS output file not found: {:?}", src_js));
+        // The File API tells where the target's JS glue is, whatever its OUTPUT_NAME or
+        // RUNTIME_OUTPUT_DIRECTORY; without it the glue is expected at `build_dir/output_name.js`
+        let glue = target.and_then(|target| {
+            target.artifacts.iter().find(|artifact| artifact.extension().is_some_and(|extension| extension == "js"))
+        });
+        match glue {
+            Some(glue) => EmscriptenRunner::copy_outputs_from(glue, config)?,
+            None => {
+                let expected = build_dir.join(format!("{}.js", crate::wasm2js::target_name(config)));
+                if !expected.exists() {
+                    return Err(CompileError::Other(format!(
+                        "No {:?}: without CMake's File API (CMake 3.14 or later) the output is expected where CMake \
+                         puts the executable target named like it. Pass --output-name <TARGET> and leave \
+                         RUNTIME_OUTPUT_DIRECTORY unset",
+                        expected
+                    )));
+                }
+                EmscriptenRunner::copy_outputs_from(&expected, config)?
+            }
         }
 
-        if src_wasm.exists() {
-            std::fs::copy(&src_wasm, &dest_wasm)
-                .map_err(|e| format!("Failed to copy WASM file {:?} to {:?}: {}", src_wasm, dest_wasm, e))?;
-            log::info!("Copied {:?} to {:?}", src_wasm, dest_wasm);
-        } else {
-            // Some emcc configurations might embed WASM in JS, or not produce a separate .wasm if only a .js target is specified.
-            // Our flags (-sWASM_BINARY_NAME) should ensure a separate .wasm file.
-            return Err(format!("Expected WASM output file not found: {:?}", src_wasm));
-        }
+        tracing::info!("Successfully compiled CMake project. Output in {:?}", config.output_dir);
+        Ok(())
+    }
 
-        // if src_html.exists() {
-        //     std::fs::copy(&src_html, &dest_html)
-        //         .map_err(|e| format!("Failed to copy HTML file: {}", e))?;
-        // }
+    fn reports_phases(&self) -> bool {
+        true
+    }
+}
 
-        log::info!("Successfully compiled CMake project. Output in {:?}", config.output_dir);
-        Ok(())
+impl Default for CMakeHandler {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -224,4 +168,117 @@ impl CMakeHandler {
     pub fn new() -> Self {
         CMakeHandler
     }
-}
\ No newline at end of file
+}
+
+/// Configures the project with emcmake in its CMake build tree, and returns the tree and the
+/// targets CMake reported, if it could
+fn configure(project_path: &Path, config: &AppConfig) -> Result<(PathBuf, Option<CodeModel>), CompileError> {
+    let build_dir = config.target_build_tree(project_path, "build_wasm_cmake");
+    file_system::ensure_dir_exists(&build_dir)?;
+
+    // 1. Configure with emcmake
+    // `emcmake cmake <path_to_source> -B<path_to_build_dir> [options]`
+    let mut cmake_args: Vec<String> = Vec::new();
+    cmake_args.push(project_path.to_string_lossy().into_owned());
+    cmake_args.push(format!("-DCMAKE_BUILD_TYPE={}", config.build_config));
+    if let Some(generator) = &config.cmake_generator {
+        cmake_args.push("-G".to_string());
+        cmake_args.push(generator.clone());
+    }
+    cmake_args.push("-DCMAKE_EXPORT_COMPILE_COMMANDS=ON".to_string());
+    // A pinned make or ninja (--make-path, --ninja-path) is the one CMake generates for
+    let generator = config.cmake_generator.clone().or_else(|| std::env::var("CMAKE_GENERATOR").ok());
+    let build_program = if generator.is_some_and(|generator| generator.starts_with("Ninja")) { "ninja" } else { "make" };
+    if let Some(program) = config.tool_path(build_program) {
+        cmake_args.push(format!("-DCMAKE_MAKE_PROGRAM={}", program.to_string_lossy()));
+    }
+
+    // Add Emscripten specific CMake flags. These flags are passed to CMake,
+    // which then uses them to configure the Emscripten toolchain.
+    // The actual emcc flags for compiling sources will be mostly handled by Emscripten's toolchain file.
+    // However, we might want to pass some high-level options.
+    // For example, if linking to specific libraries or setting definitions.
+    // cmake_args.push(format!("-DEMSCRIPTEN_OUTPUT_NAME={}", config.output_name));
+
+    // If using `-s` flags directly with emcmake, they might not always propagate as expected.
+    // It's usually better to set these via CMAKE_CXX_FLAGS or target_link_options in CMakeLists.txt
+    // or rely on Emscripten's toolchain defaults.
+    // However, some global `-s` flags can be passed via EMMAKEN_CFLAGS or EMMAKEN_LDFLAGS environment variables
+    // or by setting CMAKE_EXE_LINKER_FLAGS.
+
+    // Settings (-s) and libraries go into CMAKE_EXE_LINKER_FLAGS, include dirs and defines
+    // into the compile flags of every C and C++ source, optimization and exception
+    // handling into both (see EmccFlags).
+    // No -o: CMake links each executable to its own output (see step 3), and names the
+    // .wasm file after the .js output
+    let emcc_flags = EmccFlags::from_config(config);
+    cmake_args.push(format!("-DCMAKE_EXE_LINKER_FLAGS={}", emcc_flags.link_flags().to_cmake_value()));
+    // -Werror in CMAKE_<LANG>_FLAGS would also fail CMake's own check builds (try_compile) on
+    // their warnings; CMAKE_COMPILE_WARNING_AS_ERROR (CMake 3.24 or later) applies it to the
+    // targets of the project only
+    let all_compile_flags = emcc_flags.compile_flags();
+    let compile_flags: FlagSet = all_compile_flags.iter().filter(|flag| *flag != "-Werror").cloned().collect();
+    if compile_flags.len() < all_compile_flags.len() {
+        cmake_args.push("-DCMAKE_COMPILE_WARNING_AS_ERROR=ON".to_string());
+    }
+    if !compile_flags.is_empty() {
+        cmake_args.push(format!("-DCMAKE_CXX_FLAGS={}", compile_flags.to_cmake_value()));
+        cmake_args.push(format!("-DCMAKE_C_FLAGS={}", compile_flags.to_cmake_value()));
+    }
+    // Alternative: Set CMAKE_CXX_FLAGS for compiler-specific flags, CMAKE_C_FLAGS for C
+    // cmake_args.push(format!("-DCMAKE_CXX_FLAGS_INIT=\"{}\"", compiler_flags_str));
+
+    // Native libraries the project links by name (GL, curl, pthread, ...) become imported
+    // targets carrying their Emscripten equivalent (see system_libraries)
+    match system_libraries::cmake_link_map_args(project_path, &build_dir, &config.project_config.link_map) {
+        Ok(args) => cmake_args.extend(args),
+        Err(e) => tracing::warn!("{}", e),
+    }
+
+    // Cached clones of the FetchContent dependencies (--prefetch-content)
+    cmake_args.extend(crate::prefetch::fetch_content_args(project_path, config)?);
+
+    // Ask CMake for the codemodel, to know which targets there are
+    if let Err(e) = cmake_file_api::write_query(&build_dir) {
+        tracing::warn!("{}", e);
+    }
+
+    tracing::debug!("Running emcmake cmake with args: {:?}", cmake_args.join(" "));
+    EmscriptenRunner::run_emscripten_tool(
+        &resolve_emscripten_tool("emcmake"),
+        &[
+            config.tool_program("cmake")
+        ]
+        .iter()
+        .chain(cmake_args.iter())
+        .cloned()
+        .collect::<Vec<String>>(),
+        &build_dir,
+        config,
+    )
+    .map_err(ConfigureError::from)?;
+
+    let codemodel = match cmake_file_api::read_codemodel(&build_dir, &config.build_config) {
+        Ok(codemodel) => Some(codemodel),
+        Err(e) if config.target_graph => {
+            tracing::warn!("Cannot write the target graph: {}", e);
+            None
+        }
+        Err(e) => {
+            tracing::debug!("Building every target: {}", e);
+            None
+        }
+    };
+    Ok((build_dir, codemodel))
+}
+
+/// The names of the executables of the CMake project at `project_path` that are not tests,
+/// the outputs of `--all-executables`. Configures the project to ask CMake for them.
+pub(crate) fn app_executables(project_path: &Path, config: &AppConfig) -> Result<Vec<String>, CompileError> {
+    let _phase = config.enter_phase(Phase::Configure);
+    let (_, codemodel) = configure(project_path, config)?;
+    let codemodel = codemodel.ok_or_else(|| {
+        CompileError::Other("--all-executables needs the targets from CMake's File API (CMake 3.14 or later)".to_string())
+    })?;
+    Ok(codemodel.app_executables().map(|target| target.name.clone()).collect())
+}
@@ -0,0 +1,173 @@
+//! `compile_commands.json` in the project root, so clangd and other clang-based IDE tooling
+//! see the project the way Emscripten compiles it. Without it they use the host's headers
+//! and flag every Emscripten header as missing. Builds that run emcc directly get one entry
+//! per source, with the flags `emcc --cflags` reports (the wasm target and the Emscripten
+//! sysroot) and the compile flags of the emcc command line. CMake writes the file itself
+//! with `CMAKE_EXPORT_COMPILE_COMMANDS`; it is copied out of the build directory.
+//! `--no-compile-commands` turns both off.
+
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use crate::app_config::AppConfig;
+use crate::utils::command_runner::run_build_command;
+use super::source_language::SourceLanguage;
+
+pub const FILE_NAME: &str = "compile_commands.json";
+
+/// Target of Emscripten's clang, added when `emcc --cflags` does not name it
+const TARGET_FLAG: &str = "--target=wasm32-unknown-emscripten";
+
+#[derive(Serialize)]
+struct Entry {
+    directory: PathBuf,
+    file: PathBuf,
+    arguments: Vec<String>,
+}
+
+/// The sysroot of the Emscripten cache and the include paths and defines of the build
+pub(crate) struct Toolchain {
+    pub sysroot: Option<PathBuf>,
+    /// Include directories inside the sysroot that emcc adds (`-iwithsysroot`)
+    pub sysroot_includes: Vec<PathBuf>,
+    /// Include directories of the build, absolute
+    pub includes: Vec<PathBuf>,
+    pub defines: Vec<String>,
+    pub cpp_standard: Option<String>,
+}
+
+impl Toolchain {
+    pub fn from_flags(cflags: &[String], build_flags: &[String]) -> Self {
+        let mut toolchain = Toolchain {
+            sysroot: None,
+            sysroot_includes: Vec::new(),
+            includes: Vec::new(),
+            defines: vec!["__EMSCRIPTEN__".to_string(), "__wasm__".to_string(), "__wasm32__".to_string()],
+            cpp_standard: None,
+        };
+        let mut flags = cflags.iter().chain(build_flags).map(String::as_str);
+        while let Some(flag) = flags.next() {
+            // `-I dir` and `-Idir` alike
+            let value = |prefix: &str, flags: &mut dyn Iterator<Item = &str>| -> Option<String> {
+                match flag.strip_prefix(prefix) {
+                    Some("") => flags.next().map(str::to_string),
+                    Some(value) => Some(value.trim_start_matches('=').to_string()),
+                    None => None,
+                }
+            };
+            if let Some(sysroot) = value("--sysroot", &mut flags) {
+                toolchain.sysroot = Some(PathBuf::from(sysroot));
+            } else if let Some(dir) = value("-iwithsysroot", &mut flags) {
+                toolchain.sysroot_includes.push(PathBuf::from(dir.trim_start_matches('/')));
+            } else if let Some(dir) = value("-isystem", &mut flags).or_else(|| value("-I", &mut flags)) {
+                toolchain.includes.push(PathBuf::from(dir));
+            } else if let Some(define) = value("-D", &mut flags) {
+                if !toolchain.defines.contains(&define) {
+                    toolchain.defines.push(define);
+                }
+            } else if let Some(standard) = flag.strip_prefix("-std=").filter(|standard| standard.contains("++")) {
+                toolchain.cpp_standard = Some(standard.to_string());
+            }
+        }
+        toolchain
+    }
+
+    /// System include directories in the order clang searches them for C++
+    pub fn system_includes(&self) -> Vec<PathBuf> {
+        let Some(sysroot) = &self.sysroot else { return Vec::new() };
+        self.sysroot_includes
+            .iter()
+            .map(|dir| sysroot.join(dir))
+            .chain([sysroot.join("include").join("c++").join("v1"), sysroot.join("include")])
+            .collect()
+    }
+}
+
+/// The flags emcc passes to clang when compiling: target, sysroot and Emscripten's defines
+pub fn emscripten_cflags(config: &AppConfig) -> Result<Vec<String>, String> {
+    let output = run_build_command("emcc", &["--cflags"], None, config).map_err(|e| e.to_string())?;
+    let cflags = String::from_utf8_lossy(&output.stdout);
+    let mut flags = shell_words::split(cflags.trim()).map_err(|e| format!("Cannot parse emcc --cflags: {}", e))?;
+    if !flags.iter().any(|flag| flag == "-target" || flag.starts_with("--target=")) {
+        flags.insert(0, TARGET_FLAG.to_string());
+    }
+    Ok(flags)
+}
+
+/// The flags of an emcc command line that matter for compiling rather than linking
+pub fn compile_flags(emcc_args: &[String]) -> Vec<String> {
+    let mut flags = Vec::new();
+    let mut args = emcc_args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // Flags whose value is the next argument
+            "-I" | "-D" | "-U" | "-isystem" | "-include" | "-iquote" => {
+                flags.push(arg.clone());
+                flags.extend(args.next().cloned());
+            }
+            "-pthread" => flags.push(arg.clone()),
+            _ if ["-I", "-D", "-U", "-std=", "-O", "-m", "-isystem", "-iquote"].iter().any(|prefix| arg.starts_with(prefix)) => {
+                flags.push(arg.clone());
+            }
+            _ if arg.starts_with("-f") || (arg.starts_with("-W") && !arg.starts_with("-Wl,")) => flags.push(arg.clone()),
+            _ => {}
+        }
+    }
+    flags
+}
+
+/// Writes `compile_commands.json` to the project root for an emcc command line building
+/// `sources` with `emcc_args` and the extra flags `source_flags` gives each source, or next to
+/// the build trees for a read-only project.
+pub fn write_for_emcc(
+    project_path: &Path,
+    sources: &[PathBuf],
+    emcc_args: &[String],
+    source_flags: &dyn Fn(&Path) -> Vec<String>,
+    config: &AppConfig,
+) -> Result<(), String> {
+    let cflags = emscripten_cflags(config).unwrap_or_else(|e| {
+        tracing::warn!("{} lacks the Emscripten sysroot: {}", FILE_NAME, e);
+        vec![TARGET_FLAG.to_string()]
+    });
+    let flags = compile_flags(emcc_args);
+    let entries: Vec<Entry> = sources
+        .iter()
+        .map(|source| {
+            let file = std::path::absolute(source).unwrap_or_else(|_| source.clone());
+            let language = SourceLanguage::of(&file).unwrap_or(SourceLanguage::Cxx);
+            let arguments = std::iter::once(language.clang().to_string())
+                .chain(cflags.iter().cloned())
+                // A language standard only applies to the sources of that language
+                .chain(flags.iter().filter(|flag| language.accepts(flag)).cloned())
+                .chain(compile_flags(&source_flags(source)).into_iter().filter(|flag| language.accepts(flag)))
+                .chain(["-c".to_string(), file.to_string_lossy().into_owned()])
+                .collect();
+            Entry { directory: project_path.to_path_buf(), file, arguments }
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&entries).map_err(|e| format!("Failed to serialize {}: {}", FILE_NAME, e))?;
+    let root = if config.read_only_source { config.build_root(project_path) } else { project_path.to_path_buf() };
+    write_if_changed(&root.join(FILE_NAME), json.as_bytes())
+}
+
+/// Copies the `compile_commands.json` CMake wrote to `build_dir` to the project root.
+/// Generators other than Makefiles and Ninja do not write one.
+pub fn copy_from_build_dir(build_dir: &Path, project_path: &Path) -> Result<(), String> {
+    let source = build_dir.join(FILE_NAME);
+    if !source.is_file() {
+        tracing::debug!("CMake did not write {:?}", source);
+        return Ok(());
+    }
+    let content = std::fs::read(&source).map_err(|e| format!("Failed to read {:?}: {}", source, e))?;
+    write_if_changed(&project_path.join(FILE_NAME), &content)
+}
+
+/// Leaves the file alone when it is up to date, so editors watching it do not reindex
+fn write_if_changed(path: &Path, content: &[u8]) -> Result<(), String> {
+    if std::fs::read(path).is_ok_and(|existing| existing == content) {
+        return Ok(());
+    }
+    std::fs::write(path, content).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    tracing::info!("Wrote {:?} for clangd and other IDE tooling", path);
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! Running the CTest tests of a CMake project as WebAssembly (`--test`). The tests are built
+//! in a separate build tree, `build_wasm_cmake-test`, with settings that make every
+//! executable a plain Node.js script (no MODULARIZE, `-sNODERAWFS=1` for access to the host
+//! files), and `ctest` runs them with node as `CMAKE_CROSSCOMPILING_EMULATOR`.
+
+use std::path::Path;
+use crate::app_config::AppConfig;
+use crate::utils::command_runner::{resolve_emscripten_tool, run_build_command, CommandError};
+use crate::utils::file_system;
+use crate::utils::flag_set::FlagSet;
+use super::emscripten_runner::EmscriptenRunner;
+use super::error::{CompileError, ConfigureError};
+
+/// Settings of the main build that would keep the test executables from running under node
+const NODE_INCOMPATIBLE_FLAGS: &[&str] = &["-sENVIRONMENT=", "-sMODULARIZE", "-sEXPORT_ES6", "-sEXPORT_NAME=", "-sSINGLE_FILE"];
+
+/// The outcome of one test, as reported by ctest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+    pub name: String,
+    pub passed: bool,
+    /// The status ctest printed, e.g. `Passed`, `***Failed` or `***Timeout`
+    pub status: String,
+}
+
+/// Builds the project's tests and runs them with ctest. Failing tests are reported in the
+/// results rather than as an error; errors are failures to build or to run ctest at all.
+pub fn run_tests(project_path: &Path, config: &AppConfig) -> Result<Vec<TestResult>, CompileError> {
+    let build_dir_name = match &config.matrix_target {
+        Some(target) => format!("build_wasm_cmake-{}-test", target),
+        None => "build_wasm_cmake-test".to_string(),
+    };
+    let build_dir = config.build_tree(project_path, &build_dir_name);
+    file_system::ensure_dir_exists(&build_dir)?;
+    tracing::info!("Building the CTest tests in {:?}", build_dir);
+
+    let mut link_flags: FlagSet = ["-sENVIRONMENT=node", "-sNODERAWFS=1", "-sEXIT_RUNTIME=1"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    link_flags.extend(
+        config.emcc_flag.iter()
+            .filter(|flag| !NODE_INCOMPATIBLE_FLAGS.iter().any(|prefix| flag.starts_with(prefix)))
+            .cloned(),
+    );
+    link_flags.extend(config.lib_dir_flags());
+    link_flags.extend(config.link_lib_flags());
+
+    let mut cmake_args = vec![
+        config.tool_program("cmake"),
+        project_path.to_string_lossy().into_owned(),
+        format!("-DCMAKE_BUILD_TYPE={}", config.build_config),
+        "-DBUILD_TESTING=ON".to_string(),
+        format!("-DCMAKE_CROSSCOMPILING_EMULATOR={}", config.tool_program("node")),
+        format!("-DCMAKE_EXE_LINKER_FLAGS={}", link_flags.to_cmake_value()),
+    ];
+    let compile_flags = FlagSet::from(config.compile_flags());
+    if !compile_flags.is_empty() {
+        cmake_args.push(format!("-DCMAKE_CXX_FLAGS={}", compile_flags.to_cmake_value()));
+        cmake_args.push(format!("-DCMAKE_C_FLAGS={}", compile_flags.to_cmake_value()));
+    }
+    EmscriptenRunner::run_emscripten_tool(&resolve_emscripten_tool("emcmake"), &cmake_args, &build_dir, config)
+        .map_err(ConfigureError::from)?;
+    EmscriptenRunner::run_emscripten_tool(&resolve_emscripten_tool("emmake"), &[config.tool_program("make")], &build_dir, config)?;
+
+    let ctest_args = ["--output-on-failure", "-C", config.build_config.as_str()];
+    let stdout = match run_build_command("ctest", &ctest_args, Some(&build_dir), config) {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+        // ctest exits unsuccessfully when a test fails; its output still lists every test
+        Err(CommandError::Failed { stdout, .. }) if !parse_results(&stdout).is_empty() => stdout,
+        Err(e) => return Err(e.into()),
+    };
+
+    let results = parse_results(&stdout);
+    if results.is_empty() {
+        tracing::warn!("ctest found no tests; does the project call enable_testing() and add_test()?");
+    }
+    for result in &results {
+        if result.passed {
+            tracing::info!("Test {}: {}", result.name, result.status);
+        } else {
+            tracing::error!("Test {}: {}", result.name, result.status);
+        }
+    }
+    Ok(results)
+}
+
+/// Parses the result lines of ctest's output, e.g.
+/// `1/3 Test #1: parser_test ......................   Passed    0.02 sec`.
+fn parse_results(output: &str) -> Vec<TestResult> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" Test #")?;
+            let (_, rest) = rest.split_once(": ")?;
+            let (name, rest) = rest.split_once(" .")?;
+            let status = rest.trim_start_matches('.').trim();
+            // Drop the duration at the end of the line
+            let status = status.rsplit_once("  ").map_or(status, |(status, _)| status).trim();
+            Some(TestResult {
+                name: name.trim().to_string(),
+                passed: status == "Passed",
+                status: status.to_string(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,109 @@
+//! C++20 named modules in direct builds. Module interface units (`.cppm`, `.ixx`, or any
+//! source declaring `export module <name>;`) and module partitions are compiled before the
+//! sources importing them, in the order their imports require. Each writes its built module
+//! interface (`<name>.pcm`, `<module>-<partition>.pcm` for partitions) next to its object, where
+//! the sources compiled after it find it with `-fprebuilt-module-path`.
+//!
+//! Header units (`import <vector>;`) and `import std;` are not supported by this build.
+
+use std::path::{Path, PathBuf};
+
+/// A source declaring a module interface or partition
+#[derive(Debug, Clone)]
+pub struct ModuleUnit {
+    pub source: PathBuf,
+    /// `name` or `name:partition`
+    pub name: String,
+    /// The modules it imports, partitions written out as `name:partition`
+    pub imports: Vec<String>,
+}
+
+impl ModuleUnit {
+    /// The file name clang looks up in the prebuilt module path for this unit
+    pub fn bmi_file_name(&self) -> String {
+        format!("{}.pcm", self.name.replace(':', "-"))
+    }
+}
+
+/// The module units among `sources`, ordered so each comes after the units it imports. Empty
+/// if the project does not use modules; an error if the imports form a cycle.
+pub fn module_units(sources: &[PathBuf]) -> Result<Vec<ModuleUnit>, String> {
+    let units: Vec<ModuleUnit> = sources.iter().filter_map(|source| read_unit(source)).collect();
+    let mut ordered: Vec<ModuleUnit> = Vec::new();
+    let mut visiting: Vec<String> = Vec::new();
+    for unit in &units {
+        visit(unit, &units, &mut ordered, &mut visiting)?;
+    }
+    Ok(ordered)
+}
+
+fn visit(unit: &ModuleUnit, units: &[ModuleUnit], ordered: &mut Vec<ModuleUnit>, visiting: &mut Vec<String>) -> Result<(), String> {
+    if ordered.iter().any(|done| done.name == unit.name) {
+        return Ok(());
+    }
+    if let Some(start) = visiting.iter().position(|name| *name == unit.name) {
+        let mut cycle = visiting[start..].to_vec();
+        cycle.push(unit.name.clone());
+        return Err(format!("The C++ modules import each other in a cycle: {}", cycle.join(" -> ")));
+    }
+    visiting.push(unit.name.clone());
+    for import in &unit.imports {
+        if let Some(imported) = units.iter().find(|other| other.name == *import) {
+            visit(imported, units, ordered, visiting)?;
+        }
+    }
+    visiting.pop();
+    ordered.push(unit.clone());
+    Ok(())
+}
+
+/// The module unit `source` declares, if it is an interface unit or a partition
+fn read_unit(source: &Path) -> Option<ModuleUnit> {
+    let content = std::fs::read_to_string(source).ok()?;
+    let mut name: Option<(String, bool)> = None;
+    let mut imports = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        let (exported, line) = match line.strip_prefix("export ") {
+            Some(rest) => (true, rest.trim_start()),
+            None => (false, line),
+        };
+        if let Some(declared) = declaration(line, "module") {
+            if name.is_none() {
+                name = Some((declared.to_string(), exported));
+            }
+        } else if let Some(imported) = declaration(line, "import") {
+            if imported.starts_with('<') || imported.starts_with('"') {
+                tracing::warn!("{} imports the header unit {}, which is not supported; #include it instead", source.display(), imported);
+                continue;
+            }
+            imports.push(imported.to_string());
+        }
+    }
+
+    let (name, exported) = name?;
+    if !exported && !name.contains(':') {
+        // A module implementation unit: it only needs its interface compiled first
+        return None;
+    }
+    let module = name.split(':').next().unwrap_or(&name).to_string();
+    let imports = imports
+        .into_iter()
+        .map(|import| match import.strip_prefix(':') {
+            Some(partition) => format!("{}:{}", module, partition),
+            None => import,
+        })
+        .collect();
+    Some(ModuleUnit { source: source.to_path_buf(), name, imports })
+}
+
+/// The name in a `<keyword> <name>;` declaration; not the global module fragment `module;`
+fn declaration<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(keyword)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let name = rest.trim().strip_suffix(';')?.trim();
+    let name = name.split_whitespace().next()?;
+    (!name.is_empty()).then_some(name)
+}
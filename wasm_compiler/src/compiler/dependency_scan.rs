@@ -0,0 +1,129 @@
+//! Include directories derived from what the compiler resolves. `em++ -MM -MG` lists the
+//! headers a source includes, transitively, as make dependencies; headers it cannot find are
+//! listed as written (`widgets/button.h`). Each of those is looked up in the project and the
+//! extra search roots, the directory that makes the include resolve becomes an include
+//! directory, and the scan is repeated so the headers of the newly found ones are resolved
+//! too. Headers found nowhere are left to the include check (see [`super::include_check`]).
+
+use std::path::{Component, Path, PathBuf};
+use crate::utils::command_runner::{resolve_emscripten_tool, run_command};
+use crate::utils::source_selection::project_files;
+use super::source_language::SourceLanguage;
+
+/// Scans after which the remaining unresolved headers are given up on
+const MAX_ROUNDS: usize = 8;
+
+/// The include directories `sources` need on top of `include_dirs`, in the order they were
+/// found. Missing headers are looked for under `project_path` and `search_roots`.
+pub fn include_dirs(
+    project_path: &Path,
+    sources: &[PathBuf],
+    include_dirs: &[PathBuf],
+    compile_flags: &[String],
+    search_roots: &[PathBuf],
+) -> Vec<PathBuf> {
+    // The scans run in the directory of each source, where relative paths would not resolve
+    let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let project_path = absolute(project_path);
+    let sources: Vec<PathBuf> = sources.iter().map(|source| absolute(source)).collect();
+    let include_dirs: Vec<PathBuf> = include_dirs.iter().map(|dir| absolute(dir)).collect();
+    let search_roots: Vec<PathBuf> = search_roots.iter().map(|root| absolute(root)).collect();
+
+    let mut candidates: Option<Vec<PathBuf>> = None;
+    let mut found: Vec<PathBuf> = Vec::new();
+    let mut given_up: Vec<String> = Vec::new();
+    for _ in 0..MAX_ROUNDS {
+        let mut missing = Vec::new();
+        for source in &sources {
+            for header in scan(source, include_dirs.iter().chain(&found), compile_flags) {
+                if !Path::new(&header).is_absolute() && !source_dir(source).join(&header).exists() && !missing.contains(&header) && !given_up.contains(&header) {
+                    missing.push(header);
+                }
+            }
+        }
+        if missing.is_empty() {
+            break;
+        }
+
+        let files = candidates.get_or_insert_with(|| {
+            std::iter::once(&project_path).chain(&search_roots).flat_map(|root| project_files(root, None)).collect()
+        });
+        let mut progress = false;
+        for header in missing {
+            match include_dir_for(files, &header) {
+                Some(dir) if !found.contains(&dir) && !include_dirs.contains(&dir) => {
+                    tracing::debug!("Added include directory {:?} for '{}'", dir, header);
+                    found.push(dir);
+                    progress = true;
+                }
+                _ => given_up.push(header),
+            }
+        }
+        if !progress {
+            break;
+        }
+    }
+    found
+}
+
+/// The headers `em++ -MM -MG` lists for `source`: resolved ones as paths, missing ones as
+/// written in the include
+fn scan<'a>(source: &Path, include_dirs: impl Iterator<Item = &'a PathBuf>, compile_flags: &[String]) -> Vec<String> {
+    let language = SourceLanguage::of(source).unwrap_or(SourceLanguage::Cxx);
+    let mut args: Vec<String> = include_dirs.map(|dir| format!("-I{}", dir.to_string_lossy())).collect();
+    args.extend(compile_flags.iter().filter(|flag| language.accepts(flag) && !flag.starts_with("-I")).cloned());
+    args.extend(["-MM".to_string(), "-MG".to_string(), source.to_string_lossy().into_owned()]);
+    // A look at the sources, not a step of the build: not reported or recorded
+    match run_command(&resolve_emscripten_tool(language.driver()), &args, Some(source_dir(source))) {
+        Ok(output) => dependencies(&String::from_utf8_lossy(&output.stdout)).into_iter().skip(1).collect(),
+        Err(e) => {
+            tracing::warn!("Could not scan the includes of {:?}: {}", source, e);
+            Vec::new()
+        }
+    }
+}
+
+/// The prerequisites of the make rules `-MM` and `-MMD` write, unescaped
+pub(crate) fn dependencies(rules: &str) -> Vec<String> {
+    let mut dependencies = Vec::new();
+    for rule in rules.replace("\\\r\n", " ").replace("\\\n", " ").lines() {
+        let Some((_, prerequisites)) = rule.split_once(": ") else { continue };
+        let mut current = String::new();
+        let mut chars = prerequisites.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if matches!(chars.peek(), Some(' ' | '#')) => current.extend(chars.next()),
+                '$' if chars.peek() == Some(&'$') => current.extend(chars.next()),
+                c if c.is_whitespace() => {
+                    if !current.is_empty() {
+                        dependencies.push(std::mem::take(&mut current));
+                    }
+                }
+                c => current.push(c),
+            }
+        }
+        if !current.is_empty() {
+            dependencies.push(current);
+        }
+    }
+    dependencies
+}
+
+/// The directory that `header` resolves in: the one of the shallowest file whose path ends
+/// with it
+fn include_dir_for(files: &[PathBuf], header: &str) -> Option<PathBuf> {
+    let relative = Path::new(header);
+    if relative.components().any(|component| !matches!(component, Component::Normal(_))) {
+        return None;
+    }
+    files
+        .iter()
+        .filter(|file| file.ends_with(relative))
+        .min_by_key(|file| file.components().count())
+        .and_then(|file| file.ancestors().nth(relative.components().count()))
+        .map(Path::to_path_buf)
+}
+
+fn source_dir(source: &Path) -> &Path {
+    source.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."))
+}
@@ -0,0 +1,293 @@
+//! The compile and link graph of a build that runs emcc itself instead of a build system:
+//! direct compilation of the project's sources, and library handlers that compile a library
+//! along with them (ImGui). A handler fills a [`DirectCompilePlan`] with the sources, include
+//! directories, defines, libraries and flags of the build, and with the vendored libraries
+//! whose sources are built into cached archives (see [`super::library_cache`]), so several
+//! libraries and the project's sources end up in one module. [`DirectCompilePlan::run`] writes
+//! `compile_commands.json`, checks the includes, and compiles and links with one emcc
+//! invocation, or, when the sources mix languages, use C++ modules or have extra flags, each
+//! source on its own into `build_wasm_objects/`. Objects whose source, headers and flags did
+//! not change since the previous build are reused.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::app_config::AppConfig;
+use crate::diagnostics;
+use crate::utils::command_runner::{resolve_emscripten_tool, run_build_command};
+use crate::utils::file_system;
+use super::compile_commands;
+use super::cxx_modules::{self, ModuleUnit};
+use super::dependency_scan;
+use super::emcc_flags::EmccFlags;
+use super::error::CompileError;
+use super::include_check;
+use super::library_cache;
+use super::source_language::{self, SourceLanguage};
+
+/// A library whose sources are compiled once into a cached archive and linked with the
+/// project (or compiled with it under `--no-library-cache`)
+#[derive(Debug, Clone)]
+pub struct VendoredLibrary {
+    /// Name of the archive, `lib<name>.a`
+    pub name: String,
+    pub sources: Vec<PathBuf>,
+    /// Include directories its sources are compiled with
+    pub include_dirs: Vec<PathBuf>,
+}
+
+/// The sources, include directories, defines, libraries and flags of a direct build
+#[derive(Debug, Clone)]
+pub struct DirectCompilePlan {
+    /// Working directory of the compiler, and where `compile_commands.json` and
+    /// `build_wasm_objects/` go
+    pub project_path: PathBuf,
+    pub sources: Vec<PathBuf>,
+    pub include_dirs: Vec<PathBuf>,
+    /// `NAME` or `NAME=VALUE`, without `-D`
+    pub defines: Vec<String>,
+    /// Libraries to link: `-l` flags or archive paths
+    pub libs: Vec<String>,
+    pub flags: EmccFlags,
+    pub libraries: Vec<VendoredLibrary>,
+    /// Link arguments for the output, e.g. `--post-js` of WebIDL glue
+    pub output_args: Vec<String>,
+    /// The driver that links the module (and compiles it with a single invocation)
+    pub driver: &'static str,
+}
+
+impl DirectCompilePlan {
+    /// An empty plan with the flags every build has (see [`EmccFlags::from_config`]), linked
+    /// with emcc
+    pub fn new(project_path: &Path, config: &AppConfig) -> Self {
+        DirectCompilePlan {
+            project_path: project_path.to_path_buf(),
+            sources: Vec::new(),
+            include_dirs: Vec::new(),
+            defines: Vec::new(),
+            libs: Vec::new(),
+            flags: EmccFlags::from_config(config),
+            libraries: Vec::new(),
+            output_args: Vec::new(),
+            driver: "emcc",
+        }
+    }
+
+    /// Adds sources that are not in the plan yet
+    pub fn add_sources(&mut self, sources: impl IntoIterator<Item = PathBuf>) {
+        for source in sources {
+            if !self.sources.contains(&source) {
+                self.sources.push(source);
+            }
+        }
+    }
+
+    /// Adds an include directory that is not in the plan yet
+    pub fn add_include_dir(&mut self, dir: impl Into<PathBuf>) {
+        let dir = dir.into();
+        if !self.include_dirs.contains(&dir) {
+            self.include_dirs.push(dir);
+        }
+    }
+
+    pub fn add_define(&mut self, define: impl Into<String>) {
+        self.defines.push(define.into());
+    }
+
+    pub fn add_lib(&mut self, lib: impl Into<String>) {
+        self.libs.push(lib.into());
+    }
+
+    pub fn add_library(&mut self, library: VendoredLibrary) {
+        self.libraries.push(library);
+    }
+
+    /// The sources of the project and of the vendored libraries
+    pub fn all_sources(&self) -> Vec<PathBuf> {
+        self.sources.iter().chain(self.libraries.iter().flat_map(|library| &library.sources)).cloned().collect()
+    }
+
+    /// The include directory and define flags of the plan, then the compile flags
+    pub fn compile_flags(&self) -> Vec<String> {
+        self.include_flags(&self.include_dirs).into_iter().chain(self.flags.compile_flags().iter().cloned()).collect()
+    }
+
+    fn include_flags(&self, include_dirs: &[PathBuf]) -> Vec<String> {
+        include_dirs
+            .iter()
+            .map(|dir| format!("-I{}", dir.to_string_lossy()))
+            .chain(self.defines.iter().map(|define| format!("-D{}", define)))
+            .collect()
+    }
+
+    /// Compiles and links the plan into `output_js` and the files emcc writes next to it, with
+    /// the single-header libraries the sources are missing (see [`crate::header_deps`]).
+    pub fn run(mut self, output_js: &Path, config: &AppConfig) -> Result<(), CompileError> {
+        crate::header_deps::add_missing(&mut self, config)?;
+
+        // Vendored libraries are linked from their cached archives, or compiled with the
+        // project under --no-library-cache
+        let mut sources = self.sources.clone();
+        let mut archives = Vec::new();
+        for library in self.libraries.iter().filter(|library| !library.sources.is_empty()) {
+            if config.no_library_cache {
+                sources.extend(library.sources.iter().cloned());
+            } else {
+                let mut flags = self.include_flags(&library.include_dirs);
+                flags.extend(self.flags.compile_flags().iter().cloned());
+                archives.push(library_cache::archive(&library.name, &library.sources, &flags, config)?.to_string_lossy().into_owned());
+            }
+        }
+        if sources.is_empty() && archives.is_empty() {
+            return Err(CompileError::Other("No sources left to compile".to_string()));
+        }
+
+        let link_inputs: Vec<String> = archives.into_iter().chain(self.libs.iter().cloned()).collect();
+        let mut output_args = self.output_args.clone();
+        output_args.extend(["-o".to_string(), output_js.to_string_lossy().into_owned()]);
+        let emcc_args: Vec<String> = sources.iter()
+            .map(|source| source.to_string_lossy().into_owned())
+            .chain(link_inputs.iter().cloned())
+            .chain(self.include_flags(&self.include_dirs))
+            .chain(self.flags.all())
+            .chain(output_args.iter().cloned())
+            .collect();
+
+        let all_sources = self.all_sources();
+        let source_flags = |source: &Path| self.flags.source_flags(&self.project_path, source);
+        if !config.no_compile_commands {
+            if let Err(e) = compile_commands::write_for_emcc(&self.project_path, &all_sources, &emcc_args, &source_flags, config) {
+                tracing::warn!("{}", e);
+            }
+        }
+        include_check::check_emcc(&self.project_path, &all_sources, &emcc_args, &source_flags, config)?;
+
+        let module_units = cxx_modules::module_units(&sources)?;
+        if source_language::is_mixed(&sources) || !module_units.is_empty() || self.flags.has_source_flags(&self.project_path, &sources) {
+            self.compile_separately(&sources, &module_units, &link_inputs, &output_args, config)
+        } else {
+            tracing::debug!("Running {} with args: {:?}", self.driver, emcc_args.join(" "));
+            run_build_command(&resolve_emscripten_tool(self.driver), &emcc_args, Some(&self.project_path), config)?;
+            Ok(())
+        }
+    }
+
+    /// Compiles each source with the driver of its language and its extra flags from the
+    /// `[flags]` table into `build_wasm_objects/`, then links the objects, `link_inputs` and
+    /// `output_args` into the module. C++ module units are compiled first, in import order,
+    /// writing their module interfaces to the same directory for the sources importing them.
+    fn compile_separately(
+        &self,
+        sources: &[PathBuf],
+        module_units: &[ModuleUnit],
+        link_inputs: &[String],
+        output_args: &[String],
+        config: &AppConfig,
+    ) -> Result<(), CompileError> {
+        let project_path = &self.project_path;
+        // A warning in a header every source includes is reported once, not per source
+        let _warnings = diagnostics::WarningBatch::start();
+        let object_dir = config.target_build_tree(project_path, "build_wasm_objects");
+        file_system::ensure_dir_exists(&object_dir)?;
+        let compile_flags = self.compile_flags();
+        let mut module_flags = Vec::new();
+        if !module_units.is_empty() {
+            module_flags.push(format!("-fprebuilt-module-path={}", object_dir.to_string_lossy()));
+            if !compile_flags.iter().any(|flag| flag.starts_with("-std=") && flag.contains("++")) {
+                module_flags.push("-std=c++20".to_string());
+            }
+        }
+
+        let unit_of = |source: &PathBuf| module_units.iter().find(|unit| unit.source == *source);
+        let ordered = module_units.iter().map(|unit| &unit.source).chain(sources.iter().filter(|source| unit_of(source).is_none()));
+        let mut objects = Vec::new();
+        // Once a module interface is rebuilt, the units importing it are too
+        let mut interface_rebuilt = false;
+        for source in ordered {
+            let language = SourceLanguage::of(source).unwrap_or(SourceLanguage::Cxx);
+            let relative = source.strip_prefix(project_path).unwrap_or(source);
+            let object = object_path(&object_dir, project_path, source);
+            if let Some(parent) = object.parent() {
+                file_system::ensure_dir_exists(parent)?;
+            }
+            let mut args: Vec<String> = compile_flags.iter().filter(|flag| language.accepts(flag)).cloned().collect();
+            if language.is_cxx() {
+                args.extend(module_flags.iter().cloned());
+            }
+            args.extend(self.flags.source_flags(project_path, source).into_iter().filter(|flag| language.accepts(flag)));
+            let unit = unit_of(source);
+            if let Some(unit) = unit {
+                args.push(format!("-fmodule-output={}", object_dir.join(unit.bmi_file_name()).to_string_lossy()));
+                // Interfaces may be in `.ixx` or `.cpp` files, which clang does not take for one
+                args.extend(["-x".to_string(), "c++-module".to_string()]);
+            }
+            args.extend(["-c".to_string(), source.to_string_lossy().to_string(), "-o".to_string(), object.to_string_lossy().to_string()]);
+
+            let stamp = object.with_extension("o.args");
+            let depfile = object.with_extension("o.d");
+            let interface = unit.map(|unit| object_dir.join(unit.bmi_file_name()));
+            if !interface_rebuilt && is_up_to_date(&object, interface.as_deref(), &stamp, &depfile, &args, project_path) {
+                tracing::debug!("{} is up to date", relative.display());
+                objects.push(object);
+                continue;
+            }
+
+            if let Some(unit) = unit {
+                tracing::info!("Compiling C++ module {} from {}", unit.name, relative.display());
+                interface_rebuilt = true;
+            }
+            let mut command_args = args.clone();
+            command_args.extend(["-MMD".to_string(), "-MF".to_string(), depfile.to_string_lossy().into_owned()]);
+            tracing::debug!("Running {} with args: {:?}", language.driver(), command_args.join(" "));
+            let _ = std::fs::remove_file(&stamp);
+            run_build_command(&resolve_emscripten_tool(language.driver()), &command_args, Some(project_path), config)?;
+            if let Err(e) = std::fs::write(&stamp, args.join("\n")) {
+                tracing::debug!("Failed to write {:?}: {}", stamp, e);
+            }
+            objects.push(object);
+        }
+
+        let args: Vec<String> = objects.iter()
+            .map(|object| object.to_string_lossy().to_string())
+            .chain(link_inputs.iter().cloned())
+            .chain(self.flags.link_flags().iter().cloned())
+            .chain(output_args.iter().cloned())
+            .collect();
+        tracing::debug!("Running em++ with args: {:?}", args.join(" "));
+        run_build_command(&resolve_emscripten_tool("em++"), &args, Some(project_path), config)?;
+        Ok(())
+    }
+}
+
+/// Where the object of `source` goes: the directories of the project are mirrored below
+/// `object_dir`, so `a/b.cpp` and `a_b.cpp` get objects of their own. Sources outside the
+/// project go into `external-<hash of their directory>/`.
+fn object_path(object_dir: &Path, project_path: &Path, source: &Path) -> PathBuf {
+    let file_name = format!("{}.o", source.file_name().unwrap_or_default().to_string_lossy());
+    match source.strip_prefix(project_path) {
+        Ok(relative) => object_dir.join(relative.parent().unwrap_or(Path::new(""))).join(file_name),
+        Err(_) => {
+            let dir = source.parent().unwrap_or(Path::new("")).to_string_lossy();
+            let hash = file_system::hex_digest(dir.as_bytes());
+            object_dir.join(format!("external-{}", &hash[..12])).join(file_name)
+        }
+    }
+}
+
+/// Whether `object` (and the module `interface` it writes) was compiled with `args` and is
+/// newer than its source and every header `depfile` lists
+fn is_up_to_date(object: &Path, interface: Option<&Path>, stamp: &Path, depfile: &Path, args: &[String], project_path: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let Some(built) = modified(object) else { return false };
+    if interface.is_some_and(|interface| !interface.is_file()) {
+        return false;
+    }
+    if std::fs::read_to_string(stamp).ok().as_deref() != Some(args.join("\n").as_str()) {
+        return false;
+    }
+    let Ok(rules) = std::fs::read_to_string(depfile) else { return false };
+    let dependencies = dependency_scan::dependencies(&rules);
+    !dependencies.is_empty()
+        && dependencies.iter().all(|dependency| {
+            modified(&project_path.join(dependency)).is_some_and(|changed: SystemTime| changed <= built)
+        })
+}
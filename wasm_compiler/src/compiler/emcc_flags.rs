@@ -0,0 +1,390 @@
+//! The emcc flags of a build, assembled in one place so every handler builds with the same
+//! optimization level, exception handling, glue settings, `--with-imgui` flags and libraries.
+//! Handlers only add what is specific to them (a C++ standard, the ports of the ImGui
+//! backends they compile) and take the flags in the shape their build tool wants: one emcc
+//! command line, or compile flags and link flags apart for `CMAKE_CXX_FLAGS` /
+//! `CMAKE_EXE_LINKER_FLAGS` and make's `CXXFLAGS` / `LDFLAGS`. The `--emcc-flags` come last,
+//! so they override the defaults.
+//!
+//! Each `-s` setting appears once. A setting given in the emcc flags replaces the build's
+//! (`-sASSERTIONS=1` in a release build drops its `-sASSERTIONS=0`), one a handler adds replaces
+//! the default, and a setting given twice in the emcc flags keeps the later value; overrides
+//! of the build's settings are logged, and conflicts within the emcc flags are warned about.
+//! List settings (`EXPORTED_FUNCTIONS`, `EXPORTED_RUNTIME_METHODS`) are merged instead.
+//!
+//! Direct builds also give the sources matching a glob of the `[flags]` table of the project
+//! config its extra compile flags, after all others. When several globs match, the flags of
+//! the longer one come later.
+
+use std::path::{Path, PathBuf};
+use globset::GlobMatcher;
+use crate::app_config::AppConfig;
+use crate::utils::flag_set::FlagSet;
+use crate::utils::source_selection::path_glob;
+use super::emscripten_runner::EmscriptenRunner;
+use super::system_libraries;
+
+/// Flags whose value is the next argument
+const VALUE_FLAGS: &[&str] = &[
+    "-s", "-o", "-I", "-D", "-U", "-L", "-l", "-x", "-isystem", "-iquote", "-include",
+    "--closure", "--preload-file", "--embed-file", "--exclude-file", "--pre-js", "--post-js",
+    "--extern-pre-js", "--extern-post-js", "--js-library", "--shell-file", "--source-map-base",
+];
+
+/// Prefixes of flags that matter when compiling and when linking: optimization, debug info,
+/// code generation, threads, and ports, whose headers the sources include
+const BOTH_PREFIXES: &[&str] = &[
+    "-O", "-g", "-f", "-m", "-pthread", "-sUSE_", "--use-port", "-sMEMORY64", "-sWASM_WORKERS", "-sSHARED_MEMORY",
+];
+
+/// Prefixes of flags that only matter when compiling
+const COMPILE_PREFIXES: &[&str] = &["-I", "-D", "-U", "-std=", "-x", "-isystem", "-iquote", "-include", "-nostdinc"];
+
+/// Settings whose values are lists, merged when given more than once
+const LIST_SETTINGS: &[&str] = &["EXPORTED_FUNCTIONS", "EXPORTED_RUNTIME_METHODS"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Compile,
+    Link,
+    Both,
+}
+
+/// Whether a flag (with its value, for two-word flags) is for compiling, linking or both
+fn stage(flag: &str) -> Stage {
+    if BOTH_PREFIXES.iter().any(|prefix| flag.starts_with(prefix)) {
+        Stage::Both
+    } else if COMPILE_PREFIXES.iter().any(|prefix| flag.starts_with(prefix)) || flag == "-w" || (flag.starts_with("-W") && !flag.starts_with("-Wl,")) {
+        Stage::Compile
+    } else {
+        Stage::Link
+    }
+}
+
+/// The name and value of a `-s` setting: `-sNAME=VALUE`, `-s NAME=VALUE`, or `-sNAME` for 1
+fn setting(group: &[String]) -> Option<(&str, &str)> {
+    let assignment = match group {
+        [flag, value] if flag == "-s" => value.as_str(),
+        [flag] => flag.strip_prefix("-s")?,
+        _ => return None,
+    };
+    let (name, value) = assignment.split_once('=').unwrap_or((assignment, "1"));
+    let is_name = !name.is_empty() && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    is_name.then_some((name, value))
+}
+
+/// The union of two values of a list setting, as `-sNAME=a,b,c`
+fn merge_list(name: &str, first: &str, second: &str) -> String {
+    let items = |value: &str| -> Vec<String> {
+        value
+            .trim_matches(|c| c == '[' || c == ']')
+            .split(',')
+            .map(|item| item.trim().trim_matches(|c| c == '\'' || c == '"').to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    };
+    let mut merged = items(first);
+    for item in items(second) {
+        if !merged.contains(&item) {
+            merged.push(item);
+        }
+    }
+    format!("-s{}={}", name, merged.join(","))
+}
+
+/// The emcc flags of a build, by the steps they apply to
+#[derive(Debug, Clone, Default)]
+pub struct EmccFlags {
+    /// Flags for compiling and linking alike
+    common: FlagSet,
+    compile: FlagSet,
+    link: FlagSet,
+    /// The `--emcc-flags`, added after all others; two-word flags (`--preload-file x`) are
+    /// kept together
+    user: Vec<Vec<String>>,
+    /// Extra compile flags for the sources matching a glob, from the `[flags]` table
+    source_flags: Vec<(GlobMatcher, Vec<String>)>,
+}
+
+impl EmccFlags {
+    /// The flags every handler builds with: optimization, debug info and assertions for the
+    /// build config, exception handling, warnings (`--warn`, `--werror`), the module format,
+    /// environment and runtime methods of the glue, `--single-file`, `--no-entry`,
+    /// `--with-imgui`, and the include directories, defines, libraries and emcc flags from the
+    /// command line.
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self::build(config, config.with_imgui)
+    }
+
+    /// The flags of [`EmccFlags::from_config`] without the ports `--with-imgui` adds, for the
+    /// ImGui handler, which adds the ports of the backends it compiles
+    pub fn without_imgui(config: &AppConfig) -> Self {
+        Self::build(config, false)
+    }
+
+    fn build(config: &AppConfig, with_imgui: bool) -> Self {
+        let mut flags = EmccFlags::default();
+
+        match config.build_config.to_lowercase().as_str() {
+            "debug" => {
+                flags.push("-g4");
+                flags.push("-O0");
+                flags.push("-sASSERTIONS=2");
+                flags.push("-sSAFE_HEAP=1");
+            }
+            "release" => {
+                flags.push("-O3");
+                flags.push("-sASSERTIONS=0");
+            }
+            _ => {
+                flags.push("-O2");
+                flags.push("-sASSERTIONS=1");
+            }
+        }
+        flags.push(EmscriptenRunner::exception_flag(config));
+        for flag in config.warn.flags() {
+            flags.push(*flag);
+        }
+        if config.werror {
+            flags.push("-Werror");
+            // Build tools pass the settings for linking to compile steps as well, which emcc
+            // warns about; that must not fail the build
+            flags.push("-Wno-error=unused-command-line-argument");
+        }
+        flags.compile.extend(config.compile_flags());
+
+        // JS interop and environment of the glue
+        for flag in EmscriptenRunner::module_format_flags(config) {
+            flags.push(flag);
+        }
+        flags.push(format!("-sENVIRONMENT={}", match config.target_env.to_lowercase().as_str() {
+            "web" => "web",
+            "node" => "node",
+            _ => "web,node",
+        }));
+        flags.push(EmscriptenRunner::exported_runtime_methods_flag(config));
+        if config.single_file {
+            flags.push("-sSINGLE_FILE=1"); // Embed the wasm binary into the JS glue as base64
+        }
+        if config.no_entry {
+            flags.push("--no-entry"); // Library build: no main() to link
+        }
+
+        if with_imgui {
+            tracing::info!("ImGui support enabled, adding specific Emscripten flags.");
+            // ImGui examples use SDL for windowing and input, GLFW emulation for the GL context
+            flags.push_imgui(config, true, true);
+        }
+
+        flags.link.extend(config.lib_dir_flags());
+        for flag in system_libraries::map_link_flags(&config.link_lib_flags(), &config.project_config.link_map) {
+            flags.push(flag);
+        }
+        flags.add_user_flags(&config.emcc_flag);
+
+        let mut patterns: Vec<(&String, &Vec<String>)> = config.project_config.flags.iter().collect();
+        patterns.sort_by_key(|(pattern, _)| pattern.len());
+        for (pattern, extra) in patterns {
+            // The patterns were checked when the project config was loaded
+            if let Ok(glob) = path_glob(pattern, "[flags]") {
+                flags.source_flags.push((glob.compile_matcher(), extra.clone()));
+            }
+        }
+        flags
+    }
+
+    /// Adds a flag for the steps it applies to, unless it is there already. A setting replaces
+    /// the build's value of it, unless the emcc flags set it.
+    pub fn push(&mut self, flag: impl Into<String>) {
+        let mut flag = flag.into();
+        if let Some((name, value)) = setting(std::slice::from_ref(&flag)) {
+            let (name, value) = (name.to_string(), value.to_string());
+            if let Some(group) = self.user.iter_mut().find(|group| setting(group).is_some_and(|(user_name, _)| user_name == name)) {
+                if LIST_SETTINGS.contains(&name.as_str()) {
+                    let user_value = setting(group).map(|(_, user_value)| user_value.to_string()).unwrap_or_default();
+                    *group = vec![merge_list(&name, &value, &user_value)];
+                } else {
+                    tracing::debug!("Leaving out {}: the emcc flags set {}", flag, name);
+                }
+                return;
+            }
+            if let Some(previous) = self.take_setting(&name) {
+                let previous_value = setting(std::slice::from_ref(&previous)).map(|(_, value)| value).unwrap_or_default();
+                if LIST_SETTINGS.contains(&name.as_str()) {
+                    flag = merge_list(&name, previous_value, &value);
+                } else if previous_value != value {
+                    tracing::debug!("{} replaces {}", flag, previous);
+                }
+            }
+        }
+        match stage(&flag) {
+            Stage::Both => self.common.push_unique(flag),
+            Stage::Compile => self.compile.push_unique(flag),
+            Stage::Link => self.link.push_unique(flag),
+        }
+    }
+
+    /// Adds the flags ImGui's WebGL rendering needs, and the ports of the SDL and GLFW
+    /// backends when they are used.
+    pub fn push_imgui(&mut self, config: &AppConfig, sdl: bool, glfw: bool) {
+        if sdl {
+            self.push(crate::sdl_version::resolve(config).use_flag());
+        }
+        if glfw {
+            self.push("-sUSE_GLFW=3");
+        }
+        self.push("-sUSE_WEBGL2=1"); // Prefer WebGL2
+        self.push("-sFULL_ES3=1"); // Full GLES3 features for WebGL2
+        self.push("-sGL_ENABLE_GET_PROC_ADDRESS=1"); // Needed by some GL loaders
+        self.push("-sALLOW_MEMORY_GROWTH=1");
+        self.push("-sINITIAL_MEMORY=67108864"); // 64MB initial memory, ImGui can be memory hungry
+        if !self.link.iter().chain(self.user.iter().flatten()).any(|flag| flag.contains("EXPORT_NAME")) {
+            self.push("-sEXPORT_NAME='Module'");
+        }
+        if config.build_config.eq_ignore_ascii_case("debug") {
+            self.push("-sGL_ASSERTIONS=1");
+        }
+    }
+
+    /// Removes the setting `name` from the flags of the build, returning the flag that set it
+    fn take_setting(&mut self, name: &str) -> Option<String> {
+        for set in [&mut self.common, &mut self.compile, &mut self.link] {
+            let index = set.iter().position(|flag| setting(std::slice::from_ref(flag)).is_some_and(|(set_name, _)| set_name == name));
+            if let Some(index) = index {
+                return Some(set.remove(index));
+            }
+        }
+        None
+    }
+
+    /// Adds the `--emcc-flags`. Single flags the build already has are left out, and settings
+    /// replace the build's value, or an earlier one in the emcc flags.
+    fn add_user_flags(&mut self, flags: &[String]) {
+        let mut index = 0;
+        while index < flags.len() {
+            let takes_value = VALUE_FLAGS.contains(&flags[index].as_str()) && index + 1 < flags.len();
+            let mut group = flags[index..index + if takes_value { 2 } else { 1 }].to_vec();
+            index += group.len();
+            let present = [&self.common, &self.compile, &self.link].iter().any(|set| set.contains(&group[0]));
+            if group.len() == 1 && present {
+                continue;
+            }
+
+            if let Some((name, value)) = setting(&group).map(|(name, value)| (name.to_string(), value.to_string())) {
+                let list = LIST_SETTINGS.contains(&name.as_str());
+                let earlier = self.user.iter().position(|user| setting(user).is_some_and(|(user_name, _)| user_name == name));
+                if let Some(earlier) = earlier.map(|earlier| self.user.remove(earlier)) {
+                    let earlier_value = setting(&earlier).map(|(_, value)| value).unwrap_or_default();
+                    if list {
+                        group = vec![merge_list(&name, earlier_value, &value)];
+                    } else if earlier_value != value {
+                        tracing::warn!("The emcc flags set {} twice; {} overrides {}", name, group.join(" "), earlier.join(" "));
+                    }
+                } else if let Some(default) = self.take_setting(&name) {
+                    let default_value = setting(std::slice::from_ref(&default)).map(|(_, value)| value).unwrap_or_default();
+                    if list {
+                        group = vec![merge_list(&name, default_value, &value)];
+                        tracing::debug!("Merged {} from the emcc flags into {}", name, default);
+                    } else if default_value != value {
+                        tracing::info!("{} from the emcc flags overrides {} of the build", group.join(" "), default);
+                    }
+                }
+            }
+            self.user.push(group);
+        }
+    }
+
+    /// The `--emcc-flags` with the steps they apply to
+    fn user_flags(&self) -> impl Iterator<Item = (Stage, &Vec<String>)> {
+        self.user.iter().map(|group| (stage(&group.concat()), group))
+    }
+
+    /// Flags for compiling, e.g. `CMAKE_CXX_FLAGS` or make's `CXXFLAGS`
+    pub fn compile_flags(&self) -> FlagSet {
+        let user = self.user_flags().filter(|(stage, _)| *stage != Stage::Link).flat_map(|(_, group)| group.iter());
+        self.common.iter().chain(self.compile.iter()).chain(user).cloned().collect()
+    }
+
+    /// Flags for linking, e.g. `CMAKE_EXE_LINKER_FLAGS` or make's `LDFLAGS`
+    pub fn link_flags(&self) -> FlagSet {
+        let user = self.user_flags().filter(|(stage, _)| *stage != Stage::Compile).flat_map(|(_, group)| group.iter());
+        self.common.iter().chain(self.link.iter()).chain(user).cloned().collect()
+    }
+
+    /// The extra compile flags of `source` from the `[flags]` table
+    pub fn source_flags(&self, project_path: &Path, source: &Path) -> Vec<String> {
+        let relative = source.strip_prefix(project_path).unwrap_or(source);
+        self.source_flags
+            .iter()
+            .filter(|(glob, _)| glob.is_match(relative))
+            .flat_map(|(_, extra)| extra.iter().cloned())
+            .collect()
+    }
+
+    /// Whether any of `sources` has extra compile flags, so each needs its own command line
+    pub fn has_source_flags(&self, project_path: &Path, sources: &[PathBuf]) -> bool {
+        sources.iter().any(|source| !self.source_flags(project_path, source).is_empty())
+    }
+
+    /// All flags, for an emcc command line that compiles and links at once
+    pub fn all(&self) -> Vec<String> {
+        let user = self.user.iter().flatten();
+        self.common.iter().chain(self.compile.iter()).chain(self.link.iter()).chain(user).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_flags(flags: &[&str]) -> Vec<String> {
+        flags.iter().map(|flag| flag.to_string()).collect()
+    }
+
+    #[test]
+    fn emcc_flags_override_the_build() {
+        let mut flags = EmccFlags::default();
+        flags.push("-O3");
+        flags.push("-sASSERTIONS=0");
+        flags.add_user_flags(&user_flags(&["-sASSERTIONS=1", "-O3"]));
+        assert_eq!(flags.all(), ["-O3", "-sASSERTIONS=1"]);
+    }
+
+    #[test]
+    fn later_emcc_flags_win() {
+        let mut flags = EmccFlags::default();
+        flags.add_user_flags(&user_flags(&["-sSTACK_SIZE=1MB", "-s", "STACK_SIZE=2MB"]));
+        assert_eq!(flags.all(), ["-s", "STACK_SIZE=2MB"]);
+    }
+
+    #[test]
+    fn handler_settings_replace_defaults_but_not_emcc_flags() {
+        let mut flags = EmccFlags::default();
+        flags.push("-sINITIAL_MEMORY=16MB");
+        flags.add_user_flags(&user_flags(&["-sALLOW_MEMORY_GROWTH=0"]));
+        flags.push("-sINITIAL_MEMORY=64MB");
+        flags.push("-sALLOW_MEMORY_GROWTH=1");
+        assert_eq!(flags.all(), ["-sINITIAL_MEMORY=64MB", "-sALLOW_MEMORY_GROWTH=0"]);
+    }
+
+    #[test]
+    fn list_settings_are_merged() {
+        let mut flags = EmccFlags::default();
+        flags.push("-sEXPORTED_FUNCTIONS=_main");
+        flags.add_user_flags(&user_flags(&["-sEXPORTED_FUNCTIONS=['_add','_main']"]));
+        assert_eq!(flags.all(), ["-sEXPORTED_FUNCTIONS=_main,_add"]);
+        flags.push("-sEXPORTED_FUNCTIONS=_free");
+        assert_eq!(flags.all(), ["-sEXPORTED_FUNCTIONS=_free,_main,_add"]);
+    }
+
+    #[test]
+    fn flags_go_to_their_steps() {
+        let mut flags = EmccFlags::default();
+        for flag in ["-O2", "-Iinclude", "-DNDEBUG", "-sUSE_SDL=2", "-sALLOW_MEMORY_GROWTH=1", "-lGL"] {
+            flags.push(flag);
+        }
+        flags.add_user_flags(&user_flags(&["--preload-file", "assets", "-Wno-unused"]));
+        assert_eq!(flags.compile_flags().to_vec(), ["-O2", "-sUSE_SDL=2", "-Iinclude", "-DNDEBUG", "-Wno-unused"]);
+        assert_eq!(flags.link_flags().to_vec(), ["-O2", "-sUSE_SDL=2", "-sALLOW_MEMORY_GROWTH=1", "-lGL", "--preload-file", "assets"]);
+    }
+}
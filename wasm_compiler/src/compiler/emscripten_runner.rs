@@ -1,119 +1,92 @@
 use std::path::{Path, PathBuf};
-use crate::app_config::AppConfig;
-use crate::utils::command_runner::{self, run_command};
+use crate::app_config::{AppConfig, ModuleFormat};
+use crate::utils::command_runner::{self, run_build_command, CommandError};
+use super::error::CompileError;
 use crate::utils::file_system;
+use super::direct_plan::DirectCompilePlan;
+use super::source_language;
+use super::webidl_binder;
+
+/// Files emcc may write next to the JS glue, as the suffixes after `<output-name>.`: the
+/// wasm binary, the preloaded file package, source maps, pthread / wasm worker / audio
+/// worklet scripts, the asm.js memory initializer and symbol maps.
+pub const OUTPUT_SUFFIXES: &[&str] = &[
+    "js", "wasm", "data", "wasm.map", "js.map", "worker.js", "ww.js", "aw.js",
+    "mem", "js.mem", "symbols", "js.symbols",
+];
+
+/// The outputs other than the JS glue, wasm binary, data package and wasm source map, which
+/// [`crate::BuildArtifacts`] reports separately, plus the asm.js fallback of
+/// `--wasm2js-fallback` and its data package
+pub const AUXILIARY_OUTPUT_SUFFIXES: &[&str] = &[
+    "js.map", "worker.js", "ww.js", "aw.js", "mem", "js.mem", "symbols", "js.symbols",
+    "wasm2js.js", "wasm2js.data",
+];
 
 pub struct EmscriptenRunner;
 
+impl Default for EmscriptenRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl EmscriptenRunner {
     pub fn new() -> Self {
         EmscriptenRunner
     }
 
-    fn get_base_emcc_args(config: &AppConfig, output_name: &str) -> Vec<String> {
-        let mut args: Vec<String> = Vec::new();
-
-        // Output WASM and JS file
-        // ... (comments as before)
-
-        // Standard Library & Runtime
-        // ... (comments as before)
-        // args.push("-sFILESYSTEM=1".to_string());
-
-        // Threading:
-        // ... (comments as before)
-
-        // Exception Handling:
-        args.push("-fwasm-exceptions".to_string());
-
-        // Memory Management:
-        // args.push("-sALLOW_MEMORY_GROWTH=1".to_string()); // Default in newer Emscripten often, but good to be explicit if needed.
-                                                          // For ImGui, memory growth can be important.
-
-        // Optimization & Size
-        match config.build_config.to_lowercase().as_str() {
-            "debug" => {
-                args.push("-g4".to_string());
-                args.push("-O0".to_string());
-                args.push("-sASSERTIONS=2".to_string());
-                args.push("-sSAFE_HEAP=1".to_string());
-                args.push("-sGL_ASSERTIONS=1".to_string()); // Good for ImGui debugging
-            }
-            "release" => {
-                args.push("-O3".to_string());
-                args.push("-sASSERTIONS=0".to_string());
-                args.push("--llvm-lto".to_string());
-            }
-            _ => {
-                args.push("-O2".to_string());
-                args.push("-sASSERTIONS=1".to_string());
-            }
-        }
+    /// `-sEXPORTED_RUNTIME_METHODS` for the generated glue; `callMain` only exists when the
+    /// module has an entry point, and `cwrap` is needed by the generated TypeScript API.
+    pub fn exported_runtime_methods_flag(config: &AppConfig) -> String {
+        let call_main = if config.no_entry { "" } else { "callMain," };
+        let cwrap = if config.typescript { ",cwrap" } else { "" };
+        format!("-sEXPORTED_RUNTIME_METHODS=FS,ENV,{}setValue,getValue,UTF8ToString,stringToUTF8{}", call_main, cwrap)
+    }
 
-        // JS Interop & Environment
-        args.push("-sMODULARIZE=1".to_string());
-        args.push("-sEXPORT_ES6=1".to_string());
-        match config.target_env.to_lowercase().as_str() {
-            "web" => args.push("-sENVIRONMENT=web".to_string()),
-            "node" => args.push("-sENVIRONMENT=node".to_string()),
-            // ... (wasi comments as before)
-            _ => args.push("-sENVIRONMENT=web,node".to_string()),
-        }
-        args.push("-sEXPORTED_RUNTIME_METHODS=FS,callMain,setValue,getValue,UTF8ToString,stringToUTF8".to_string());
-        args.push(format!("-sWASM_BINARY_NAME={}.wasm", output_name));
-
-
-        // Third-party libs / UI specific flags
-        if config.with_imgui {
-            log::info!("ImGui support enabled, adding specific Emscripten flags.");
-            args.push("-sUSE_GLFW=3".to_string());      // Use Emscripten's GLFW emulation for window/input
-            args.push("-sUSE_WEBGL2=1".to_string());    // Prefer WebGL2
-            args.push("-sFULL_ES3=1".to_string());      // Request full GLES3 features for WebGL2
-            // args.push("-sMIN_WEBGL_VERSION=2".to_string()); // Explicitly require WebGL2
-            args.push("-sGL_ENABLE_GET_PROC_ADDRESS=1".to_string()); // Needed by some GL loaders
-            args.push("-sALLOW_MEMORY_GROWTH=1".to_string()); // ImGui can use a fair bit of memory
-
-            // For older ImGui examples or simpler WebGL1 contexts, one might use:
-            // args.push("-sLEGACY_GL_EMULATION=1".to_string());
-            // args.push("-sGL_VERSION=2".to_string()); // For GLES2/WebGL1
-
-            // Export the main loop function if the C++ code uses emscripten_set_main_loop
-            // This is often the case with ImGui examples.
-            if !config.emcc_flags.as_deref().unwrap_or("").contains("EXPORT_NAME") &&
-               !args.iter().any(|arg| arg.contains("EXPORT_NAME")) { // Avoid duplicate if user adds it
-                args.push("-sEXPORT_NAME='Module'".to_string()); // Default export name for emscripten_set_main_loop related things
-            }
-             // Common ImGui examples might require these for the main loop and canvas setup
-            args.push("-sUSE_SDL=2".to_string()); // ImGui examples often use SDL for windowing/input even with GLFW for GL
-                                               // If using SDL for event handling with ImGui.
-                                               // This pulls in SDL2 which provides a main loop and event handling.
-                                               // If the C++ code directly uses emscripten_set_main_loop with manual canvas,
-                                               // then USE_SDL might not be strictly necessary, but often helps.
-            args.push("-sINITIAL_MEMORY=67108864".to_string()); // 64MB initial memory, ImGui can be memory hungry
-        }
+    /// Native wasm exception handling, or Emscripten's JavaScript-based exceptions when the
+    /// `--browser-baseline` includes browsers without it.
+    pub fn exception_flag(config: &AppConfig) -> &'static str {
+        if crate::browser_baseline::native_exceptions(config) { "-fwasm-exceptions" } else { "-fexceptions" }
+    }
 
-        // Add any user-specified flags last, so they can override defaults
-        if let Some(flags_str) = &config.emcc_flags {
-            for flag in flags_str.split_whitespace() {
-                // Avoid duplicating flags if they were already added by with_imgui logic
-                if !args.contains(&flag.to_string()) {
-                    args.push(flag.to_string());
-                }
+    /// Flags selecting the module system of the glue. CommonJS and UMD builds use emcc's
+    /// classic MODULARIZE output, which `module_format::post_process_glue` adapts afterwards.
+    pub fn module_format_flags(config: &AppConfig) -> Vec<String> {
+        let mut flags = vec!["-sMODULARIZE=1".to_string()];
+        match config.module_format {
+            ModuleFormat::Esm => flags.push("-sEXPORT_ES6=1".to_string()),
+            ModuleFormat::Cjs | ModuleFormat::Umd => {
+                flags.push(format!("-sEXPORT_NAME={}", crate::module_format::DEFAULT_EXPORT_NAME));
             }
         }
-
-        args
+        flags
     }
 
     pub fn compile_file(
         &self,
         source_file: &Path,
         config: &AppConfig,
-    ) -> Result<PathBuf, String> {
-        log::info!("Compiling single file with emcc: {:?}", source_file);
+    ) -> Result<PathBuf, CompileError> {
+        self.compile_files(&[source_file.to_path_buf()], config)
+    }
 
-        if !command_runner::is_command_in_path("emcc") {
-            return Err("emcc not found in PATH. Please ensure Emscripten SDK is installed and configured.".to_string());
+    /// Compiles and links several sources into one module with a single emcc invocation, or,
+    /// when they mix languages, use C++ modules or have extra flags, each on its own (see
+    /// [`DirectCompilePlan`]).
+    pub fn compile_files(
+        &self,
+        source_files: &[PathBuf],
+        config: &AppConfig,
+    ) -> Result<PathBuf, CompileError> {
+        let source_files = source_language::compilable_sources(source_files, config)?;
+        if source_files.is_empty() {
+            return Err(CompileError::Other("No sources left to compile".to_string()));
+        }
+        tracing::info!("Compiling {} file(s) with emcc: {:?}", source_files.len(), source_files);
+
+        if !command_runner::is_command_in_path("emcc", config) {
+            return Err(CommandError::NotFound { tool: "emcc".to_string() }.into());
         }
 
         file_system::ensure_dir_exists(&config.output_dir)?;
@@ -121,28 +94,96 @@ impl EmscriptenRunner {
         let output_js_target_path = config.output_dir.join(format!("{}.js", config.output_name));
         let output_wasm_target_path = config.output_dir.join(format!("{}.wasm", config.output_name));
 
-        let mut emcc_args = Self::get_base_emcc_args(config, &config.output_name);
-        emcc_args.insert(0, source_file.to_string_lossy().to_string());
-        emcc_args.push("-o".to_string());
-        emcc_args.push(output_js_target_path.to_string_lossy().to_string());
+        let project_path = config.project_path.canonicalize().unwrap_or_else(|_| config.project_path.clone());
+        let webidl_bindings = webidl_binder::generate_bindings(&project_path, config)?;
 
-        log::debug!("Running emcc with args: {:?}", emcc_args.join(" "));
-
-        match run_command("emcc", &emcc_args, Some(config.project_path.as_path())) {
-            Ok(_output) => {
-                log::info!("File compiled successfully. JS output: {:?}, WASM output: {:?}",
+        let mut plan = DirectCompilePlan::new(&project_path, config);
+        plan.flags.push(format!("-sWASM_BINARY_NAME={}.wasm", config.output_name));
+        match config.build_config.to_lowercase().as_str() {
+            "debug" => plan.flags.push("-sGL_ASSERTIONS=1"), // Good for ImGui debugging
+            // The value form, so emcc does not take the next flag as the value of --llvm-lto
+            "release" => plan.flags.push("--llvm-lto=1"),
+            _ => {}
+        }
+        plan.add_sources(source_files);
+        for bindings in &webidl_bindings {
+            plan.add_sources([bindings.wrapper_source.clone()]);
+            plan.output_args.push("--post-js".to_string());
+            plan.output_args.push(bindings.glue_js.to_string_lossy().to_string());
+        }
+        let result = plan.run(&output_js_target_path, config);
+        match result {
+            Ok(()) => {
+                tracing::info!("File compiled successfully. JS output: {:?}, WASM output: {:?}",
                     output_js_target_path, output_wasm_target_path);
-                if output_wasm_target_path.exists() {
+                if config.single_file && output_js_target_path.exists() {
+                    Ok(output_js_target_path)
+                } else if output_wasm_target_path.exists() {
                     Ok(output_wasm_target_path)
                 } else {
-                    Err(format!("WASM file {:?} not found after compilation, though emcc succeeded. Check emcc flags.", output_wasm_target_path))
+                    tracing::error!("WASM file {:?} not found after compilation, though emcc succeeded. Check emcc flags.", output_wasm_target_path);
+                    Err(CompileError::MissingOutput(output_wasm_target_path))
                 }
             }
             Err(e) => {
-                log::error!("emcc compilation failed: {}", e);
-                Err(format!("emcc compilation failed: {}", e))
+                tracing::error!("emcc compilation failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Copies the JS glue and every file emcc wrote next to it (see [`OUTPUT_SUFFIXES`]) from
+    /// `build_dir` to the output directory. Used by handlers whose build tools write their
+    /// outputs elsewhere; direct emcc builds write to the output directory themselves.
+    pub fn copy_outputs(build_dir: &Path, config: &AppConfig) -> Result<(), CompileError> {
+        Self::copy_outputs_from(&build_dir.join(format!("{}.js", config.output_name)), config)
+    }
+
+    /// Copies the JS glue `glue` and the files emcc wrote next to it to the output directory,
+    /// renaming them after `config.output_name`. When the build named them differently, the
+    /// references to the renamed files inside the glue are rewritten to match.
+    pub fn copy_outputs_from(glue: &Path, config: &AppConfig) -> Result<(), CompileError> {
+        let stem = glue.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let stem = stem.strip_suffix(".js").unwrap_or(&stem).to_string();
+        let build_dir = glue.parent().unwrap_or(Path::new("."));
+        let built = |suffix: &str| build_dir.join(format!("{}.{}", stem, suffix));
+        if !built("js").exists() {
+            return Err(CompileError::MissingOutput(built("js")));
+        }
+        if !built("wasm").exists() {
+            if !config.single_file {
+                return Err(CompileError::MissingOutput(built("wasm")));
+            }
+            tracing::debug!("Single-file build: WASM is embedded in {:?}", built("js"));
+        }
+
+        let mut renamed = Vec::new();
+        for suffix in OUTPUT_SUFFIXES {
+            let source = built(suffix);
+            if !source.exists() {
+                continue;
+            }
+            let dest = config.output_dir.join(format!("{}.{}", config.output_name, suffix));
+            std::fs::copy(&source, &dest)
+                .map_err(|e| format!("Failed to copy {:?} to {:?}: {}", source, dest, e))?;
+            tracing::info!("Copied {:?} to {:?}", source, dest);
+            if *suffix != "js" && stem != config.output_name {
+                renamed.push((format!("{}.{}", stem, suffix), format!("{}.{}", config.output_name, suffix)));
+            }
+        }
+
+        if !renamed.is_empty() {
+            let js_path = config.output_dir.join(format!("{}.js", config.output_name));
+            let mut js = std::fs::read_to_string(&js_path)
+                .map_err(|e| format!("Failed to read JS output {:?}: {}", js_path, e))?;
+            for (name, new_name) in &renamed {
+                for quote in ['"', '\''] {
+                    js = js.replace(&format!("{q}{}{q}", name, q = quote), &format!("{q}{}{q}", new_name, q = quote));
+                }
             }
+            std::fs::write(&js_path, js).map_err(|e| format!("Failed to rewrite JS output {:?}: {}", js_path, e))?;
         }
+        Ok(())
     }
 
     pub fn run_emscripten_tool(
@@ -150,37 +191,34 @@ impl EmscriptenRunner {
         args: &[String],
         current_dir: &Path,
         config: &AppConfig, // Pass config for context if needed for env vars or toolchain paths
-    ) -> Result<String, String> {
-        if !command_runner::is_command_in_path(tool) {
-            return Err(format!(
-                "{} not found in PATH. Please ensure Emscripten SDK is installed and configured.",
-                tool
-            ));
+    ) -> Result<String, CommandError> {
+        if !command_runner::is_command_in_path(tool, config) {
+            return Err(CommandError::NotFound { tool: tool.to_string() });
         }
 
         // Potentially set Emscripten-specific environment variables if not using emcmake/emmake
         // e.g., EMCC_CFLAGS, if the tool doesn't automatically pick up the toolchain.
         // For emcmake and emmake, they handle setting up the environment for cmake/make.
 
-        log::info!("Executing Emscripten tool: {} {} in {:?}", tool, args.join(" "), current_dir);
+        tracing::info!("Executing Emscripten tool: {} {} in {:?}", tool, args.join(" "), current_dir);
 
         // Create a string representation of the args for logging/error messages
         // let args_str_vec: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
 
 
-        match run_command(tool, args, Some(current_dir)) {
+        match run_build_command(tool, args, Some(current_dir), config) {
             Ok(output) => {
                 let stdout_str = String::from_utf8_lossy(&output.stdout).to_string();
                 let stderr_str = String::from_utf8_lossy(&output.stderr).to_string();
                 if !stderr_str.is_empty() && !output.status.success() { // emcmake might print to stderr on success
-                    log::warn!("{} execution produced stderr:\n{}", tool, stderr_str);
+                    tracing::warn!("{} execution produced stderr:\n{}", tool, stderr_str);
                 }
-                log::info!("{} executed successfully. Output:\n{}", tool, stdout_str);
+                tracing::info!("{} executed successfully. Output:\n{}", tool, stdout_str);
                 Ok(stdout_str)
             }
             Err(e) => {
-                log::error!("{} execution failed: {}", tool, e);
-                Err(format!("{} execution failed: {}", tool, e))
+                tracing::error!("{} execution failed: {}", tool, e);
+                Err(e)
             }
         }
     }
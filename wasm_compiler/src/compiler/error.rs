@@ -0,0 +1,62 @@
+//! Errors of the individual build stages. They convert into the top-level [`crate::Error`],
+//! and keep the captured output of failed commands for library consumers.
+
+use std::path::PathBuf;
+use crate::utils::command_runner::CommandError;
+use super::include_check::MissingInclude;
+
+/// Failure to find something to build in the project.
+#[derive(thiserror::Error, Debug)]
+pub enum DetectionError {
+    #[error("No files in {project:?} match the --source patterns {patterns:?}.")]
+    NoMatchingSources { project: PathBuf, patterns: Vec<String> },
+    #[error("No CMakeLists.txt, Makefile, or C/C++ source file found in the project.")]
+    NoSources,
+    /// Several candidates were found and `--non-interactive fail` forbids picking one
+    #[error("Several {what} found in the project: {}. Run on a terminal to choose, or pass --non-interactive default to use {}.", .candidates.join(", "), .candidates[0])]
+    Ambiguous { what: String, candidates: Vec<String> },
+}
+
+/// Failure while generating the build files of a project (the `emcmake cmake` step).
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigureError {
+    #[error("{} configure step failed: {0}", .0.tool())]
+    Command(#[from] CommandError),
+}
+
+/// Failure while building a project.
+#[derive(thiserror::Error, Debug)]
+pub enum CompileError {
+    #[error(transparent)]
+    Detection(#[from] DetectionError),
+    #[error(transparent)]
+    Configure(#[from] ConfigureError),
+    #[error("{} execution failed: {0}", .0.tool())]
+    Command(#[from] CommandError),
+    #[error("Expected output file not found: {0:?}")]
+    MissingOutput(PathBuf),
+    /// The project uses a library that cannot be built for WebAssembly
+    #[error("{message}")]
+    Unsupported { library: &'static str, message: String },
+    /// Includes that name no existing header, found before running emcc
+    #[error("{} include(s) cannot be resolved (--no-include-check skips this check):\n{}", .0.len(), .0.iter().map(|include| format!("  {}", include)).collect::<Vec<_>>().join("\n"))]
+    MissingIncludes(Vec<MissingInclude>),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl CompileError {
+    /// The failed command, if the error came from one.
+    pub fn command_error(&self) -> Option<&CommandError> {
+        match self {
+            CompileError::Command(e) | CompileError::Configure(ConfigureError::Command(e)) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for CompileError {
+    fn from(message: String) -> Self {
+        CompileError::Other(message)
+    }
+}
@@ -0,0 +1,290 @@
+//! Preflight check of the `#include` directives of a build, before emcc runs
+//! (`--no-include-check` turns it off). The includes of every source, and of the project
+//! headers those include, are looked up the way clang does: next to the including file for
+//! `"..."`, then in the include directories of the build and the Emscripten sysroot. A missing
+//! header fails the build at once, with the list of unresolved headers and what provides them
+//! (an Emscripten port, `--with-imgui`, `--include-dir`), instead of after a long emcc run.
+//!
+//! Includes inside conditionals that do not test for the browser (`#ifdef _WIN32`,
+//! `__has_include`, `#if 0`, ...) are not checked. Without the sysroot (`emcc --cflags`
+//! failed or named none) nothing is checked.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::app_config::AppConfig;
+use crate::diagnostics::{self, Diagnostic, Severity};
+use crate::exports::strip_comments;
+use crate::utils::preprocessor::{lines_with_branches, Branch};
+use super::compile_commands::{self, Toolchain};
+use super::error::CompileError;
+
+/// Headers of Emscripten ports: the header, or a directory or name prefix ending in `/` or
+/// `_`, the `-s` setting that adds the port, and the port's name for `--use-port`
+const PORT_HEADERS: &[(&str, &str, &str)] = &[
+    ("SDL2/SDL_image.h", "USE_SDL_IMAGE=2", "sdl2_image"),
+    ("SDL_image.h", "USE_SDL_IMAGE=2", "sdl2_image"),
+    ("SDL2/SDL_mixer.h", "USE_SDL_MIXER=2", "sdl2_mixer"),
+    ("SDL_mixer.h", "USE_SDL_MIXER=2", "sdl2_mixer"),
+    ("SDL2/SDL_ttf.h", "USE_SDL_TTF=2", "sdl2_ttf"),
+    ("SDL_ttf.h", "USE_SDL_TTF=2", "sdl2_ttf"),
+    ("SDL2/SDL_net.h", "USE_SDL_NET=2", "sdl2_net"),
+    ("SDL_net.h", "USE_SDL_NET=2", "sdl2_net"),
+    ("SDL2/", "USE_SDL=2", "sdl2"),
+    ("SDL.h", "USE_SDL=2", "sdl2"),
+    ("SDL_", "USE_SDL=2", "sdl2"),
+    ("SDL3/", "USE_SDL=3", "sdl3"),
+    ("png.h", "USE_LIBPNG=1", "libpng"),
+    ("jpeglib.h", "USE_LIBJPEG=1", "libjpeg"),
+    ("zlib.h", "USE_ZLIB=1", "zlib"),
+    ("bzlib.h", "USE_BZIP2=1", "bzip2"),
+    ("ft2build.h", "USE_FREETYPE=1", "freetype"),
+    ("freetype/", "USE_FREETYPE=1", "freetype"),
+    ("hb.h", "USE_HARFBUZZ=1", "harfbuzz"),
+    ("harfbuzz/", "USE_HARFBUZZ=1", "harfbuzz"),
+    ("boost/", "USE_BOOST_HEADERS=1", "boost_headers"),
+    ("ogg/", "USE_OGG=1", "ogg"),
+    ("vorbis/", "USE_VORBIS=1", "vorbis"),
+];
+
+/// Headers of Windows and macOS APIs that do not exist in the browser
+const PLATFORM_HEADERS: &[&str] = &[
+    "windows.h", "winsock2.h", "ws2tcpip.h", "direct.h", "conio.h", "tchar.h", "shlobj.h", "dxgi.h",
+    "d3d9.h", "d3d11.h", "d3d12.h", "dsound.h", "xinput.h", "cocoa/", "corefoundation/", "appkit/",
+];
+
+/// Headers of clang's resource directory, which is not part of the sysroot
+const COMPILER_HEADERS: &[&str] = &[
+    "stddef.h", "stdarg.h", "stdbool.h", "stdint.h", "float.h", "limits.h", "stdalign.h",
+    "stdatomic.h", "stdnoreturn.h", "iso646.h", "tgmath.h", "varargs.h", "unwind.h",
+    "wasm_simd128.h", "arm_neon.h",
+];
+
+/// An `#include` that names no existing header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingInclude {
+    pub file: PathBuf,
+    pub line: u32,
+    /// The name as written, without the quotes or angle brackets
+    pub header: String,
+    pub angled: bool,
+    /// What provides the header
+    pub remedy: String,
+}
+
+impl fmt::Display for MissingInclude {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (open, close) = if self.angled { ('<', '>') } else { ('"', '"') };
+        write!(f, "{}:{}: {}{}{} not found; {}", self.file.display(), self.line, open, self.header, close, self.remedy)
+    }
+}
+
+/// A source with the include directories it is compiled with
+struct Unit {
+    source: PathBuf,
+    include_dirs: Vec<PathBuf>,
+}
+
+/// Checks the includes of `sources` compiled with the emcc command line `emcc_args` and the
+/// extra flags `source_flags` gives each source (the per-path `[flags]` of the project config).
+pub fn check_emcc(
+    project_path: &Path,
+    sources: &[PathBuf],
+    emcc_args: &[String],
+    source_flags: &dyn Fn(&Path) -> Vec<String>,
+    config: &AppConfig,
+) -> Result<(), CompileError> {
+    if config.no_include_check {
+        return Ok(());
+    }
+    let Some(system_dirs) = system_include_dirs(config) else { return Ok(()) };
+    let include_dirs = Toolchain::from_flags(&[], &compile_commands::compile_flags(emcc_args)).includes;
+    let units = sources
+        .iter()
+        .map(|source| {
+            let mut include_dirs = include_dirs.clone();
+            include_dirs.extend(Toolchain::from_flags(&[], &compile_commands::compile_flags(&source_flags(source))).includes);
+            Unit { source: source.clone(), include_dirs }
+        })
+        .collect();
+    report(find_missing(project_path, units, &system_dirs, emcc_args))
+}
+
+/// Checks the includes of the sources in the `compile_commands.json` CMake wrote to
+/// `build_dir`. Does nothing if there is none, as for generators other than Makefiles and Ninja.
+pub fn check_compile_database(project_path: &Path, build_dir: &Path, config: &AppConfig) -> Result<(), CompileError> {
+    #[derive(Deserialize)]
+    struct Entry {
+        directory: PathBuf,
+        file: PathBuf,
+        arguments: Option<Vec<String>>,
+        command: Option<String>,
+    }
+
+    if config.no_include_check {
+        return Ok(());
+    }
+    let Ok(content) = std::fs::read_to_string(build_dir.join(compile_commands::FILE_NAME)) else {
+        return Ok(());
+    };
+    let entries: Vec<Entry> = match serde_json::from_str(&content) {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::debug!("Not checking includes: cannot parse {}: {}", compile_commands::FILE_NAME, e);
+            return Ok(());
+        }
+    };
+    let Some(system_dirs) = system_include_dirs(config) else { return Ok(()) };
+
+    let mut flags: Vec<String> = config.emcc_flag.clone();
+    let units = entries
+        .into_iter()
+        .map(|entry| {
+            let arguments = entry.arguments
+                .or_else(|| entry.command.as_deref().and_then(|command| shell_words::split(command).ok()))
+                .unwrap_or_default();
+            let include_dirs = Toolchain::from_flags(&[], &compile_commands::compile_flags(&arguments))
+                .includes
+                .into_iter()
+                .map(|dir| entry.directory.join(dir))
+                .collect();
+            flags.extend(arguments);
+            Unit { source: entry.directory.join(entry.file), include_dirs }
+        })
+        .collect();
+    report(find_missing(project_path, units, &system_dirs, &flags))
+}
+
+/// The include directories of the Emscripten sysroot, or `None` if it is unknown
+fn system_include_dirs(config: &AppConfig) -> Option<Vec<PathBuf>> {
+    let cflags = match compile_commands::emscripten_cflags(config) {
+        Ok(cflags) => cflags,
+        Err(e) => {
+            tracing::debug!("Not checking includes: {}", e);
+            return None;
+        }
+    };
+    let toolchain = Toolchain::from_flags(&cflags, &[]);
+    if !toolchain.sysroot.as_deref().is_some_and(Path::is_dir) {
+        tracing::debug!("Not checking includes: the Emscripten sysroot {:?} does not exist", toolchain.sysroot);
+        return None;
+    }
+    Some(toolchain.system_includes())
+}
+
+fn report(missing: Vec<MissingInclude>) -> Result<(), CompileError> {
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let diagnostics: Vec<Diagnostic> = missing
+        .iter()
+        .map(|include| {
+            let message = format!("'{}' file not found; {}", include.header, include.remedy);
+            Diagnostic::new(Some(include.file.display().to_string()), Some(include.line), None, Severity::Error, message)
+        })
+        .collect();
+    diagnostics::emit_json(&diagnostics, "wasm_compiler");
+    Err(CompileError::MissingIncludes(missing))
+}
+
+/// The unresolved includes of the units and of the project headers they include
+fn find_missing(project_path: &Path, units: Vec<Unit>, system_dirs: &[PathBuf], flags: &[String]) -> Vec<MissingInclude> {
+    let mut missing = Vec::new();
+    let mut scanned: HashSet<PathBuf> = HashSet::new();
+    for unit in units {
+        let mut queue = VecDeque::from([unit.source]);
+        while let Some(file) = queue.pop_front() {
+            if !scanned.insert(file.clone()) {
+                continue;
+            }
+            let Ok(content) = std::fs::read_to_string(&file) else { continue };
+            for (line, header, angled) in includes(&content) {
+                let local_dir = (!angled).then(|| file.parent().map(Path::to_path_buf)).flatten();
+                let found = local_dir
+                    .iter()
+                    .chain(&unit.include_dirs)
+                    .chain(system_dirs)
+                    .map(|dir| dir.join(&header))
+                    .find(|path| path.is_file());
+                match found {
+                    // Project headers are checked too; third-party and system headers are not
+                    Some(path) => {
+                        if path.starts_with(project_path) && !system_dirs.iter().any(|dir| path.starts_with(dir)) {
+                            queue.push_back(path);
+                        }
+                    }
+                    None => {
+                        if let Some(remedy) = remedy(&header, angled, flags) {
+                            missing.push(MissingInclude { file: file.clone(), line, header, angled, remedy });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    missing
+}
+
+/// The `#include` directives of the lines compiled for the browser: line, header, whether
+/// it is an angle-bracket include
+pub(crate) fn includes(content: &str) -> Vec<(u32, String, bool)> {
+    let code = strip_comments(content);
+    lines_with_branches(&code)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, branch))| *branch == Branch::Compiled)
+        .filter_map(|(index, (line, _))| {
+            let rest = line.trim_start().strip_prefix('#')?.trim_start().strip_prefix("include")?.trim_start();
+            let (close, rest) = match rest.chars().next()? {
+                '<' => ('>', &rest[1..]),
+                '"' => ('"', &rest[1..]),
+                // `#include MACRO` and `#include_next`
+                _ => return None,
+            };
+            let header = &rest[..rest.find(close)?];
+            Some((index as u32 + 1, header.to_string(), close == '>'))
+        })
+        .collect()
+}
+
+/// What provides a header that was not found, or `None` if it is provided anyway
+fn remedy(header: &str, angled: bool, flags: &[String]) -> Option<String> {
+    let matches = |pattern: &str, name: &str| {
+        if pattern.ends_with('/') || pattern.ends_with('_') {
+            name.starts_with(pattern)
+        } else {
+            name == pattern
+        }
+    };
+    if let Some((_, setting, port)) = PORT_HEADERS.iter().find(|(pattern, _, _)| matches(pattern, header)) {
+        let name = setting.split('=').next().unwrap_or(setting);
+        let given = flags.iter().any(|flag| {
+            flag.strip_prefix("-s").and_then(|flag| flag.strip_prefix(name)).is_some_and(|rest| rest.is_empty() || rest.starts_with('='))
+                || flag.strip_prefix("--use-port=").is_some_and(|ports| ports.split(':').next() == Some(port))
+        });
+        // A port's headers are installed when emcc builds it
+        return (!given).then(|| format!("it comes with Emscripten's {} port: add -s{} to --emcc-flags", port, setting));
+    }
+    let file_name = header.rsplit('/').next().unwrap_or(header);
+    if COMPILER_HEADERS.contains(&header) || (!header.contains('/') && file_name.ends_with("intrin.h")) {
+        return None;
+    }
+    if crate::header_deps::is_known(header) {
+        return Some("it is a single-header library; --fetch-headers fetches a pinned copy".to_string());
+    }
+    if file_name.starts_with("imgui") {
+        return Some("pass --with-imgui, or the Dear ImGui directory with --include-dir".to_string());
+    }
+    let lowercase = header.to_lowercase();
+    if PLATFORM_HEADERS.iter().any(|pattern| matches(pattern, &lowercase)) {
+        return Some("it is a desktop platform header; put the include under #ifndef __EMSCRIPTEN__".to_string());
+    }
+    Some(if angled {
+        "it is not in the include directories or the Emscripten sysroot; add its directory with --include-dir, \
+         or build the library it belongs to for Emscripten"
+            .to_string()
+    } else {
+        "it is not next to the including file or in the include directories; add its directory with --include-dir".to_string()
+    })
+}
@@ -0,0 +1,138 @@
+//! Prebuilt archives of vendored libraries (ImGui), so iterative builds link a cached
+//! `lib<name>.a` instead of compiling the same library sources every run. An archive is keyed
+//! by the emcc version, the compile flags and the contents of the library sources, of the
+//! headers next to them and of the headers in the include directories of the flags, so a new
+//! SDK, other flags (a debug build, another SDL port) or an edited `imconfig.h` build a new one. Archives live in `prebuilt/` in the cache directory
+//! (see `--imgui-version`); `--no-library-cache` compiles the sources with the project instead.
+
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use crate::app_config::AppConfig;
+use crate::build_dir::BUILD_TREE_PREFIX;
+use crate::utils::command_runner::{resolve_emscripten_tool, run_build_command};
+use crate::utils::file_system;
+use super::error::CompileError;
+use super::source_language::SourceLanguage;
+
+/// The extensions of the headers hashed into the key of an archive
+const HEADER_EXTENSIONS: &[&str] = &["h", "hh", "hpp", "hxx", "inl"];
+
+/// The archive of `sources` of the library `name` compiled with `compile_flags`, built into
+/// the cache if it is not there yet
+pub fn archive(name: &str, sources: &[PathBuf], compile_flags: &[String], config: &AppConfig) -> Result<PathBuf, CompileError> {
+    let cache = file_system::cache_dir()
+        .ok_or_else(|| CompileError::Other(format!("There is no cache directory for the prebuilt {} archive (pass --no-library-cache)", name)))?;
+    let key = key(name, sources, compile_flags, config)?;
+    let dir = cache.join("prebuilt").join(format!("{}-{}", name, &key[..16]));
+    let archive = dir.join(format!("lib{}.a", name));
+    if archive.is_file() {
+        tracing::info!("Linking the prebuilt {} archive {:?}", name, archive);
+        return Ok(archive);
+    }
+
+    tracing::info!("Building {} into the cached archive {:?}; later builds with the same flags link it", name, archive);
+    // Built next to the final directory and moved in place, so an interrupted build is not
+    // taken for an archive by the next one
+    let partial = cache.join("prebuilt").join(format!("{}-{}.partial-{}", name, &key[..16], std::process::id()));
+    let _ = std::fs::remove_dir_all(&partial);
+    let result = build(name, sources, compile_flags, &partial, config);
+    if let Err(e) = result {
+        let _ = std::fs::remove_dir_all(&partial);
+        return Err(e);
+    }
+    if let Err(e) = std::fs::rename(&partial, &dir) {
+        let _ = std::fs::remove_dir_all(&partial);
+        // Another build cached it in the meantime
+        if !archive.is_file() {
+            return Err(CompileError::Other(format!("Failed to move the {} archive to {:?}: {}", name, dir, e)));
+        }
+    }
+    Ok(archive)
+}
+
+/// Compiles `sources` into objects in `dir` and archives them with emar
+fn build(name: &str, sources: &[PathBuf], compile_flags: &[String], dir: &Path, config: &AppConfig) -> Result<(), CompileError> {
+    file_system::ensure_dir_exists(dir)?;
+    let mut objects = Vec::new();
+    for source in sources {
+        let language = SourceLanguage::of(source).unwrap_or(SourceLanguage::Cxx);
+        let stem = source.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let object = dir.join(format!("{}.o", stem));
+        let mut args: Vec<String> = compile_flags.iter().filter(|flag| language.accepts(flag)).cloned().collect();
+        args.extend(["-c".to_string(), source.to_string_lossy().into_owned(), "-o".to_string(), object.to_string_lossy().into_owned()]);
+        tracing::debug!("Running {} with args: {:?}", language.driver(), args.join(" "));
+        run_build_command(&resolve_emscripten_tool(language.driver()), &args, None, config)?;
+        objects.push(object);
+    }
+
+    let mut args = vec!["rcs".to_string(), dir.join(format!("lib{}.a", name)).to_string_lossy().into_owned()];
+    args.extend(objects.iter().map(|object| object.to_string_lossy().into_owned()));
+    run_build_command(&resolve_emscripten_tool("emar"), &args, None, config)?;
+    for object in &objects {
+        let _ = std::fs::remove_file(object);
+    }
+    Ok(())
+}
+
+/// Hash of everything the objects of the archive depend on
+fn key(name: &str, sources: &[PathBuf], compile_flags: &[String], config: &AppConfig) -> Result<String, CompileError> {
+    let version = run_build_command(&resolve_emscripten_tool("emcc"), &["--version"], None, config)?;
+    let mut inputs = vec![
+        name.to_string(),
+        String::from_utf8_lossy(&version.stdout).lines().next().unwrap_or_default().to_string(),
+        compile_flags.join("\u{0}"),
+    ];
+    let mut sorted = sources.to_vec();
+    sorted.sort();
+    for source in &sorted {
+        inputs.push(format!("{} {}", source.display(), file_system::hash_file(source)?));
+    }
+    // The headers next to the sources, and everything under the include directories and the
+    // forced includes of the flags
+    let mut headers: Vec<PathBuf> = Vec::new();
+    for dir in sorted.iter().filter_map(|source| source.parent()) {
+        let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to read {:?}: {}", dir, e))?;
+        headers.extend(entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|path| is_header(path)));
+    }
+    for path in include_paths(compile_flags) {
+        if path.is_file() {
+            headers.push(path);
+            continue;
+        }
+        let entries = WalkDir::new(&path).into_iter().filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.depth() == 0 || !(name == ".git" || name.starts_with(BUILD_TREE_PREFIX))
+        });
+        headers.extend(entries.filter_map(Result::ok).map(|entry| entry.into_path()).filter(|path| is_header(path)));
+    }
+    headers.sort();
+    headers.dedup();
+    for header in headers {
+        inputs.push(format!("{} {}", header.display(), file_system::hash_file(&header)?));
+    }
+    Ok(file_system::hex_digest(inputs.join("\n").as_bytes()))
+}
+
+fn is_header(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|extension| HEADER_EXTENSIONS.iter().any(|header| extension == *header))
+}
+
+/// The include directories (`-I`, `-isystem`, `-iquote`) and forced includes (`-include`) of
+/// `compile_flags`, whether their path is attached or the next flag
+fn include_paths(compile_flags: &[String]) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut flags = compile_flags.iter();
+    while let Some(flag) = flags.next() {
+        for prefix in ["-isystem", "-iquote", "-include", "-I"] {
+            if flag == prefix {
+                paths.extend(flags.next().map(PathBuf::from));
+                break;
+            }
+            if let Some(path) = flag.strip_prefix(prefix) {
+                paths.push(PathBuf::from(path));
+                break;
+            }
+        }
+    }
+    paths
+}
@@ -1,9 +1,17 @@
 use std::path::Path;
 use crate::app_config::AppConfig;
-use super::LibraryHandler;
+use crate::compiler::error::CompileError;
+use crate::utils::source_selection::project_files;
+use super::{DetectionLimits, LibraryHandler};
 
 pub struct CefHandler;
 
+impl Default for CefHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CefHandler {
     pub fn new() -> Self {
         CefHandler
@@ -16,21 +24,22 @@ impl LibraryHandler for CefHandler {
     }
     
     fn detect(&self, project_path: &Path) -> bool {
+        self.detect_limited(project_path, &DetectionLimits::default())
+    }
+
+    fn detect_limited(&self, project_path: &Path, limits: &DetectionLimits) -> bool {
         // Check for CEF includes in source files
-        for entry in std::fs::read_dir(project_path).unwrap_or_else(|_| std::fs::read_dir(".").unwrap()) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
-                        if let Ok(content) = std::fs::read_to_string(&path) {
-                            if content.contains("#include \"include/cef") || 
-                               content.contains("#include <include/cef") ||
-                               content.contains("CefApp") ||
-                               content.contains("CefClient") ||
-                               content.contains("CefBrowser") ||
-                               content.contains("cef_") {
-                                return true;
-                            }
+        for path in project_files(project_path, Some(limits.max_depth)) {
+            if let Some(extension) = path.extension() {
+                if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
+                    if let Some(content) = limits.read_source(&path) {
+                        if content.contains("#include \"include/cef") || 
+                           content.contains("#include <include/cef") ||
+                           content.contains("CefApp") ||
+                           content.contains("CefClient") ||
+                           content.contains("CefBrowser") ||
+                           content.contains("cef_") {
+                            return true;
                         }
                     }
                 }
@@ -55,12 +64,13 @@ impl LibraryHandler for CefHandler {
         false
     }
     
-    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), String> {
-        Err(format!(
-            "CEF (Chromium Embedded Framework) compilation to WASM is not supported and makes no conceptual sense. \
-            CEF is designed to embed a web browser in native applications, but WASM runs inside a web browser. \
-            If you need web content in a WASM application, consider using iframe elements or direct DOM manipulation."
-        ))
+    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), CompileError> {
+        Err(CompileError::Unsupported {
+            library: self.library_name(),
+            message: "CEF (Chromium Embedded Framework) compilation to WASM is not supported and makes no conceptual sense. \
+                CEF is designed to embed a web browser in native applications, but WASM runs inside a web browser. \
+                If you need web content in a WASM application, consider using iframe elements or direct DOM manipulation.".to_string(),
+        })
     }
     
     fn priority(&self) -> u32 {
@@ -1,9 +1,17 @@
 use std::path::Path;
 use crate::app_config::AppConfig;
-use super::LibraryHandler;
+use crate::compiler::error::CompileError;
+use crate::utils::source_selection::project_files;
+use super::{DetectionLimits, LibraryHandler};
 
 pub struct FltkHandler;
 
+impl Default for FltkHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FltkHandler {
     pub fn new() -> Self {
         FltkHandler
@@ -16,20 +24,21 @@ impl LibraryHandler for FltkHandler {
     }
     
     fn detect(&self, project_path: &Path) -> bool {
+        self.detect_limited(project_path, &DetectionLimits::default())
+    }
+
+    fn detect_limited(&self, project_path: &Path, limits: &DetectionLimits) -> bool {
         // Check for FLTK includes in source files
-        for entry in std::fs::read_dir(project_path).unwrap_or_else(|_| std::fs::read_dir(".").unwrap()) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
-                        if let Ok(content) = std::fs::read_to_string(&path) {
-                            if content.contains("#include <FL/") || 
-                               content.contains("#include \"FL/") ||
-                               content.contains("Fl_") ||
-                               content.contains("Fl::") ||
-                               content.contains("FLTK") {
-                                return true;
-                            }
+        for path in project_files(project_path, Some(limits.max_depth)) {
+            if let Some(extension) = path.extension() {
+                if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
+                    if let Some(content) = limits.read_source(&path) {
+                        if content.contains("#include <FL/") || 
+                           content.contains("#include \"FL/") ||
+                           content.contains("Fl_") ||
+                           content.contains("Fl::") ||
+                           content.contains("FLTK") {
+                            return true;
                         }
                     }
                 }
@@ -57,12 +66,13 @@ impl LibraryHandler for FltkHandler {
         false
     }
     
-    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), String> {
-        Err(format!(
-            "FLTK compilation to WASM is not yet implemented. \
-            FLTK relies on native windowing systems and OpenGL contexts that are not directly available in WebAssembly. \
-            Consider using web-based UI frameworks or ImGui for WASM applications."
-        ))
+    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), CompileError> {
+        Err(CompileError::Unsupported {
+            library: self.library_name(),
+            message: "FLTK compilation to WASM is not yet implemented. \
+                FLTK relies on native windowing systems and OpenGL contexts that are not directly available in WebAssembly. \
+                Consider using web-based UI frameworks or ImGui for WASM applications.".to_string(),
+        })
     }
     
     fn priority(&self) -> u32 {
@@ -1,9 +1,17 @@
 use std::path::Path;
 use crate::app_config::AppConfig;
-use super::LibraryHandler;
+use crate::compiler::error::CompileError;
+use crate::utils::source_selection::project_files;
+use super::{DetectionLimits, LibraryHandler};
 
 pub struct JuceHandler;
 
+impl Default for JuceHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl JuceHandler {
     pub fn new() -> Self {
         JuceHandler
@@ -16,20 +24,21 @@ impl LibraryHandler for JuceHandler {
     }
     
     fn detect(&self, project_path: &Path) -> bool {
+        self.detect_limited(project_path, &DetectionLimits::default())
+    }
+
+    fn detect_limited(&self, project_path: &Path, limits: &DetectionLimits) -> bool {
         // Check for JUCE includes in source files
-        for entry in std::fs::read_dir(project_path).unwrap_or_else(|_| std::fs::read_dir(".").unwrap()) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
-                        if let Ok(content) = std::fs::read_to_string(&path) {
-                            if content.contains("#include <juce_") || 
-                               content.contains("#include \"juce_") ||
-                               content.contains("JUCE_") ||
-                               content.contains("juce::") ||
-                               content.contains("JUCEApplication") {
-                                return true;
-                            }
+        for path in project_files(project_path, Some(limits.max_depth)) {
+            if let Some(extension) = path.extension() {
+                if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
+                    if let Some(content) = limits.read_source(&path) {
+                        if content.contains("#include <juce_") || 
+                           content.contains("#include \"juce_") ||
+                           content.contains("JUCE_") ||
+                           content.contains("juce::") ||
+                           content.contains("JUCEApplication") {
+                            return true;
                         }
                     }
                 }
@@ -37,13 +46,10 @@ impl LibraryHandler for JuceHandler {
         }
         
         // Check for JUCE project files
-        for entry in std::fs::read_dir(project_path).unwrap_or_else(|_| std::fs::read_dir(".").unwrap()) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "jucer" {
-                        return true;
-                    }
+        for path in project_files(project_path, Some(1)) {
+            if let Some(extension) = path.extension() {
+                if extension == "jucer" {
+                    return true;
                 }
             }
         }
@@ -60,13 +66,14 @@ impl LibraryHandler for JuceHandler {
         false
     }
     
-    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), String> {
-        Err(format!(
-            "JUCE compilation to WASM is not yet implemented. \
-            JUCE is primarily designed for audio applications and desktop/mobile platforms. \
-            WebAssembly support for JUCE is experimental and requires special configuration. \
-            Consider using Web Audio API for web-based audio applications."
-        ))
+    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), CompileError> {
+        Err(CompileError::Unsupported {
+            library: self.library_name(),
+            message: "JUCE compilation to WASM is not yet implemented. \
+                JUCE is primarily designed for audio applications and desktop/mobile platforms. \
+                WebAssembly support for JUCE is experimental and requires special configuration. \
+                Consider using Web Audio API for web-based audio applications.".to_string(),
+        })
     }
     
     fn priority(&self) -> u32 {
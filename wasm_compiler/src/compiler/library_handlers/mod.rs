@@ -1,17 +1,69 @@
+use std::io::Read;
 use std::path::Path;
 use crate::app_config::AppConfig;
+use super::error::CompileError;
+
+/// How much of the project library detection reads (`--detect-depth`,
+/// `--detect-max-file-size`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectionLimits {
+    /// Directory levels searched for sources; 1 is the project root only
+    pub max_depth: usize,
+    /// Larger files (generated sources, amalgamations) are not read
+    pub max_file_size: u64,
+}
+
+impl Default for DetectionLimits {
+    fn default() -> Self {
+        DetectionLimits { max_depth: 3, max_file_size: 1024 * 1024 }
+    }
+}
+
+impl DetectionLimits {
+    /// The text of a source file, or `None` if it cannot be read, is larger than
+    /// `max_file_size` or is binary (has a NUL byte in its first 8 KiB)
+    pub fn read_source(&self, path: &Path) -> Option<String> {
+        let file = std::fs::File::open(path).ok()?;
+        let size = file.metadata().ok()?.len();
+        if size > self.max_file_size {
+            tracing::debug!("Not reading {:?} for library detection: {} bytes exceeds the limit of {}", path, size, self.max_file_size);
+            return None;
+        }
+        let mut content = Vec::with_capacity(size as usize);
+        file.take(self.max_file_size).read_to_end(&mut content).ok()?;
+        if content.iter().take(8192).any(|&byte| byte == 0) {
+            tracing::debug!("Not reading {:?} for library detection: binary file", path);
+            return None;
+        }
+        Some(String::from_utf8_lossy(&content).into_owned())
+    }
+}
 
 /// Trait for handling specific UI libraries in C++ projects
-pub trait LibraryHandler {
+pub trait LibraryHandler: Send + Sync {
     /// Returns the name of the library this handler manages
     fn library_name(&self) -> &'static str;
     
     /// Detects if this library is used in the project
     fn detect(&self, project_path: &Path) -> bool;
+
+    /// Like [`LibraryHandler::detect`], reading no more of the project than `limits` allow
+    /// (`--detect-depth`, `--detect-max-file-size`). Handlers that do not implement it detect
+    /// without limits.
+    fn detect_limited(&self, project_path: &Path, limits: &DetectionLimits) -> bool {
+        let _ = limits;
+        self.detect(project_path)
+    }
     
     /// Compiles the project using this library's specific requirements
-    fn compile(&self, project_path: &Path, config: &AppConfig) -> Result<(), String>;
+    fn compile(&self, project_path: &Path, config: &AppConfig) -> Result<(), CompileError>;
     
+    /// Whether projects using this library are graphical applications, which get a webapp
+    /// with `--webapp auto`
+    fn is_graphical(&self) -> bool {
+        true
+    }
+
     /// Returns the priority of this handler (lower numbers have higher priority)
     /// Used when multiple libraries are detected
     fn priority(&self) -> u32 {
@@ -53,22 +105,3 @@ pub fn get_all_handlers() -> Vec<Box<dyn LibraryHandler>> {
         Box::new(UltimatePlusPlusHandler::new()),
     ]
 }
-
-/// Detect which library handler should be used for the project
-pub fn detect_library_handler(project_path: &Path) -> Option<Box<dyn LibraryHandler>> {
-    let handlers = get_all_handlers();
-    
-    // Find all handlers that detect the project
-    let mut detected_handlers: Vec<Box<dyn LibraryHandler>> = handlers
-        .into_iter()
-        .filter(|handler| handler.detect(project_path))
-        .collect();
-    
-    if detected_handlers.is_empty() {
-        return None;
-    }
-    
-    // Sort by priority and return the highest priority handler
-    detected_handlers.sort_by_key(|handler| handler.priority());
-    detected_handlers.into_iter().next()
-}
@@ -1,9 +1,17 @@
 use std::path::Path;
 use crate::app_config::AppConfig;
-use super::LibraryHandler;
+use crate::compiler::error::CompileError;
+use crate::utils::source_selection::project_files;
+use super::{DetectionLimits, LibraryHandler};
 
 pub struct OpenCVHandler;
 
+impl Default for OpenCVHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl OpenCVHandler {
     pub fn new() -> Self {
         OpenCVHandler
@@ -16,19 +24,20 @@ impl LibraryHandler for OpenCVHandler {
     }
     
     fn detect(&self, project_path: &Path) -> bool {
+        self.detect_limited(project_path, &DetectionLimits::default())
+    }
+
+    fn detect_limited(&self, project_path: &Path, limits: &DetectionLimits) -> bool {
         // Check for OpenCV includes in source files
-        for entry in std::fs::read_dir(project_path).unwrap_or_else(|_| std::fs::read_dir(".").unwrap()) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "c" {
-                        if let Ok(content) = std::fs::read_to_string(&path) {
-                            if content.contains("#include <opencv2/") || 
-                               content.contains("#include \"opencv2/") ||
-                               content.contains("cv::") ||
-                               content.contains("CV_") {
-                                return true;
-                            }
+        for path in project_files(project_path, Some(limits.max_depth)) {
+            if let Some(extension) = path.extension() {
+                if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "c" {
+                    if let Some(content) = limits.read_source(&path) {
+                        if content.contains("#include <opencv2/") || 
+                           content.contains("#include \"opencv2/") ||
+                           content.contains("cv::") ||
+                           content.contains("CV_") {
+                            return true;
                         }
                     }
                 }
@@ -57,14 +66,20 @@ impl LibraryHandler for OpenCVHandler {
         false
     }
     
-    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), String> {
-        Err(format!(
-            "OpenCV compilation to WASM is not yet implemented. \
-            OpenCV support for WebAssembly requires special configuration and is currently not supported by this compiler. \
-            Consider using OpenCV.js for web-based computer vision applications."
-        ))
+    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), CompileError> {
+        Err(CompileError::Unsupported {
+            library: self.library_name(),
+            message: "OpenCV compilation to WASM is not yet implemented. \
+                OpenCV support for WebAssembly requires special configuration and is currently not supported by this compiler. \
+                Consider using OpenCV.js for web-based computer vision applications.".to_string(),
+        })
     }
     
+    fn is_graphical(&self) -> bool {
+        // Most OpenCV code processes images without showing a window
+        false
+    }
+
     fn priority(&self) -> u32 {
         20 // High priority as it's a commonly used library
     }
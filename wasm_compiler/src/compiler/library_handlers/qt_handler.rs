@@ -1,9 +1,17 @@
 use std::path::Path;
 use crate::app_config::AppConfig;
-use super::LibraryHandler;
+use crate::compiler::error::CompileError;
+use crate::utils::source_selection::project_files;
+use super::{DetectionLimits, LibraryHandler};
 
 pub struct QtHandler;
 
+impl Default for QtHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl QtHandler {
     pub fn new() -> Self {
         QtHandler
@@ -16,20 +24,21 @@ impl LibraryHandler for QtHandler {
     }
     
     fn detect(&self, project_path: &Path) -> bool {
+        self.detect_limited(project_path, &DetectionLimits::default())
+    }
+
+    fn detect_limited(&self, project_path: &Path, limits: &DetectionLimits) -> bool {
         // Check for Qt includes in source files
-        for entry in std::fs::read_dir(project_path).unwrap_or_else(|_| std::fs::read_dir(".").unwrap()) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
-                        if let Ok(content) = std::fs::read_to_string(&path) {
-                            if content.contains("#include <Q") || 
-                               content.contains("#include \"Q") ||
-                               content.contains("QWidget") ||
-                               content.contains("QApplication") ||
-                               content.contains("Q_OBJECT") {
-                                return true;
-                            }
+        for path in project_files(project_path, Some(limits.max_depth)) {
+            if let Some(extension) = path.extension() {
+                if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
+                    if let Some(content) = limits.read_source(&path) {
+                        if content.contains("#include <Q") || 
+                           content.contains("#include \"Q") ||
+                           content.contains("QWidget") ||
+                           content.contains("QApplication") ||
+                           content.contains("Q_OBJECT") {
+                            return true;
                         }
                     }
                 }
@@ -46,13 +55,10 @@ impl LibraryHandler for QtHandler {
         }
         
         // Check for .pro files (qmake)
-        for entry in std::fs::read_dir(project_path).unwrap_or_else(|_| std::fs::read_dir(".").unwrap()) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "pro" {
-                        return true;
-                    }
+        for path in project_files(project_path, Some(1)) {
+            if let Some(extension) = path.extension() {
+                if extension == "pro" {
+                    return true;
                 }
             }
         }
@@ -60,12 +66,13 @@ impl LibraryHandler for QtHandler {
         false
     }
     
-    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), String> {
-        Err(format!(
-            "Qt compilation to WASM is not yet implemented. \
-            Qt for WebAssembly requires Qt 5.12+ with special configuration and is currently not supported by this compiler. \
-            Please refer to Qt's official WebAssembly documentation for manual compilation."
-        ))
+    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), CompileError> {
+        Err(CompileError::Unsupported {
+            library: self.library_name(),
+            message: "Qt compilation to WASM is not yet implemented. \
+                Qt for WebAssembly requires Qt 5.12+ with special configuration and is currently not supported by this compiler. \
+                Please refer to Qt's official WebAssembly documentation for manual compilation.".to_string(),
+        })
     }
     
     fn priority(&self) -> u32 {
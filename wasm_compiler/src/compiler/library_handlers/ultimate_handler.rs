@@ -1,9 +1,17 @@
 use std::path::Path;
 use crate::app_config::AppConfig;
-use super::LibraryHandler;
+use crate::compiler::error::CompileError;
+use crate::utils::source_selection::project_files;
+use super::{DetectionLimits, LibraryHandler};
 
 pub struct UltimatePlusPlusHandler;
 
+impl Default for UltimatePlusPlusHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl UltimatePlusPlusHandler {
     pub fn new() -> Self {
         UltimatePlusPlusHandler
@@ -16,21 +24,22 @@ impl LibraryHandler for UltimatePlusPlusHandler {
     }
     
     fn detect(&self, project_path: &Path) -> bool {
+        self.detect_limited(project_path, &DetectionLimits::default())
+    }
+
+    fn detect_limited(&self, project_path: &Path, limits: &DetectionLimits) -> bool {
         // Check for Ultimate++ includes in source files
-        for entry in std::fs::read_dir(project_path).unwrap_or_else(|_| std::fs::read_dir(".").unwrap()) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
-                        if let Ok(content) = std::fs::read_to_string(&path) {
-                            if content.contains("#include <CtrlLib/") || 
-                               content.contains("#include \"CtrlLib/") ||
-                               content.contains("#include <Core/") ||
-                               content.contains("NAMESPACE_UPP") ||
-                               content.contains("using namespace Upp;") ||
-                               content.contains("Upp::") {
-                                return true;
-                            }
+        for path in project_files(project_path, Some(limits.max_depth)) {
+            if let Some(extension) = path.extension() {
+                if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
+                    if let Some(content) = limits.read_source(&path) {
+                        if content.contains("#include <CtrlLib/") || 
+                           content.contains("#include \"CtrlLib/") ||
+                           content.contains("#include <Core/") ||
+                           content.contains("NAMESPACE_UPP") ||
+                           content.contains("using namespace Upp;") ||
+                           content.contains("Upp::") {
+                            return true;
                         }
                     }
                 }
@@ -38,13 +47,10 @@ impl LibraryHandler for UltimatePlusPlusHandler {
         }
         
         // Check for Ultimate++ project files
-        for entry in std::fs::read_dir(project_path).unwrap_or_else(|_| std::fs::read_dir(".").unwrap()) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "upp" {
-                        return true;
-                    }
+        for path in project_files(project_path, Some(1)) {
+            if let Some(extension) = path.extension() {
+                if extension == "upp" {
+                    return true;
                 }
             }
         }
@@ -57,13 +63,14 @@ impl LibraryHandler for UltimatePlusPlusHandler {
         false
     }
     
-    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), String> {
-        Err(format!(
-            "Ultimate++ compilation to WASM is not yet implemented. \
-            Ultimate++ is a C++ cross-platform rapid application development suite that relies on native windowing systems. \
-            WebAssembly support would require significant framework modifications. \
-            Consider using web-based UI frameworks or ImGui for WASM applications."
-        ))
+    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), CompileError> {
+        Err(CompileError::Unsupported {
+            library: self.library_name(),
+            message: "Ultimate++ compilation to WASM is not yet implemented. \
+                Ultimate++ is a C++ cross-platform rapid application development suite that relies on native windowing systems. \
+                WebAssembly support would require significant framework modifications. \
+                Consider using web-based UI frameworks or ImGui for WASM applications.".to_string(),
+        })
     }
     
     fn priority(&self) -> u32 {
@@ -1,9 +1,17 @@
 use std::path::Path;
 use crate::app_config::AppConfig;
-use super::LibraryHandler;
+use crate::compiler::error::CompileError;
+use crate::utils::source_selection::project_files;
+use super::{DetectionLimits, LibraryHandler};
 
 pub struct WxWidgetsHandler;
 
+impl Default for WxWidgetsHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl WxWidgetsHandler {
     pub fn new() -> Self {
         WxWidgetsHandler
@@ -16,21 +24,22 @@ impl LibraryHandler for WxWidgetsHandler {
     }
     
     fn detect(&self, project_path: &Path) -> bool {
+        self.detect_limited(project_path, &DetectionLimits::default())
+    }
+
+    fn detect_limited(&self, project_path: &Path, limits: &DetectionLimits) -> bool {
         // Check for wxWidgets includes in source files
-        for entry in std::fs::read_dir(project_path).unwrap_or_else(|_| std::fs::read_dir(".").unwrap()) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
-                        if let Ok(content) = std::fs::read_to_string(&path) {
-                            if content.contains("#include <wx/") || 
-                               content.contains("#include \"wx/") ||
-                               content.contains("wxApp") ||
-                               content.contains("wxFrame") ||
-                               content.contains("wxWidget") ||
-                               content.contains("wx") && (content.contains("IMPLEMENT_APP") || content.contains("wxDECLARE_")) {
-                                return true;
-                            }
+        for path in project_files(project_path, Some(limits.max_depth)) {
+            if let Some(extension) = path.extension() {
+                if extension == "cpp" || extension == "cxx" || extension == "cc" || extension == "h" || extension == "hpp" {
+                    if let Some(content) = limits.read_source(&path) {
+                        if content.contains("#include <wx/") || 
+                           content.contains("#include \"wx/") ||
+                           content.contains("wxApp") ||
+                           content.contains("wxFrame") ||
+                           content.contains("wxWidget") ||
+                           content.contains("wx") && (content.contains("IMPLEMENT_APP") || content.contains("wxDECLARE_")) {
+                            return true;
                         }
                     }
                 }
@@ -58,12 +67,13 @@ impl LibraryHandler for WxWidgetsHandler {
         false
     }
     
-    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), String> {
-        Err(format!(
-            "wxWidgets compilation to WASM is not yet implemented. \
-            wxWidgets relies on native windowing systems and is not designed for WebAssembly. \
-            Consider using web-based UI frameworks or ImGui for WASM applications."
-        ))
+    fn compile(&self, _project_path: &Path, _config: &AppConfig) -> Result<(), CompileError> {
+        Err(CompileError::Unsupported {
+            library: self.library_name(),
+            message: "wxWidgets compilation to WASM is not yet implemented. \
+                wxWidgets relies on native windowing systems and is not designed for WebAssembly. \
+                Consider using web-based UI frameworks or ImGui for WASM applications.".to_string(),
+        })
     }
     
     fn priority(&self) -> u32 {
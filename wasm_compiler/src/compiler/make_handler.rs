@@ -1,44 +1,69 @@
 use std::path::Path;
-use crate::app_config::AppConfig;
+use crate::app_config::{AppConfig, Shell};
 use super::BuildSystemHandler;
+use super::error::CompileError;
 use super::emscripten_runner::EmscriptenRunner; // Import EmscriptenRunner
+use crate::build_dir;
 use crate::utils::file_system;
 use crate::utils::command_runner::resolve_emscripten_tool;
-use std::fs;
+use crate::utils::posix_shell;
+use super::emcc_flags::EmccFlags;
+use super::makefile_flags;
+use crate::utils::flag_set::FlagSet;
 
 pub struct MakeHandler;
 
+/// Whether the Makefile of `project_path` is one CMake generated, i.e. left by an in-source
+/// CMake build rather than written for Make
+pub fn is_cmake_generated(project_path: &Path) -> bool {
+    ["Makefile", "makefile"]
+        .into_iter()
+        .find_map(|name| std::fs::read_to_string(project_path.join(name)).ok())
+        .is_some_and(|content| content.lines().take(5).any(|line| line.to_ascii_lowercase().contains("cmake generated file")))
+}
+
 impl BuildSystemHandler for MakeHandler {
-    fn detect(project_path: &Path) -> bool {
+    fn name(&self) -> &str {
+        "Make"
+    }
+
+    fn detect(&self, project_path: &Path) -> bool {
         project_path.join("Makefile").exists() || project_path.join("makefile").exists()
     }
 
-    fn compile(&self, project_path: &Path, config: &AppConfig) -> Result<(), String> {
-        log::info!("Compiling project with Make at: {:?}", project_path);
-        if !Self::detect(project_path) {
-            return Err("Makefile not found.".to_string());
+    fn compile(&self, project_path: &Path, config: &AppConfig) -> Result<(), CompileError> {
+        tracing::info!("Compiling project with Make at: {:?}", project_path);
+        if !self.detect(project_path) {
+            return Err(CompileError::Other("Makefile not found.".to_string()));
         }
 
         file_system::ensure_dir_exists(&config.output_dir)?;
 
         // Check if any library handler can handle this project
-        if let Some(library_handler) = super::library_handlers::detect_library_handler(project_path) {
-            log::info!("Detected {} library, delegating to specialized handler", library_handler.library_name());
+        if let Some(library_handler) = crate::ambiguity::choose_library(config, project_path)? {
+            tracing::info!("Detected {} library, delegating to specialized handler", library_handler.library_name());
+            let _span = tracing::info_span!("library", handler = library_handler.library_name()).entered();
             return library_handler.compile(project_path, config);
         }
 
         // If no library handler is found, proceed with generic Makefile compilation
-        log::info!("No specific library detected, proceeding with generic Makefile compilation");
+        tracing::info!("No specific library detected, proceeding with generic Makefile compilation");
         self.compile_generic_makefile(project_path, config)
     }
 }
 
+impl Default for MakeHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MakeHandler {
     pub fn new() -> Self {
         MakeHandler
     }
 
-    fn compile_generic_makefile(&self, project_path: &Path, config: &AppConfig) -> Result<(), String> {
+    fn compile_generic_makefile(&self, project_path: &Path, config: &AppConfig) -> Result<(), CompileError> {
 
         // For Makefile projects, emmake handles wrapping most things.
         // We need to pass relevant emcc flags. This can be done by:
@@ -47,47 +72,32 @@ impl MakeHandler {
         // Emscripten's `emmake` sets `CC`, `CXX`, `LD`, etc., to `emcc`, `em++`, `emlink` internally.
         // So, we mainly need to provide additional flags.
 
+        // Makefiles written for Unix need a POSIX shell on Windows (see posix_shell)
+        let shell = posix_shell::choose(config, project_path)?;
+        let routed_config;
+        let config = if shell == Shell::Native {
+            config
+        } else {
+            tracing::info!("Running the Makefile build in {:?}", shell);
+            routed_config = AppConfig { posix_shell: Some(shell), ..config.clone() };
+            &routed_config
+        };
+
         let mut make_args: Vec<String> = Vec::new();
-        make_args.push("make".to_string()); // The command emmake will run
-
-        // Construct CXXFLAGS and LDFLAGS strings
-        // Most of the get_base_emcc_args are linker flags or general compiler options.
-        // We might need to separate them if Makefile distinguishes CFLAGS/CXXFLAGS from LDFLAGS.
-        // For simplicity, let's try passing most as CXXFLAGS and some specific linker flags as LDFLAGS.
-
-        let mut cxx_flags = Vec::new();
-        let mut ld_flags = Vec::new();
-
-        // Common flags (optimization, debug, exceptions)
-        match config.build_config.to_lowercase().as_str() {
-            "debug" => {
-                cxx_flags.push("-g4".to_string());
-                cxx_flags.push("-O0".to_string());
-                cxx_flags.push("-sASSERTIONS=2".to_string());
-                cxx_flags.push("-sSAFE_HEAP=1".to_string()); // Good for debugging
-            }
-            "release" => {
-                cxx_flags.push("-O3".to_string());
-                cxx_flags.push("-sASSERTIONS=0".to_string());
-                // Note: --llvm-lto is deprecated and ignored in newer Emscripten versions
-                // LTO is enabled by default in -O3 builds
-            }
-            _ => {
-                cxx_flags.push("-O2".to_string());
-                cxx_flags.push("-sASSERTIONS=1".to_string());
-            }
+        // The command emmake will run; a pinned make is a Windows program
+        make_args.push(if config.posix_shell.is_some() { "make".to_string() } else { config.tool_program("make") });
+
+        // CXXFLAGS/CFLAGS get the compile flags, LDFLAGS the settings and libraries (see EmccFlags).
+        // Variables on the make command line replace the Makefile's, so its own flags are
+        // passed along, translated for emcc, with ours after them.
+        let makefile = ["Makefile", "makefile"].into_iter().map(|name| project_path.join(name)).find(|path| path.exists());
+        let makefile_flags = makefile.map(|makefile| makefile_flags::read(&makefile, &config.project_config.link_map)).unwrap_or_default();
+        let mut emcc_flags = EmccFlags::from_config(config);
+        for port in &makefile_flags.ports {
+            emcc_flags.push(port.clone());
         }
-        cxx_flags.push("-fwasm-exceptions".to_string());
-
-        // Linker specific flags for JS interop and output naming
-        ld_flags.push(format!("-sMODULARIZE=1"));
-        ld_flags.push(format!("-sEXPORT_ES6=1"));
-        ld_flags.push(format!("-sENVIRONMENT={}", match config.target_env.to_lowercase().as_str() {
-            "web" => "web",
-            "node" => "node",
-            _ => "web,node"
-        }));
-        ld_flags.push("-sEXPORTED_RUNTIME_METHODS=FS,callMain,setValue,getValue,UTF8ToString,stringToUTF8".to_string());
+        let cxx_flags = emcc_flags.compile_flags();
+        let mut ld_flags = emcc_flags.link_flags();
 
         // Output for Makefiles is trickier if the Makefile itself defines the output location.
         // We aim for the final linked product to be named according to config.output_name and be in config.output_dir.
@@ -97,109 +107,58 @@ impl MakeHandler {
         // and we'll try to control the final linking step's output name if possible.
         // This often requires modifying the Makefile or hoping it uses LDFLAGS for the output command.
 
-        // Add user-defined emcc flags
-        if let Some(user_flags_str) = &config.emcc_flags {
-            for flag in user_flags_str.split_whitespace() {
-                // Heuristic: if it starts with -o or is known linker flag, add to LDFLAGS
-                if flag.starts_with("-o") || flag.starts_with("-s") || flag.contains("LINK") || flag.contains("LTO") {
-                    ld_flags.push(flag.to_string());
-                } else {
-                    cxx_flags.push(flag.to_string());
-                }
-            }
-        }
-
-        // Important: The final output naming with `-o <file>.js` and `-sWASM_BINARY_NAME`
-        // must be part of the LDFLAGS for the final link command.
-
-        // Add ImGui specific flags if enabled
-        if config.with_imgui {
-            log::info!("ImGui support enabled for Make, adding specific linker and compiler flags.");
-            ld_flags.push("-sUSE_GLFW=3".to_string());
-            ld_flags.push("-sUSE_WEBGL2=1".to_string());
-            ld_flags.push("-sFULL_ES3=1".to_string());
-            ld_flags.push("-sGL_ENABLE_GET_PROC_ADDRESS=1".to_string());
-            ld_flags.push("-sALLOW_MEMORY_GROWTH=1".to_string());
-            if !config.emcc_flags.as_deref().unwrap_or("").contains("EXPORT_NAME") &&
-               !ld_flags.iter().any(|arg| arg.contains("EXPORT_NAME")) {
-                ld_flags.push("-sEXPORT_NAME='Module'".to_string());
-            }
-            ld_flags.push("-sUSE_SDL=2".to_string());
-            ld_flags.push("-sINITIAL_MEMORY=67108864".to_string());
-
-            // Add GL_ASSERTIONS to CXXFLAGS for debug builds with ImGui
-            if config.build_config.to_lowercase().as_str() == "debug" {
-                if !cxx_flags.contains(&"-sGL_ASSERTIONS=1".to_string()) {
-                    cxx_flags.push("-sGL_ASSERTIONS=1".to_string());
-                }
-            }
-        }
-
-        // Ensure user-provided emcc_flags are de-duplicated if already added by ImGui
-        if let Some(user_flags_str) = &config.emcc_flags {
-            for flag_str in user_flags_str.split_whitespace() {
-                let flag = flag_str.to_string();
-                // Heuristic: if it starts with -o or is known linker flag, add to LDFLAGS
-                if flag.starts_with("-o") || flag.starts_with("-s") || flag.contains("LINK") || flag.contains("LTO") {
-                    if !ld_flags.contains(&flag) {
-                        ld_flags.push(flag);
-                    }
-                } else {
-                    if !cxx_flags.contains(&flag) {
-                        cxx_flags.push(flag);
-                    }
-                }
-            }
-        }
-
+        // Important: The final output naming with `-o <file>.js` must be part of the LDFLAGS
+        // for the final link command.
         let output_js_name_for_ld = format!("{}.js", config.output_name); // This will be relative to where make runs link step
         ld_flags.push("-o".to_string());
         ld_flags.push(output_js_name_for_ld.clone()); // Make will create this in its build dir
         // Note: WASM_BINARY_NAME is not a valid setting, the .wasm file will be automatically named based on the .js output
 
 
+        let with_makefile_flags = |name: &str, flags: &FlagSet| match makefile_flags.variable(name) {
+            Some(variable) if !variable.words.is_empty() => format!("{}={} {}", name, variable.to_make_value(), flags.to_make_value()),
+            _ => format!("{}={}", name, flags.to_make_value()),
+        };
         if !cxx_flags.is_empty() {
-            make_args.push(format!("CXXFLAGS={}", cxx_flags.join(" ")));
-            make_args.push(format!("CFLAGS={}", cxx_flags.join(" "))); // Apply to C files too
+            make_args.push(with_makefile_flags("CXXFLAGS", &cxx_flags));
+            make_args.push(with_makefile_flags("CFLAGS", &cxx_flags)); // Apply to C files too
         }
         if !ld_flags.is_empty() {
-            make_args.push(format!("LDFLAGS={}", ld_flags.join(" ")));
+            make_args.push(with_makefile_flags("LDFLAGS", &ld_flags));
+        }
+        for name in ["CPPFLAGS", "LDLIBS", "LIBS"] {
+            if let Some(variable) = makefile_flags.variable(name) {
+                make_args.push(format!("{}={}", name, variable.to_make_value()));
+            }
         }
 
         // Optionally, allow specifying a make target
         // make_args.push("all"); // or some default target
 
-        log::debug!("Running emmake with args: {:?}", make_args.join(" "));
-        // `emmake` needs to be run from the project path where Makefile exists.
-        EmscriptenRunner::run_emscripten_tool(&resolve_emscripten_tool("emmake"), &make_args, project_path, config)?;
-
-        log::info!("Make project build command executed via emmake.");
-
-        // After `emmake make` finishes, the output files (`output_name.js`, `output_name.wasm`)
-        // should be in the `project_path` (or wherever Makefile places its output, typically CWD).
-        // We then copy them to the configured `output_dir`.
+        // make writes its objects and outputs next to the sources; with a build directory, and
+        // for matrix targets and the wasm2js fallback, it builds a copy of the project instead
+        let make_dir = if config.build_dir.is_some() || config.matrix_target.is_some() {
+            build_dir::mirror_project(project_path, config)?
+        } else {
+            project_path.to_path_buf()
+        };
+        // The copy carries the objects of earlier builds, dated like the sources, which make
+        // would take as up to date; they were built with the flags of the WebAssembly build
+        if crate::wasm2js::is_fallback(config) {
+            make_args.insert(1, "-B".to_string());
+        }
 
-        let built_js_path = project_path.join(format!("{}.js", config.output_name));
-        let built_wasm_path = project_path.join(format!("{}.wasm", config.output_name));
+        tracing::debug!("Running emmake with args: {:?}", make_args.join(" "));
+        // `emmake` needs to be run from the directory where the Makefile exists.
+        EmscriptenRunner::run_emscripten_tool(&resolve_emscripten_tool("emmake"), &make_args, &make_dir, config)?;
 
-        let dest_js_path = config.output_dir.join(format!("{}.js", config.output_name));
-        let dest_wasm_path = config.output_dir.join(format!("{}.wasm", config.output_name));
+        tracing::info!("Make project build command executed via emmake.");
 
-        if built_js_path.exists() {
-            fs::copy(&built_js_path, &dest_js_path)
-                .map_err(|e| format!("Failed to copy JS from {:?} to {:?}: {}", built_js_path, dest_js_path, e))?;
-            log::info!("Copied JS to {:?}", dest_js_path);
-        } else {
-            return Err(format!("Expected JS output file not found after make: {:?}", built_js_path));
-        }
+        // After `emmake make` finishes, the output files (`output_name.js`, `output_name.wasm`, ...)
+        // should be in `make_dir` (or wherever Makefile places its output, typically CWD).
+        // We then copy them to the configured `output_dir`.
 
-        if built_wasm_path.exists() {
-            fs::copy(&built_wasm_path, &dest_wasm_path)
-                .map_err(|e| format!("Failed to copy WASM from {:?} to {:?}: {}", built_wasm_path, dest_wasm_path, e))?;
-            log::info!("Copied WASM to {:?}", dest_wasm_path);
-        } else {
-            return Err(format!("Expected WASM output file not found after make: {:?}", built_wasm_path));
-        }
+        EmscriptenRunner::copy_outputs(&make_dir, config)?;
 
         // Clean up build artifacts from source directory? Optional.
         // fs::remove_file(&built_js_path).ok();
@@ -207,7 +166,7 @@ impl MakeHandler {
         // fs::remove_file(project_path.join(format!("{}.html", config.output_name))).ok(); // If HTML is generated
         // fs::remove_file(project_path.join(format!("{}.worker.js", config.output_name))).ok(); // If pthreads worker is generated
 
-        log::info!("Successfully compiled Makefile project. Output in {:?}", config.output_dir);
+        tracing::info!("Successfully compiled Makefile project. Output in {:?}", config.output_dir);
         Ok(())
     }
 }
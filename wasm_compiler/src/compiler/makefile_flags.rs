@@ -0,0 +1,328 @@
+//! The flag variables a project's Makefile sets for the host compiler (`CXXFLAGS`, `LDLIBS`,
+//! ...), translated for emcc. Flags that have a wasm equivalent are mapped to it (`-ggdb` to
+//! `-g`, and libraries as [`super::system_libraries`] maps them, `-lSDL2` to `-sUSE_SDL=2`),
+//! flags without one are dropped (`-march=native`, `-lpthread`, `-Wl,-rpath`), and each change
+//! is logged.
+//!
+//! Only plain assignments at the top level of the Makefile are read; those inside conditionals
+//! (`ifeq`, `ifdef`, ...) and `define` blocks are left to make. References to other
+//! variables and `$(shell ...)` are kept as they are for make to expand, so flags they
+//! produce are not translated.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use super::system_libraries;
+use crate::utils::flag_set::FlagSet;
+
+/// The variables that hold compiler and linker flags
+pub const FLAG_VARIABLES: &[&str] = &["CFLAGS", "CXXFLAGS", "CPPFLAGS", "LDFLAGS", "LDLIBS", "LIBS"];
+
+/// Host flags and their emcc equivalent (`None` drops the flag), with the reason
+const TRANSLATIONS: &[(&str, Option<&str>, &str)] = &[
+    ("-fopenmp", None, "OpenMP is not supported"),
+    ("-rdynamic", None, "there is no dynamic symbol table"),
+    ("-no-pie", None, "wasm code is not position dependent"),
+    ("-pie", None, "wasm modules are not position independent executables"),
+];
+
+/// Prefixes of host flags that are dropped, with the reason
+const DROPPED_PREFIXES: &[(&str, &str)] = &[
+    ("-march=", "there is a single wasm target"),
+    ("-mtune=", "there is a single wasm target"),
+    ("-mcpu=", "there is a single wasm target"),
+    ("-m64", "wasm32 has 32-bit pointers"),
+    ("-m32", "wasm32 is the only target"),
+    ("-mavx2", "AVX2 has no wasm SIMD equivalent"),
+    ("-mavx512", "AVX-512 has no wasm SIMD equivalent"),
+    ("-mfpmath=", "there is a single wasm target"),
+    ("-static", "wasm modules are always linked statically"),
+    ("-Wl,-rpath", "there are no shared library search paths"),
+    ("-Wl,--as-needed", "wasm-ld does not support it"),
+    ("-Wl,--no-as-needed", "wasm-ld does not support it"),
+    ("-Wl,-z,", "wasm-ld does not support ELF options"),
+];
+
+/// A word of a translated variable: a flag, or a make reference kept for make to expand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Word {
+    Flag(String),
+    Reference(String),
+}
+
+/// A flag variable of the Makefile after translation
+#[derive(Debug, Clone, Default)]
+pub struct Variable {
+    pub name: String,
+    pub words: Vec<Word>,
+}
+
+impl Variable {
+    /// The value for the make command line, with flags quoted and escaped for make and
+    /// references left as they are
+    pub fn to_make_value(&self) -> String {
+        self.words
+            .iter()
+            .map(|word| match word {
+                Word::Flag(flag) => FlagSet::from(vec![flag.clone()]).to_make_value(),
+                Word::Reference(reference) => reference.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// The flag variables of the Makefile after translation, and the Emscripten ports the
+/// libraries they link map to, which apply to compiling as well
+#[derive(Debug, Clone, Default)]
+pub struct MakefileFlags {
+    pub variables: Vec<Variable>,
+    pub ports: Vec<String>,
+}
+
+impl MakefileFlags {
+    /// The translated variable `name`, if the Makefile sets it
+    pub fn variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.iter().find(|variable| variable.name == name)
+    }
+}
+
+/// Reads and translates the flag variables of `makefile`, mapping libraries with the
+/// `[link_map]` of the project config over the built-in mapping; nothing if it cannot be read.
+pub fn read(makefile: &Path, link_map: &BTreeMap<String, Vec<String>>) -> MakefileFlags {
+    let Ok(content) = std::fs::read_to_string(makefile) else { return MakefileFlags::default() };
+    let mut flags = MakefileFlags::default();
+    let mut notes: Vec<String> = Vec::new();
+    let (assignments, conditional) = assignments(&content);
+    for variable in conditional {
+        notes.push(format!("variable {} is also set in a conditional, which is not read; only its unconditional flags are passed on", variable));
+    }
+    for (assignment, value) in assignments {
+        let mut words = Vec::new();
+        let mut raw = split_words(&value).into_iter();
+        while let Some(word) = raw.next() {
+            if word.contains('$') {
+                words.push(Word::Reference(word));
+                continue;
+            }
+            // `-framework Cocoa` is two words
+            if word == "-framework" {
+                let framework = raw.next().unwrap_or_default();
+                notes.push(format!("-framework {} dropped: macOS frameworks do not exist in the browser", framework));
+                continue;
+            }
+            let (translated, note) = translate(&word, link_map);
+            if let Some(note) = note {
+                notes.push(format!("{} {}", word, note));
+            }
+            for flag in translated {
+                if flag.starts_with("-sUSE_") {
+                    if !flags.ports.contains(&flag) {
+                        flags.ports.push(flag);
+                    }
+                } else {
+                    words.push(Word::Flag(flag));
+                }
+            }
+        }
+        match flags.variables.iter_mut().find(|variable| variable.name == assignment.variable) {
+            Some(variable) if assignment.append => variable.words.extend(words),
+            Some(variable) if !assignment.if_unset => variable.words = words,
+            Some(_) => {}
+            None => flags.variables.push(Variable { name: assignment.variable.to_string(), words }),
+        }
+    }
+
+    let mut seen = Vec::new();
+    for note in notes {
+        if !seen.contains(&note) {
+            tracing::info!("Makefile flag {}", note);
+            seen.push(note);
+        }
+    }
+    flags
+}
+
+/// The emcc flags for a host flag, with a note when it was changed
+fn translate(flag: &str, link_map: &BTreeMap<String, Vec<String>>) -> (Vec<String>, Option<String>) {
+    if let Some(mapping) = flag.strip_prefix("-l").and_then(|library| system_libraries::map_library(library, link_map)) {
+        let note = mapping.outcome();
+        return (mapping.flags, Some(note));
+    }
+    if let Some((_, replacement, reason)) = TRANSLATIONS.iter().find(|(host, _, _)| *host == flag) {
+        return match replacement {
+            Some(replacement) => (vec![replacement.to_string()], Some(format!("mapped to {}: {}", replacement, reason))),
+            None => (Vec::new(), Some(format!("dropped: {}", reason))),
+        };
+    }
+    if let Some((_, reason)) = DROPPED_PREFIXES.iter().find(|(prefix, _)| flag.starts_with(prefix)) {
+        return (Vec::new(), Some(format!("dropped: {}", reason)));
+    }
+    if flag.starts_with("-ggdb") {
+        return (vec!["-g".to_string()], Some("mapped to -g".to_string()));
+    }
+    if flag.starts_with("-flto=") {
+        return (vec!["-flto".to_string()], Some("mapped to -flto".to_string()));
+    }
+    // Emscripten emulates SSE up to 4.2 and AVX on wasm SIMD
+    if flag.starts_with("-msse") || flag.starts_with("-mssse") || flag == "-mavx" {
+        return (vec![flag.to_string(), "-msimd128".to_string()], Some("kept with -msimd128, which Emscripten's emulation of it needs".to_string()));
+    }
+    (vec![flag.to_string()], None)
+}
+
+/// How a variable is assigned
+struct Assignment {
+    variable: &'static str,
+    /// `+=`
+    append: bool,
+    /// `?=`
+    if_unset: bool,
+}
+
+/// The assignments to flag variables at the top level of a Makefile, in order, and the flag
+/// variables assigned in conditionals. Assignments in conditionals and `define` blocks are
+/// skipped: which branch make takes is not known here.
+fn assignments(content: &str) -> (Vec<(Assignment, String)>, Vec<&'static str>) {
+    let joined = content.replace("\\\r\n", " ").replace("\\\n", " ");
+    let mut conditionals = 0usize;
+    let mut in_define = false;
+    let mut conditional_variables = Vec::new();
+    let assignments = joined
+        .lines()
+        .filter(|line| {
+            let mut words = line.split_whitespace();
+            let directive = match words.next() {
+                Some("override") => words.next(),
+                word => word,
+            };
+            // `ifeq(a,b)` has no space before its arguments
+            match directive.unwrap_or("").split('(').next().unwrap_or("") {
+                "define" => in_define = true,
+                "endef" => in_define = false,
+                _ if in_define => {}
+                "ifeq" | "ifneq" | "ifdef" | "ifndef" => conditionals += 1,
+                "endif" => conditionals = conditionals.saturating_sub(1),
+                _ if conditionals > 0 => {
+                    if let Some((assignment, _)) = assignment(line) {
+                        if !conditional_variables.contains(&assignment.variable) {
+                            conditional_variables.push(assignment.variable);
+                        }
+                    }
+                }
+                // The directive lines themselves hold no assignment
+                _ => return true,
+            }
+            false
+        })
+        .filter_map(assignment)
+        .collect();
+    (assignments, conditional_variables)
+}
+
+/// The assignment to a flag variable on `line`, if any
+fn assignment(line: &str) -> Option<(Assignment, String)> {
+    // Recipe lines start with a tab
+    if line.starts_with('\t') {
+        return None;
+    }
+    let line = line.split('#').next().unwrap_or("");
+    let line = line.trim_start();
+    let line = line.strip_prefix("override ").or_else(|| line.strip_prefix("export ")).unwrap_or(line).trim_start();
+    let variable = FLAG_VARIABLES.iter().find(|variable| line.starts_with(*variable))?;
+    let rest = line[variable.len()..].trim_start();
+    let (operator, value) = ["+=", "?=", "::=", ":=", "="]
+        .iter()
+        .find_map(|operator| rest.strip_prefix(operator).map(|value| (*operator, value)))?;
+    let assignment = Assignment { variable, append: operator == "+=", if_unset: operator == "?=" };
+    Some((assignment, value.trim().to_string()))
+}
+
+/// The words of a variable value; `$(...)` and `${...}` are one word even with spaces inside
+fn split_words(value: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut depth = 0usize;
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' if matches!(chars.peek(), Some('(') | Some('{')) => {
+                word.push(c);
+                word.extend(chars.next());
+                depth += 1;
+            }
+            '(' | '{' if depth > 0 => {
+                word.push(c);
+                depth += 1;
+            }
+            ')' | '}' if depth > 0 => {
+                word.push(c);
+                depth -= 1;
+            }
+            c if c.is_whitespace() && depth == 0 => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn top_level(content: &str) -> Vec<(&'static str, bool, bool, String)> {
+        assignments(content).0.into_iter().map(|(assignment, value)| (assignment.variable, assignment.append, assignment.if_unset, value)).collect()
+    }
+
+    #[test]
+    fn plain_assignments() {
+        let content = "CXXFLAGS = -O2 \\\n  -Wall\nLDLIBS += -lm # math\nCFLAGS ?= -g\noverride LDFLAGS := -s\n\tCXXFLAGS = recipe\n";
+        assert_eq!(
+            top_level(content),
+            [
+                ("CXXFLAGS", false, false, "-O2    -Wall".to_string()),
+                ("LDLIBS", true, false, "-lm".to_string()),
+                ("CFLAGS", false, true, "-g".to_string()),
+                ("LDFLAGS", false, false, "-s".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn conditionals_are_skipped() {
+        let content = "CXXFLAGS = -O2\nifeq($(OS),Windows_NT)\nCXXFLAGS += -DWIN\nifdef DEBUG\nLDFLAGS = -g\nendif\nCXXFLAGS += -DNESTED\nelse\nLIBS = -lpthread\nendif\nLDLIBS = -lm\n";
+        let (assignments, conditional) = assignments(content);
+        let variables: Vec<&str> = assignments.iter().map(|(assignment, _)| assignment.variable).collect();
+        assert_eq!(variables, ["CXXFLAGS", "LDLIBS"]);
+        assert_eq!(conditional, ["CXXFLAGS", "LDFLAGS", "LIBS"]);
+    }
+
+    #[test]
+    fn define_blocks_are_skipped() {
+        let content = "define RULE\nCXXFLAGS = -DINSIDE\nifeq (a,b)\nendef\nCXXFLAGS = -DOUTSIDE\n";
+        let (assignments, conditional) = assignments(content);
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].1, "-DOUTSIDE");
+        assert!(conditional.is_empty());
+    }
+
+    #[test]
+    fn references_are_one_word() {
+        assert_eq!(split_words("-O2 $(shell pkg-config --cflags sdl2) ${EXTRA} -g"), ["-O2", "$(shell pkg-config --cflags sdl2)", "${EXTRA}", "-g"]);
+    }
+
+    #[test]
+    fn host_flags_are_translated() {
+        let link_map = BTreeMap::new();
+        assert_eq!(translate("-march=native", &link_map).0, Vec::<String>::new());
+        assert_eq!(translate("-ggdb3", &link_map).0, ["-g"]);
+        assert_eq!(translate("-msse4.1", &link_map).0, ["-msse4.1", "-msimd128"]);
+        assert_eq!(translate("-Wall", &link_map), (vec!["-Wall".to_string()], None));
+    }
+}
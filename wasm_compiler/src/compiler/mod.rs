@@ -2,19 +2,41 @@
 //! build systems (like CMake, Make) and orchestrating the compilation process
 //! using Emscripten.
 
+pub mod build_progress;
+pub mod cmake_file_api;
 pub mod cmake_handler;
+pub mod compile_commands;
+pub mod cxx_modules;
+pub mod dependency_scan;
+pub mod direct_plan;
+pub mod ctest;
+pub mod emcc_flags;
 pub mod emscripten_runner;
+pub mod error;
+pub mod include_check;
+pub mod library_cache;
 pub mod make_handler;
+pub mod makefile_flags;
+pub mod ninja;
 pub mod library_handlers;
+pub mod registry;
+pub mod source_language;
+pub mod system_libraries;
+pub mod webidl_binder;
 
 use crate::app_config::AppConfig;
+use error::CompileError;
 use std::path::Path;
 
 /// A trait representing a handler for a specific build system.
 ///
 /// Each build system (like CMake or Make) will have an implementation of this trait
 /// to detect if a project uses that system and to perform the compilation steps.
-pub trait BuildSystemHandler {
+/// Handlers are registered in a [`registry::HandlerRegistry`].
+pub trait BuildSystemHandler: Send + Sync {
+    /// Name of the build system, for logs
+    fn name(&self) -> &str;
+
     /// Detects if the given project path is managed by this build system.
     ///
     /// # Arguments
@@ -22,7 +44,7 @@ pub trait BuildSystemHandler {
     ///
     /// # Returns
     /// `true` if the build system is detected, `false` otherwise.
-    fn detect(project_path: &Path) -> bool where Self: Sized;
+    fn detect(&self, project_path: &Path) -> bool;
 
     /// Compiles the project using this build system and Emscripten.
     ///
@@ -31,6 +53,13 @@ pub trait BuildSystemHandler {
     /// * `config` - The application configuration containing build settings.
     ///
     /// # Returns
-    /// A `Result` indicating success or an error message string.
-    fn compile(&self, project_path: &Path, config: &AppConfig) -> Result<(), String>;
+    /// A `Result` indicating success or the [`CompileError`] that stopped the build.
+    fn compile(&self, project_path: &Path, config: &AppConfig) -> Result<(), CompileError>;
+
+    /// Whether the handler reports [`Phase::Configure`](crate::events::Phase::Configure) and
+    /// [`Phase::Build`](crate::events::Phase::Build) itself. Otherwise the build phase starts
+    /// when the handler is called.
+    fn reports_phases(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,42 @@
+//! Builds with the Ninja generator (`--cmake-generator Ninja`). Ninja prints a status line
+//! for every build step (see [`super::build_progress`]) and records the duration of every
+//! step in `.ninja_log`, from which the slowest steps of a build are logged.
+
+use std::path::Path;
+use std::time::Duration;
+
+/// Whether the CMake build tree `build_dir` was generated for Ninja
+pub fn is_ninja_build(build_dir: &Path) -> bool {
+    build_dir.join("build.ninja").is_file()
+}
+
+/// Length of `.ninja_log` before a build, so [`slowest_steps`] only looks at the steps the
+/// build ran
+pub fn log_length(build_dir: &Path) -> u64 {
+    std::fs::metadata(build_dir.join(".ninja_log")).map_or(0, |metadata| metadata.len())
+}
+
+/// The `count` longest steps recorded in `.ninja_log` after `offset`, longest first, as their
+/// output file and duration.
+pub fn slowest_steps(build_dir: &Path, offset: u64, count: usize) -> Vec<(String, Duration)> {
+    let Ok(log) = std::fs::read_to_string(build_dir.join(".ninja_log")) else {
+        return Vec::new();
+    };
+    // Ninja rewrites the log when it grows too large; then every entry is considered
+    let new_entries = usize::try_from(offset).ok().and_then(|offset| log.get(offset..)).unwrap_or(&log);
+    // Lines are `<start ms>\t<end ms>\t<mtime>\t<output>\t<command hash>`
+    let mut steps: Vec<(String, Duration)> = new_entries
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let start: u64 = fields.next()?.parse().ok()?;
+            let end: u64 = fields.next()?.parse().ok()?;
+            let output = fields.nth(1)?;
+            Some((output.to_string(), Duration::from_millis(end.saturating_sub(start))))
+        })
+        .collect();
+    steps.sort_by_key(|(_, duration)| std::cmp::Reverse(*duration));
+    steps.truncate(count);
+    steps
+}
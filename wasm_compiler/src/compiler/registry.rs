@@ -0,0 +1,97 @@
+//! The set of build-system and library handlers consulted for a project. Applications can
+//! register their own handlers next to the built-in ones.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use super::{BuildSystemHandler, cmake_handler::CMakeHandler, make_handler::MakeHandler};
+use super::library_handlers::{self, DetectionLimits, LibraryHandler};
+
+#[derive(Clone)]
+pub struct HandlerRegistry {
+    build_systems: Vec<Arc<dyn BuildSystemHandler>>,
+    libraries: Vec<Arc<dyn LibraryHandler>>,
+}
+
+impl HandlerRegistry {
+    /// A registry without any handlers.
+    pub fn empty() -> Self {
+        HandlerRegistry { build_systems: Vec::new(), libraries: Vec::new() }
+    }
+
+    /// Registers a build-system handler. It is consulted before the handlers registered
+    /// earlier, so custom handlers take precedence over CMake and Make.
+    pub fn register_build_system(&mut self, handler: impl BuildSystemHandler + 'static) -> &mut Self {
+        self.build_systems.insert(0, Arc::new(handler));
+        self
+    }
+
+    /// Registers a library handler. Among the detected libraries, the handler with the
+    /// lowest [`LibraryHandler::priority`] is used.
+    pub fn register_library(&mut self, handler: impl LibraryHandler + 'static) -> &mut Self {
+        self.libraries.push(Arc::new(handler));
+        self
+    }
+
+    /// The build-system handler for the project, if any detects it.
+    pub fn detect_build_system(&self, project_path: &Path) -> Option<&dyn BuildSystemHandler> {
+        self.detect_build_systems(project_path).into_iter().next()
+    }
+
+    /// Every build-system handler that detects the project, in order of precedence.
+    pub fn detect_build_systems(&self, project_path: &Path) -> Vec<&dyn BuildSystemHandler> {
+        self.build_systems
+            .iter()
+            .filter(|handler| handler.detect(project_path))
+            .map(|handler| handler.as_ref())
+            .collect()
+    }
+
+    /// The highest-priority library handler that detects the project, if any.
+    pub fn detect_library(&self, project_path: &Path) -> Option<&dyn LibraryHandler> {
+        self.detect_library_limited(project_path, &DetectionLimits::default())
+    }
+
+    /// Every library handler that detects the project, highest priority first.
+    pub fn detect_libraries(&self, project_path: &Path) -> Vec<&dyn LibraryHandler> {
+        self.detect_libraries_limited(project_path, &DetectionLimits::default())
+    }
+
+    /// Like [`HandlerRegistry::detect_library`], reading no more of the project than `limits`
+    /// allow (see [`LibraryHandler::detect_limited`]).
+    pub fn detect_library_limited(&self, project_path: &Path, limits: &DetectionLimits) -> Option<&dyn LibraryHandler> {
+        self.detect_libraries_limited(project_path, limits).into_iter().next()
+    }
+
+    /// Like [`HandlerRegistry::detect_libraries`], reading no more of the project than
+    /// `limits` allow (see [`LibraryHandler::detect_limited`]).
+    pub fn detect_libraries_limited(&self, project_path: &Path, limits: &DetectionLimits) -> Vec<&dyn LibraryHandler> {
+        let mut handlers: Vec<&dyn LibraryHandler> = self.libraries
+            .iter()
+            .filter(|handler| handler.detect_limited(project_path, limits))
+            .map(|handler| handler.as_ref())
+            .collect();
+        handlers.sort_by_key(|handler| handler.priority());
+        handlers
+    }
+}
+
+impl Default for HandlerRegistry {
+    /// The built-in handlers: CMake, Make and all library handlers.
+    fn default() -> Self {
+        let mut registry = HandlerRegistry::empty();
+        registry.register_build_system(MakeHandler::new());
+        registry.register_build_system(CMakeHandler::new());
+        registry.libraries = library_handlers::get_all_handlers().into_iter().map(Arc::from).collect();
+        registry
+    }
+}
+
+impl fmt::Debug for HandlerRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandlerRegistry")
+            .field("build_systems", &self.build_systems.iter().map(|h| h.name()).collect::<Vec<_>>())
+            .field("libraries", &self.libraries.iter().map(|h| h.library_name()).collect::<Vec<_>>())
+            .finish()
+    }
+}
@@ -0,0 +1,98 @@
+//! The language of a source file, which decides the compiler driver and the flags it gets when
+//! a direct build mixes languages: C (`.c`) and assembly (`.s`, `.S`) go to emcc, C++ (`.cpp`,
+//! `.cxx`, `.cc`), C++ module interfaces (`.cppm`, `.ixx`, see [`super::cxx_modules`]) and
+//! Objective-C++ (`.mm`) to em++, and a language standard given for one language
+//! (`-std=c++17`) is not passed to the others.
+//!
+//! Assembly has to be WebAssembly assembly. Files written for the host CPU (x86 or ARM
+//! optimizations that come with a C fallback) are left out of the build, which is an error with
+//! `--strict`. Emscripten has no Objective-C runtime, so Objective-C++ files only link when they
+//! use the C++ side of the language.
+
+use std::path::{Path, PathBuf};
+use crate::app_config::AppConfig;
+
+/// Text only WebAssembly assembly contains
+const WASM_ASSEMBLY_MARKERS: &[&str] = &[".functype", "end_function", ".globaltype", "local.get", "i32.const"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceLanguage {
+    C,
+    Cxx,
+    CxxModule,
+    ObjectiveCxx,
+    Assembly,
+}
+
+impl SourceLanguage {
+    /// The language of `path` from its extension; `None` for files that are not compiled
+    pub fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "c" => Some(SourceLanguage::C),
+            "cpp" | "cxx" | "cc" => Some(SourceLanguage::Cxx),
+            "cppm" | "ixx" => Some(SourceLanguage::CxxModule),
+            "mm" => Some(SourceLanguage::ObjectiveCxx),
+            "s" | "S" => Some(SourceLanguage::Assembly),
+            _ => None,
+        }
+    }
+
+    /// Whether it is one of the C++ languages
+    pub fn is_cxx(&self) -> bool {
+        matches!(self, SourceLanguage::Cxx | SourceLanguage::CxxModule | SourceLanguage::ObjectiveCxx)
+    }
+
+    /// The Emscripten driver compiling it
+    pub fn driver(&self) -> &'static str {
+        if self.is_cxx() { "em++" } else { "emcc" }
+    }
+
+    /// The clang driver for `compile_commands.json`
+    pub fn clang(&self) -> &'static str {
+        if self.is_cxx() { "clang++" } else { "clang" }
+    }
+
+    /// Whether a compile flag applies to this language: `-std=` only to the language it names
+    pub fn accepts(&self, flag: &str) -> bool {
+        let Some(standard) = flag.strip_prefix("-std=") else { return true };
+        match self {
+            SourceLanguage::C => !standard.contains("++"),
+            SourceLanguage::Assembly => false,
+            _ => standard.contains("++"),
+        }
+    }
+}
+
+/// Whether `sources` are in more than one language, so each needs its own driver
+pub fn is_mixed(sources: &[PathBuf]) -> bool {
+    let mut languages = sources.iter().filter_map(|source| SourceLanguage::of(source));
+    let Some(first) = languages.next() else { return false };
+    languages.any(|language| language != first)
+}
+
+/// `sources` without assembly that is not WebAssembly assembly, which is skipped with a
+/// warning (an error with `--strict`).
+pub fn compilable_sources(sources: &[PathBuf], config: &AppConfig) -> Result<Vec<PathBuf>, String> {
+    let mut compilable = Vec::new();
+    for source in sources {
+        match SourceLanguage::of(source) {
+            Some(SourceLanguage::Assembly) if !is_wasm_assembly(source) => {
+                crate::strict::fallback(config, format!(
+                    "{} is not WebAssembly assembly and is left out of the build; the project needs a C or C++ fallback for it (exclude it with --exclude)",
+                    source.display()
+                ))?;
+            }
+            Some(SourceLanguage::ObjectiveCxx) => {
+                tracing::info!("Compiling {} as Objective-C++; Emscripten has no Objective-C runtime, so only its C++ code links", source.display());
+                compilable.push(source.clone());
+            }
+            _ => compilable.push(source.clone()),
+        }
+    }
+    Ok(compilable)
+}
+
+/// Whether the assembly file `path` is written for WebAssembly
+fn is_wasm_assembly(path: &Path) -> bool {
+    std::fs::read_to_string(path).is_ok_and(|content| WASM_ASSEMBLY_MARKERS.iter().any(|marker| content.contains(marker)))
+}
@@ -0,0 +1,247 @@
+//! Native system libraries a project links (`-lGL`, `-lcurl`, `-lpthread`, ...) mapped to
+//! their Emscripten equivalents: the `-s` setting of a port or browser API (`-sUSE_SDL=2`,
+//! `-sFETCH=1`), Emscripten's own library under another name (`-lfreeglut` is `-lglut`), or
+//! nothing for libraries whose functions are part of libc or that do not exist in the browser.
+//! The `[link_map]` table of the project config adds mappings and overrides the built-in ones:
+//!
+//! ```toml
+//! [link_map]
+//! mylegacyaudio = []
+//! curl = ["-sFETCH=1", "-sFETCH_SUPPORT_INDEXEDDB=0"]
+//! ```
+//!
+//! Make projects get the mapping applied to the flag variables of their Makefile (see
+//! [`super::makefile_flags`]), CMake projects to the plain library names in
+//! `target_link_libraries`, which become imported targets carrying the replacement flags, and
+//! every build to `--link-lib`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use crate::utils::cmake_script;
+use crate::utils::source_selection::project_files;
+
+/// Libraries, what emcc gets instead (no flags drops the library), and why
+const DEFAULT_MAP: &[(&str, &[&str], &str)] = &[
+    ("GL", &["-sMAX_WEBGL_VERSION=2"], "OpenGL is WebGL, which emcc links itself"),
+    ("OpenGL", &["-sMAX_WEBGL_VERSION=2"], "OpenGL is WebGL, which emcc links itself"),
+    ("opengl32", &["-sMAX_WEBGL_VERSION=2"], "OpenGL is WebGL, which emcc links itself"),
+    ("GLESv2", &["-sMAX_WEBGL_VERSION=2"], "OpenGL ES is WebGL, which emcc links itself"),
+    ("GLESv3", &["-sMAX_WEBGL_VERSION=2"], "OpenGL ES is WebGL, which emcc links itself"),
+    ("GLU", &[], "GLU is not available for WebGL"),
+    ("freeglut", &["-lglut"], "Emscripten emulates GLUT"),
+    ("glew32", &["-lGLEW"], "Emscripten provides GLEW"),
+    ("OpenAL32", &["-lopenal"], "Emscripten implements OpenAL on Web Audio"),
+    ("openal32", &["-lopenal"], "Emscripten implements OpenAL on Web Audio"),
+    ("curl", &["-sFETCH=1"], "libcurl does not exist in the browser; HTTP requests go through emscripten_fetch() (-sFETCH), which the code has to use instead"),
+    ("glfw", &["-sUSE_GLFW=3"], "GLFW is an Emscripten port"),
+    ("glfw3", &["-sUSE_GLFW=3"], "GLFW is an Emscripten port"),
+    ("SDL2", &["-sUSE_SDL=2"], "SDL2 is an Emscripten port"),
+    ("SDL2main", &[], "the SDL2 port needs no SDL_main"),
+    ("SDL2_image", &["-sUSE_SDL_IMAGE=2"], "SDL2_image is an Emscripten port"),
+    ("SDL2_mixer", &["-sUSE_SDL_MIXER=2"], "SDL2_mixer is an Emscripten port"),
+    ("SDL2_ttf", &["-sUSE_SDL_TTF=2"], "SDL2_ttf is an Emscripten port"),
+    ("SDL2_net", &["-sUSE_SDL_NET=2"], "SDL2_net is an Emscripten port"),
+    ("SDL3", &["-sUSE_SDL=3"], "SDL3 is an Emscripten port"),
+    ("z", &["-sUSE_ZLIB=1"], "zlib is an Emscripten port"),
+    ("png", &["-sUSE_LIBPNG=1"], "libpng is an Emscripten port"),
+    ("png16", &["-sUSE_LIBPNG=1"], "libpng is an Emscripten port"),
+    ("jpeg", &["-sUSE_LIBJPEG=1"], "libjpeg is an Emscripten port"),
+    ("freetype", &["-sUSE_FREETYPE=1"], "FreeType is an Emscripten port"),
+    ("harfbuzz", &["-sUSE_HARFBUZZ=1"], "HarfBuzz is an Emscripten port"),
+    ("bz2", &["-sUSE_BZIP2=1"], "bzip2 is an Emscripten port"),
+    ("ogg", &["-sUSE_OGG=1"], "libogg is an Emscripten port"),
+    ("vorbis", &["-sUSE_VORBIS=1"], "libvorbis is an Emscripten port"),
+    ("pthread", &[], "threads need -pthread and a cross-origin isolated page; add -pthread to --emcc-flags for them"),
+    ("dl", &[], "dynamic loading is part of libc"),
+    ("rt", &[], "it is part of libc"),
+    ("atomic", &[], "atomics are built in"),
+    ("stdc++", &[], "emcc links libc++ itself"),
+    ("stdc++fs", &[], "std::filesystem is part of libc++"),
+    ("X11", &[], "there is no X11 in the browser"),
+    ("Xrandr", &[], "there is no X11 in the browser"),
+    ("Xi", &[], "there is no X11 in the browser"),
+    ("Xext", &[], "there is no X11 in the browser"),
+    ("Xcursor", &[], "there is no X11 in the browser"),
+    ("Xinerama", &[], "there is no X11 in the browser"),
+    ("Xxf86vm", &[], "there is no X11 in the browser"),
+    ("xcb", &[], "there is no X11 in the browser"),
+    ("asound", &[], "audio goes through Web Audio; use SDL audio or OpenAL"),
+    ("pulse", &[], "audio goes through Web Audio; use SDL audio or OpenAL"),
+    ("pulse-simple", &[], "audio goes through Web Audio; use SDL audio or OpenAL"),
+    ("ws2_32", &[], "Windows libraries do not exist in the browser"),
+    ("winmm", &[], "Windows libraries do not exist in the browser"),
+    ("gdi32", &[], "Windows libraries do not exist in the browser"),
+    ("user32", &[], "Windows libraries do not exist in the browser"),
+    ("kernel32", &[], "Windows libraries do not exist in the browser"),
+];
+
+/// What a native library becomes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    pub library: String,
+    /// The emcc flags replacing `-l<library>`; none drops it
+    pub flags: Vec<String>,
+    pub reason: String,
+}
+
+impl Mapping {
+    /// What happens to the library and why, e.g. `mapped to -sFETCH=1: ...`
+    pub fn outcome(&self) -> String {
+        if self.flags.is_empty() {
+            format!("dropped: {}", self.reason)
+        } else {
+            format!("mapped to {}: {}", self.flags.join(" "), self.reason)
+        }
+    }
+
+    /// The mapping for the log, e.g. `-lcurl mapped to -sFETCH=1: ...`
+    pub fn describe(&self) -> String {
+        format!("-l{} {}", self.library, self.outcome())
+    }
+}
+
+/// The mapping of `library` (without `-l`), from `[link_map]` or the built-in table.
+pub fn map_library(library: &str, overrides: &BTreeMap<String, Vec<String>>) -> Option<Mapping> {
+    if let Some(flags) = overrides.get(library) {
+        return Some(Mapping { library: library.to_string(), flags: flags.clone(), reason: "[link_map] of the project config".to_string() });
+    }
+    DEFAULT_MAP.iter().find(|(name, _, _)| *name == library).map(|(name, flags, reason)| Mapping {
+        library: name.to_string(),
+        flags: flags.iter().map(|flag| flag.to_string()).collect(),
+        reason: reason.to_string(),
+    })
+}
+
+/// Link flags with every `-l<library>` that has a mapping replaced, logging each change.
+pub fn map_link_flags(flags: &[String], overrides: &BTreeMap<String, Vec<String>>) -> Vec<String> {
+    let mut mapped = Vec::new();
+    for flag in flags {
+        match flag.strip_prefix("-l").and_then(|library| map_library(library, overrides)) {
+            Some(mapping) => {
+                tracing::info!("Link flag {}", mapping.describe());
+                mapped.extend(mapping.flags);
+            }
+            None => mapped.push(flag.clone()),
+        }
+    }
+    mapped
+}
+
+/// The file name of the link map in the build tree
+const LINK_MAP_FILE: &str = "wasm_compiler_link_map.cmake";
+
+/// The cache variable keeping the `CMAKE_PROJECT_INCLUDE` the build tree had before the link
+/// map took its place, which the link map includes in turn
+const CHAINED_PROJECT_INCLUDE: &str = "WASM_COMPILER_CHAINED_PROJECT_INCLUDE";
+
+/// The `-D` arguments for configuring `build_dir` with the link map of the project (see
+/// [`write_cmake_link_map`]) as `CMAKE_PROJECT_INCLUDE`. A `CMAKE_PROJECT_INCLUDE` the build
+/// tree was configured with before is not replaced but included by the link map, and is
+/// restored when the project no longer links a mapped library.
+pub fn cmake_link_map_args(project_path: &Path, build_dir: &Path, overrides: &BTreeMap<String, Vec<String>>) -> Result<Vec<String>, String> {
+    let own = build_dir.join(LINK_MAP_FILE);
+    let cached = cached_variable(build_dir, "CMAKE_PROJECT_INCLUDE").filter(|value| !value.is_empty());
+    let cached_is_own = cached.as_deref().is_some_and(|value| Path::new(value) == own);
+    let mut args = Vec::new();
+    match write_cmake_link_map(project_path, build_dir, overrides)? {
+        Some(link_map) => {
+            if let Some(user_include) = cached.filter(|_| !cached_is_own) {
+                tracing::info!("The link map includes the CMAKE_PROJECT_INCLUDE {} of the build tree", user_include);
+                args.push(format!("-D{}={}", CHAINED_PROJECT_INCLUDE, user_include));
+            }
+            args.push(format!("-DCMAKE_PROJECT_INCLUDE={}", link_map.to_string_lossy()));
+        }
+        // A link map of an earlier build is still set
+        None if cached_is_own => {
+            let user_include = cached_variable(build_dir, CHAINED_PROJECT_INCLUDE).unwrap_or_default();
+            args.push(format!("-DCMAKE_PROJECT_INCLUDE={}", user_include));
+        }
+        None => {}
+    }
+    Ok(args)
+}
+
+/// Writes a CMake script defining an imported target for each mapped library the project
+/// links by plain name with `target_link_libraries` or `link_libraries`, carrying the
+/// replacement flags, so CMake links that instead of `-l<library>`. Returns the script for
+/// `CMAKE_PROJECT_INCLUDE`, or `None` if the project links no mapped library. Libraries the
+/// project builds itself are left alone.
+pub fn write_cmake_link_map(project_path: &Path, build_dir: &Path, overrides: &BTreeMap<String, Vec<String>>) -> Result<Option<PathBuf>, String> {
+    let mut linked: Vec<String> = Vec::new();
+    let mut defined: Vec<String> = Vec::new();
+    let cmake_files = project_files(project_path, None).into_iter().filter(|path| {
+        path.file_name().is_some_and(|name| name == "CMakeLists.txt") || path.extension().is_some_and(|ext| ext == "cmake")
+    });
+    for file in cmake_files {
+        let Ok(content) = std::fs::read_to_string(&file) else { continue };
+        for (command, arguments) in commands(&content) {
+            match command.as_str() {
+                // The first argument is the target being linked
+                "target_link_libraries" => linked.extend(arguments.into_iter().skip(1)),
+                "link_libraries" => linked.extend(arguments),
+                "add_library" | "add_executable" => defined.extend(arguments.into_iter().take(1)),
+                _ => {}
+            }
+        }
+    }
+
+    let mut mappings: Vec<Mapping> = Vec::new();
+    for library in linked {
+        if defined.contains(&library) || mappings.iter().any(|mapping| mapping.library == library) {
+            continue;
+        }
+        if let Some(mapping) = map_library(&library, overrides) {
+            mappings.push(mapping);
+        }
+    }
+    if mappings.is_empty() {
+        return Ok(None);
+    }
+
+    let mut script = String::from("# Generated by wasm_compiler: native libraries mapped to their Emscripten equivalents\n");
+    for mapping in &mappings {
+        tracing::info!("CMake link library {}", mapping.describe());
+        script.push_str(&format!("if(NOT TARGET {name})\n  add_library({name} INTERFACE IMPORTED GLOBAL)\n", name = mapping.library));
+        if !mapping.flags.is_empty() {
+            // Ports also provide their headers, so their settings apply to compiling as well
+            let ports: Vec<&str> = mapping.flags.iter().map(String::as_str).filter(|flag| flag.starts_with("-sUSE_")).collect();
+            script.push_str(&format!(
+                "  set_target_properties({} PROPERTIES INTERFACE_COMPILE_OPTIONS \"{}\" INTERFACE_LINK_OPTIONS \"{}\")\n",
+                mapping.library,
+                ports.join(";"),
+                mapping.flags.join(";")
+            ));
+        }
+        script.push_str("endif()\n");
+    }
+    script.push_str(&format!("if({chained})\n  include(\"${{{chained}}}\")\nendif()\n", chained = CHAINED_PROJECT_INCLUDE));
+    let path = build_dir.join(LINK_MAP_FILE);
+    std::fs::write(&path, script).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    Ok(Some(path))
+}
+
+/// The invocations of a CMake file with their plain arguments: variable references and
+/// keywords (`PRIVATE`, `debug`, ...) are left out
+fn commands(content: &str) -> Vec<(String, Vec<String>)> {
+    const KEYWORDS: &[&str] = &["PUBLIC", "PRIVATE", "INTERFACE", "LINK_PUBLIC", "LINK_PRIVATE", "debug", "optimized", "general"];
+    cmake_script::invocations(content)
+        .into_iter()
+        .map(|invocation| {
+            let arguments = invocation
+                .arguments
+                .into_iter()
+                .filter(|argument| !argument.is_empty() && !argument.contains('$') && !KEYWORDS.contains(&argument.as_str()))
+                .collect();
+            (invocation.name, arguments)
+        })
+        .collect()
+}
+
+/// The value of the cache variable `name` in the `CMakeCache.txt` of `build_dir`
+fn cached_variable(build_dir: &Path, name: &str) -> Option<String> {
+    let cache = std::fs::read_to_string(build_dir.join("CMakeCache.txt")).ok()?;
+    cache.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.split(':').next() == Some(name)).then(|| value.to_string())
+    })
+}
@@ -0,0 +1,144 @@
+//! WebIDL Binder support: projects that ship `.idl` files get JavaScript bindings generated
+//! with Emscripten's `tools/webidl_binder.py`. The generated C++ glue is compiled together with
+//! the project sources and the generated JS glue is appended to the module with `--post-js`.
+
+use std::path::{Path, PathBuf};
+use crate::app_config::AppConfig;
+use crate::utils::command_runner::run_build_command;
+use crate::utils::file_system;
+use crate::utils::source_selection::project_files;
+
+/// Build tree the generated glue is written to (see [`AppConfig::build_tree`])
+const BUILD_DIR_NAME: &str = "build_wasm_webidl";
+
+const HEADER_EXTENSIONS: &[&str] = &["h", "hpp", "hh", "hxx"];
+
+/// Generated glue for one `.idl` file
+#[derive(Debug, Clone)]
+pub struct WebIdlBindings {
+    /// C++ file that includes the bound headers and the generated glue; compile it with the sources
+    pub wrapper_source: PathBuf,
+    /// Generated JavaScript glue, passed to emcc with `--post-js`
+    pub glue_js: PathBuf,
+}
+
+/// Finds the `.idl` files of the project, skipping ignored, hidden and build directories.
+pub fn find_idl_files(project_path: &Path) -> Vec<PathBuf> {
+    project_files(project_path, Some(3))
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "idl"))
+        .collect()
+}
+
+/// Runs the WebIDL binder for every `.idl` file in the project. Returns an empty list when
+/// the project has none.
+pub fn generate_bindings(project_path: &Path, config: &AppConfig) -> Result<Vec<WebIdlBindings>, String> {
+    let idl_files = find_idl_files(project_path);
+    if idl_files.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let binder = find_webidl_binder().ok_or_else(|| {
+        "Project contains .idl files but webidl_binder.py was not found. Make sure emcc is in PATH \
+         or EMSCRIPTEN points to the Emscripten directory.".to_string()
+    })?;
+    let build_dir = config.build_tree(project_path, BUILD_DIR_NAME);
+    file_system::ensure_dir_exists(&build_dir)?;
+
+    let mut bindings = Vec::new();
+    for idl_file in idl_files {
+        let stem = idl_file.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let output_base = build_dir.join(format!("{}_glue", stem));
+        tracing::info!("Generating WebIDL bindings for {:?}", idl_file);
+
+        let args = vec![
+            binder.to_string_lossy().into_owned(),
+            idl_file.to_string_lossy().into_owned(),
+            output_base.to_string_lossy().into_owned(),
+        ];
+        run_build_command(python_command(), &args, Some(project_path), config)
+            .map_err(|e| format!("webidl_binder.py failed for {:?}: {}", idl_file, e))?;
+
+        let glue_cpp = output_base.with_extension("cpp");
+        let glue_js = output_base.with_extension("js");
+        if !glue_cpp.exists() || !glue_js.exists() {
+            return Err(format!("webidl_binder.py did not produce {:?} and {:?}", glue_cpp, glue_js));
+        }
+
+        let headers = bound_headers(&idl_file, config);
+        if headers.is_empty() {
+            tracing::warn!("No header found for {:?}; pass the declarations it binds with --webidl-header", idl_file);
+        }
+        let mut wrapper = String::from("// Generated by wasm_compiler: the WebIDL glue needs the declarations it binds\n");
+        for header in &headers {
+            wrapper.push_str(&format!("#include \"{}\"\n", header.display()));
+        }
+        wrapper.push_str(&format!("#include \"{}\"\n", glue_cpp.display()));
+        let wrapper_source = build_dir.join(format!("{}_glue_wrapper.cpp", stem));
+        std::fs::write(&wrapper_source, wrapper)
+            .map_err(|e| format!("Failed to write {:?}: {}", wrapper_source, e))?;
+
+        bindings.push(WebIdlBindings { wrapper_source, glue_js });
+    }
+    Ok(bindings)
+}
+
+/// The headers included before the glue: `--webidl-header` if given, otherwise the header
+/// with the same name as the `.idl` file, otherwise every header next to it.
+fn bound_headers(idl_file: &Path, config: &AppConfig) -> Vec<PathBuf> {
+    if !config.webidl_headers.is_empty() {
+        return config
+            .webidl_headers
+            .iter()
+            .map(|header| std::path::absolute(header).unwrap_or_else(|_| header.clone()))
+            .collect();
+    }
+
+    let idl_dir = idl_file.parent().unwrap_or(Path::new("."));
+    let same_stem = HEADER_EXTENSIONS
+        .iter()
+        .map(|ext| idl_file.with_extension(ext))
+        .find(|header| header.is_file());
+    if let Some(header) = same_stem {
+        return vec![header];
+    }
+
+    let mut headers: Vec<PathBuf> = std::fs::read_dir(idl_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| HEADER_EXTENSIONS.contains(&ext))
+        })
+        .collect();
+    headers.sort();
+    headers
+}
+
+/// Locates `tools/webidl_binder.py` from `$EMSCRIPTEN`, the directory of `emcc` in PATH, or
+/// the emsdk layout under `$EMSDK`.
+fn find_webidl_binder() -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Some(dir) = std::env::var_os("EMSCRIPTEN") {
+        candidates.push(PathBuf::from(dir));
+    }
+    if let Some(path) = std::env::var_os("PATH") {
+        candidates.extend(
+            std::env::split_paths(&path).filter(|dir| dir.join("emcc").exists() || dir.join("emcc.bat").exists()),
+        );
+    }
+    if let Some(emsdk) = std::env::var_os("EMSDK") {
+        candidates.push(PathBuf::from(emsdk).join("upstream").join("emscripten"));
+    }
+    candidates
+        .into_iter()
+        .map(|dir| dir.join("tools").join("webidl_binder.py"))
+        .find(|binder| binder.is_file())
+}
+
+fn python_command() -> &'static str {
+    if cfg!(windows) { "python" } else { "python3" }
+}
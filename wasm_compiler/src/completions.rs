@@ -0,0 +1,65 @@
+//! The `completions` subcommand: shell completions for bash, zsh, fish and PowerShell, and
+//! the man page, generated from the command-line definition so they list every option.
+
+use std::io::Write;
+use std::path::Path;
+use clap::CommandFactory;
+use clap_complete::Shell;
+use crate::app_config::{AppConfig, CompletionFormat, CompletionsArgs};
+
+/// Prints the completions or man page, or writes them to `--output-dir`.
+pub fn generate(args: &CompletionsArgs) -> Result<(), String> {
+    let mut command = AppConfig::command();
+    let bin_name = command.get_name().to_string();
+    let shell = match args.format {
+        CompletionFormat::Bash => Shell::Bash,
+        CompletionFormat::Zsh => Shell::Zsh,
+        CompletionFormat::Fish => Shell::Fish,
+        CompletionFormat::PowerShell => Shell::PowerShell,
+        CompletionFormat::Man => return generate_man(command, args.output_dir.as_deref()),
+    };
+
+    match &args.output_dir {
+        Some(output_dir) => {
+            create_dir(output_dir)?;
+            let path = clap_complete::generate_to(shell, &mut command, &bin_name, output_dir)
+                .map_err(|e| format!("Failed to write {} completions to {:?}: {}", shell, output_dir, e))?;
+            println!("Wrote {}", path.display());
+        }
+        None => {
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut command, bin_name, &mut script);
+            print(&script)?;
+        }
+    }
+    Ok(())
+}
+
+fn generate_man(command: clap::Command, output_dir: Option<&Path>) -> Result<(), String> {
+    match output_dir {
+        Some(output_dir) => {
+            create_dir(output_dir)?;
+            clap_mangen::generate_to(command, output_dir)
+                .map_err(|e| format!("Failed to write man pages to {:?}: {}", output_dir, e))?;
+            println!("Wrote man pages to {}", output_dir.display());
+            Ok(())
+        }
+        None => {
+            let mut page = Vec::new();
+            clap_mangen::Man::new(command).render(&mut page).map_err(|e| format!("Failed to render the man page: {}", e))?;
+            print(&page)
+        }
+    }
+}
+
+/// Writes to stdout; a closed pipe (e.g. `| head`) is not an error
+fn print(content: &[u8]) -> Result<(), String> {
+    match std::io::stdout().lock().write_all(content) {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(format!("Failed to write to stdout: {}", e)),
+        _ => Ok(()),
+    }
+}
+
+fn create_dir(dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))
+}
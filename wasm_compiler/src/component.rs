@@ -0,0 +1,65 @@
+//! Experimental WebAssembly Component Model output (`--component`).
+//!
+//! The core module is built with `-sSTANDALONE_WASM=1` so it imports WASI instead of the JS
+//! glue, then wrapped with `wasm-tools`: `component embed` attaches the WIT world, and
+//! `component new` produces the component, adapting WASI preview 1 imports to preview 2 with
+//! the adapter module given by `--wasi-adapter`.
+
+use std::path::PathBuf;
+use crate::app_config::AppConfig;
+use crate::utils::command_runner::{is_command_in_path, run_build_command};
+
+/// Wraps the built core module into `<output>.component.wasm` and returns its path.
+pub fn build_component(config: &AppConfig) -> Result<PathBuf, String> {
+    let wit = config.wit.as_ref().ok_or("--component requires --wit <PATH>")?;
+    if !is_command_in_path("wasm-tools", config) {
+        return Err("wasm-tools not found in PATH. Install it with `cargo install wasm-tools` to use --component.".to_string());
+    }
+
+    let core_wasm = config.output_dir.join(format!("{}.wasm", config.output_name));
+    if !core_wasm.exists() {
+        return Err(format!("Core module {:?} not found; --component cannot be combined with --single-file", core_wasm));
+    }
+    let embedded = config.output_dir.join(format!("{}.embedded.wasm", config.output_name));
+    let component = config.output_dir.join(format!("{}.component.wasm", config.output_name));
+
+    let mut embed_args = vec![
+        "component".to_string(),
+        "embed".to_string(),
+        wit.to_string_lossy().into_owned(),
+        core_wasm.to_string_lossy().into_owned(),
+        "-o".to_string(),
+        embedded.to_string_lossy().into_owned(),
+    ];
+    if let Some(world) = &config.wit_world {
+        embed_args.push("--world".to_string());
+        embed_args.push(world.clone());
+    }
+    run_build_command("wasm-tools", &embed_args, None, config)
+        .map_err(|e| format!("wasm-tools component embed failed: {}", e))?;
+
+    let mut new_args = vec![
+        "component".to_string(),
+        "new".to_string(),
+        embedded.to_string_lossy().into_owned(),
+        "-o".to_string(),
+        component.to_string_lossy().into_owned(),
+    ];
+    match &config.wasi_adapter {
+        Some(adapter) => {
+            new_args.push("--adapt".to_string());
+            new_args.push(format!("wasi_snapshot_preview1={}", adapter.display()));
+        }
+        None => tracing::warn!(
+            "No --wasi-adapter given; componentizing fails if the module imports WASI preview 1 \
+             (use wasi_snapshot_preview1.reactor.wasm or .command.wasm from the wasmtime releases)"
+        ),
+    }
+    let result = run_build_command("wasm-tools", &new_args, None, config)
+        .map_err(|e| format!("wasm-tools component new failed: {}", e));
+    std::fs::remove_file(&embedded).ok();
+    result?;
+
+    tracing::info!("Created WebAssembly component: {:?}", component);
+    Ok(component)
+}
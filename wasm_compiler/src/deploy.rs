@@ -0,0 +1,109 @@
+//! Static-hosting deployment helpers: arranging the output directory for GitHub Pages and
+//! the `deploy` subcommand, which publishes the output directory to a git branch.
+
+use std::path::{Path, PathBuf};
+use crate::app_config::{AppConfig, DeployArgs};
+use crate::build_manifest::BUILD_MANIFEST_FILE_NAME;
+use crate::output_dir::{self, OUTPUT_MARKER_FILE_NAME};
+use crate::utils::command_runner::run_command;
+use crate::utils::file_system;
+
+/// Arranges `config.output_dir` for GitHub Pages: adds `.nojekyll` (so files starting with
+/// an underscore are served) and a `404.html` copy of the page, and warns about absolute
+/// asset paths, which break when the site is served from a `/<repo>/` sub-path. Returns the
+/// files it wrote.
+pub fn apply_gh_pages_layout(config: &AppConfig) -> Result<Vec<PathBuf>, String> {
+    let output_dir = config.output_dir.as_path();
+    file_system::ensure_dir_exists(output_dir)?;
+
+    let mut written = vec![output_dir.join(".nojekyll")];
+    std::fs::write(&written[0], "")
+        .map_err(|e| format!("Failed to write .nojekyll in {:?}: {}", output_dir, e))?;
+
+    let index = output_dir.join("index.html");
+    if index.exists() {
+        let not_found = output_dir.join("404.html");
+        std::fs::copy(&index, &not_found)
+            .map_err(|e| format!("Failed to create 404.html in {:?}: {}", output_dir, e))?;
+        written.push(not_found);
+    }
+
+    if let Some(prefix) = &config.project_config.runtime.locate_file_prefix {
+        if prefix.starts_with('/') {
+            tracing::warn!(
+                "locate_file_prefix {:?} is an absolute path; it will not resolve when the site is served from a GitHub Pages sub-path",
+                prefix
+            );
+        }
+    }
+
+    tracing::info!("Arranged {:?} for GitHub Pages", output_dir);
+    Ok(written)
+}
+
+/// Publishes the contents of `args.output_dir` as a single commit on `args.branch` of the
+/// repository's remote, replacing the branch history (the usual gh-pages workflow).
+/// The user's working tree is never touched: the commit is made in a temporary repository.
+/// Only directories written by a build are published, so `deploy -o .` cannot push the
+/// sources, and `.git` directories and the output marker file are left out.
+pub fn deploy(args: &DeployArgs) -> Result<(), String> {
+    if !args.output_dir.is_dir() {
+        return Err(format!("Output directory {:?} does not exist; build the project first", args.output_dir));
+    }
+    if !output_dir::is_build_output(&args.output_dir) {
+        return Err(format!(
+            "{:?} is not the output directory of a build ({} is missing); refusing to publish it",
+            args.output_dir, BUILD_MANIFEST_FILE_NAME
+        ));
+    }
+
+    let repo = args.repo.clone().unwrap_or_else(|| PathBuf::from("."));
+    let remote_url = git_output(&["remote", "get-url", &args.remote], &repo)
+        .map_err(|e| format!("Could not resolve git remote '{}' in {:?}: {}", args.remote, repo, e))?;
+
+    let staging_dir = std::env::temp_dir().join(format!("wasm_compiler_deploy_{}", std::process::id()));
+    if staging_dir.exists() {
+        std::fs::remove_dir_all(&staging_dir)
+            .map_err(|e| format!("Failed to clean staging directory {:?}: {}", staging_dir, e))?;
+    }
+    file_system::copy_dir_filtered(&args.output_dir, &staging_dir, |entry| {
+        entry.file_name() == ".git" || entry.file_name() == OUTPUT_MARKER_FILE_NAME
+    })?;
+
+    // Commit with the source repository's identity, which may be configured only locally there
+    let identity: Vec<String> = ["user.name", "user.email"]
+        .iter()
+        .filter_map(|key| git_output(&["config", key], &repo).ok().map(|value| format!("{}={}", key, value)))
+        .filter(|setting| !setting.ends_with('='))
+        .collect();
+
+    let result = publish(&staging_dir, &remote_url, &identity, args);
+    std::fs::remove_dir_all(&staging_dir).ok();
+    result?;
+
+    tracing::info!("Deployed {:?} to branch '{}' of {}", args.output_dir, args.branch, remote_url);
+    Ok(())
+}
+
+fn publish(staging_dir: &Path, remote_url: &str, identity: &[String], args: &DeployArgs) -> Result<(), String> {
+    git(&["init", "--quiet"], staging_dir)?;
+    git(&["checkout", "--quiet", "--orphan", &args.branch], staging_dir)?;
+    git(&["add", "--all"], staging_dir)?;
+    let mut commit_args: Vec<&str> = Vec::new();
+    for setting in identity {
+        commit_args.extend(["-c", setting.as_str()]);
+    }
+    commit_args.extend(["commit", "--quiet", "-m", &args.message]);
+    git(&commit_args, staging_dir)?;
+    git(&["push", "--force", remote_url, &format!("HEAD:refs/heads/{}", args.branch)], staging_dir)?;
+    Ok(())
+}
+
+fn git(args: &[&str], dir: &Path) -> Result<(), String> {
+    run_command("git", args, Some(dir)).map(|_| ()).map_err(|e| e.to_string())
+}
+
+fn git_output(args: &[&str], dir: &Path) -> Result<String, String> {
+    let output = run_command("git", args, Some(dir)).map_err(|e| e.to_string())?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
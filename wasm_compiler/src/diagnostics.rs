@@ -0,0 +1,368 @@
+//! Structured compiler diagnostics parsed from emcc/clang/wasm-ld output.
+//!
+//! Toolchain stderr is turned into a deduplicated list of [`Diagnostic`]s which are either
+//! rendered for the terminal (colorized when stderr is a TTY) or emitted as JSON lines on
+//! stdout when `--message-format json` is selected. A diagnostic reported again, like a
+//! warning in a header every translation unit includes, is listed once with its count; a
+//! [`WarningBatch`] does the same across the commands of a build that compiles each
+//! translation unit on its own.
+//!
+//! On a terminal, builds ask clang for colored diagnostics, which it leaves out when its
+//! output is piped to this tool. The flag goes to emcc through `EMCC_CFLAGS` (see
+//! [`color_env`]) rather than into the build files and the compile database; the colors are
+//! stripped again for parsing and for the `--log-file` transcript.
+
+use serde::Serialize;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::ffi::OsString;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// How diagnostics and results are reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum MessageFormat {
+    /// Human-readable text on stderr
+    #[default]
+    Human,
+    /// One JSON object per line on stdout
+    Json,
+}
+
+static MESSAGE_FORMAT: OnceLock<MessageFormat> = OnceLock::new();
+
+/// Sets the process-wide message format. Only the first call has an effect.
+pub fn set_message_format(format: MessageFormat) {
+    MESSAGE_FORMAT.set(format).ok();
+}
+
+pub fn message_format() -> MessageFormat {
+    MESSAGE_FORMAT.get().copied().unwrap_or_default()
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    /// Source file, or the reporting tool (e.g. `wasm-ld`) when there is no location
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+    /// How many times the toolchain reported it
+    pub count: usize,
+}
+
+impl Diagnostic {
+    /// A diagnostic reported once
+    pub fn new(file: Option<String>, line: Option<u32>, column: Option<u32>, severity: Severity, message: String) -> Self {
+        Diagnostic { file, line, column, severity, message, count: 1 }
+    }
+
+    /// Whether `other` is the same diagnostic, whatever the counts
+    fn same_as(&self, other: &Diagnostic) -> bool {
+        self.file == other.file
+            && self.line == other.line
+            && self.column == other.column
+            && self.severity == other.severity
+            && self.message == other.message
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}", file)?;
+            if let Some(line) = self.line {
+                write!(f, ":{}", line)?;
+                if let Some(column) = self.column {
+                    write!(f, ":{}", column)?;
+                }
+            }
+            write!(f, ": ")?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        };
+        write!(f, "{}: {}", severity, self.message)?;
+        if self.count > 1 {
+            write!(f, " ({} times)", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Warnings Emscripten prints for builds that are fine, which say nothing about the project:
+/// link settings build tools also pass to compile steps, the reduced post-link optimization of
+/// builds with debug info, and threads combined with memory growth
+const NOISY_WARNINGS: &[&str] = &[
+    "[-Wunused-command-line-argument]",
+    "[-Wlimited-postlink-optimizations]",
+    "[-Wpthreads-mem-growth]",
+];
+
+/// Whether `diagnostic` is one of Emscripten's known-noisy warnings, which are left out of
+/// the reported warnings
+pub fn is_noise(diagnostic: &Diagnostic) -> bool {
+    diagnostic.severity == Severity::Warning && NOISY_WARNINGS.iter().any(|flag| diagnostic.message.contains(flag))
+}
+
+/// Parses toolchain output into diagnostics, counting exact duplicates instead of listing them
+/// again (the same header included from several translation units reports the same warning
+/// many times).
+///
+/// Recognized forms:
+/// - `file:line:col: error: message` (clang)
+/// - `file:line: warning: message`
+/// - `wasm-ld: error: message`, `emcc: error: message`, `error: message`
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for line in strip_ansi(output).lines() {
+        let Some(diagnostic) = parse_line(line.trim_end()) else { continue };
+        match diagnostics.iter_mut().find(|other| other.same_as(&diagnostic)) {
+            Some(reported) => reported.count += 1,
+            None => diagnostics.push(diagnostic),
+        }
+    }
+    diagnostics
+}
+
+fn parse_line(line: &str) -> Option<Diagnostic> {
+    let (severity, marker, index) = [
+        (Severity::Error, "error: "),
+        (Severity::Error, "fatal error: "),
+        (Severity::Warning, "warning: "),
+        (Severity::Note, "note: "),
+    ]
+    .into_iter()
+    .filter_map(|(severity, marker)| line.find(marker).map(|index| (severity, marker, index)))
+    .min_by_key(|(_, _, index)| *index)?;
+
+    let message = line[index + marker.len()..].trim().to_string();
+    if message.is_empty() {
+        return None;
+    }
+
+    let location = line[..index].trim_end().trim_end_matches(':');
+    if location.is_empty() {
+        return Some(Diagnostic::new(None, None, None, severity, message));
+    }
+
+    // Split "file:line:col" from the right so Windows drive letters ("C:\...") survive
+    let mut parts = location.rsplitn(3, ':');
+    let last = parts.next();
+    let middle = parts.next();
+    let first = parts.next();
+    let (file, line_no, column) = match (first, middle.and_then(|m| m.parse().ok()), last.and_then(|l| l.parse().ok())) {
+        (Some(file), Some(line_no), Some(column)) => (file.to_string(), Some(line_no), Some(column)),
+        _ => match (middle, last.and_then(|l| l.parse().ok())) {
+            (Some(_), Some(line_no)) => {
+                let file = location.rsplit_once(':').map(|(f, _)| f).unwrap_or(location);
+                (file.to_string(), Some(line_no), None)
+            }
+            _ => (location.to_string(), None, None),
+        },
+    };
+
+    // Lines like "In file included from x.h:3:" or make's own output are not diagnostics
+    if file.contains(' ') && line_no.is_none() {
+        return None;
+    }
+
+    Some(Diagnostic::new(Some(file), line_no, column, severity, message))
+}
+
+/// Whether output goes to a terminal that shows colors: stderr is a TTY, the message format
+/// is human and `NO_COLOR` is not set
+pub fn color_enabled() -> bool {
+    std::io::stderr().is_terminal() && message_format() == MessageFormat::Human && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// `EMCC_CFLAGS` of `env` (or of this process) with `-fcolor-diagnostics` added, when colors
+/// are enabled: clang only colors its diagnostics on a terminal, and its output is piped to us
+pub fn color_env(env: &[(OsString, OsString)]) -> Option<(OsString, OsString)> {
+    if !color_enabled() {
+        return None;
+    }
+    let cflags = env
+        .iter()
+        .rev()
+        .find(|(key, _)| key == "EMCC_CFLAGS")
+        .map(|(_, value)| value.clone())
+        .or_else(|| std::env::var_os("EMCC_CFLAGS"))
+        .unwrap_or_default();
+    let mut cflags = cflags.to_string_lossy().trim().to_string();
+    if !cflags.split_whitespace().any(|flag| flag == "-fcolor-diagnostics" || flag == "-fno-color-diagnostics") {
+        cflags = format!("{} -fcolor-diagnostics", cflags).trim_start().to_string();
+    }
+    Some(("EMCC_CFLAGS".into(), cflags.into()))
+}
+
+/// `text` without ANSI escape sequences (colors, bold, cursor movement)
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        // A CSI sequence runs up to its final byte in @..~; other escapes are one character
+        if chars.next_if_eq(&'[').is_some() {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        } else {
+            chars.next();
+        }
+    }
+    Cow::Owned(stripped)
+}
+
+/// Renders diagnostics for the terminal, with ANSI colors when stderr is a TTY.
+pub fn render_human(diagnostics: &[Diagnostic]) -> String {
+    let color = color_enabled();
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let text = diagnostic.to_string();
+            if !color {
+                return text;
+            }
+            let code = match diagnostic.severity {
+                Severity::Error => "1;31",
+                Severity::Warning => "1;33",
+                Severity::Note => "1;36",
+            };
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Emits diagnostics as JSON lines on stdout when the JSON message format is active.
+/// Returns `true` if they were emitted.
+pub fn emit_json(diagnostics: &[Diagnostic], tool: &str) -> bool {
+    if message_format() != MessageFormat::Json {
+        return false;
+    }
+    for diagnostic in diagnostics {
+        let message = serde_json::json!({
+            "reason": "compiler-message",
+            "tool": tool,
+            "diagnostic": diagnostic,
+        });
+        println!("{}", message);
+    }
+    true
+}
+
+thread_local! {
+    /// Warnings held back by the [`WarningBatch`] of this thread, with the reporting tool
+    static HELD_WARNINGS: RefCell<Option<Vec<(String, Diagnostic)>>> = const { RefCell::new(None) };
+}
+
+/// Holds back the warnings of the commands run on this thread until it is dropped, then reports
+/// them at once, with the counts of a warning several commands reported added up: a warning in
+/// a header of separately compiled translation units is listed once. Batches do not nest.
+pub struct WarningBatch(());
+
+impl WarningBatch {
+    pub fn start() -> Self {
+        HELD_WARNINGS.with(|held| *held.borrow_mut() = Some(Vec::new()));
+        WarningBatch(())
+    }
+}
+
+impl Drop for WarningBatch {
+    fn drop(&mut self) {
+        let held = HELD_WARNINGS.with(|held| held.borrow_mut().take()).unwrap_or_default();
+        let mut merged: Vec<(String, Diagnostic)> = Vec::new();
+        for (tool, diagnostic) in held {
+            match merged.iter_mut().find(|(_, other)| other.same_as(&diagnostic)) {
+                Some((_, reported)) => reported.count += diagnostic.count,
+                None => merged.push((tool, diagnostic)),
+            }
+        }
+        let mut tools: Vec<&String> = merged.iter().map(|(tool, _)| tool).collect();
+        tools.dedup();
+        for tool in tools {
+            let warnings: Vec<Diagnostic> = merged.iter().filter(|(other, _)| other == tool).map(|(_, diagnostic)| diagnostic.clone()).collect();
+            show_warnings(tool, &warnings);
+        }
+    }
+}
+
+/// Reports the warnings of a successful command, or holds them back for the active
+/// [`WarningBatch`]
+pub fn report_warnings(tool: &str, warnings: Vec<Diagnostic>) {
+    let warnings = HELD_WARNINGS.with(|held| match held.borrow_mut().as_mut() {
+        Some(held) => {
+            held.extend(warnings.into_iter().map(|warning| (tool.to_string(), warning)));
+            None
+        }
+        None => Some(warnings),
+    });
+    if let Some(warnings) = warnings {
+        show_warnings(tool, &warnings);
+    }
+}
+
+fn show_warnings(tool: &str, warnings: &[Diagnostic]) {
+    if !warnings.is_empty() && !emit_json(warnings, tool) {
+        tracing::warn!("'{}' reported:\n{}", tool, render_human(warnings));
+    }
+}
+
+/// Emits the error a run ended with as a JSON line on stdout when the JSON message format is
+/// active, with its code and the process exit code. Returns `true` if it was emitted.
+pub fn emit_json_error(code: &str, exit_code: i32, message: &str) -> bool {
+    if message_format() != MessageFormat::Json {
+        return false;
+    }
+    let message = serde_json::json!({
+        "reason": "error",
+        "code": code,
+        "exit_code": exit_code,
+        "message": message,
+    });
+    println!("{}", message);
+    true
+}
+
+/// Summarizes the diagnostics of a failed command for an error message. Falls back to the
+/// raw output when nothing could be parsed.
+pub fn summarize_failure(tool: &str, status: &str, stdout: &str, stderr: &str) -> String {
+    let mut diagnostics = parse(stderr);
+    diagnostics.extend(parse(stdout).into_iter().filter(|d| d.severity == Severity::Error));
+    if diagnostics.is_empty() {
+        return format!(
+            "Command '{}' failed with status: {}.\nStdout: {}\nStderr: {}",
+            tool, status, stdout, stderr
+        );
+    }
+
+    emit_json(&diagnostics, tool);
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    let warnings = diagnostics.iter().filter(|d| d.severity == Severity::Warning).count();
+    format!(
+        "Command '{}' failed with status: {} ({} error(s), {} warning(s)):\n{}",
+        tool, status, errors, warnings, render_human(&diagnostics)
+    )
+}
@@ -0,0 +1,49 @@
+//! The `doctor` subcommand: prints the toolchain snapshot (see [`crate::toolchain`]) of the
+//! machine, to check an installation and to paste into bug reports, as text or as JSON.
+
+use crate::app_config::AppConfig;
+use crate::toolchain::ToolchainSnapshot;
+
+/// Prints the tools and environment variables the builds of `config` would use.
+pub fn doctor(config: &AppConfig, json: bool) {
+    let snapshot = ToolchainSnapshot::collect(config);
+    if json {
+        let report = serde_json::json!({
+            "reason": "doctor",
+            "tool_version": env!("CARGO_PKG_VERSION"),
+            "toolchain": snapshot,
+        });
+        println!("{}", report);
+        return;
+    }
+
+    println!("wasm_compiler {} ({})", env!("CARGO_PKG_VERSION"), snapshot.host);
+    println!();
+    println!("Tools:");
+    let width = snapshot.tools.iter().map(|tool| tool.name.len()).max().unwrap_or(0);
+    for tool in &snapshot.tools {
+        match &tool.path {
+            Some(path) => println!(
+                "  {:width$}  {}\n  {:width$}  {}",
+                tool.name,
+                tool.version.as_deref().unwrap_or("(no version reported)"),
+                "",
+                path.display(),
+                width = width
+            ),
+            None => println!("  {:width$}  not found", tool.name, width = width),
+        }
+    }
+    println!();
+    println!("Environment:");
+    if snapshot.env.is_empty() {
+        println!("  (none of the variables Emscripten reads are set)");
+    }
+    for (name, value) in &snapshot.env {
+        println!("  {}={}", name, value);
+    }
+    if !snapshot.has("emcc") {
+        println!();
+        println!("emcc was not found: install the Emscripten SDK and activate it (emsdk_env), or pass --emcc-path.");
+    }
+}
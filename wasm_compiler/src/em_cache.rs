@@ -0,0 +1,36 @@
+//! Management of the Emscripten cache, where emcc keeps the system libraries and ports
+//! (libc, SDL2, ...) it builds on first use. `--em-cache` points builds at a shared or
+//! persisted cache, `--prewarm` builds libraries into it with `embuilder` before the project
+//! is compiled, and the `cache` subcommand warms or clears it on its own.
+
+use crate::app_config::{AppConfig, CacheAction, CacheArgs};
+use crate::utils::command_runner::{run_build_command, CommandError};
+
+/// Builds the `--prewarm` libraries into the cache with `embuilder`, so that their build time
+/// shows up as its own step instead of inside the first emcc invocation.
+pub fn prewarm(config: &AppConfig) -> Result<(), CommandError> {
+    if config.prewarm.is_empty() {
+        return Ok(());
+    }
+    tracing::info!("Pre-building Emscripten libraries into the cache: {}", config.prewarm.join(", "));
+    let args: Vec<&str> = std::iter::once("build").chain(config.prewarm.iter().map(String::as_str)).collect();
+    run_build_command("embuilder", &args, None, config)?;
+    Ok(())
+}
+
+/// Runs the `cache` subcommand.
+pub fn run_cache_command(args: &CacheArgs) -> Result<(), String> {
+    let mut config = AppConfig { em_cache: args.em_cache.clone(), ..AppConfig::default() };
+    match &args.action {
+        CacheAction::Warm { libraries } => {
+            config.prewarm = libraries.clone();
+            prewarm(&config).map_err(|e| e.to_string())
+        }
+        CacheAction::Clear => {
+            tracing::info!("Clearing the Emscripten cache");
+            run_build_command("emcc", &["--clear-cache"], None, &config)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+    }
+}
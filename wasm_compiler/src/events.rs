@@ -0,0 +1,145 @@
+//! Progress events for applications embedding `wasm_compiler`. An [`EventSink`] registered
+//! with [`CompileOptions::event_sink`](crate::app_config::CompileOptions::event_sink) is told
+//! about build phases, every external command, its stderr as it is produced, the progress
+//! of the build tool, and the resulting files.
+
+use std::fmt;
+use std::sync::Arc;
+use crate::Artifact;
+
+/// Stages of a build, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Resolving flags, the entry point and exports from the project sources
+    Prepare,
+    /// Generating the build files (`emcmake cmake`)
+    Configure,
+    /// Compiling and linking with emcc / make
+    Build,
+    /// Module format conversion, componentization, hashing and the build manifest
+    PostProcess,
+    /// Generating the web application around the module
+    Webapp,
+    /// Running the module with `--run-test`, or the CTest tests with `--test`
+    Test,
+    /// Running the program with `--run`
+    Run,
+}
+
+impl Phase {
+    /// Name of the phase in the `[limits]` table of the project config
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Prepare => "prepare",
+            Phase::Configure => "configure",
+            Phase::Build => "build",
+            Phase::PostProcess => "post-process",
+            Phase::Webapp => "webapp",
+            Phase::Test => "test",
+            Phase::Run => "run",
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Progress of the build tool, from its status lines (Ninja's `[12/345] ...`, or the
+/// percentage of CMake's Makefiles)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildProgress {
+    /// Build steps finished, including this one
+    pub completed: usize,
+    /// Build steps in total
+    pub total: usize,
+    /// What the step does, e.g. `compiling main.cpp` or `linking app.js`
+    pub description: String,
+}
+
+/// Receives progress events. All methods default to doing nothing.
+///
+/// Events are reported on the thread running the build; implementations must be
+/// `Send + Sync` so the build can run on a worker thread.
+pub trait EventSink: Send + Sync {
+    fn on_phase_start(&self, _phase: Phase) {}
+
+    /// An external command is about to run
+    fn on_command(&self, _program: &str, _args: &[String]) {}
+
+    /// A line written to stderr by the running command
+    fn on_stderr_line(&self, _program: &str, _line: &str) {}
+
+    /// A build step started; reported for CMake builds
+    fn on_progress(&self, _progress: &BuildProgress) {}
+
+    /// A file produced by the build
+    fn on_artifact(&self, _artifact: &Artifact) {}
+}
+
+/// The sink registered for a build, if any.
+#[derive(Clone, Default)]
+pub struct Events(Option<Arc<dyn EventSink>>);
+
+impl Events {
+    pub fn new(sink: Arc<dyn EventSink>) -> Self {
+        Events(Some(sink))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn phase_start(&self, phase: Phase) {
+        if let Some(sink) = &self.0 {
+            sink.on_phase_start(phase);
+        }
+    }
+
+    /// Reports the start of `phase` and enters a tracing span for it, which ends when the
+    /// returned guard is dropped.
+    pub(crate) fn enter_phase(&self, phase: Phase) -> tracing::span::EnteredSpan {
+        self.phase_start(phase);
+        tracing::info_span!("phase", ?phase).entered()
+    }
+
+    pub fn command(&self, program: &str, args: &[String]) {
+        if let Some(sink) = &self.0 {
+            sink.on_command(program, args);
+        }
+    }
+
+    pub fn stderr_line(&self, program: &str, line: &str) {
+        if let Some(sink) = &self.0 {
+            sink.on_stderr_line(program, line);
+        }
+    }
+
+    pub fn progress(&self, progress: &BuildProgress) {
+        if let Some(sink) = &self.0 {
+            sink.on_progress(progress);
+        }
+    }
+
+    /// A line written to stdout by the running command; status lines of the build tool are
+    /// reported as progress
+    pub(crate) fn stdout_line(&self, line: &str) {
+        if let Some(progress) = self.0.as_ref().and_then(|_| crate::compiler::build_progress::parse_status_line(line)) {
+            self.progress(&progress);
+        }
+    }
+
+    pub fn artifact(&self, artifact: &Artifact) {
+        if let Some(sink) = &self.0 {
+            sink.on_artifact(artifact);
+        }
+    }
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.is_enabled() { "Events(<sink>)" } else { "Events(None)" })
+    }
+}
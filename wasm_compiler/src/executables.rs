@@ -0,0 +1,74 @@
+//! `--all-executables`: building every executable of a CMake project (e.g. an app and its
+//! tools) in one invocation. The executables that are not tests are listed from CMake's File
+//! API, and each is built into `<output-dir>/<target>/` as if it were the only output, with
+//! its own webapp. A merged `build-manifest.json` and an `index.html` linking to the pages of
+//! all of them are written to the output directory.
+
+use std::path::PathBuf;
+use crate::app_config::{AppConfig, CompileOptions};
+use crate::compiler::cmake_handler;
+use crate::{build_dir, compile, matrix, remote_project, Error, TargetArtifacts};
+
+/// Builds the executables of the CMake project one after another. Fails at the first
+/// executable that fails, and when the project is not a CMake project or has no executables.
+pub fn compile_executables(options: CompileOptions) -> Result<Vec<TargetArtifacts>, Error> {
+    let mut config = options.build();
+    remote_project::resolve(&mut config).map_err(Error::Command)?;
+    config.load_project_config().map_err(Error::Config)?;
+    if !config.project_config.targets.is_empty() || !config.targets.is_empty() {
+        return Err(Error::Config("--all-executables cannot be combined with a target matrix ([targets] or --target)".to_string()));
+    }
+    let project_path_abs = config.project_path.canonicalize().map_err(Error::Io)?;
+    if !project_path_abs.join("CMakeLists.txt").is_file() {
+        return Err(Error::Config("--all-executables builds the executables of a CMake project".to_string()));
+    }
+
+    // The executables are configured in the build tree they are then built in
+    let ephemeral = build_dir::prepare(&mut config).map_err(Error::Config)?;
+    let result = cmake_handler::app_executables(&project_path_abs, &config)
+        .map_err(Error::from)
+        .and_then(|names| compile_each(&config, names));
+    if let Some(ephemeral) = ephemeral {
+        ephemeral.finish(result.is_ok());
+    }
+    let built = result?;
+
+    let manifest = matrix::write_merged_manifest(&config, &built).map_err(Error::PostProcessing)?;
+    #[cfg(feature = "webapp")]
+    let index_page = crate::webapp_generator::create_index_page(&config, &built)?;
+    #[cfg(not(feature = "webapp"))]
+    let index_page = None;
+    // One archive of the outputs of all executables
+    if let Some(archive) = &config.archive_output {
+        let files: Vec<PathBuf> =
+            built.iter().flat_map(|executable| executable.artifacts.files()).chain([manifest]).chain(index_page).collect();
+        crate::output_archive::write_archive(&config.output_dir, &files, archive).map_err(Error::PostProcessing)?;
+    }
+    Ok(built)
+}
+
+fn compile_each(config: &AppConfig, names: Vec<String>) -> Result<Vec<TargetArtifacts>, Error> {
+    if names.is_empty() {
+        return Err(Error::Config(format!("The CMake project {:?} has no executables", config.project_path)));
+    }
+    tracing::info!("Building {} executables: {}", names.len(), names.join(", "));
+    let mut built = Vec::with_capacity(names.len());
+    for name in names {
+        tracing::info!("Building executable '{}'", name);
+        let artifacts = compile(CompileOptions::from(executable_config(config, &name)))?;
+        built.push(TargetArtifacts { name, artifacts });
+    }
+    Ok(built)
+}
+
+/// The configuration of one executable: its output named after it, in `<output-dir>/<name>/`.
+/// All executables share the CMake build tree, so the libraries they link are built once.
+fn executable_config(config: &AppConfig, name: &str) -> AppConfig {
+    let mut executable_config = config.clone();
+    executable_config.output_name = name.to_string();
+    executable_config.output_dir = config.output_dir.join(name);
+    executable_config.archive_output = None;
+    // The shared build trees are removed once all executables are built
+    executable_config.ephemeral = false;
+    executable_config
+}
@@ -0,0 +1,338 @@
+//! Discovery of the functions a module exports to JavaScript, used to generate the
+//! `exports.html` test harness page.
+
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use crate::utils::source_selection::project_files;
+use crate::app_config::AppConfig;
+
+/// How an export is reached from JavaScript
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportKind {
+    /// C function listed in `EXPORTED_FUNCTIONS`, available as `Module._name`
+    CFunction,
+    /// Free function registered with embind, available as `Module.name`
+    Embind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExportedFunction {
+    /// Name without the leading underscore emcc uses for C symbols
+    pub name: String,
+    pub kind: ExportKind,
+}
+
+/// Collects the exports known from the emcc flags and from embind registrations in the
+/// project sources. `main` is skipped since it is not meant to be called by hand.
+pub fn known_exports(config: &AppConfig) -> Vec<ExportedFunction> {
+    let mut exports: Vec<ExportedFunction> = parse_exported_functions(&config.emcc_flag)
+        .into_iter()
+        .map(|name| ExportedFunction { name, kind: ExportKind::CFunction })
+        .collect();
+
+    for name in scan_embind_functions(&config.project_path) {
+        if !exports.iter().any(|e| e.name == name) {
+            exports.push(ExportedFunction { name, kind: ExportKind::Embind });
+        }
+    }
+
+    exports.retain(|e| e.name != "main");
+    exports
+}
+
+/// Extracts function names from `-sEXPORTED_FUNCTIONS=...` flags. Both the plain
+/// (`_foo,_bar`) and the list (`['_foo','_bar']`) syntaxes are accepted.
+pub fn parse_exported_functions(flags: &[String]) -> Vec<String> {
+    let mut names = Vec::new();
+    for flag in flags {
+        let value = match flag.strip_prefix("-sEXPORTED_FUNCTIONS=")
+            .or_else(|| flag.strip_prefix("EXPORTED_FUNCTIONS=")) {
+            Some(value) => value,
+            None => continue,
+        };
+        if value.starts_with('@') {
+            tracing::debug!("EXPORTED_FUNCTIONS read from a response file ({}) is not inspected", value);
+            continue;
+        }
+        for item in value.trim_matches(|c| c == '[' || c == ']').split(',') {
+            let name = item.trim().trim_matches(|c| c == '\'' || c == '"').trim_start_matches('_');
+            if !name.is_empty() && !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Whether the source text defines a `main` function (`int main(`, `int main (void)`, ...).
+pub fn defines_main(content: &str) -> bool {
+    content.match_indices("main").any(|(index, _)| {
+        let before = &content[..index];
+        let after = content[index + "main".len()..].trim_start();
+        before.ends_with(char::is_whitespace)
+            && ["int", "void", "auto"].iter().any(|ty| before.trim_end().ends_with(ty))
+            && after.starts_with('(')
+    })
+}
+
+/// Removes `_main` from `-sEXPORTED_FUNCTIONS` flags, dropping flags that become empty.
+/// Exporting `_main` from a module without an entry point fails to link.
+pub fn remove_main_export(flags: &mut Vec<String>) {
+    flags.retain_mut(|flag| {
+        if !flag.starts_with("-sEXPORTED_FUNCTIONS=") {
+            return true;
+        }
+        let names = parse_exported_functions(std::slice::from_ref(flag));
+        if !names.iter().any(|name| name == "main") {
+            return true;
+        }
+        let remaining: Vec<String> = names
+            .into_iter()
+            .filter(|name| name != "main")
+            .map(|name| format!("_{}", name))
+            .collect();
+        tracing::info!("Removing _main from {} for the --no-entry build", flag);
+        if remaining.is_empty() {
+            return false;
+        }
+        *flag = format!("-sEXPORTED_FUNCTIONS={}", remaining.join(","));
+        true
+    });
+}
+
+/// Finds the functions the sources mark for export: those annotated with
+/// `EMSCRIPTEN_KEEPALIVE`, and functions defined with `extern "C"` linkage (either
+/// `extern "C" int f(...) {` or inside an `extern "C" { ... }` block). Declarations without a
+/// body are skipped since they usually refer to functions implemented in JavaScript, and so are
+/// `extern "C" {` blocks right after an `#ifdef __cplusplus`, which give C code C linkage when
+/// it is compiled as C++ rather than marking its functions for export.
+pub fn scan_c_exports(sources: &[PathBuf]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut add = |name: String| {
+        if name != "main" && !names.contains(&name) {
+            names.push(name);
+        }
+    };
+
+    for source in sources {
+        let Ok(content) = std::fs::read_to_string(source) else {
+            continue;
+        };
+        if !content.contains("EMSCRIPTEN_KEEPALIVE") && !content.contains("extern \"C\"") {
+            continue;
+        }
+        let content = strip_comments(&content);
+
+        for (index, _) in content.match_indices("EMSCRIPTEN_KEEPALIVE") {
+            let rest = &content[index + "EMSCRIPTEN_KEEPALIVE".len()..];
+            let statement_end = rest.find([';', '{']).unwrap_or(rest.len());
+            if let Some(name) = function_name(&rest[..statement_end]) {
+                add(name);
+            }
+        }
+
+        for (index, _) in content.match_indices("extern \"C\"") {
+            if follows_cplusplus_check(&content[..index]) {
+                continue;
+            }
+            let rest = content[index + "extern \"C\"".len()..].trim_start();
+            if let Some(block) = rest.strip_prefix('{') {
+                for name in extern_block_definitions(block) {
+                    add(name);
+                }
+            } else if let Some(end) = rest.find([';', '{']) {
+                if rest[end..].starts_with('{') {
+                    if let Some(name) = function_name(&rest[..end]) {
+                        add(name);
+                    }
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Whether the line ending `before` is empty and the line before it an `#if` on `__cplusplus`
+fn follows_cplusplus_check(before: &str) -> bool {
+    let mut lines = before.rsplit('\n');
+    lines.next().is_some_and(|line| line.trim().is_empty())
+        && lines
+            .find(|line| !line.trim().is_empty())
+            .is_some_and(|line| line.trim_start().starts_with("#if") && line.contains("__cplusplus"))
+}
+
+/// Collects the names of functions defined at the top level of an `extern "C" {` block
+/// (`block` starts right after the opening brace).
+fn extern_block_definitions(block: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut depth = 0;
+    let mut statement_start = 0;
+    for (index, c) in block.char_indices() {
+        match c {
+            '{' => {
+                if depth == 0 {
+                    if let Some(name) = function_name(&block[statement_start..index]) {
+                        names.push(name);
+                    }
+                }
+                depth += 1;
+            }
+            '}' if depth == 0 => break,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    statement_start = index + 1;
+                }
+            }
+            ';' if depth == 0 => statement_start = index + 1,
+            _ => {}
+        }
+    }
+    names
+}
+
+/// The identifier right before the first `(` of a declaration, if it looks like a function.
+fn function_name(declaration: &str) -> Option<String> {
+    let declaration: String = declaration
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let paren = declaration.find('(')?;
+    let head = declaration[..paren].trim_end();
+    let start = head.rfind(|c: char| !(c.is_alphanumeric() || c == '_')).map_or(0, |i| i + 1);
+    let name = &head[start..];
+    let first_word = head.split_whitespace().next().unwrap_or("");
+    let is_function = !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && !["if", "while", "for", "switch", "return", "sizeof", "EMSCRIPTEN_KEEPALIVE"].contains(&name)
+        && !["typedef", "struct", "class", "enum", "union", "using", "namespace"].contains(&first_word)
+        && !head.contains('=')
+        && !head.split_whitespace().any(|word| word == "static"); // internal linkage cannot be exported
+    is_function.then(|| name.to_string())
+}
+
+/// Replaces `//` and `/* */` comments with whitespace so they are not mistaken for code. Line
+/// breaks are kept, so line numbers stay valid.
+pub(crate) fn strip_comments(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        result.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    if next == '\n' {
+                        result.push('\n');
+                    }
+                    previous = next;
+                }
+                result.push(' ');
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// C++ sources near the project root (up to three levels deep, skipping ignored, hidden and
+/// build directories), which is where embind registrations live in practice.
+fn cpp_sources(project_path: &Path) -> impl Iterator<Item = PathBuf> {
+    project_files(project_path, Some(3))
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "cpp" || ext == "cxx" || ext == "cc"))
+}
+
+/// Contents of the sources that contain an `EMSCRIPTEN_BINDINGS` block
+fn embind_sources(project_path: &Path) -> Vec<String> {
+    cpp_sources(project_path)
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .filter(|content| content.contains("EMSCRIPTEN_BINDINGS"))
+        .collect()
+}
+
+/// Whether the project uses embind (includes `emscripten/bind.h` or declares bindings)
+pub fn uses_embind(project_path: &Path) -> bool {
+    cpp_sources(project_path).any(|path| {
+        std::fs::read_to_string(path).is_ok_and(|content| {
+            content.contains("emscripten/bind.h") || content.contains("EMSCRIPTEN_BINDINGS")
+        })
+    })
+}
+
+/// Finds the JavaScript names of the types registered with embind: `class_<T>("Name")`,
+/// `enum_<T>("Name")`, `value_object<T>("Name")` and `value_array<T>("Name")`.
+pub fn scan_embind_classes(project_path: &Path) -> Vec<String> {
+    let mut names = Vec::new();
+    for content in embind_sources(project_path) {
+        for registration in ["class_<", "enum_<", "value_object<", "value_array<"] {
+            for (index, _) in content.match_indices(registration) {
+                let preceding = content[..index].chars().next_back();
+                if preceding.is_some_and(|c| c == '_' || c.is_alphanumeric()) {
+                    continue;
+                }
+                // Skip the template arguments, which may nest (`class_<Foo<int>>`)
+                let rest = &content[index + registration.len()..];
+                let mut depth = 1;
+                let Some(end) = rest.find(|c| {
+                    match c {
+                        '<' => depth += 1,
+                        '>' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                }) else {
+                    continue;
+                };
+                let rest = rest[end + 1..].trim_start();
+                let Some(rest) = rest.strip_prefix('(').map(str::trim_start).and_then(|r| r.strip_prefix('"')) else {
+                    continue;
+                };
+                if let Some(end) = rest.find('"') {
+                    let name = &rest[..end];
+                    if !name.is_empty() && !names.iter().any(|n| n == name) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Finds `function("name", ...)` registrations inside `EMSCRIPTEN_BINDINGS` blocks.
+pub fn scan_embind_functions(project_path: &Path) -> Vec<String> {
+    let mut names = Vec::new();
+    for content in embind_sources(project_path) {
+        for (index, _) in content.match_indices("function(") {
+            // Skip `.function(...)` (class methods) and identifiers merely ending in "function"
+            let preceding = content[..index].chars().next_back();
+            if preceding.is_some_and(|c| c == '.' || c == '_' || c.is_alphanumeric()) {
+                continue;
+            }
+            let rest = content[index + "function(".len()..].trim_start();
+            if let Some(rest) = rest.strip_prefix('"') {
+                if let Some(end) = rest.find('"') {
+                    let name = &rest[..end];
+                    if !name.is_empty() && !names.iter().any(|n| n == name) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+    }
+    names
+}
@@ -0,0 +1,137 @@
+//! Validation of user-provided emcc flags before the build starts.
+//!
+//! Deprecated flags and misspelled `-s` settings otherwise surface as opaque emcc failures
+//! late in the build. Each problem is reported as a warning; with `--fix-flags` the flag is
+//! replaced by its modern equivalent (or dropped) instead.
+
+/// Deprecated or removed flags and their replacement (`None` means the flag should be dropped)
+const DEPRECATED_FLAGS: &[(&str, Option<&str>, &str)] = &[
+    ("-g4", Some("-gsource-map"), "-g4 was removed; use -gsource-map for source maps"),
+    ("--llvm-lto", None, "--llvm-lto is ignored by current Emscripten; LTO is controlled with -flto"),
+    ("--bind", Some("-lembind"), "--bind is deprecated; use -lembind"),
+    ("-sUSE_PTHREADS=1", Some("-pthread"), "-sUSE_PTHREADS is deprecated; use -pthread"),
+    ("-sLLD_REPORT_UNDEFINED", None, "-sLLD_REPORT_UNDEFINED is now the default behavior"),
+    ("-sLLD_REPORT_UNDEFINED=1", None, "-sLLD_REPORT_UNDEFINED is now the default behavior"),
+    ("-sDEMANGLE_SUPPORT=1", None, "-sDEMANGLE_SUPPORT was removed; stack traces are demangled by default"),
+    ("-sBINARYEN=1", None, "-sBINARYEN was removed; wasm output is the default"),
+    ("-sWASM_BINARY_NAME", None, "WASM_BINARY_NAME is not a valid setting; the .wasm name follows the -o output"),
+];
+
+/// Settings that were renamed; the value is kept
+const RENAMED_SETTINGS: &[(&str, &str)] = &[
+    ("TOTAL_MEMORY", "INITIAL_MEMORY"),
+    ("EXTRA_EXPORTED_RUNTIME_METHODS", "EXPORTED_RUNTIME_METHODS"),
+    ("TOTAL_STACK", "STACK_SIZE"),
+];
+
+/// Commonly used `-s` settings, used to detect typos. Settings not listed here are not
+/// reported unless they are a close misspelling of one that is.
+const KNOWN_SETTINGS: &[&str] = &[
+    "ALLOW_MEMORY_GROWTH", "ALLOW_TABLE_GROWTH", "ASSERTIONS", "ASYNCIFY", "ASYNCIFY_IMPORTS",
+    "ASYNCIFY_STACK_SIZE", "DISABLE_EXCEPTION_CATCHING", "ENVIRONMENT", "ERROR_ON_UNDEFINED_SYMBOLS",
+    "EXIT_RUNTIME", "EXPORTED_FUNCTIONS", "EXPORTED_RUNTIME_METHODS", "EXPORT_ALL", "EXPORT_ES6",
+    "EXPORT_NAME", "FETCH", "FILESYSTEM", "FORCE_FILESYSTEM", "FULL_ES2", "FULL_ES3",
+    "GL_ASSERTIONS", "GL_ENABLE_GET_PROC_ADDRESS", "INITIAL_MEMORY", "INVOKE_RUN",
+    "LEGACY_GL_EMULATION", "MAIN_MODULE", "MAXIMUM_MEMORY", "MAX_WEBGL_VERSION",
+    "MIN_WEBGL_VERSION", "MIN_CHROME_VERSION", "MIN_FIREFOX_VERSION", "MIN_SAFARI_VERSION",
+    "MODULARIZE", "NODERAWFS", "NO_EXIT_RUNTIME", "OFFSCREENCANVAS_SUPPORT", "PROXY_TO_PTHREAD",
+    "PTHREAD_POOL_SIZE", "SAFE_HEAP", "SIDE_MODULE", "SINGLE_FILE", "STACK_OVERFLOW_CHECK",
+    "STACK_SIZE", "STANDALONE_WASM", "STRICT", "USE_BOOST_HEADERS", "USE_BULLET", "USE_FREETYPE",
+    "USE_GLFW", "USE_HARFBUZZ", "USE_LIBJPEG", "USE_LIBPNG", "USE_SDL", "USE_SDL_IMAGE",
+    "USE_SDL_MIXER", "USE_SDL_NET", "USE_SDL_TTF", "USE_WEBGL2", "USE_ZLIB", "WASM", "WASM_BIGINT",
+    "WASM_WORKERS", "WASMFS",
+];
+
+/// Result of linting a flag string
+#[derive(Debug, Default)]
+pub struct FlagLint {
+    /// Human-readable problems found
+    pub warnings: Vec<String>,
+    /// The flags with all fixable problems corrected
+    pub fixed_flags: Vec<String>,
+}
+
+/// Checks the given flags against the deprecation and typo tables.
+pub fn lint_flags(flags: &[String]) -> FlagLint {
+    let mut lint = FlagLint::default();
+    let mut iter = flags.iter().peekable();
+
+    while let Some(flag) = iter.next() {
+        // Normalize "-s NAME=VALUE" into "-sNAME=VALUE"
+        let flag = if flag == "-s" {
+            match iter.next() {
+                Some(setting) => format!("-s{}", setting),
+                None => {
+                    lint.warnings.push("Dangling '-s' without a setting".to_string());
+                    continue;
+                }
+            }
+        } else {
+            flag.clone()
+        };
+
+        if let Some((_, replacement, reason)) = DEPRECATED_FLAGS
+            .iter()
+            .find(|(deprecated, _, _)| flag == *deprecated || flag.starts_with(&format!("{}=", deprecated)))
+        {
+            lint.warnings.push(format!("'{}': {}", flag, reason));
+            if let Some(replacement) = replacement {
+                lint.fixed_flags.push(replacement.to_string());
+            }
+            continue;
+        }
+
+        let Some(setting) = flag.strip_prefix("-s") else {
+            lint.fixed_flags.push(flag);
+            continue;
+        };
+        let (name, value) = match setting.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (setting, None),
+        };
+        let rebuild = |name: &str| match value {
+            Some(value) => format!("-s{}={}", name, value),
+            None => format!("-s{}", name),
+        };
+
+        if let Some((_, new_name)) = RENAMED_SETTINGS.iter().find(|(old, _)| *old == name) {
+            lint.warnings.push(format!("'{}': {} was renamed to {}", flag, name, new_name));
+            lint.fixed_flags.push(rebuild(new_name));
+        } else if KNOWN_SETTINGS.contains(&name) {
+            lint.fixed_flags.push(flag);
+        } else if let Some(suggestion) = closest_setting(name) {
+            lint.warnings.push(format!("'{}': unknown setting {}, did you mean {}?", flag, name, suggestion));
+            lint.fixed_flags.push(rebuild(suggestion));
+        } else {
+            lint.fixed_flags.push(flag);
+        }
+    }
+
+    lint
+}
+
+/// Finds a known setting within a small edit distance of `name`
+fn closest_setting(name: &str) -> Option<&'static str> {
+    let max_distance = if name.len() > 8 { 2 } else { 1 };
+    KNOWN_SETTINGS
+        .iter()
+        .map(|known| (*known, edit_distance(name, known)))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(known, _)| known)
+}
+
+/// Levenshtein distance between two ASCII strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b_bytes = b.as_bytes();
+    let mut previous: Vec<usize> = (0..=b_bytes.len()).collect();
+    for (i, ca) in a.bytes().enumerate() {
+        let mut current = vec![i + 1; b_bytes.len() + 1];
+        for (j, cb) in b_bytes.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b_bytes.len()]
+}
@@ -0,0 +1,192 @@
+//! Popular single-header libraries (stb, nlohmann/json, doctest, header-only fmt) that a
+//! direct build includes but the project does not have. Quick demos often include
+//! `stb_image.h` and expect it to be around; instead of failing on the missing header, a
+//! pinned copy is fetched with curl into `.wasm_compiler/deps/<name>-<version>/` in the
+//! project (next to the build trees for a read-only project) and added to the include
+//! directories. On a terminal the tool asks first;
+//! `--fetch-headers` fetches without asking, and otherwise the missing headers are only
+//! reported. Copies fetched before are used without asking.
+
+use std::path::{Path, PathBuf};
+use crate::app_config::AppConfig;
+use crate::compiler::direct_plan::DirectCompilePlan;
+use crate::compiler::error::CompileError;
+use crate::compiler::include_check;
+use crate::utils::command_runner::run_build_command;
+use crate::utils::source_selection::project_files;
+
+/// Where the fetched copies go, relative to the project root
+pub const DEPS_DIR: &str = ".wasm_compiler/deps";
+
+/// A single-header library and where its pinned copy comes from
+struct HeaderLibrary {
+    name: &'static str,
+    version: &'static str,
+    /// The includes that name it, as written in the sources
+    includes: &'static [&'static str],
+    /// URL the files are fetched from, followed by their path in it
+    base_url: &'static str,
+    /// Path of each file below `base_url`, and in the directory of the copy
+    files: &'static [(&'static str, &'static str)],
+    /// Defines the library needs, without `-D`
+    defines: &'static [&'static str],
+}
+
+const STB_URL: &str = "https://raw.githubusercontent.com/nothings/stb/f75e8d1cad7d90d72ef7a4661f1b994ef78b4e31/";
+const STB_VERSION: &str = "f75e8d1";
+
+/// One header of stb, each its own library
+macro_rules! stb {
+    ($name:literal) => {
+        HeaderLibrary {
+            name: $name,
+            version: STB_VERSION,
+            includes: &[concat!($name, ".h")],
+            base_url: STB_URL,
+            files: &[(concat!($name, ".h"), concat!($name, ".h"))],
+            defines: &[],
+        }
+    };
+}
+
+const FMT_HEADERS: &[(&str, &str)] = &[
+    ("args.h", "fmt/args.h"),
+    ("chrono.h", "fmt/chrono.h"),
+    ("color.h", "fmt/color.h"),
+    ("compile.h", "fmt/compile.h"),
+    ("core.h", "fmt/core.h"),
+    ("format-inl.h", "fmt/format-inl.h"),
+    ("format.h", "fmt/format.h"),
+    ("os.h", "fmt/os.h"),
+    ("ostream.h", "fmt/ostream.h"),
+    ("printf.h", "fmt/printf.h"),
+    ("ranges.h", "fmt/ranges.h"),
+    ("std.h", "fmt/std.h"),
+    ("xchar.h", "fmt/xchar.h"),
+];
+
+const LIBRARIES: &[HeaderLibrary] = &[
+    stb!("stb_image"),
+    stb!("stb_image_write"),
+    stb!("stb_image_resize2"),
+    stb!("stb_truetype"),
+    stb!("stb_rect_pack"),
+    stb!("stb_sprintf"),
+    stb!("stb_ds"),
+    stb!("stb_perlin"),
+    HeaderLibrary {
+        name: "nlohmann_json",
+        version: "3.11.3",
+        includes: &["nlohmann/json.hpp", "nlohmann/json_fwd.hpp"],
+        base_url: "https://raw.githubusercontent.com/nlohmann/json/v3.11.3/single_include/nlohmann/",
+        files: &[("json.hpp", "nlohmann/json.hpp"), ("json_fwd.hpp", "nlohmann/json_fwd.hpp")],
+        defines: &[],
+    },
+    HeaderLibrary {
+        name: "doctest",
+        version: "2.4.11",
+        includes: &["doctest.h", "doctest/doctest.h"],
+        base_url: "https://raw.githubusercontent.com/doctest/doctest/v2.4.11/doctest/",
+        files: &[("doctest.h", "doctest/doctest.h")],
+        defines: &[],
+    },
+    HeaderLibrary {
+        name: "fmt",
+        version: "10.2.1",
+        includes: &["fmt/core.h", "fmt/format.h", "fmt/args.h", "fmt/chrono.h", "fmt/color.h", "fmt/compile.h", "fmt/os.h", "fmt/ostream.h", "fmt/printf.h", "fmt/ranges.h", "fmt/std.h", "fmt/xchar.h"],
+        base_url: "https://raw.githubusercontent.com/fmtlib/fmt/10.2.1/include/fmt/",
+        files: FMT_HEADERS,
+        // Without the compiled part of fmt
+        defines: &["FMT_HEADER_ONLY"],
+    },
+];
+
+/// Adds the copies of the known single-header libraries the sources of `plan` include but
+/// the project does not have to its include directories, fetching them if allowed.
+pub fn add_missing(plan: &mut DirectCompilePlan, config: &AppConfig) -> Result<(), CompileError> {
+    let project_path = plan.project_path.clone();
+    let mut project_headers: Option<Vec<PathBuf>> = None;
+    let mut missing: Vec<(&HeaderLibrary, String, PathBuf)> = Vec::new();
+    for source in plan.all_sources() {
+        let Ok(content) = std::fs::read_to_string(&source) else { continue };
+        for (_, header, _) in include_check::includes(&content) {
+            let Some(library) = LIBRARIES.iter().find(|library| library.includes.contains(&header.as_str())) else { continue };
+            if missing.iter().any(|(known, _, _)| known.name == library.name) {
+                continue;
+            }
+            let source_dir = source.parent().unwrap_or(Path::new("."));
+            let resolves = source_dir.join(&header).is_file()
+                || plan.include_dirs.iter().chain(&config.include_dirs).any(|dir| dir.join(&header).is_file())
+                || project_headers
+                    .get_or_insert_with(|| project_files(&project_path, None))
+                    .iter()
+                    .any(|file| file.ends_with(&header));
+            if !resolves {
+                missing.push((library, header, source.clone()));
+            }
+        }
+    }
+
+    for (library, header, source) in missing {
+        let name = format!("{}-{}", library.name, library.version);
+        let in_project = project_path.join(DEPS_DIR).join(&name);
+        // A read-only project gets its copies next to its build trees
+        let copy = if config.read_only_source && !in_project.is_dir() {
+            config.build_root(&project_path).join(DEPS_DIR).join(&name)
+        } else {
+            in_project
+        };
+        let fetched = library.files.iter().all(|(_, path)| copy.join(path).is_file());
+        if !fetched {
+            let relative = source.strip_prefix(&project_path).unwrap_or(&source).display().to_string();
+            let question = format!("{} includes {}, which the project does not have. Fetch {} {} into {}?", relative, header, library.name, library.version, DEPS_DIR);
+            let fetch = config.fetch_headers || crate::ambiguity::confirm(config, &question).unwrap_or(false);
+            if !fetch {
+                tracing::warn!("{} includes {}, which the project does not have; --fetch-headers fetches {} {} into {}", relative, header, library.name, library.version, DEPS_DIR);
+                continue;
+            }
+            fetch_library(library, &copy, config)?;
+        }
+
+        for include in library.includes {
+            let Some((_, path)) = library.files.iter().find(|(_, path)| Path::new(path).ends_with(include)) else { continue };
+            // The directory the include resolves in, e.g. `doctest/` for `doctest.h`
+            if let Some(dir) = copy.join(path).ancestors().nth(Path::new(include).components().count()) {
+                plan.add_include_dir(dir);
+            }
+        }
+        for define in library.defines {
+            plan.add_define(*define);
+        }
+        tracing::info!("Using {} {} from {:?}", library.name, library.version, copy);
+        crate::lockfile::record(&project_path, library.name, library.version, library.base_url, &copy);
+    }
+    Ok(())
+}
+
+/// Whether `header` is one of the known single-header libraries
+pub fn is_known(header: &str) -> bool {
+    LIBRARIES.iter().any(|library| library.includes.contains(&header))
+}
+
+/// Downloads the files of `library` into `copy`
+fn fetch_library(library: &HeaderLibrary, copy: &Path, config: &AppConfig) -> Result<(), CompileError> {
+    tracing::info!("Fetching {} {} into {:?}", library.name, library.version, copy);
+    for (url_path, path) in library.files {
+        let destination = copy.join(path);
+        if let Some(dir) = destination.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+        }
+        // Downloaded next to the final file and moved in place, so an interrupted download
+        // is not taken for the header
+        let partial = destination.with_extension("partial");
+        let url = format!("{}{}", library.base_url, url_path);
+        let args = ["-fsSL".to_string(), "-o".to_string(), partial.to_string_lossy().into_owned(), url];
+        if let Err(e) = run_build_command("curl", &args, None, config) {
+            let _ = std::fs::remove_file(&partial);
+            return Err(e.into());
+        }
+        std::fs::rename(&partial, &destination).map_err(|e| format!("Failed to move {:?} to {:?}: {}", partial, destination, e))?;
+    }
+    Ok(())
+}
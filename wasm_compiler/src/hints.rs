@@ -0,0 +1,59 @@
+//! Knowledge base of common Emscripten build/link failures and how to fix them.
+//!
+//! Failed compilation messages are matched against [`KNOWN_ISSUES`] and the matching
+//! suggestions are appended to the reported error.
+
+/// A pattern found in toolchain output and the suggestion shown when it matches
+struct KnownIssue {
+    pattern: &'static str,
+    suggestion: &'static str,
+}
+
+const KNOWN_ISSUES: &[KnownIssue] = &[
+    KnownIssue { pattern: "undefined symbol: main", suggestion: "No main() was found. For a library, pass --emcc-flags=\"--no-entry\" and list the functions to export with -sEXPORTED_FUNCTIONS." },
+    KnownIssue { pattern: "entry symbol not defined", suggestion: "No main() was found. For a library, pass --emcc-flags=\"--no-entry\" and list the functions to export with -sEXPORTED_FUNCTIONS." },
+    KnownIssue { pattern: "undefined symbol: SDL_", suggestion: "SDL is not linked: add -sUSE_SDL=2 (or -sUSE_SDL=3 for SDL3) to --emcc-flags." },
+    KnownIssue { pattern: "undefined symbol: Mix_", suggestion: "SDL_mixer is not linked: add -sUSE_SDL_MIXER=2 to --emcc-flags." },
+    KnownIssue { pattern: "undefined symbol: IMG_", suggestion: "SDL_image is not linked: add -sUSE_SDL_IMAGE=2 to --emcc-flags." },
+    KnownIssue { pattern: "undefined symbol: TTF_", suggestion: "SDL_ttf is not linked: add -sUSE_SDL_TTF=2 to --emcc-flags." },
+    KnownIssue { pattern: "undefined symbol: glfw", suggestion: "GLFW is not linked: add -sUSE_GLFW=3 to --emcc-flags (or use --with-imgui)." },
+    KnownIssue { pattern: "undefined symbol: glBegin", suggestion: "Fixed-function OpenGL is not available in WebGL: add -sLEGACY_GL_EMULATION=1, or port the rendering to OpenGL ES 2/3." },
+    KnownIssue { pattern: "undefined symbol: glut", suggestion: "GLUT is not linked: add -lglut to --emcc-flags." },
+    KnownIssue { pattern: "undefined symbol: png_", suggestion: "libpng is not linked: add -sUSE_LIBPNG=1 to --emcc-flags." },
+    KnownIssue { pattern: "undefined symbol: jpeg_", suggestion: "libjpeg is not linked: add -sUSE_LIBJPEG=1 to --emcc-flags." },
+    KnownIssue { pattern: "undefined symbol: FT_", suggestion: "FreeType is not linked: add -sUSE_FREETYPE=1 to --emcc-flags." },
+    KnownIssue { pattern: "undefined symbol: inflate", suggestion: "zlib is not linked: add -sUSE_ZLIB=1 to --emcc-flags." },
+    KnownIssue { pattern: "undefined symbol: deflate", suggestion: "zlib is not linked: add -sUSE_ZLIB=1 to --emcc-flags." },
+    KnownIssue { pattern: "undefined symbol: alc", suggestion: "OpenAL is not linked: add -lopenal to --emcc-flags." },
+    KnownIssue { pattern: "undefined symbol: pthread_", suggestion: "Threads are not enabled: add -pthread to --emcc-flags (requires cross-origin isolation headers when served)." },
+    KnownIssue { pattern: "undefined symbol: _embind_", suggestion: "embind is not linked: add -lembind to --emcc-flags." },
+    KnownIssue { pattern: "undefined exported symbol", suggestion: "A name in -sEXPORTED_FUNCTIONS does not exist. C symbols need a leading underscore (e.g. _my_func) and C++ functions must be declared extern \"C\"." },
+    KnownIssue { pattern: "emcc: error: invalid command line setting", suggestion: "An -s setting is misspelled or no longer supported by this Emscripten version; check the flags passed with --emcc-flags." },
+    KnownIssue { pattern: "fatal error: 'emscripten.h' file not found", suggestion: "The file is being compiled with a host compiler instead of emcc; make sure the build uses $(CC)/$(CXX) rather than hard-coded gcc/g++." },
+];
+
+/// Returns the suggestions matching an error message, without duplicates.
+pub fn suggestions_for(error: &str) -> Vec<&'static str> {
+    let mut suggestions: Vec<&'static str> = Vec::new();
+    for issue in KNOWN_ISSUES {
+        if error.contains(issue.pattern) && !suggestions.contains(&issue.suggestion) {
+            suggestions.push(issue.suggestion);
+        }
+    }
+    suggestions
+}
+
+/// Appends matching suggestions to an error message.
+pub fn with_suggestions(error: String) -> String {
+    let suggestions = suggestions_for(&error);
+    if suggestions.is_empty() {
+        return error;
+    }
+    let mut message = error;
+    message.push_str("\n\nSuggestions:");
+    for suggestion in suggestions {
+        message.push_str("\n  - ");
+        message.push_str(suggestion);
+    }
+    message
+}
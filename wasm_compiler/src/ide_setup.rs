@@ -0,0 +1,104 @@
+//! The `ide-setup` subcommand: editor configuration for a C++ project built with Emscripten.
+//! `.clangd` adds the flags `emcc --cflags` reports (the wasm target, the Emscripten sysroot
+//! and its defines) and the include directories, defines and compile flags of the build to
+//! every file clangd opens, and `.vscode/c_cpp_properties.json` gives the Microsoft C/C++
+//! extension the same include paths and defines. Sources listed in a `compile_commands.json`
+//! (see [`crate::compiler::compile_commands`]) keep the flags recorded there.
+
+use std::path::Path;
+use serde_json::json;
+use crate::app_config::{AppConfig, IdeSetupArgs};
+use crate::compiler::compile_commands::{self, Toolchain};
+
+/// Writes `.clangd` and `.vscode/c_cpp_properties.json` to the project.
+pub fn ide_setup(args: &IdeSetupArgs) -> Result<(), String> {
+    let project_path = args.project_path
+        .canonicalize()
+        .map_err(|e| format!("Project path {:?} does not exist: {}", args.project_path, e))?;
+    let clangd_path = project_path.join(".clangd");
+    let properties_path = project_path.join(".vscode").join("c_cpp_properties.json");
+    let existing: Vec<String> = [&clangd_path, &properties_path]
+        .into_iter()
+        .filter(|path| path.exists())
+        .map(|path| format!("{:?}", path))
+        .collect();
+    if !existing.is_empty() && !args.force {
+        let verb = if existing.len() == 1 { "exists" } else { "exist" };
+        return Err(format!("{} already {}; pass --force to replace", existing.join(" and "), verb));
+    }
+
+    let mut builder = AppConfig::builder().project_path(&project_path);
+    for dir in &args.include_dirs {
+        builder = builder.include_dir(dir);
+    }
+    for define in &args.defines {
+        builder = builder.define(define);
+    }
+    for flag in &args.emcc_flags {
+        builder = builder.emcc_flag(flag);
+    }
+    let mut config = builder.build();
+    // The project config's [env] and path_prefix tell where emcc is
+    config.load_project_config()?;
+
+    let cflags = compile_commands::emscripten_cflags(&config)?;
+    let build_flags = compile_commands::compile_flags(&config.compile_flags().into_iter().chain(config.emcc_flag.clone()).collect::<Vec<_>>());
+    let toolchain = Toolchain::from_flags(&cflags, &build_flags);
+
+    std::fs::write(&clangd_path, clangd_config(&cflags, &build_flags))
+        .map_err(|e| format!("Failed to write {:?}: {}", clangd_path, e))?;
+    println!("Wrote {}", clangd_path.display());
+
+    let properties = serde_json::to_string_pretty(&cpp_properties(&toolchain, &project_path))
+        .map_err(|e| format!("Failed to serialize {:?}: {}", properties_path, e))?;
+    if let Some(parent) = properties_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    std::fs::write(&properties_path, properties).map_err(|e| format!("Failed to write {:?}: {}", properties_path, e))?;
+    println!("Wrote {}", properties_path.display());
+
+    match &toolchain.sysroot {
+        Some(sysroot) => println!("Emscripten sysroot: {}", sysroot.display()),
+        None => println!("emcc --cflags named no sysroot; system headers may not be found"),
+    }
+    Ok(())
+}
+
+/// `.clangd` adding the Emscripten and build flags; YAML accepts JSON strings as scalars. A
+/// C++ standard is only added for C++ sources, in a second fragment.
+fn clangd_config(cflags: &[String], build_flags: &[String]) -> String {
+    let is_cpp_standard = |flag: &&String| flag.starts_with("-std=") && flag.contains("++");
+    let flag_list = |flags: &mut dyn Iterator<Item = &String>| -> String {
+        flags.map(|flag| format!("    - {}\n", serde_json::Value::from(flag.as_str()))).collect()
+    };
+    let mut config = String::from("# Generated by `wasm_compiler ide-setup`\nCompileFlags:\n  Add:\n");
+    config.push_str(&flag_list(&mut cflags.iter().chain(build_flags).filter(|flag| !is_cpp_standard(flag))));
+    let cpp_flags = flag_list(&mut build_flags.iter().filter(is_cpp_standard));
+    if !cpp_flags.is_empty() {
+        config.push_str("---\nIf:\n  PathMatch: .*\\.(cpp|cc|cxx|c\\+\\+|hpp|hh|hxx)\nCompileFlags:\n  Add:\n");
+        config.push_str(&cpp_flags);
+    }
+    config
+}
+
+/// `c_cpp_properties.json` for the Microsoft C/C++ extension
+fn cpp_properties(toolchain: &Toolchain, project_path: &Path) -> serde_json::Value {
+    let include_path: Vec<String> = std::iter::once("${workspaceFolder}/**".to_string())
+        .chain(toolchain.includes.iter().chain(&toolchain.system_includes()).map(|dir| dir.to_string_lossy().into_owned()))
+        .collect();
+    let mut configuration = json!({
+        "name": "Emscripten",
+        "includePath": include_path,
+        "defines": toolchain.defines,
+        // An empty path keeps the extension from querying the host compiler
+        "compilerPath": "",
+        "cStandard": "c17",
+        "cppStandard": toolchain.cpp_standard.as_deref().unwrap_or("c++17"),
+        // wasm32 has 32-bit pointers
+        "intelliSenseMode": "clang-x86",
+    });
+    if project_path.join(compile_commands::FILE_NAME).is_file() {
+        configuration["compileCommands"] = json!(format!("${{workspaceFolder}}/{}", compile_commands::FILE_NAME));
+    }
+    json!({ "configurations": [configuration], "version": 4 })
+}
@@ -0,0 +1,84 @@
+//! Where the ImGui sources of an ImGui build come from. ImGui has no Emscripten port; its
+//! sources are compiled along with the project. `--imgui-path` names the checkout; otherwise
+//! it is looked for in the project (`imgui/`, `third_party/imgui/`, ...) and two levels above
+//! it, where the examples of the ImGui repository have it. A project that has none gets a
+//! pinned ImGui release (`--imgui-version` picks another), cloned once into the cache
+//! directory and shared by later builds; `--no-imgui-fetch` turns that into an error.
+
+use std::path::{Path, PathBuf};
+use crate::app_config::AppConfig;
+use crate::compiler::error::CompileError;
+use crate::utils::command_runner::run_build_command;
+use crate::utils::file_system;
+
+/// The ImGui release fetched when `--imgui-version` is not given
+pub const IMGUI_VERSION: &str = "v1.91.9";
+
+const IMGUI_REPOSITORY: &str = "https://github.com/ocornut/imgui.git";
+
+/// Directories of the project ImGui is usually vendored in
+const VENDOR_DIRS: &[&str] = &["imgui", "third_party/imgui", "thirdparty/imgui", "external/imgui", "extern/imgui", "vendor/imgui", "libs/imgui", "lib/imgui"];
+
+/// The ImGui checkout to build `project_path` with: `--imgui-path`, one found around the
+/// project, or the fetched release. `None` with `--no-imgui-fetch` when there is none.
+pub fn locate(config: &AppConfig, project_path: &Path) -> Result<Option<PathBuf>, CompileError> {
+    if let Some(dir) = &config.imgui_path {
+        if !is_imgui_checkout(dir) {
+            return Err(CompileError::Other(format!("--imgui-path {:?} is not an ImGui checkout (it has no imgui.h and imgui.cpp)", dir)));
+        }
+        return Ok(Some(dir.clone()));
+    }
+
+    let candidates = std::iter::once(project_path.to_path_buf())
+        .chain(VENDOR_DIRS.iter().map(|dir| project_path.join(dir)))
+        .chain(std::iter::once(project_path.join("..").join("..")));
+    for dir in candidates {
+        if is_imgui_checkout(&dir) {
+            tracing::debug!("Using ImGui in {:?}", dir);
+            return Ok(Some(dir));
+        }
+    }
+
+    if config.no_imgui_fetch {
+        return Ok(None);
+    }
+    fetch(config, project_path).map(Some)
+}
+
+/// Whether `dir` has the ImGui sources
+pub fn is_imgui_checkout(dir: &Path) -> bool {
+    dir.join("imgui.h").is_file() && dir.join("imgui.cpp").is_file()
+}
+
+/// The checkout of the `--imgui-version` release in the cache directory, cloned if it is not
+/// there yet, recorded in the lockfile of `project_path`
+fn fetch(config: &AppConfig, project_path: &Path) -> Result<PathBuf, CompileError> {
+    let version = config.imgui_version.as_deref().unwrap_or(IMGUI_VERSION);
+    let cache = file_system::cache_dir().ok_or_else(|| {
+        CompileError::Other("The project uses ImGui but has no ImGui checkout, and there is no cache directory to fetch it into (pass --imgui-path)".to_string())
+    })?;
+    let checkout = cache.join(format!("imgui-{}", version));
+    if is_imgui_checkout(&checkout) {
+        tracing::debug!("Using the cached ImGui {} in {:?}", version, checkout);
+        crate::lockfile::record(project_path, "imgui", version, IMGUI_REPOSITORY, &checkout);
+        return Ok(checkout);
+    }
+
+    tracing::info!("The project comes without ImGui; fetching ImGui {} into {:?}", version, checkout);
+    file_system::create_dir_atomically(&checkout, |partial| {
+        let args = [
+            "-c".to_string(),
+            "advice.detachedHead=false".to_string(),
+            "clone".to_string(),
+            "--depth".to_string(),
+            "1".to_string(),
+            "--branch".to_string(),
+            version.to_string(),
+            IMGUI_REPOSITORY.to_string(),
+            partial.to_string_lossy().into_owned(),
+        ];
+        run_build_command("git", &args, None, config).map(|_| ()).map_err(CompileError::from)
+    })?;
+    crate::lockfile::record(project_path, "imgui", version, IMGUI_REPOSITORY, &checkout);
+    Ok(checkout)
+}
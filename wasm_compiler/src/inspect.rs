@@ -0,0 +1,206 @@
+//! The `inspect` subcommand: prints the imports and exports of a `.wasm` file, its memories,
+//! the sizes of its sections, the post-MVP features it uses, the toolchain recorded in its
+//! `producers` section and the build provenance embedded with `--provenance`. It works on any
+//! module, so it also helps to debug third-party modules.
+
+use wasmparser::{ExternalKind, KnownCustom, MemoryType, Parser, Payload, TypeRef};
+use crate::app_config::InspectArgs;
+use crate::provenance::{self, Provenance};
+use crate::wasm_validation::{self, WasmFeature};
+
+/// What `inspect` reports about a module
+#[derive(Debug, Clone)]
+pub struct ModuleInfo {
+    /// Size of the file in bytes
+    pub size: usize,
+    /// Name and size in bytes of each section, in file order
+    pub sections: Vec<(String, usize)>,
+    /// `module.name` and the kind of each import
+    pub imports: Vec<(String, String)>,
+    /// Name and kind of each export
+    pub exports: Vec<(String, String)>,
+    /// Imported and defined memories, with whether they are imported
+    pub memories: Vec<(MemoryType, bool)>,
+    pub features: Vec<WasmFeature>,
+    /// Fields of the `producers` section (`language`, `processed-by`, `sdk`) with their
+    /// `name version` values
+    pub producers: Vec<(String, Vec<String>)>,
+    pub provenance: Option<Provenance>,
+}
+
+/// Parses and validates a module.
+pub fn module_info(module: &[u8]) -> Result<ModuleInfo, String> {
+    let mut info = ModuleInfo {
+        size: module.len(),
+        sections: Vec::new(),
+        imports: Vec::new(),
+        exports: Vec::new(),
+        memories: Vec::new(),
+        features: wasm_validation::module_features(module)?,
+        producers: Vec::new(),
+        provenance: provenance::read_provenance(module)?,
+    };
+
+    for payload in Parser::new(0).parse_all(module) {
+        let payload = payload.map_err(|e| e.to_string())?;
+        if let Some((id, range)) = payload.as_section() {
+            let name = match &payload {
+                Payload::CustomSection(section) => format!("custom \"{}\"", section.name()),
+                _ => section_name(id).to_string(),
+            };
+            info.sections.push((name, range.len()));
+        }
+        match payload {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    let import = import.map_err(|e| e.to_string())?;
+                    let kind = match import.ty {
+                        TypeRef::Func(_) => "func",
+                        TypeRef::Table(_) => "table",
+                        TypeRef::Memory(memory) => {
+                            info.memories.push((memory, true));
+                            "memory"
+                        }
+                        TypeRef::Global(_) => "global",
+                        TypeRef::Tag(_) => "tag",
+                    };
+                    info.imports.push((format!("{}.{}", import.module, import.name), kind.to_string()));
+                }
+            }
+            Payload::MemorySection(reader) => {
+                for memory in reader {
+                    info.memories.push((memory.map_err(|e| e.to_string())?, false));
+                }
+            }
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    let export = export.map_err(|e| e.to_string())?;
+                    let kind = match export.kind {
+                        ExternalKind::Func => "func",
+                        ExternalKind::Table => "table",
+                        ExternalKind::Memory => "memory",
+                        ExternalKind::Global => "global",
+                        ExternalKind::Tag => "tag",
+                    };
+                    info.exports.push((export.name.to_string(), kind.to_string()));
+                }
+            }
+            Payload::CustomSection(section) => {
+                if let KnownCustom::Producers(reader) = section.as_known() {
+                    for field in reader {
+                        let field = field.map_err(|e| e.to_string())?;
+                        let values = field.values
+                            .into_iter()
+                            .map(|value| value.map(|value| format!("{} {}", value.name, value.version).trim().to_string()))
+                            .collect::<Result<_, _>>()
+                            .map_err(|e| e.to_string())?;
+                        info.producers.push((field.name.to_string(), values));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(info)
+}
+
+/// Reads the module given to `inspect` and prints its report.
+pub fn inspect(args: &InspectArgs) -> Result<(), String> {
+    let module = std::fs::read(&args.file).map_err(|e| format!("Failed to read {:?}: {}", args.file, e))?;
+    let info = module_info(&module).map_err(|e| format!("{:?} is not a valid WebAssembly module: {}", args.file, e))?;
+
+    println!("{} ({})", args.file.display(), format_size(info.size as u64));
+    println!("Sections:");
+    for (name, size) in &info.sections {
+        println!("  {:<28} {}", name, format_size(*size as u64));
+    }
+    println!("Imports ({}):", info.imports.len());
+    for (name, kind) in &info.imports {
+        println!("  {} ({})", name, kind);
+    }
+    println!("Exports ({}):", info.exports.len());
+    for (name, kind) in &info.exports {
+        println!("  {} ({})", name, kind);
+    }
+    println!("Memories ({}):", info.memories.len());
+    for (memory, imported) in &info.memories {
+        println!("  {}{}", describe_memory(memory), if *imported { ", imported" } else { "" });
+    }
+    println!(
+        "Features: {}",
+        if info.features.is_empty() {
+            "none beyond WebAssembly 1.0".to_string()
+        } else {
+            info.features.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+        }
+    );
+    if info.producers.is_empty() {
+        println!("Producers: none recorded");
+    } else {
+        println!("Producers:");
+        for (field, values) in &info.producers {
+            println!("  {}: {}", field, values.join(", "));
+        }
+    }
+    match &info.provenance {
+        Some(provenance) => {
+            println!("Provenance:");
+            println!("  wasm_compiler: {}", provenance.tool_version);
+            println!("  Emscripten:    {}", provenance.emscripten_version.as_deref().unwrap_or("unknown"));
+            println!("  Flags hash:    {}", provenance.flags_hash);
+            println!(
+                "  Git commit:    {}{}",
+                provenance.git_commit.as_deref().unwrap_or("unknown"),
+                if provenance.git_dirty { " (with uncommitted changes)" } else { "" }
+            );
+        }
+        None => println!("Provenance: none (build with --provenance to embed it)"),
+    }
+    Ok(())
+}
+
+fn section_name(id: u8) -> &'static str {
+    match id {
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "data count",
+        13 => "tag",
+        _ => "unknown",
+    }
+}
+
+/// Limits of a memory, e.g. `256 pages (16 MiB) to 32768 pages (2 GiB), shared`
+fn describe_memory(memory: &MemoryType) -> String {
+    let page_size = 1u64 << memory.page_size_log2.unwrap_or(16);
+    let pages = |count: u64| format!("{} pages ({})", count, format_size(count.saturating_mul(page_size)));
+    let mut description = match memory.maximum {
+        Some(maximum) => format!("{} to {}", pages(memory.initial), pages(maximum)),
+        None => format!("{}, no maximum", pages(memory.initial)),
+    };
+    if memory.shared {
+        description.push_str(", shared");
+    }
+    if memory.memory64 {
+        description.push_str(", 64-bit");
+    }
+    description
+}
+
+fn format_size(bytes: u64) -> String {
+    const KIB: u64 = 1024;
+    match bytes {
+        b if b >= KIB * KIB * KIB => format!("{:.1} GiB", b as f64 / (KIB * KIB * KIB) as f64),
+        b if b >= KIB * KIB => format!("{:.1} MiB", b as f64 / (KIB * KIB) as f64),
+        b if b >= KIB => format!("{:.1} KiB", b as f64 / KIB as f64),
+        b => format!("{} B", b),
+    }
+}
@@ -18,13 +18,78 @@
 //! wasm_compiler --project-path /path/to/cpp-project --output-dir /path/to/output [--with-imgui]
 //! ```
 
+pub mod ambiguity;
 pub mod app_config;
+#[cfg(feature = "async")]
+pub mod async_compile;
+pub mod batch;
+pub mod browser_baseline;
+pub mod build_dir;
+pub mod build_manifest;
+pub mod cancel;
 pub mod compiler;
+#[cfg(feature = "cli")]
+mod completions;
+pub mod component;
+pub mod deploy;
+pub mod diagnostics;
+pub mod doctor;
+pub mod em_cache;
+pub mod events;
+pub mod executables;
+pub mod exports;
+pub mod flag_lint;
+pub mod header_deps;
+pub mod hints;
+pub mod ide_setup;
+pub mod imgui_vendor;
+pub mod inspect;
+pub mod lockfile;
+pub mod main_loop;
+pub mod matrix;
+pub mod module_format;
+pub mod output_archive;
+pub mod output_assets;
+pub mod output_dir;
+pub mod plugins;
+pub mod prefetch;
+#[cfg(feature = "cli")]
+mod progress;
+pub mod project_config;
+pub mod provenance;
+pub mod remote_project;
+pub mod repro_script;
+pub mod resource_limits;
+pub mod sdl_version;
+pub mod signing;
+pub mod smoke_test;
+pub mod strict;
+pub mod strip_names;
+pub mod toolchain;
+pub mod transcript;
+pub mod typescript_gen;
+pub mod ui_kind;
 pub mod utils;
+pub mod wasm_runtime;
+pub mod wasm_validation;
+pub mod wasm2js;
+#[cfg(feature = "webapp")]
 pub mod webapp_generator;
 
-use app_config::AppConfig;
-use compiler::{BuildSystemHandler, cmake_handler::CMakeHandler, make_handler::MakeHandler, emscripten_runner::EmscriptenRunner};
+use app_config::{AppConfig, CompileOptions, DeployLayout};
+pub use batch::{compile_all, BatchReport, ProjectReport};
+pub use cancel::CancelHandle;
+pub use executables::compile_executables;
+pub use matrix::{compile_matrix, TargetArtifacts};
+use std::path::PathBuf;
+use compiler::emscripten_runner::EmscriptenRunner;
+pub use compiler::error::{CompileError, ConfigureError, DetectionError};
+pub use compiler::registry::HandlerRegistry;
+pub use utils::command_runner::CommandError;
+#[cfg(feature = "async")]
+pub use async_compile::{compile_async, CancellationToken};
+use events::Phase;
+use utils::source_selection::SourceSelection;
 // use std::path::Path; // Not directly used here anymore, but kept for context if needed
 
 #[derive(thiserror::Error, Debug)]
@@ -34,24 +99,313 @@ pub enum Error {
     #[error("Configuration error: {0}")]
     Config(String),
     #[error("Build system detection failed: {0}")]
-    Detection(String),
+    Detection(#[from] DetectionError),
+    /// Displays as `<tool> configure step failed: ...`
+    #[error(transparent)]
+    Configure(ConfigureError),
     #[error("Compilation failed: {0}")]
-    Compilation(String),
+    Compilation(CompileError),
     #[error("Command execution failed: {0}")] // Retained if direct command usage happens elsewhere
     Command(String),
     #[error("File system operation failed: {0}")]
     FileSystem(String),
+    /// Processing the outputs of a finished build failed: rewriting the glue, hashing file
+    /// names, writing the manifests, checksums or archive
+    #[error("Processing the build outputs failed: {0}")]
+    PostProcessing(String),
+    #[error("Build cancelled")]
+    Cancelled,
+    /// A `--run-test` smoke test or a `--test` CTest test failed
+    #[error("{0}")]
+    Test(String),
+    /// The program started with `--run` exited unsuccessfully; `None` if it was killed by a signal
+    #[error("The program exited with {}", .status.map_or("a signal".to_string(), |code| format!("status {}", code)))]
+    Run { status: Option<i32> },
+    /// The `verify` subcommand found a modified or missing file, or an invalid signature
+    #[error("Verification failed: {0}")]
+    Verify(String),
+    #[error("{failed} of {total} projects failed to build")]
+    Batch { failed: usize, total: usize },
 }
 
-pub fn run() -> Result<(), Error> {
-    // Ensure logger is initialized. If main.rs also does it, this is fine.
-    // Consider using `try_init` if multiple initializations are an issue.
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).try_init().ok();
+/// Process exit codes of the command-line tool, stable across releases so scripts can tell a
+/// mistake in the invocation from a failed build. Errors not listed here exit with 1, and a
+/// program started with `--run` passes its own exit status on.
+pub mod exit_code {
+    /// Invalid options or project config
+    pub const CONFIG: i32 = 2;
+    /// Nothing to build found in the project, or an ambiguity that was not resolved
+    pub const DETECTION: i32 = 3;
+    /// emcc, emcmake or another tool of the build is not installed
+    pub const TOOLCHAIN_MISSING: i32 = 4;
+    /// The configure step or the compilation failed
+    pub const COMPILE: i32 = 5;
+    /// Processing the build outputs failed (glue rewriting, manifests, checksums, ...)
+    pub const POST_PROCESSING: i32 = 6;
+    /// Any other failure
+    pub const OTHER: i32 = 1;
+}
+
+impl Error {
+    /// The process exit code for this error (see [`exit_code`])
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Config(_) => exit_code::CONFIG,
+            Error::Detection(_) => exit_code::DETECTION,
+            _ if self.is_toolchain_missing() => exit_code::TOOLCHAIN_MISSING,
+            Error::Configure(_) | Error::Compilation(_) | Error::Batch { .. } => exit_code::COMPILE,
+            Error::PostProcessing(_) => exit_code::POST_PROCESSING,
+            Error::Run { status } => status.unwrap_or(exit_code::OTHER),
+            Error::Io(_) | Error::Command(_) | Error::FileSystem(_) | Error::Cancelled | Error::Test(_) | Error::Verify(_) => exit_code::OTHER,
+        }
+    }
+
+    /// A machine-readable name of the kind of error, for `--message-format json`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Config(_) => "config",
+            Error::Detection(_) => "detection",
+            _ if self.is_toolchain_missing() => "toolchain-missing",
+            _ if matches!(self.command_error(), Some(CommandError::TimedOut { .. } | CommandError::PhaseTimedOut { .. })) => "timed-out",
+            _ if matches!(self.command_error(), Some(CommandError::DiskLimitExceeded { .. })) => "disk-limit",
+            Error::Configure(_) => "configure-failed",
+            Error::Compilation(_) => "compile-failed",
+            Error::Batch { .. } => "batch-failed",
+            Error::PostProcessing(_) => "post-processing",
+            Error::FileSystem(_) => "file-system",
+            Error::Run { .. } => "run-failed",
+            Error::Io(_) => "io",
+            Error::Command(_) => "command-failed",
+            Error::Cancelled => "cancelled",
+            Error::Test(_) => "test-failed",
+            Error::Verify(_) => "verify-failed",
+        }
+    }
+
+    fn is_toolchain_missing(&self) -> bool {
+        matches!(self.command_error(), Some(CommandError::NotFound { .. }))
+    }
+
+    /// The failed command of a configure or compile error
+    fn command_error(&self) -> Option<&CommandError> {
+        match self {
+            Error::Configure(ConfigureError::Command(e)) => Some(e),
+            Error::Compilation(e) => e.command_error(),
+            _ => None,
+        }
+    }
+}
+
+impl From<CompileError> for Error {
+    fn from(error: CompileError) -> Self {
+        match error {
+            CompileError::Detection(e) => Error::Detection(e),
+            CompileError::Configure(e) => Error::Configure(e),
+            e => Error::Compilation(e),
+        }
+    }
+}
+
+/// A file produced by the build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub path: PathBuf,
+    /// Size in bytes
+    pub size: u64,
+    /// Hex-encoded SHA-256 digest of the contents
+    pub sha256: String,
+}
+
+impl Artifact {
+    fn from_path(path: PathBuf) -> Result<Self, String> {
+        let size = std::fs::metadata(&path)
+            .map_err(|e| format!("Failed to read metadata of {:?}: {}", path, e))?
+            .len();
+        let sha256 = utils::file_system::hash_file(&path)?;
+        Ok(Artifact { path, size, sha256 })
+    }
+}
+
+/// The files produced by [`compile`]
+#[derive(Debug, Clone)]
+pub struct BuildArtifacts {
+    pub output_dir: PathBuf,
+    /// JS glue (with the wasm embedded in single-file builds)
+    pub js: Artifact,
+    /// WebAssembly binary, unless embedded in the JS glue
+    pub wasm: Option<Artifact>,
+    /// Preloaded file package, if the build produced one
+    pub data: Option<Artifact>,
+    /// Source map of the wasm binary, for Debug builds (`-g4`) and builds with `-gsource-map`
+    pub map: Option<Artifact>,
+    /// WebAssembly component, for `--component` builds
+    pub component: Option<Artifact>,
+    /// Further files emcc wrote next to the glue, such as pthread worker scripts
+    pub auxiliary: Vec<Artifact>,
+    pub build_manifest: PathBuf,
+    /// Files generated around the module: the webapp pages, the Node.js launcher or Electron
+    /// wrapper, the `--typescript` API, the asset manifest of `--hash-filenames` and the
+    /// GitHub Pages files
+    pub generated: Vec<PathBuf>,
+    /// `SHA256SUMS`, with `--checksums` or a signing key
+    pub checksums: Option<PathBuf>,
+    /// `SHA256SUMS.minisig`, when a signing key is configured
+    pub signature: Option<PathBuf>,
+    /// The `--archive-output` archive of the output directory
+    pub archive: Option<PathBuf>,
+}
+
+impl BuildArtifacts {
+    /// Every file the build wrote to the output directory, except the archive
+    pub fn files(&self) -> Vec<PathBuf> {
+        let optional = [&self.wasm, &self.data, &self.map, &self.component];
+        std::iter::once(&self.js)
+            .chain(optional.into_iter().flatten())
+            .chain(&self.auxiliary)
+            .map(|artifact| artifact.path.clone())
+            .chain(std::iter::once(self.build_manifest.clone()))
+            .chain(self.generated.iter().cloned())
+            .chain(self.checksums.clone())
+            .chain(self.signature.clone())
+            .collect()
+    }
+}
+
+/// Entry point of the command-line tool: parses the arguments and builds the project or runs
+/// the given subcommand. Returns the build outputs, or `None` when a subcommand was run or
+/// several projects, matrix targets or executables were built.
+#[cfg(feature = "cli")]
+pub fn run() -> Result<Option<BuildArtifacts>, Error> {
+    let mut config = AppConfig::new();
+    // Initializes the logger too; if main.rs also did it, this is fine
+    let (events, progress) = progress::Progress::init(config.quiet);
+    config.events = events;
+    config.interactive = true;
+    config.cancel = Some(cancel::cancel_on_ctrl_c());
+    diagnostics::set_message_format(config.message_format);
+    // Kept for the whole run, so the builds of several projects or targets share the transcript
+    // and the script
+    let _transcript = config.log_file.as_deref().map(transcript::start).transpose().map_err(Error::Config)?;
+    let _script = config.emit_script.as_deref().map(repro_script::start).transpose().map_err(Error::Config)?;
+
+    let result = run_command_line(config);
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    match &result {
+        Ok(_) => transcript::note("Finished successfully"),
+        Err(e) => transcript::note(format_args!("Failed: {}", e)),
+    }
+    result
+}
+
+/// [`run`] after the logger is set up
+#[cfg(feature = "cli")]
+fn run_command_line(mut config: AppConfig) -> Result<Option<BuildArtifacts>, Error> {
+    match &config.command {
+        Some(app_config::Command::Deploy(args)) => return deploy::deploy(args).map(|_| None).map_err(Error::Command),
+        Some(app_config::Command::Cache(args)) => return em_cache::run_cache_command(args).map(|_| None).map_err(Error::Command),
+        Some(app_config::Command::Verify(args)) => return signing::verify(args).map(|_| None).map_err(Error::Verify),
+        Some(app_config::Command::Inspect(args)) => return inspect::inspect(args).map(|_| None).map_err(Error::FileSystem),
+        Some(app_config::Command::IdeSetup(args)) => return ide_setup::ide_setup(args).map(|_| None).map_err(Error::Config),
+        Some(app_config::Command::Completions(args)) => return completions::generate(args).map(|_| None).map_err(Error::FileSystem),
+        Some(app_config::Command::Doctor(args)) => {
+            let json = args.json;
+            config.project_path = args.project_path.clone();
+            config.load_project_config().map_err(Error::Config)?;
+            doctor::doctor(&config, json);
+            return Ok(None);
+        }
+        None => {}
+    }
+
+    if config.all_executables && config.project_paths.len() > 1 {
+        return Err(Error::Config("--all-executables builds a single project; pass one --project-path".to_string()));
+    }
+    if config.project_paths.len() > 1 {
+        let report = compile_all(batch::split_projects(&config));
+        match diagnostics::message_format() {
+            diagnostics::MessageFormat::Json => println!("{}", report.to_json()),
+            diagnostics::MessageFormat::Human => println!("{}", report.summary()),
+        }
+        return match report.failed() {
+            0 => {
+                // One archive of the outputs of all projects
+                if let Some(archive) = &config.archive_output {
+                    let files: Vec<PathBuf> = report.projects.iter().flat_map(|project| &project.result).flat_map(BuildArtifacts::files).collect();
+                    output_archive::write_archive(&config.output_dir, &files, archive).map_err(Error::PostProcessing)?;
+                }
+                Ok(None)
+            }
+            failed => Err(Error::Batch { failed, total: report.projects.len() }),
+        };
+    }
+
+    if let Some(project_path) = config.project_paths.first() {
+        config.project_path = project_path.clone();
+    }
+    remote_project::resolve(&mut config).map_err(Error::Command)?;
+    config.load_project_config().map_err(Error::Config)?;
+    if config.all_executables {
+        for executable in compile_executables(CompileOptions::from(config))? {
+            println!("Built executable '{}' into {}", executable.name, executable.artifacts.output_dir.display());
+        }
+        return Ok(None);
+    }
+    if !config.project_config.targets.is_empty() || !config.targets.is_empty() {
+        for target in compile_matrix(CompileOptions::from(config))? {
+            println!("Built target '{}' into {}", target.name, target.artifacts.output_dir.display());
+        }
+        return Ok(None);
+    }
+    compile(CompileOptions::from(config)).map(Some)
+}
+
+/// Builds a project as configured by `options` (see [`AppConfig::builder`]) and returns the
+/// produced files with their sizes and hashes. This does not parse command-line arguments,
+/// so it can be used when `wasm_compiler` is a library dependency. See [`compile_all`] for
+/// building several projects at once, [`compile_matrix`] for the targets declared in the
+/// project config and [`compile_executables`] for every executable of a CMake project. A
+/// build cancelled through its [`CancelHandle`] removes the files it created in the output
+/// directory and fails with [`Error::Cancelled`].
+pub fn compile(options: CompileOptions) -> Result<BuildArtifacts, Error> {
+    let mut config = options.build();
+    remote_project::resolve(&mut config).map_err(Error::Command)?;
+    let cancel = config.cancel.clone();
+    let output_dir = config.output_dir.clone();
+    let snapshot = cancel.as_ref().map(|_| cancel::OutputSnapshot::take(&output_dir));
+    let ephemeral = build_dir::prepare(&mut config).map_err(Error::Config)?;
+    let result = compile_config(config);
+    if let Some(ephemeral) = ephemeral {
+        ephemeral.finish(result.is_ok());
+    }
+    match cancel {
+        Some(cancel) if cancel.is_cancelled() && result.is_err() => {
+            tracing::info!("Build cancelled; removing its files from {:?}", output_dir);
+            if let Some(snapshot) = &snapshot {
+                snapshot.remove_new_outputs();
+            }
+            Err(Error::Cancelled)
+        }
+        _ => result,
+    }
+}
+
+/// The build of [`compile`], without the clean-up of a cancelled build
+fn compile_config(mut config: AppConfig) -> Result<BuildArtifacts, Error> {
+    let _span = tracing::info_span!("compile", project = %config.project_path.display()).entered();
+    let phase = config.enter_phase(Phase::Prepare);
+    let _transcript = config.log_file.as_deref().map(transcript::start).transpose().map_err(Error::Config)?;
+    let _script = config.emit_script.as_deref().map(repro_script::start).transpose().map_err(Error::Config)?;
+    transcript::note(format_args!("Building {}", config.project_path.display()));
 
-    let config = AppConfig::new();
+    config.load_project_config().map_err(Error::Config)?;
+    plugins::register_plugins(&mut config).map_err(Error::Config)?;
+    config.resolve_emcc_flags().map_err(Error::Config)?;
+    SourceSelection::from_config(&config).map_err(Error::Config)?;
 
-    log::info!("Starting WASM compilation for project at: {:?}", config.project_path);
-    log::debug!("Using configuration: {:?}", config);
+    tracing::info!("Starting WASM compilation for project at: {:?}", config.project_path);
+    tracing::debug!("Using configuration: {:?}", config);
 
     if !config.project_path.exists() || !config.project_path.is_dir() {
         return Err(Error::Config(format!(
@@ -66,67 +420,466 @@ pub fn run() -> Result<(), Error> {
     // Or, pass project_path_abs to handlers and they can use it with original config.
     // For simplicity, let's assume handlers will use the absolute path when needed.
 
-    utils::file_system::ensure_dir_exists(&config.output_dir)
-        .map_err(Error::FileSystem)?;
+    output_dir::prepare_output_dir(&config, &project_path_abs).map_err(Error::Config)?;
+
+    config.resolve_output_name(&project_path_abs);
+    check_emcc_flags(&mut config)?;
+    browser_baseline::apply_browser_baseline(&mut config).map_err(Error::Config)?;
+    configure_entry_point(&project_path_abs, &mut config)?;
+    configure_exports(&project_path_abs, &mut config)?;
+    configure_embind(&project_path_abs, &mut config);
+    if config.ui.is_none() {
+        let (kind, reason) = ui_kind::detect(&config, &project_path_abs);
+        match kind {
+            app_config::UiKind::Graphical => tracing::info!("Detected a graphical application ({})", reason),
+            app_config::UiKind::Console => tracing::info!("Detected a console application ({})", reason),
+        }
+        config.ui = Some(kind);
+    }
+    configure_sdl_version(&project_path_abs, &mut config)?;
+    if config.main_loop != app_config::MainLoopMode::Off {
+        let sources = project_sources(&project_path_abs, &config)?;
+        main_loop::check_main_loop(&mut config, &sources);
+    }
+    let wasi = config.component || config.target_env.eq_ignore_ascii_case("wasi");
+    if wasi && !config.emcc_flag.iter().any(|flag| flag.starts_with("-sSTANDALONE_WASM")) {
+        // Components and WASI builds import WASI rather than the Emscripten JS glue
+        config.emcc_flag.push("-sSTANDALONE_WASM=1".to_string());
+    }
+    if config.ctest && !project_path_abs.join("CMakeLists.txt").is_file() {
+        return Err(Error::Config("--test runs CTest tests and needs a CMake project".to_string()));
+    }
+    let runtime = config.run.then(|| wasm_runtime::check_runnable(&config)).transpose()?;
+    config.wasm2js_fallback = config.wasm2js_fallback && wasm2js::check_fallback(&config)?;
+    let signing_key = config.signing_key_path().map(signing::SecretKey::load).transpose().map_err(Error::Config)?;
+    if let Some(archive) = &config.archive_output {
+        output_archive::ArchiveFormat::of(archive).map_err(Error::Config)?;
+    }
+    em_cache::prewarm(&config).map_err(|e| Error::Compilation(e.into()))?;
+    lockfile::check_toolchain(&config, &project_path_abs).map_err(Error::Verify)?;
+
+    drop(phase);
 
     // Compile the project first
     compile_project(&project_path_abs, &config)?;
+    let fallback = config.wasm2js_fallback.then(|| wasm2js::fallback_config(&config));
+    if let Some(fallback) = &fallback {
+        tracing::info!("Building the asm.js fallback {}.js with -sWASM=0", fallback.output_name);
+        compile_project(&project_path_abs, fallback)?;
+    }
+    lockfile::finish(&config, &project_path_abs).map_err(Error::Verify)?;
+
+    let phase = config.enter_phase(Phase::PostProcess);
+    if !config.no_wasm_validation {
+        wasm_validation::validate_output(&config).map_err(|e| Error::Compilation(e.into()))?;
+    }
+    if config.provenance {
+        provenance::embed_provenance(&config, &project_path_abs).map_err(Error::PostProcessing)?;
+    }
+    if config.strip_names {
+        strip_names::strip_names(&config).map_err(Error::PostProcessing)?;
+    }
+    module_format::post_process_glue(&config).map_err(Error::PostProcessing)?;
+    if let Some(fallback) = &fallback {
+        module_format::post_process_glue(fallback).map_err(Error::PostProcessing)?;
+    }
+
+    let component = if config.component {
+        Some(component::build_component(&config).map_err(|e| Error::Compilation(e.into()))?)
+    } else {
+        None
+    };
+
+    let mut generated = Vec::new();
+    let assets = if config.hash_filenames && config.single_file {
+        strict::fallback(&config, "--hash-filenames has no effect with --single-file; keeping original file names")
+            .map_err(Error::Config)?;
+        output_assets::AssetNames::unhashed(&config)
+    } else if config.hash_filenames {
+        let assets = output_assets::hash_output_filenames(&config).map_err(Error::PostProcessing)?;
+        generated.push(config.output_dir.join(output_assets::ASSET_MANIFEST_FILE_NAME));
+        assets
+    } else {
+        output_assets::AssetNames::unhashed(&config)
+    };
+
+    build_manifest::write_build_manifest(&config, &assets).map_err(Error::PostProcessing)?;
+
+    if config.typescript {
+        generated.extend(typescript_gen::generate_typescript_api(&config, &assets, &project_path_abs).map_err(Error::PostProcessing)?);
+    }
 
     // Generate webapp if it's a GUI application
-    webapp_generator::create_webapp(&config)?;
+    drop(phase);
+    let phase = config.enter_phase(Phase::Webapp);
+    #[cfg(feature = "webapp")]
+    generated.extend(webapp_generator::create_webapp(&config, &assets)?);
+    #[cfg(not(feature = "webapp"))]
+    if config.webapp == app_config::WebappMode::Always {
+        strict::fallback(&config, "wasm_compiler was built without the `webapp` feature; no webapp is generated")
+            .map_err(Error::Config)?;
+    }
+
+    if config.deploy_layout == Some(DeployLayout::GhPages) {
+        generated.extend(deploy::apply_gh_pages_layout(&config).map_err(Error::PostProcessing)?);
+    }
+
+    drop(phase);
 
-    log::info!(
+    if config.run_test.is_some() {
+        let _phase = config.enter_phase(Phase::Test);
+        smoke_test::run_smoke_test(&config, &assets)
+            .map_err(|e| Error::Test(format!("Smoke test failed: {}", e)))?;
+    }
+
+    if config.ctest {
+        let _phase = config.enter_phase(Phase::Test);
+        run_ctest(&project_path_abs, &config)?;
+    }
+
+    if let Some(runtime) = runtime {
+        let _phase = config.enter_phase(Phase::Run);
+        wasm_runtime::run_module(&config, runtime, &assets)?;
+    }
+
+    tracing::info!(
         "Compilation process finished. Output should be in {:?} (check for {}.js and {}.wasm)",
         config.output_dir, config.output_name, config.output_name
     );
 
+    let output_path = |name: &str| config.output_dir.join(name);
+    let existing = |path: Option<PathBuf>| {
+        path.filter(|path| path.exists()).map(Artifact::from_path).transpose().map_err(Error::PostProcessing)
+    };
+    let mut artifacts = BuildArtifacts {
+        js: Artifact::from_path(output_path(&assets.js)).map_err(Error::PostProcessing)?,
+        wasm: existing(Some(output_path(&assets.wasm)))?,
+        data: existing(assets.data.as_deref().map(output_path))?,
+        map: existing(Some(output_path(&format!("{}.wasm.map", config.output_name))))?,
+        component: existing(component)?,
+        auxiliary: assets.auxiliary.iter()
+            .map(|name| Artifact::from_path(output_path(name)))
+            .collect::<Result<_, _>>()
+            .map_err(Error::PostProcessing)?,
+        build_manifest: output_path(build_manifest::BUILD_MANIFEST_FILE_NAME),
+        generated,
+        output_dir: config.output_dir.clone(),
+        checksums: None,
+        signature: None,
+        archive: None,
+    };
+    if config.checksums || signing_key.is_some() {
+        let checksums = signing::write_checksums(&artifacts).map_err(Error::PostProcessing)?;
+        if let Some(key) = &signing_key {
+            artifacts.signature = Some(signing::sign_file(&checksums, key).map_err(Error::PostProcessing)?);
+        }
+        artifacts.checksums = Some(checksums);
+    }
+    if let Some(archive) = &config.archive_output {
+        artifacts.archive = Some(output_archive::write_archive(&config.output_dir, &artifacts.files(), archive).map_err(Error::PostProcessing)?);
+    }
+    let produced = [&artifacts.wasm, &artifacts.data, &artifacts.map, &artifacts.component];
+    config.events.artifact(&artifacts.js);
+    for artifact in produced.into_iter().flatten().chain(&artifacts.auxiliary) {
+        config.events.artifact(artifact);
+    }
+    Ok(artifacts)
+}
+
+/// Reports deprecated or misspelled user emcc flags, correcting them when `--fix-flags` is set.
+/// With `--strict` they fail the build unless corrected.
+fn check_emcc_flags(config: &mut AppConfig) -> Result<(), Error> {
+    let lint = flag_lint::lint_flags(&config.emcc_flag);
+    if lint.warnings.is_empty() {
+        return Ok(());
+    }
+
+    for warning in &lint.warnings {
+        tracing::warn!("emcc flag: {}", warning);
+    }
+    if config.fix_flags {
+        tracing::info!("Using corrected emcc flags: {}", shell_words::join(&lint.fixed_flags));
+        config.emcc_flag = lint.fixed_flags;
+    } else if config.strict {
+        return Err(Error::Config(format!(
+            "{} problem(s) with the emcc flags (failing because of --strict); run with --fix-flags to correct them",
+            lint.warnings.len()
+        )));
+    } else {
+        tracing::warn!("Run with --fix-flags to correct these automatically");
+    }
     Ok(())
 }
 
+/// Runs the CTest tests of the project (`--test`) and fails if any of them fails.
+fn run_ctest(project_path_abs: &std::path::Path, config: &AppConfig) -> Result<(), Error> {
+    let results = compiler::ctest::run_tests(project_path_abs, config)?;
+    let failed: Vec<&str> = results.iter().filter(|result| !result.passed).map(|result| result.name.as_str()).collect();
+    tracing::info!("CTest: {} of {} tests passed", results.len() - failed.len(), results.len());
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Test(format!("{} of {} CTest tests failed: {}", failed.len(), results.len(), failed.join(", "))))
+    }
+}
+
 fn compile_project(project_path_abs: &std::path::Path, config: &AppConfig) -> Result<(), Error> {
+    // Submodules may hold the build files or libraries detection looks for
+    prefetch::init_submodules(project_path_abs, config)?;
+
     // 1. Detect build system
-    if CMakeHandler::detect(&project_path_abs) {
-        log::info!("CMake project detected.");
-        let cmake_handler = CMakeHandler::new();
-        cmake_handler.compile(&project_path_abs, &config).map_err(Error::Compilation)?;
-    } else if MakeHandler::detect(&project_path_abs) {
-        log::info!("Makefile project detected.");
-        let make_handler = MakeHandler::new();
-        make_handler.compile(&project_path_abs, &config).map_err(Error::Compilation)?;
+    let build_system = ambiguity::choose_build_system(config, project_path_abs)?;
+    if build_system.is_some() && !compiler::webidl_binder::find_idl_files(project_path_abs).is_empty() {
+        strict::fallback(config, "WebIDL bindings are only generated for projects without CMake or Make; run webidl_binder.py from your build instead.")
+            .map_err(Error::Config)?;
+    }
+
+    if let Some(handler) = build_system {
+        let _span = tracing::info_span!("build_system", handler = handler.name()).entered();
+        tracing::info!("{} project detected.", handler.name());
+        let _phase = (!handler.reports_phases()).then(|| config.enter_phase(Phase::Build));
+        handler.compile(project_path_abs, config)?;
     } else {
-        log::warn!("No CMakeLists.txt or Makefile found. Attempting to find a C++ source file to compile directly.");
-
-        let mut cpp_file_to_compile: Option<std::path::PathBuf> = None;
-        for entry in walkdir::WalkDir::new(&project_path_abs).max_depth(1).into_iter().filter_map(|e| e.ok()) {
-            if entry.file_type().is_file() {
-                if let Some(ext) = entry.path().extension() {
-                    if ext == "cpp" || ext == "cxx" || ext == "cc" {
-                        if entry.file_name().to_string_lossy().contains("main") {
-                            cpp_file_to_compile = Some(entry.path().to_path_buf());
-                            break;
-                        }
-                        if cpp_file_to_compile.is_none() {
-                             cpp_file_to_compile = Some(entry.path().to_path_buf());
-                        }
-                    }
+        let _phase = config.enter_phase(Phase::Build);
+        let selection = SourceSelection::from_config(config).map_err(Error::Config)?;
+        if selection.is_explicit() {
+            let sources = selection.collect(project_path_abs);
+            if sources.is_empty() {
+                return Err(DetectionError::NoMatchingSources {
+                    project: project_path_abs.to_path_buf(),
+                    patterns: config.sources.clone(),
                 }
+                .into());
             }
+            tracing::info!("Compiling {} source file(s) selected with --source.", sources.len());
+            EmscriptenRunner::new().compile_files(&sources, config)?;
+            tracing::info!("Direct compilation successful.");
+            return Ok(());
+        }
+
+        if config.recursive {
+            return compile_discovered_sources(project_path_abs, config, &selection);
         }
 
-        if let Some(source_file) = cpp_file_to_compile {
-            log::info!("Found source file: {:?}. Attempting direct Emscripten compilation.", source_file);
+        strict::fallback(
+            config,
+            "No CMakeLists.txt or Makefile found; compiling a C++ source file from the project root directly (select the sources with --source)",
+        )
+        .map_err(Error::Config)?;
+
+        if let Some(source_file) = root_main_file(project_path_abs, config, &selection)? {
+            tracing::info!("Found source file: {:?}. Attempting direct Emscripten compilation.", source_file);
             let em_runner = EmscriptenRunner::new();
             // Pass the whole config to compile_file
-            em_runner.compile_file(&source_file, &config)
-                .map_err(Error::Compilation)?;
-            log::info!("Direct compilation successful.");
+            em_runner.compile_file(&source_file, config)?;
+            tracing::info!("Direct compilation successful.");
         } else {
-             return Err(Error::Detection(
-                "No CMakeLists.txt, Makefile, or obvious C++ source file found in the project root.".to_string()
-            ));
+            tracing::info!("No C++ source file in the project root, searching subdirectories.");
+            return compile_discovered_sources(project_path_abs, config, &selection);
+        }
+    }
+
+    tracing::info!("Compilation completed successfully");
+    Ok(())
+}
+
+/// The C++ file in the project root a build without CMake, Make, `--source` or `--recursive`
+/// compiles: among those with `main` in their name, or all of them if none has, the one
+/// [`ambiguity::choose`] picks
+fn root_main_file(
+    project_path_abs: &std::path::Path,
+    config: &AppConfig,
+    selection: &SourceSelection,
+) -> Result<Option<std::path::PathBuf>, Error> {
+    let mut cpp_files: Vec<std::path::PathBuf> = utils::source_selection::project_files(project_path_abs, Some(1))
+        .into_iter()
+        .filter(|path| !selection.is_excluded(project_path_abs, path))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "cpp" || ext == "cxx" || ext == "cc"))
+        .collect();
+    let file_name = |path: &std::path::PathBuf| path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    if cpp_files.iter().any(|path| file_name(path).contains("main")) {
+        cpp_files.retain(|path| file_name(path).contains("main"));
+    }
+    let names: Vec<String> = cpp_files.iter().map(file_name).collect();
+    Ok(ambiguity::choose(config, project_path_abs, "main files", &names)?.map(|index| cpp_files[index].clone()))
+}
+
+/// The sources a direct build links into the module: those selected with `--source`, the main
+/// file in the project root (see [`root_main_file`]), or those found by recursive discovery
+fn linked_sources(project_path_abs: &std::path::Path, config: &AppConfig) -> Result<Vec<std::path::PathBuf>, Error> {
+    let selection = SourceSelection::from_config(config).map_err(Error::Config)?;
+    if selection.is_explicit() {
+        return Ok(selection.collect(project_path_abs));
+    }
+    if !config.recursive {
+        if let Some(main_file) = root_main_file(project_path_abs, config, &selection)? {
+            return Ok(vec![main_file]);
+        }
+    }
+    Ok(selection.discover_recursive(project_path_abs, &config.output_dir))
+}
+
+/// Switches to a library build (`--no-entry`) when no project source defines main(), and
+/// prepares the exports for it.
+fn configure_entry_point(project_path_abs: &std::path::Path, config: &mut AppConfig) -> Result<(), Error> {
+    if !config.no_entry {
+        let sources = project_sources(project_path_abs, config)?;
+        let has_main = sources.iter().any(|source| {
+            std::fs::read_to_string(source).is_ok_and(|content| exports::defines_main(&content))
+        });
+        if sources.is_empty() || has_main {
+            return Ok(());
+        }
+        tracing::info!("No main() found in the project sources; building a library module (--no-entry).");
+        config.no_entry = true;
+    }
+
+    exports::remove_main_export(&mut config.emcc_flag);
+    let has_exports = !exports::parse_exported_functions(&config.emcc_flag).is_empty()
+        || config.emcc_flag.iter().any(|flag| flag == "-sEXPORT_ALL=1" || flag == "-sEXPORT_ALL")
+        || !exports::scan_embind_functions(project_path_abs).is_empty();
+    if !has_exports {
+        tracing::warn!(
+            "The library build has no EXPORTED_FUNCTIONS or embind bindings; only functions marked \
+             EMSCRIPTEN_KEEPALIVE will be callable from JavaScript."
+        );
+    }
+    Ok(())
+}
+
+/// Builds a single `-sEXPORTED_FUNCTIONS` flag from the `--export` names, or, without them in a
+/// direct build, from the functions the linked sources mark with `EMSCRIPTEN_KEEPALIVE` or
+/// `extern "C"`. Names from user-provided `-sEXPORTED_FUNCTIONS` flags are kept.
+///
+/// CMake and Make projects only get the `--export` names: the flag applies to every executable
+/// they link, and one that does not link the file defining a discovered function would fail with
+/// an undefined exported symbol.
+fn configure_exports(project_path_abs: &std::path::Path, config: &mut AppConfig) -> Result<(), Error> {
+    let is_export_flag = |flag: &String| flag.starts_with("-sEXPORTED_FUNCTIONS=");
+    if config.emcc_flag.iter().any(|flag| flag.starts_with("-sEXPORTED_FUNCTIONS=@")) {
+        tracing::info!("EXPORTED_FUNCTIONS is read from a response file; not generating the export list");
+        return Ok(());
+    }
+
+    let discovered = if !config.exports.is_empty() {
+        config.exports.iter().map(|name| name.trim_start_matches('_').to_string()).collect()
+    } else if config.handlers.detect_build_systems(project_path_abs).is_empty() {
+        exports::scan_c_exports(&linked_sources(project_path_abs, config)?)
+    } else {
+        tracing::debug!("Not generating the export list for a CMake or Make project; use --export to export functions");
+        Vec::new()
+    };
+    if discovered.is_empty() {
+        return Ok(());
+    }
+
+    let mut names = exports::parse_exported_functions(&config.emcc_flag);
+    if !config.no_entry && !names.iter().any(|name| name == "main") {
+        names.insert(0, "main".to_string());
+    }
+    for name in discovered {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    let flag = format!(
+        "-sEXPORTED_FUNCTIONS={}",
+        names.iter().map(|name| format!("_{}", name)).collect::<Vec<_>>().join(",")
+    );
+    tracing::info!("Exporting functions: {}", flag);
+    config.emcc_flag.retain(|flag| !is_export_flag(flag));
+    config.emcc_flag.push(flag);
+    Ok(())
+}
+
+/// Links embind (`-lembind`) when the project uses it, and drops user flags that break embind:
+/// `-sDYNAMIC_EXECUTION=0` (embind generates its invokers with `new Function`) and Closure
+/// Compiler (which renames the properties embind looks up by name).
+fn configure_embind(project_path_abs: &std::path::Path, config: &mut AppConfig) {
+    if !exports::uses_embind(project_path_abs) {
+        return;
+    }
+
+    let mut flags = std::mem::take(&mut config.emcc_flag).into_iter().peekable();
+    while let Some(flag) = flags.next() {
+        if flag == "-sDYNAMIC_EXECUTION=0" {
+            tracing::warn!("Removing {} since it is incompatible with embind", flag);
+            continue;
         }
+        if flag == "--closure" || flag.starts_with("--closure=") {
+            // Its value is attached ("--closure=1") or a separate argument ("--closure 1")
+            let attached = flag.strip_prefix("--closure=").map(str::to_string);
+            let value = match &attached {
+                Some(value) => value.clone(),
+                None => flags.next_if(|value| !value.starts_with('-')).unwrap_or_default(),
+            };
+            // --closure 0 turns Closure Compiler off, which embind is fine with
+            if value == "0" {
+                config.emcc_flag.push(flag);
+                if attached.is_none() {
+                    config.emcc_flag.push(value);
+                }
+            } else {
+                tracing::warn!("Removing --closure {} since it is incompatible with embind", value);
+            }
+            continue;
+        }
+        config.emcc_flag.push(flag);
+    }
+
+    if !config.emcc_flag.iter().any(|flag| flag == "-lembind" || flag == "--bind") {
+        tracing::info!("embind usage detected, linking with -lembind");
+        config.emcc_flag.push("-lembind".to_string());
+    }
+}
+
+/// Decides the SDL version every handler builds with (see [`sdl_version`]), and rejects an
+/// emcc flag asking for another one than `--sdl-version`.
+fn configure_sdl_version(project_path_abs: &std::path::Path, config: &mut AppConfig) -> Result<(), Error> {
+    if let Some(version) = config.sdl_version {
+        return match sdl_version::from_flags(&config.emcc_flag) {
+            Some((flagged, flag)) if flagged != version => Err(Error::Config(format!(
+                "--sdl-version {} conflicts with the emcc flag {}",
+                version.major(),
+                flag
+            ))),
+            _ => Ok(()),
+        };
+    }
+    // Kept in the config, so the handlers do not scan the project again
+    let (version, reason) = sdl_version::detect(config, project_path_abs)
+        .map_err(Error::Config)?
+        .unwrap_or((sdl_version::DEFAULT, "the project shows no SDL version".to_string()));
+    tracing::info!("Building with SDL{} ({})", version.major(), reason);
+    config.sdl_version = Some(version);
+    Ok(())
+}
+
+/// The sources that make up the project: the `--source` selection if given, otherwise all
+/// sources found by recursive discovery.
+fn project_sources(project_path_abs: &std::path::Path, config: &AppConfig) -> Result<Vec<std::path::PathBuf>, Error> {
+    let selection = SourceSelection::from_config(config).map_err(Error::Config)?;
+    Ok(if selection.is_explicit() {
+        selection.collect(project_path_abs)
+    } else {
+        selection.discover_recursive(project_path_abs, &config.output_dir)
+    })
+}
+
+/// Compiles and links all sources found by recursive discovery into one module.
+fn compile_discovered_sources(
+    project_path_abs: &std::path::Path,
+    config: &AppConfig,
+    selection: &SourceSelection,
+) -> Result<(), Error> {
+    let sources = selection.discover_recursive(project_path_abs, &config.output_dir);
+    if sources.is_empty() {
+        return Err(DetectionError::NoSources.into());
     }
 
-    log::info!("Compilation completed successfully");
+    tracing::info!("Found {} source file(s): {:?}", sources.len(), sources);
+    EmscriptenRunner::new().compile_files(&sources, config)?;
+    tracing::info!("Direct compilation successful.");
     Ok(())
 }
@@ -0,0 +1,261 @@
+//! `wasm_compiler.lock` in the project root: the Emscripten version, the versions of the
+//! Emscripten ports the build links, and the dependencies the tool downloaded for it (the
+//! fetched ImGui release, single-header libraries, prefetched FetchContent repositories) with
+//! hashes of their contents. Every build writes it; with `--locked` the build instead fails
+//! when the toolchain or a dependency differs from the lockfile, or when there is none, so an
+//! artifact shipped long ago can be rebuilt from the same inputs. Emscripten and its ports are
+//! checked before compiling, the dependencies once the build has fetched them.
+//!
+//! The builds of a target matrix link different ports, so ports are recorded per target, and
+//! a build only replaces the ports of its own target. Builds do not run in containers, so the
+//! lockfile records no container image.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+use crate::app_config::AppConfig;
+use crate::compiler::emcc_flags::EmccFlags;
+use crate::utils::command_runner::{resolve_emscripten_tool, run_command};
+use crate::utils::file_system;
+
+/// File name of the lockfile in the project root
+pub const LOCKFILE_NAME: &str = "wasm_compiler.lock";
+
+const HEADER: &str = "# Toolchain and dependency versions of the last build, written by wasm_compiler.\n# Builds with --locked fail when they differ.\n\n";
+
+/// `USE_*` settings of emcc that are not ports
+const NON_PORT_SETTINGS: &[&str] = &["pthreads", "webgpu", "glfw", "webgl2", "es6_import_meta", "offset_converter", "closure_compiler", "asan", "lsan"];
+
+/// The contents of `wasm_compiler.lock`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// First line of `emcc --version`
+    pub emscripten: String,
+    #[serde(default, rename = "port", skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<LockedPort>,
+    #[serde(default, rename = "dependency", skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<LockedDependency>,
+}
+
+/// An Emscripten port, with the release and archive hash its port file pins
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedPort {
+    pub name: String,
+    /// The target of the matrix build that uses it (`--target`), if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// A dependency the tool downloaded for the build
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedDependency {
+    pub name: String,
+    pub version: String,
+    /// Where it was downloaded from
+    pub source: String,
+    /// Hash of the paths and contents of its files
+    pub sha256: String,
+}
+
+/// Dependencies recorded during the builds of each project
+static RECORDED: OnceLock<Mutex<BTreeMap<PathBuf, Vec<LockedDependency>>>> = OnceLock::new();
+
+/// Records that the build of `project_path` uses the downloaded copy of `name` in `dir`
+pub fn record(project_path: &Path, name: &str, version: &str, source: &str, dir: &Path) {
+    let sha256 = match hash_dir(dir) {
+        Ok(sha256) => sha256,
+        Err(e) => {
+            tracing::warn!("Cannot record {} in {}: {}", name, LOCKFILE_NAME, e);
+            return;
+        }
+    };
+    let dependency = LockedDependency { name: name.to_string(), version: version.to_string(), source: source.to_string(), sha256 };
+    let mut recorded = RECORDED.get_or_init(Default::default).lock().expect("lockfile lock");
+    let dependencies = recorded.entry(project_path.to_path_buf()).or_default();
+    dependencies.retain(|known| known.name != dependency.name);
+    dependencies.push(dependency);
+}
+
+/// With `--locked`, checks Emscripten and the ports against the lockfile of `project_path`
+pub fn check_toolchain(config: &AppConfig, project_path: &Path) -> Result<(), String> {
+    if !config.locked {
+        return Ok(());
+    }
+    let locked = read(project_path)?;
+    let current = toolchain(config)?;
+    let mut differences = Vec::new();
+    if current.emscripten != locked.emscripten {
+        differences.push(format!("Emscripten is {} instead of {}", current.emscripten, locked.emscripten));
+    }
+    for port in &current.ports {
+        match locked.ports.iter().find(|locked| locked.name == port.name && locked.target == port.target) {
+            Some(locked) if locked == port => {}
+            Some(locked) => differences.push(format!("port {} is {} instead of {}", port.name, describe_port(port), describe_port(locked))),
+            None => differences.push(format!("port {} is not in the lockfile", port.name)),
+        }
+    }
+    fail_on(differences)
+}
+
+/// Writes the lockfile of the finished build of `project_path`, or, with `--locked`, checks
+/// the dependencies it downloaded against it
+pub fn finish(config: &AppConfig, project_path: &Path) -> Result<(), String> {
+    let mut dependencies = RECORDED
+        .get_or_init(Default::default)
+        .lock()
+        .expect("lockfile lock")
+        .remove(project_path)
+        .unwrap_or_default();
+    dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if config.locked {
+        let locked = read(project_path)?;
+        let differences = dependencies
+            .iter()
+            .filter_map(|dependency| match locked.dependencies.iter().find(|locked| locked.name == dependency.name) {
+                Some(locked) if locked == dependency => None,
+                Some(locked) if locked.version != dependency.version => {
+                    Some(format!("{} is {} instead of {}", dependency.name, dependency.version, locked.version))
+                }
+                Some(_) => Some(format!("the contents of {} {} changed", dependency.name, dependency.version)),
+                None => Some(format!("{} is not in the lockfile", dependency.name)),
+            })
+            .collect();
+        return fail_on(differences);
+    }
+
+    if config.read_only_source {
+        tracing::debug!("Not writing {} into the read-only project", LOCKFILE_NAME);
+        return Ok(());
+    }
+    // The ports of the other targets of a matrix stay
+    let current = toolchain(config)?;
+    let mut ports: Vec<LockedPort> = read(project_path)
+        .map(|previous| previous.ports)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|port| port.target != config.matrix_target)
+        .chain(current.ports)
+        .collect();
+    ports.sort_by(|a, b| (&a.target, &a.name).cmp(&(&b.target, &b.name)));
+    let lockfile = Lockfile { emscripten: current.emscripten, ports, dependencies };
+    let path = project_path.join(LOCKFILE_NAME);
+    let content = format!("{}{}", HEADER, toml::to_string(&lockfile).map_err(|e| format!("Failed to serialize {}: {}", LOCKFILE_NAME, e))?);
+    if std::fs::read_to_string(&path).ok().as_deref() != Some(content.as_str()) {
+        tracing::info!("Writing {:?}", path);
+        if let Err(e) = std::fs::write(&path, content) {
+            tracing::warn!("Failed to write {:?}: {}", path, e);
+        }
+    }
+    Ok(())
+}
+
+fn read(project_path: &Path) -> Result<Lockfile, String> {
+    let path = project_path.join(LOCKFILE_NAME);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("--locked needs {:?}, written by a build without --locked: {}", path, e))?;
+    toml::from_str(&content).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+}
+
+fn fail_on(differences: Vec<String>) -> Result<(), String> {
+    if differences.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "The build differs from {} (failing because of --locked): {}; build without --locked to update it",
+        LOCKFILE_NAME,
+        differences.join("; ")
+    ))
+}
+
+/// The Emscripten version and the ports of the build
+fn toolchain(config: &AppConfig) -> Result<Lockfile, String> {
+    let output = run_command(&resolve_emscripten_tool("emcc"), &["--version"], None).map_err(|e| e.to_string())?;
+    let emscripten = String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or_default().trim().to_string();
+    // The port files are in `tools/ports/` next to emcc; a symlinked emcc is followed there
+    let ports_dir = crate::toolchain::locate(config, "emcc", &config.tool_env())
+        .and_then(|emcc| emcc.canonicalize().ok())
+        .and_then(|emcc| Some(emcc.parent()?.join("tools").join("ports")));
+    let mut ports: Vec<LockedPort> = ports(&EmccFlags::from_config(config).all())
+        .into_iter()
+        .map(|name| {
+            let port_file = ports_dir.as_ref().map(|dir| dir.join(format!("{}.py", name.replace('.', "/"))));
+            let content = port_file.and_then(|file| std::fs::read_to_string(file).ok()).unwrap_or_default();
+            LockedPort { tag: assignment(&content, "TAG"), hash: assignment(&content, "HASH"), target: config.matrix_target.clone(), name }
+        })
+        .collect();
+    ports.sort_by(|a, b| a.name.cmp(&b.name));
+    ports.dedup_by(|a, b| a.name == b.name);
+    Ok(Lockfile { emscripten, ports, dependencies: Vec::new() })
+}
+
+/// The names of the ports `flags` use (`-sUSE_SDL=2`, `--use-port=sdl2_image:formats=png`),
+/// as their port files are named
+fn ports(flags: &[String]) -> Vec<String> {
+    let mut ports = Vec::new();
+    for (index, flag) in flags.iter().enumerate() {
+        let port = if let Some(port) = flag.strip_prefix("--use-port=") {
+            port.to_string()
+        } else if flag == "--use-port" {
+            let Some(port) = flags.get(index + 1) else { continue };
+            port.clone()
+        } else if let Some(setting) = flag.strip_prefix("-sUSE_").or_else(|| flag.strip_prefix("-s USE_")) {
+            let (name, value) = setting.split_once('=').unwrap_or((setting, "1"));
+            if value == "0" {
+                continue;
+            }
+            let name = name.to_lowercase();
+            match name.strip_prefix("sdl") {
+                Some("") => format!("sdl{}", value),
+                // SDL_image and the other SDL libraries of SDL2 are `sdl2_image`, ...
+                Some(library) => format!("sdl{}{}", if value == "3" { "3" } else { "2" }, library),
+                None if NON_PORT_SETTINGS.contains(&name.as_str()) => continue,
+                None => name,
+            }
+        } else {
+            continue;
+        };
+        // Options after `:`; local port files are not versioned by Emscripten
+        let name = port.split(':').next().unwrap_or_default();
+        if !name.is_empty() && !name.ends_with(".py") {
+            ports.push(name.to_string());
+        }
+    }
+    ports
+}
+
+/// The string value of `NAME = '...'` in a port file
+fn assignment(content: &str, name: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == name).then(|| value.trim().trim_matches(|c| c == '\'' || c == '"').to_string())
+    })
+}
+
+fn describe_port(port: &LockedPort) -> String {
+    match (&port.tag, &port.hash) {
+        (Some(tag), _) => tag.clone(),
+        (None, Some(hash)) => hash.clone(),
+        (None, None) => "unversioned".to_string(),
+    }
+}
+
+/// Hash of the relative paths and contents of the files below `dir`, without `.git`
+fn hash_dir(dir: &Path) -> Result<String, String> {
+    let mut lines = Vec::new();
+    let entries = WalkDir::new(dir).sort_by_file_name().into_iter().filter_entry(|entry| entry.file_name() != ".git");
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {:?}: {}", dir, e))?;
+        if entry.file_type().is_file() {
+            let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+            lines.push(format!("{} {}", relative.to_string_lossy().replace('\\', "/"), file_system::hash_file(entry.path())?));
+        }
+    }
+    Ok(file_system::hex_digest(lines.join("\n").as_bytes()))
+}
@@ -1,21 +1,30 @@
-use std::process::Command;
 
-use wasm_compiler::Error;
+use wasm_compiler::{diagnostics, hints, Error};
 
 fn main() {
     // Initialize logger globally, if not already done by the library
     // env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
     if let Err(e) = wasm_compiler::run() {
-        log::error!("Application error: {}", e);
+        tracing::error!("Application error: {}", e);
+        let exit_code = e.exit_code();
+        diagnostics::emit_json_error(e.code(), exit_code, &e.to_string());
         match e {
             Error::Io(io_err) => eprintln!("Error: A file system I/O error occurred: {}", io_err),
             Error::Config(msg) => eprintln!("Error: Configuration issue: {}", msg),
-            Error::Detection(msg) => eprintln!("Error: Build system detection failed: {}", msg),
-            Error::Compilation(msg) => eprintln!("Error: Compilation process failed: {}", msg),
+            Error::Detection(err) => eprintln!("Error: Build system detection failed: {}", err),
+            Error::Configure(err) => eprintln!("Error: {}", hints::with_suggestions(err.to_string())),
+            Error::Compilation(err) => eprintln!("Error: Compilation process failed: {}", hints::with_suggestions(err.to_string())),
             Error::Command(msg) => eprintln!("Error: External command execution failed: {}", msg),
             Error::FileSystem(msg) => eprintln!("Error: File system operation failed: {}", msg),
+            Error::PostProcessing(msg) => eprintln!("Error: Processing the build outputs failed: {}", msg),
+            Error::Cancelled => eprintln!("Error: Build cancelled"),
+            Error::Test(msg) => eprintln!("Error: {}", msg),
+            // The program's exit status is passed on, as if it had been run directly
+            Error::Run { .. } => eprintln!("Error: {}", e),
+            Error::Verify(msg) => eprintln!("Error: Verification failed: {}", msg),
+            Error::Batch { failed, total } => eprintln!("Error: {} of {} projects failed to build", failed, total),
         }
-        std::process::exit(1);
+        std::process::exit(exit_code);
     }
 }
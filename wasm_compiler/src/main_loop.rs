@@ -0,0 +1,193 @@
+//! Detection of blocking render loops (`--main-loop`), the most common reason a ported desktop
+//! application freezes the browser tab: a `while (!done) { poll events; draw; swap }` loop never
+//! returns to the browser's event loop, so nothing is ever drawn and input never arrives.
+//!
+//! The sources are searched for loops that poll events or present frames (SDL, GLFW, EGL,
+//! raylib) outside of code excluded for Emscripten with `#ifndef __EMSCRIPTEN__` or the `#else`
+//! of `#ifdef __EMSCRIPTEN__` (or raylib's `PLATFORM_WEB`). Loops draining the event queue
+//! (`while (SDL_PollEvent(&event))`) end by themselves and are not reported. If such a loop
+//! waits with `SDL_Delay` or `emscripten_sleep`, building with `-sASYNCIFY` turns the wait into
+//! a yield to the browser, and `--main-loop auto` adds the flag. Otherwise a warning points at
+//! the loop with the two ways to fix it.
+
+use std::path::{Path, PathBuf};
+use crate::app_config::{AppConfig, MainLoopMode};
+use crate::diagnostics::{self, Diagnostic, Severity};
+use crate::exports::strip_comments;
+use crate::utils::preprocessor::{lines_with_branches, Branch};
+
+/// Calls that show a loop polls events or presents frames, i.e. is a render loop
+const RENDER_CALLS: &[&str] = &[
+    "SDL_PollEvent",
+    "SDL_WaitEvent",
+    "SDL_GL_SwapWindow",
+    "SDL_RenderPresent",
+    "SDL_UpdateWindowSurface",
+    "glfwPollEvents",
+    "glfwWaitEvents",
+    "glfwSwapBuffers",
+    "glfwWindowShouldClose",
+    "eglSwapBuffers",
+    "WindowShouldClose",
+    "EndDrawing",
+];
+
+/// Calls that yield to the browser when the module is built with ASYNCIFY
+const YIELD_CALLS: &[&str] = &["emscripten_sleep", "SDL_Delay"];
+
+/// Loop conditions that end once the pending events are handled
+const DRAIN_CALLS: &[&str] = &["SDL_PollEvent"];
+
+/// A render loop that does not return to the browser
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockingLoop {
+    pub file: PathBuf,
+    /// 1-based line of the `while`, `for` or `do`
+    pub line: u32,
+    /// The call in the loop that yields under ASYNCIFY, if any
+    pub yield_call: Option<&'static str>,
+}
+
+/// Checks the project for blocking render loops and, depending on `--main-loop`, enables
+/// ASYNCIFY or warns.
+pub fn check_main_loop(config: &mut AppConfig, sources: &[PathBuf]) {
+    if config.main_loop == MainLoopMode::Off || config.no_entry {
+        return;
+    }
+    let target_env = config.target_env.to_lowercase();
+    if target_env == "node" || target_env == "wasi" {
+        // Without a page there is nothing to freeze
+        return;
+    }
+    let Some(blocking) = sources.iter().find_map(|source| find_blocking_loop(source)) else {
+        return;
+    };
+    let asyncify = config.emcc_flag.iter().any(|flag| {
+        (flag == "-sASYNCIFY" || flag.starts_with("-sASYNCIFY=") || flag == "-sJSPI" || flag.starts_with("-sJSPI="))
+            && !flag.ends_with("=0")
+    });
+
+    let location = format!("{}:{}", blocking.file.display(), blocking.line);
+    match blocking.yield_call {
+        Some(_) if asyncify => {
+            tracing::debug!("Render loop at {} yields to the browser through ASYNCIFY", location);
+        }
+        Some(call) if config.main_loop == MainLoopMode::Auto => {
+            tracing::info!(
+                "The render loop at {} blocks the browser but waits with {}; building with -sASYNCIFY so it yields (--main-loop warn to leave the flags alone)",
+                location,
+                call
+            );
+            config.emcc_flag.push("-sASYNCIFY".to_string());
+        }
+        Some(call) => warn(&blocking, format!(
+            "this render loop never returns to the browser, so the page will freeze. It waits with {}, which yields to the browser when the module is built with -sASYNCIFY (added automatically with --main-loop auto)",
+            call
+        )),
+        None => warn(&blocking, "this render loop never returns to the browser, so the page will freeze. \
+            Run one iteration per frame with emscripten_set_main_loop() (guarded by #ifdef __EMSCRIPTEN__), \
+            or call emscripten_sleep(0) at the end of each iteration and build with -sASYNCIFY \
+            (added automatically with --main-loop auto)".to_string()),
+    }
+}
+
+fn warn(blocking: &BlockingLoop, message: String) {
+    let diagnostic = Diagnostic::new(Some(blocking.file.display().to_string()), Some(blocking.line), None, Severity::Warning, message);
+    let diagnostics = [diagnostic];
+    if !diagnostics::emit_json(&diagnostics, "wasm_compiler") {
+        tracing::warn!("{}", diagnostics::render_human(&diagnostics));
+    }
+}
+
+/// The first render loop in `source` that is compiled for Emscripten
+pub fn find_blocking_loop(source: &Path) -> Option<BlockingLoop> {
+    let content = std::fs::read_to_string(source).ok()?;
+    let code = emscripten_code(&strip_comments(&content));
+    let bytes = code.as_bytes();
+
+    let mut index = 0;
+    while let Some(found) = next_loop_keyword(&code, index) {
+        let (keyword_start, keyword) = found;
+        let after_keyword = keyword_start + keyword.len();
+        let Some((head_end, body)) = loop_extent(&code, keyword, after_keyword) else {
+            index = after_keyword;
+            continue;
+        };
+        let head = &code[after_keyword..head_end];
+        let body_text = &code[body];
+        let drains_events = DRAIN_CALLS.iter().any(|call| head.contains(call));
+        if !drains_events && RENDER_CALLS.iter().any(|call| head.contains(call) || body_text.contains(call)) {
+            let line = bytes[..keyword_start].iter().filter(|&&byte| byte == b'\n').count() as u32 + 1;
+            let yield_call = YIELD_CALLS.iter().copied().find(|call| body_text.contains(call));
+            return Some(BlockingLoop { file: source.to_path_buf(), line, yield_call });
+        }
+        // Loops nested in this one are searched as well
+        index = after_keyword;
+    }
+    None
+}
+
+/// The next `while`, `for` or `do` keyword at or after `from`
+fn next_loop_keyword(code: &str, from: usize) -> Option<(usize, &'static str)> {
+    let is_identifier = |byte: u8| byte.is_ascii_alphanumeric() || byte == b'_';
+    let bytes = code.as_bytes();
+    (from..bytes.len()).find_map(|index| {
+        if index > 0 && is_identifier(bytes[index - 1]) {
+            return None;
+        }
+        ["while", "for", "do"].into_iter().find_map(|keyword| {
+            let end = index + keyword.len();
+            (bytes[index..].starts_with(keyword.as_bytes()) && bytes.get(end).is_none_or(|&byte| !is_identifier(byte)))
+                .then_some((index, keyword))
+        })
+    })
+}
+
+/// The end of the loop head (the parenthesized condition) and the byte range of the body.
+/// `None` for the `while (...);` that ends a `do` loop, empty loops and unparsable code.
+fn loop_extent(code: &str, keyword: &str, after_keyword: usize) -> Option<(usize, std::ops::Range<usize>)> {
+    let head_end = if keyword == "do" {
+        after_keyword
+    } else {
+        let open = after_keyword + code[after_keyword..].find(|c: char| !c.is_whitespace())?;
+        if code.as_bytes()[open] != b'(' {
+            return None;
+        }
+        matching(code, open, b'(', b')')? + 1
+    };
+    let body_start = head_end + code[head_end..].find(|c: char| !c.is_whitespace())?;
+    match code.as_bytes()[body_start] {
+        // The `while (...);` closing a do loop, or an empty loop
+        b';' => None,
+        b'{' => Some((head_end, body_start..matching(code, body_start, b'{', b'}')? + 1)),
+        _ => Some((head_end, body_start..body_start + code[body_start..].find(';')? + 1)),
+    }
+}
+
+/// The index of the bracket closing the one at `open`
+fn matching(code: &str, open: usize, opening: u8, closing: u8) -> Option<usize> {
+    let mut depth = 0usize;
+    for (index, &byte) in code.as_bytes().iter().enumerate().skip(open) {
+        if byte == opening {
+            depth += 1;
+        } else if byte == closing {
+            depth -= 1;
+            if depth == 0 {
+                return Some(index);
+            }
+        }
+    }
+    None
+}
+
+/// `code` with the lines excluded for the browser blanked, keeping line numbers
+fn emscripten_code(code: &str) -> String {
+    lines_with_branches(code)
+        .into_iter()
+        .map(|(line, branch)| match branch {
+            Branch::Excluded if line.ends_with('\n') => "\n",
+            Branch::Excluded => "",
+            _ => line,
+        })
+        .collect()
+}
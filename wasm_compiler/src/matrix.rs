@@ -0,0 +1,115 @@
+//! Building every target of the `[targets]` matrix declared in the project config (e.g.
+//! web + node, debug + release) in one invocation. Each target is built into
+//! `<output-dir>/<name>/`, and a merged `build-manifest.json` listing all targets is written
+//! to the output directory.
+
+use std::path::PathBuf;
+use serde_json::{Map, Value};
+use crate::app_config::{AppConfig, CompileOptions};
+use crate::build_manifest::BUILD_MANIFEST_FILE_NAME;
+use crate::project_config::TargetConfig;
+use crate::{compile, BuildArtifacts, Error};
+
+/// The outputs of one matrix target
+#[derive(Debug, Clone)]
+pub struct TargetArtifacts {
+    pub name: String,
+    pub artifacts: BuildArtifacts,
+}
+
+/// Builds the targets of the project config one after another (all of them, or those
+/// selected with `--target`). Fails at the first target that fails, and when the project
+/// config declares no targets.
+pub fn compile_matrix(options: CompileOptions) -> Result<Vec<TargetArtifacts>, Error> {
+    let mut config = options.build();
+    config.load_project_config().map_err(Error::Config)?;
+    let targets = selected_targets(&config)?;
+
+    let mut built = Vec::with_capacity(targets.len());
+    for (name, target) in targets {
+        tracing::info!("Building target '{}'", name);
+        let target_config = target_config(&config, &name, &target);
+        let artifacts = compile(CompileOptions::from(target_config))?;
+        built.push(TargetArtifacts { name, artifacts });
+    }
+
+    let manifest = write_merged_manifest(&config, &built).map_err(Error::PostProcessing)?;
+    // One archive of the outputs of all targets
+    if let Some(archive) = &config.archive_output {
+        let files: Vec<PathBuf> = built.iter().flat_map(|target| target.artifacts.files()).chain([manifest]).collect();
+        crate::output_archive::write_archive(&config.output_dir, &files, archive).map_err(Error::PostProcessing)?;
+    }
+    Ok(built)
+}
+
+fn selected_targets(config: &AppConfig) -> Result<Vec<(String, TargetConfig)>, Error> {
+    let declared = &config.project_config.targets;
+    if declared.is_empty() {
+        return Err(Error::Config("The project config declares no [targets]".to_string()));
+    }
+    if let Some(unknown) = config.targets.iter().find(|name| !declared.contains_key(*name)) {
+        return Err(Error::Config(format!(
+            "Unknown target '{}'; the project config declares: {}",
+            unknown,
+            declared.keys().cloned().collect::<Vec<_>>().join(", ")
+        )));
+    }
+    Ok(declared
+        .iter()
+        .filter(|(name, _)| config.targets.is_empty() || config.targets.contains(name))
+        .map(|(name, target)| (name.clone(), target.clone()))
+        .collect())
+}
+
+/// The configuration of one target: the command-line options with the target's settings
+/// applied, writing to `<output-dir>/<name>/`.
+fn target_config(config: &AppConfig, name: &str, target: &TargetConfig) -> AppConfig {
+    let mut target_config = config.clone();
+    if let Some(build_config) = &target.build_config {
+        target_config.build_config = build_config.clone();
+    }
+    if let Some(target_env) = &target.target_env {
+        target_config.target_env = target_env.clone();
+    }
+    target_config.emcc_flag.extend(target.emcc_flags.iter().cloned());
+    target_config.defines.extend(target.defines.iter().cloned());
+    target_config.output_dir = config.output_dir.join(name);
+    target_config.matrix_target = Some(name.to_string());
+    target_config.archive_output = None;
+    target_config
+}
+
+/// Writes `<output-dir>/build-manifest.json` with the manifest of every target under
+/// `targets.<name>`, its file names made relative to the output directory.
+pub(crate) fn write_merged_manifest(config: &AppConfig, built: &[TargetArtifacts]) -> Result<PathBuf, String> {
+    let mut targets = Map::new();
+    for target in built {
+        let path = &target.artifacts.build_manifest;
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read build manifest {:?}: {}", path, e))?;
+        let mut manifest: Value = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse build manifest {:?}: {}", path, e))?;
+        if let Some(Value::Object(files)) = manifest.get_mut("files") {
+            for file in files.values_mut() {
+                let file_names = match file {
+                    Value::Array(file_names) => file_names.iter_mut().collect(),
+                    file => vec![file],
+                };
+                for file_name in file_names {
+                    if let Value::String(file_name) = file_name {
+                        *file_name = format!("{}/{}", target.name, file_name);
+                    }
+                }
+            }
+        }
+        targets.insert(target.name.clone(), manifest);
+    }
+
+    let content = serde_json::to_string_pretty(&serde_json::json!({ "targets": targets }))
+        .map_err(|e| format!("Failed to serialize build manifest: {}", e))?;
+    let path = config.output_dir.join(BUILD_MANIFEST_FILE_NAME);
+    std::fs::write(&path, content)
+        .map_err(|e| format!("Failed to write build manifest {:?}: {}", path, e))?;
+    tracing::info!("Wrote merged build manifest to {:?}", path);
+    Ok(path)
+}
@@ -0,0 +1,71 @@
+//! Post-processing of the JS glue for the CommonJS and UMD module formats.
+//!
+//! emcc's classic (non-ES6) MODULARIZE output declares the factory as a top-level variable and
+//! ends with a CommonJS/AMD export of it. For `cjs` the export is made explicit (including
+//! `default`, so `import` from Node also works); for `umd` the glue is wrapped in the standard
+//! UMD pattern so it also defines a global when loaded with a plain `<script>` tag.
+
+use crate::app_config::{AppConfig, ModuleFormat};
+
+/// Name of the factory for CommonJS and UMD builds, unless overridden with `-sEXPORT_NAME`
+pub const DEFAULT_EXPORT_NAME: &str = "createModule";
+
+/// Name of the factory the glue defines: the last `-sEXPORT_NAME` among the user flags, or
+/// the default.
+pub fn export_name(config: &AppConfig) -> String {
+    config
+        .emcc_flag
+        .iter()
+        .rev()
+        .find_map(|flag| flag.strip_prefix("-sEXPORT_NAME="))
+        .map(|name| name.trim_matches(|c| c == '\'' || c == '"').to_string())
+        .unwrap_or_else(|| DEFAULT_EXPORT_NAME.to_string())
+}
+
+/// Rewrites the JS glue in the output directory for the configured module format.
+pub fn post_process_glue(config: &AppConfig) -> Result<(), String> {
+    if config.module_format == ModuleFormat::Esm {
+        return Ok(());
+    }
+
+    let glue_path = config.output_dir.join(format!("{}.js", config.output_name));
+    let glue = std::fs::read_to_string(&glue_path)
+        .map_err(|e| format!("Failed to read JS output {:?}: {}", glue_path, e))?;
+    let name = export_name(config);
+
+    let processed = match config.module_format {
+        ModuleFormat::Esm => return Ok(()),
+        ModuleFormat::Cjs => format!(
+            "{glue}\n// Added by wasm_compiler (--module-format cjs)\n\
+             if (typeof module === 'object') {{\n\
+             \x20   module.exports = {name};\n\
+             \x20   module.exports.default = {name};\n\
+             }}\n"
+        ),
+        // The glue runs with `module`, `exports` and `define` shadowed so its own CommonJS/AMD
+        // export does not fire; the wrapper performs the export instead.
+        ModuleFormat::Umd => format!(
+            "// UMD wrapper added by wasm_compiler (--module-format umd)\n\
+             (function (root, factory) {{\n\
+             \x20   if (typeof define === 'function' && define.amd) {{\n\
+             \x20       define([], factory);\n\
+             \x20   }} else if (typeof module === 'object' && module.exports) {{\n\
+             \x20       module.exports = factory();\n\
+             \x20       module.exports.default = module.exports;\n\
+             \x20   }} else {{\n\
+             \x20       root.{name} = factory();\n\
+             \x20   }}\n\
+             }}(typeof self !== 'undefined' ? self : this, function () {{\n\
+             return (function (module, exports, define) {{\n\
+             {glue}\n\
+             return {name};\n\
+             }})();\n\
+             }}));\n"
+        ),
+    };
+
+    std::fs::write(&glue_path, processed)
+        .map_err(|e| format!("Failed to write JS output {:?}: {}", glue_path, e))?;
+    tracing::info!("Converted {:?} to a {:?} module", glue_path, config.module_format);
+    Ok(())
+}
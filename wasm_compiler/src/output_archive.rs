@@ -0,0 +1,136 @@
+//! `--archive-output`: the files the build wrote to the output directory (see
+//! [`BuildArtifacts::files`](crate::BuildArtifacts::files)) packed into a single `.zip` or
+//! `.tar.gz` file, to attach to a release or upload to a CDN. Other files in the directory,
+//! like the outputs of earlier builds, are left out. The archive is reproducible: the files are
+//! stored in the order of their paths, with fixed timestamps (`SOURCE_DATE_EPOCH` when set),
+//! modes and owners, so the same build outputs give a byte-identical archive.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// The kinds of archive, by file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// The format of the archive `path`, from its extension
+    pub fn of(path: &Path) -> Result<Self, String> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Ok(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(ArchiveFormat::TarGz)
+        } else {
+            Err(format!("Unknown archive format of {:?}; --archive-output writes .zip, .tar.gz and .tgz files", path))
+        }
+    }
+}
+
+/// Packs `files`, the outputs of a build in `output_dir`, into `archive` under their paths
+/// relative to `output_dir`, and returns its path.
+pub fn write_archive(output_dir: &Path, files: &[PathBuf], archive: &Path) -> Result<PathBuf, String> {
+    let format = ArchiveFormat::of(archive)?;
+    let mut files: Vec<(String, PathBuf)> = files
+        .iter()
+        .map(|path| (path.strip_prefix(output_dir).unwrap_or(path).to_string_lossy().replace('\\', "/"), path.clone()))
+        .collect();
+    // Paths in byte order, whatever order the build wrote them in
+    files.sort();
+    files.dedup();
+
+    if let Some(parent) = archive.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    // Written next to the archive and moved in place, so a failed build leaves no half archive
+    let partial = archive.with_extension("partial");
+    let file = File::create(&partial).map_err(|e| format!("Failed to create {:?}: {}", partial, e))?;
+    let written = match format {
+        ArchiveFormat::Zip => write_zip(file, &files),
+        ArchiveFormat::TarGz => write_tar_gz(file, &files),
+    }
+    .and_then(|_| std::fs::rename(&partial, archive).map_err(|e| format!("Failed to move {:?} to {:?}: {}", partial, archive, e)));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(format!("Failed to write {:?}: {}", archive, e));
+    }
+    tracing::info!("Packed {} files of {:?} into {:?}", files.len(), output_dir, archive);
+    Ok(archive.to_path_buf())
+}
+
+/// Seconds since the epoch the files are dated: `SOURCE_DATE_EPOCH`, or 1980-01-01, the
+/// earliest date a zip file can hold
+fn timestamp() -> u64 {
+    const ZIP_EPOCH: u64 = 315_532_800;
+    std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|epoch| epoch.trim().parse().ok()).unwrap_or(ZIP_EPOCH).max(ZIP_EPOCH)
+}
+
+fn write_zip(file: File, files: &[(String, PathBuf)]) -> Result<(), String> {
+    use zip::write::SimpleFileOptions;
+
+    let seconds = timestamp();
+    let (year, month, day) = civil_date((seconds / 86_400) as i64);
+    let time_of_day = seconds % 86_400;
+    let modified = zip::DateTime::from_date_and_time(
+        year as u16,
+        month as u8,
+        day as u8,
+        (time_of_day / 3600) as u8,
+        (time_of_day / 60 % 60) as u8,
+        (time_of_day % 60) as u8,
+    )
+    .unwrap_or_default();
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(modified)
+        .unix_permissions(0o644);
+
+    let mut zip = zip::ZipWriter::new(BufWriter::new(file));
+    for (name, path) in files {
+        zip.start_file(name.as_str(), options).map_err(|e| e.to_string())?;
+        let mut source = File::open(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        std::io::copy(&mut source, &mut zip).map_err(|e| format!("Failed to pack {:?}: {}", path, e))?;
+    }
+    zip.finish().map_err(|e| e.to_string())?.flush().map_err(|e| e.to_string())
+}
+
+fn write_tar_gz(file: File, files: &[(String, PathBuf)]) -> Result<(), String> {
+    // No file name or time in the gzip header
+    let gzip = flate2::GzBuilder::new().mtime(0).write(BufWriter::new(file), flate2::Compression::best());
+    let mut tar = tar::Builder::new(gzip);
+    let seconds = timestamp();
+    for (name, path) in files {
+        let source = File::open(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        let size = source.metadata().map_err(|e| format!("Failed to read {:?}: {}", path, e))?.len();
+        let mut header = tar::Header::new_ustar();
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_mtime(seconds);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_entry_type(tar::EntryType::Regular);
+        tar.append_data(&mut header, name, source).map_err(|e| format!("Failed to pack {:?}: {}", path, e))?;
+    }
+    tar.into_inner()
+        .and_then(|gzip| gzip.finish())
+        .and_then(|mut writer| writer.flush())
+        .map_err(|e| e.to_string())
+}
+
+/// The year, month and day of the day `days` after 1970-01-01
+fn civil_date(days: i64) -> (i64, i64, i64) {
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
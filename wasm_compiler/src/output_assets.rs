@@ -0,0 +1,114 @@
+//! Names of the files produced by the build and the optional content-hashing step that
+//! renames them (`output.js` -> `output.1a2b3c4d.js`) so CDNs and browsers never serve a
+//! stale module after an update.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use crate::app_config::AppConfig;
+use crate::compiler::emscripten_runner::AUXILIARY_OUTPUT_SUFFIXES;
+use crate::utils::file_system;
+
+/// File name of the asset manifest written next to the hashed outputs
+pub const ASSET_MANIFEST_FILE_NAME: &str = "asset-manifest.json";
+
+/// Number of hex digits of the SHA-256 digest used in hashed file names
+const HASH_LENGTH: usize = 8;
+
+/// File names (relative to the output directory) of the build outputs that generated
+/// pages and loaders refer to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetNames {
+    /// Emscripten JS glue (ES6 module)
+    pub js: String,
+    /// WebAssembly binary
+    pub wasm: String,
+    /// Preloaded file package, if the build produced one
+    pub data: Option<String>,
+    /// Worker scripts, memory initializers and other files emcc wrote next to the glue (see
+    /// [`AUXILIARY_OUTPUT_SUFFIXES`]); they keep their names when the outputs are hashed
+    pub auxiliary: Vec<String>,
+}
+
+impl AssetNames {
+    /// The names emcc produces for `config.output_name`, before any renaming.
+    pub fn unhashed(config: &AppConfig) -> Self {
+        let data = format!("{}.data", config.output_name);
+        AssetNames {
+            js: format!("{}.js", config.output_name),
+            wasm: format!("{}.wasm", config.output_name),
+            data: config.output_dir.join(&data).exists().then_some(data),
+            auxiliary: AUXILIARY_OUTPUT_SUFFIXES
+                .iter()
+                .map(|suffix| format!("{}.{}", config.output_name, suffix))
+                .filter(|name| config.output_dir.join(name).exists())
+                .collect(),
+        }
+    }
+}
+
+/// Renames the build outputs in `config.output_dir` to content-hashed names, rewrites the
+/// references to the wasm/data files inside the JS glue, and writes an asset manifest that
+/// maps the original names to the hashed ones.
+///
+/// The JS glue is hashed last, after its references have been rewritten, so its hash also
+/// changes whenever any file it loads changes.
+pub fn hash_output_filenames(config: &AppConfig) -> Result<AssetNames, String> {
+    let original = AssetNames::unhashed(config);
+    let output_dir = config.output_dir.as_path();
+    let mut manifest = BTreeMap::new();
+
+    let js_path = output_dir.join(&original.js);
+    let mut glue = std::fs::read_to_string(&js_path)
+        .map_err(|e| format!("Failed to read JS output {:?}: {}", js_path, e))?;
+
+    let mut hashed = original.clone();
+    let mut rename_referenced = |name: &str, glue: &mut String| -> Result<Option<String>, String> {
+        let path = output_dir.join(name);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let new_name = hashed_name(name, &file_system::hash_file(&path)?);
+        rename(output_dir, name, &new_name)?;
+        for quote in ['"', '\''] {
+            *glue = glue.replace(&format!("{q}{}{q}", name, q = quote), &format!("{q}{}{q}", new_name, q = quote));
+        }
+        manifest.insert(name.to_string(), new_name.clone());
+        Ok(Some(new_name))
+    };
+
+    if let Some(new_name) = rename_referenced(&original.wasm, &mut glue)? {
+        hashed.wasm = new_name;
+    }
+    if let Some(data) = &original.data {
+        hashed.data = rename_referenced(data, &mut glue)?;
+    }
+
+    std::fs::write(&js_path, &glue)
+        .map_err(|e| format!("Failed to rewrite JS output {:?}: {}", js_path, e))?;
+    hashed.js = hashed_name(&original.js, &file_system::hex_digest(glue.as_bytes()));
+    rename(output_dir, &original.js, &hashed.js)?;
+    manifest.insert(original.js.clone(), hashed.js.clone());
+
+    let manifest_path = output_dir.join(ASSET_MANIFEST_FILE_NAME);
+    let manifest_json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize asset manifest: {}", e))?;
+    std::fs::write(&manifest_path, manifest_json)
+        .map_err(|e| format!("Failed to write asset manifest {:?}: {}", manifest_path, e))?;
+
+    tracing::info!("Content-hashed output files: {:?}", manifest);
+    Ok(hashed)
+}
+
+/// Inserts the (shortened) hash before the extension: `output.js` -> `output.1a2b3c4d.js`
+fn hashed_name(name: &str, hash: &str) -> String {
+    let short_hash = &hash[..HASH_LENGTH.min(hash.len())];
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}.{}.{}", stem, short_hash, ext),
+        None => format!("{}.{}", name, short_hash),
+    }
+}
+
+fn rename(dir: &Path, from: &str, to: &str) -> Result<(), String> {
+    std::fs::rename(dir.join(from), dir.join(to))
+        .map_err(|e| format!("Failed to rename {:?} to {:?}: {}", from, to, e))
+}
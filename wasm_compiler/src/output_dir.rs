@@ -0,0 +1,68 @@
+//! Protects existing directories from being overwritten by a build. An output directory is
+//! only written to when it is empty or was produced by an earlier build (it contains a
+//! `build-manifest.json`, or the marker file left by a build that failed before writing
+//! one), unless `--force` is given. `--clean-output` removes the files of the previous build
+//! first, so renamed or hashed outputs do not pile up.
+
+use std::fs;
+use std::path::Path;
+use crate::app_config::AppConfig;
+use crate::build_manifest::BUILD_MANIFEST_FILE_NAME;
+
+/// Hidden file marking a directory as written by `wasm_compiler`
+pub const OUTPUT_MARKER_FILE_NAME: &str = ".wasm_compiler-output";
+
+/// Checks that the build may write to the output directory, cleans it when requested, and
+/// creates it.
+pub fn prepare_output_dir(config: &AppConfig, project_path_abs: &Path) -> Result<(), String> {
+    let output_dir = &config.output_dir;
+    let entries = visible_entries(output_dir)?;
+    if !entries.is_empty() && !is_build_output(output_dir) && !config.force {
+        return Err(format!(
+            "Output directory {:?} is not empty and does not contain a previous build ({} is missing); \
+             use --force to write into it anyway",
+            output_dir, BUILD_MANIFEST_FILE_NAME
+        ));
+    }
+
+    if config.clean_output && !entries.is_empty() {
+        let output_dir_abs = output_dir.canonicalize()
+            .map_err(|e| format!("Failed to resolve output directory {:?}: {}", output_dir, e))?;
+        if project_path_abs.starts_with(&output_dir_abs) {
+            return Err(format!(
+                "Refusing to clean output directory {:?} because it contains the project",
+                output_dir
+            ));
+        }
+        for entry in &entries {
+            let result = if entry.is_dir() { fs::remove_dir_all(entry) } else { fs::remove_file(entry) };
+            result.map_err(|e| format!("Failed to remove {:?}: {}", entry, e))?;
+        }
+        tracing::info!("Removed {} entries from output directory {:?}", entries.len(), output_dir);
+    }
+
+    crate::utils::file_system::ensure_dir_exists(output_dir)?;
+    let marker = output_dir.join(OUTPUT_MARKER_FILE_NAME);
+    fs::write(&marker, "").map_err(|e| format!("Failed to write {:?}: {}", marker, e))
+}
+
+/// Whether `dir` was written by a build: it has a `build-manifest.json`, or the marker file
+/// left by a build that failed before writing one
+pub fn is_build_output(dir: &Path) -> bool {
+    dir.join(BUILD_MANIFEST_FILE_NAME).is_file() || dir.join(OUTPUT_MARKER_FILE_NAME).is_file()
+}
+
+/// Entries of `dir` other than hidden ones such as `.git`, which are neither counted nor
+/// cleaned. A missing directory has no entries.
+fn visible_entries(dir: &Path) -> Result<Vec<std::path::PathBuf>, String> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read output directory {:?}: {}", dir, e)),
+    };
+    Ok(read_dir
+        .filter_map(Result::ok)
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| entry.path())
+        .collect())
+}
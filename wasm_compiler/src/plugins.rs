@@ -0,0 +1,155 @@
+//! Declarative build-system plugins read from TOML files, for in-house build systems that
+//! have no compiled-in handler. A plugin says how to recognize a project, which commands
+//! build it, and where the outputs end up:
+//!
+//! ```toml
+//! name = "bazel"
+//!
+//! [detect]
+//! files = ["WORKSPACE", "*.bazel"]
+//!
+//! [[commands]]
+//! program = "bazel"
+//! args = ["build", "//:app", "--compilation_mode={build_config}"]
+//!
+//! [artifacts]
+//! js = "bazel-bin/app.js"
+//! wasm = "bazel-bin/app.wasm"
+//! ```
+//!
+//! Plugins are given with `--plugin` or listed under `plugins` in `wasm_compiler.toml`, and
+//! are consulted before the CMake and Make handlers.
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use crate::app_config::AppConfig;
+use crate::compiler::BuildSystemHandler;
+use crate::compiler::error::CompileError;
+use crate::utils::command_runner::run_build_command;
+use crate::utils::flag_set::FlagSet;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PluginDefinition {
+    pub name: String,
+    pub detect: DetectRules,
+    /// Run in order; the build fails at the first command that fails
+    pub commands: Vec<PluginCommand>,
+    pub artifacts: PluginArtifacts,
+}
+
+/// A project is detected when any of its top-level entries matches one of `files`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DetectRules {
+    /// File names or globs, relative to the project root
+    pub files: Vec<String>,
+}
+
+/// A command of the build. `{project_path}`, `{output_dir}`, `{output_name}`,
+/// `{build_config}` and `{emcc_flags}` in the arguments are replaced with the build settings.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PluginCommand {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Working directory relative to the project root (default: the project root)
+    pub cwd: Option<PathBuf>,
+}
+
+/// Outputs of the build relative to the project root, copied to the output directory as
+/// `<output-name>.js` / `.wasm` / `.data`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PluginArtifacts {
+    pub js: PathBuf,
+    pub wasm: Option<PathBuf>,
+    pub data: Option<PathBuf>,
+}
+
+/// A build-system handler backed by a [`PluginDefinition`].
+pub struct PluginHandler {
+    definition: PluginDefinition,
+    detect: GlobSet,
+}
+
+impl PluginHandler {
+    /// Reads and validates a plugin file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read plugin {:?}: {}", path, e))?;
+        let definition: PluginDefinition = toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse plugin {:?}: {}", path, e))?;
+        Self::new(definition).map_err(|e| format!("Invalid plugin {:?}: {}", path, e))
+    }
+
+    pub fn new(definition: PluginDefinition) -> Result<Self, String> {
+        if definition.commands.is_empty() {
+            return Err("a plugin needs at least one command".to_string());
+        }
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &definition.detect.files {
+            builder.add(Glob::new(pattern).map_err(|e| format!("invalid detect pattern {:?}: {}", pattern, e))?);
+        }
+        let detect = builder.build().map_err(|e| e.to_string())?;
+        Ok(PluginHandler { definition, detect })
+    }
+
+    fn expand(&self, arg: &str, project_path: &Path, config: &AppConfig) -> String {
+        arg.replace("{project_path}", &project_path.to_string_lossy())
+            .replace("{output_dir}", &config.output_dir.to_string_lossy())
+            .replace("{output_name}", &config.output_name)
+            .replace("{build_config}", &config.build_config)
+            .replace("{emcc_flags}", &FlagSet::from(config.emcc_flag.clone()).to_shell_string())
+    }
+}
+
+impl BuildSystemHandler for PluginHandler {
+    fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    fn detect(&self, project_path: &Path) -> bool {
+        std::fs::read_dir(project_path)
+            .map(|entries| entries.filter_map(Result::ok).any(|entry| self.detect.is_match(entry.file_name())))
+            .unwrap_or(false)
+    }
+
+    fn compile(&self, project_path: &Path, config: &AppConfig) -> Result<(), CompileError> {
+        tracing::info!("Building with plugin '{}'", self.definition.name);
+        for command in &self.definition.commands {
+            let args: Vec<String> = command.args.iter().map(|arg| self.expand(arg, project_path, config)).collect();
+            let cwd = command.cwd.as_ref().map_or_else(|| project_path.to_path_buf(), |dir| project_path.join(dir));
+            run_build_command(&command.program, &args, Some(&cwd), config)?;
+        }
+
+        let artifacts = &self.definition.artifacts;
+        let outputs = [(Some(&artifacts.js), "js"), (artifacts.wasm.as_ref(), "wasm"), (artifacts.data.as_ref(), "data")];
+        for (source, extension) in outputs {
+            let Some(source) = source else { continue };
+            let source = project_path.join(source);
+            if !source.exists() {
+                return Err(CompileError::MissingOutput(source));
+            }
+            let dest = config.output_dir.join(format!("{}.{}", config.output_name, extension));
+            std::fs::copy(&source, &dest)
+                .map_err(|e| format!("Failed to copy {:?} to {:?}: {}", source, dest, e))?;
+            tracing::info!("Copied {:?} to {:?}", source, dest);
+        }
+        Ok(())
+    }
+}
+
+/// Registers the plugins listed in the project config and given with `--plugin` with the
+/// handlers of `config`. Plugins registered later take precedence.
+pub fn register_plugins(config: &mut AppConfig) -> Result<(), String> {
+    let paths: Vec<PathBuf> = config.project_config.plugins.iter().chain(&config.plugins).cloned().collect();
+    for path in paths {
+        let plugin = PluginHandler::load(&path)?;
+        tracing::debug!("Loaded plugin '{}' from {:?}", plugin.definition.name, path);
+        config.handlers.register_build_system(plugin);
+    }
+    Ok(())
+}
@@ -0,0 +1,169 @@
+//! Dependencies a project pulls in before it can be configured: git submodules and the git
+//! repositories CMake's FetchContent downloads. Submodules that are not checked out are the
+//! most common reason a cloned project does not build, so they are checked out with
+//! `git submodule update --init --recursive` before the build system is detected: on a
+//! terminal after asking, with `--init-submodules` without asking, and otherwise the missing
+//! ones are reported. With `--prefetch-content`, each `FetchContent_Declare` with a
+//! `GIT_REPOSITORY` is cloned once into `fetchcontent/` in the cache directory and handed to
+//! CMake as `FETCHCONTENT_SOURCE_DIR_<NAME>`, so later configures (and other projects using
+//! the same dependency) need no network access. `URL` dependencies are left to CMake.
+
+use std::path::{Path, PathBuf};
+use crate::app_config::AppConfig;
+use crate::compiler::error::CompileError;
+use crate::utils::cmake_script;
+use crate::utils::command_runner::run_build_command;
+use crate::utils::file_system;
+use crate::utils::source_selection::project_files;
+
+/// A `FetchContent_Declare` of a git repository
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitContent {
+    pub name: String,
+    pub repository: String,
+    /// `GIT_TAG`: a branch, tag or commit; the default branch when not given
+    pub tag: Option<String>,
+}
+
+/// Checks out the submodules of `project_path` that are not checked out, if allowed
+pub fn init_submodules(project_path: &Path, config: &AppConfig) -> Result<(), CompileError> {
+    let missing = missing_submodules(project_path);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let listed = missing.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ");
+    if !project_path.join(".git").exists() {
+        tracing::warn!("The submodules {} are not checked out, and the project is not a git checkout to fetch them in; the build may fail on their missing files", listed);
+        return Ok(());
+    }
+
+    if config.read_only_source {
+        tracing::warn!("The submodules {} are not checked out, and the project is read-only; the build may fail on their missing files", listed);
+        return Ok(());
+    }
+
+    let question = format!("The submodules {} are not checked out. Run git submodule update --init --recursive?", listed);
+    let init = config.init_submodules || crate::ambiguity::confirm(config, &question).unwrap_or(false);
+    if !init {
+        tracing::warn!("The submodules {} are not checked out; --init-submodules checks them out before building", listed);
+        return Ok(());
+    }
+    tracing::info!("Checking out the submodules {}", listed);
+    let args = ["submodule", "update", "--init", "--recursive"];
+    run_build_command("git", &args, Some(project_path), config)?;
+    Ok(())
+}
+
+/// The paths in `.gitmodules` whose directory is missing or empty
+fn missing_submodules(project_path: &Path) -> Vec<PathBuf> {
+    let Ok(content) = std::fs::read_to_string(project_path.join(".gitmodules")) else { return Vec::new() };
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "path").then(|| PathBuf::from(value.trim()))
+        })
+        .filter(|path| {
+            std::fs::read_dir(project_path.join(path)).map_or(true, |mut entries| entries.next().is_none())
+        })
+        .collect()
+}
+
+/// The CMake arguments that point FetchContent at the cached clones of the git dependencies
+/// of `project_path`, cloning those not cached yet. Empty without `--prefetch-content`.
+pub fn fetch_content_args(project_path: &Path, config: &AppConfig) -> Result<Vec<String>, CompileError> {
+    let declared = git_contents(project_path);
+    if declared.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !config.prefetch_content {
+        tracing::debug!("CMake downloads {} FetchContent dependencies; --prefetch-content caches them", declared.len());
+        return Ok(Vec::new());
+    }
+    let cache = file_system::cache_dir()
+        .ok_or_else(|| CompileError::Other("There is no cache directory to prefetch the FetchContent dependencies into".to_string()))?;
+
+    let mut args = Vec::new();
+    for content in declared {
+        let key = file_system::hex_digest(format!("{}\n{}", content.repository, content.tag.as_deref().unwrap_or_default()).as_bytes());
+        let dir = cache.join("fetchcontent").join(format!("{}-{}", content.name.to_lowercase(), &key[..16]));
+        if !dir.is_dir() {
+            if let Err(e) = clone(&content, &dir, config) {
+                crate::strict::fallback(config, format!("Could not prefetch {} ({}); CMake downloads it: {}", content.name, content.repository, e))?;
+                continue;
+            }
+        } else {
+            tracing::debug!("Using the cached {} in {:?}", content.name, dir);
+        }
+        crate::lockfile::record(project_path, &content.name, content.tag.as_deref().unwrap_or("HEAD"), &content.repository, &dir);
+        args.push(format!("-DFETCHCONTENT_SOURCE_DIR_{}={}", content.name.to_uppercase(), dir.to_string_lossy()));
+    }
+    Ok(args)
+}
+
+/// The git dependencies declared in the CMake files of `project_path`
+pub fn git_contents(project_path: &Path) -> Vec<GitContent> {
+    let mut contents: Vec<GitContent> = Vec::new();
+    let cmake_files = project_files(project_path, None).into_iter().filter(|path| {
+        path.file_name().is_some_and(|name| name == "CMakeLists.txt") || path.extension().is_some_and(|extension| extension == "cmake")
+    });
+    for file in cmake_files {
+        let Ok(content) = std::fs::read_to_string(&file) else { continue };
+        for arguments in command_arguments(&content, "FetchContent_Declare") {
+            let Some(declared) = git_content(&arguments) else { continue };
+            if !contents.iter().any(|known| known.name.eq_ignore_ascii_case(&declared.name)) {
+                contents.push(declared);
+            }
+        }
+    }
+    contents
+}
+
+/// The `GitContent` of the arguments of a `FetchContent_Declare`, unless it downloads a URL or
+/// depends on variables
+fn git_content(arguments: &[String]) -> Option<GitContent> {
+    let (name, options) = arguments.split_first()?;
+    let value = |keyword: &str| options.iter().position(|option| option == keyword).and_then(|index| options.get(index + 1));
+    let repository = value("GIT_REPOSITORY")?;
+    let tag = value("GIT_TAG");
+    if [Some(name), Some(repository), tag].into_iter().flatten().any(|value| value.contains("${")) {
+        return None;
+    }
+    Some(GitContent { name: name.clone(), repository: repository.clone(), tag: tag.cloned() })
+}
+
+/// The arguments of every call of the CMake command `name` (commands are case-insensitive)
+fn command_arguments(content: &str, name: &str) -> Vec<Vec<String>> {
+    cmake_script::invocations(content)
+        .into_iter()
+        .filter(|invocation| invocation.name.eq_ignore_ascii_case(name))
+        .map(|invocation| invocation.arguments)
+        .collect()
+}
+
+/// Clones `content` into `dir`, through a partial directory so an interrupted clone is not
+/// taken for a cached one
+pub(crate) fn clone(content: &GitContent, dir: &Path, config: &AppConfig) -> Result<(), CompileError> {
+    tracing::info!("Cloning {} from {} into {:?}", content.name, content.repository, dir);
+    file_system::create_dir_atomically(dir, |partial| clone_into(content, partial, config))
+}
+
+fn clone_into(content: &GitContent, dir: &Path, config: &AppConfig) -> Result<(), CompileError> {
+    let dir_arg = dir.to_string_lossy().into_owned();
+    let mut args: Vec<String> = ["-c", "advice.detachedHead=false", "clone", "--recurse-submodules"].map(String::from).to_vec();
+    // A commit cannot be cloned shallowly by name; it is checked out after a full clone
+    let commit = content.tag.as_deref().filter(|tag| tag.len() >= 7 && tag.chars().all(|c| c.is_ascii_hexdigit()));
+    if commit.is_none() {
+        args.extend(["--depth".to_string(), "1".to_string(), "--shallow-submodules".to_string()]);
+        if let Some(tag) = &content.tag {
+            args.extend(["--branch".to_string(), tag.clone()]);
+        }
+    }
+    args.extend([content.repository.clone(), dir_arg.clone()]);
+    run_build_command("git", &args, None, config)?;
+    if let Some(commit) = commit {
+        run_build_command("git", &["-c", "advice.detachedHead=false", "-C", &dir_arg, "checkout", commit], None, config)?;
+        run_build_command("git", &["-C", &dir_arg, "submodule", "update", "--init", "--recursive"], None, config)?;
+    }
+    Ok(())
+}
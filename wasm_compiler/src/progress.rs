@@ -0,0 +1,167 @@
+//! Progress display of the command-line tool. On a terminal, a progress bar shows the
+//! current phase and what the build tools are doing: the command that is running, each
+//! step of a CMake build with its count or percentage, and wasm-opt when emcc reports
+//! running it. Log lines are printed above the bar. Elsewhere, e.g. in CI logs, the build
+//! steps are logged instead. `--quiet` turns both off.
+
+use std::io::IsTerminal;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use crate::events::{BuildProgress, EventSink, Events, Phase};
+
+/// The bars of the command-line tool, for [`suspend`]
+static BARS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Runs `f` with the progress bar hidden, e.g. to ask a question on the terminal.
+pub(crate) fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    match BARS.get() {
+        Some(bars) => bars.suspend(f),
+        None => f(),
+    }
+}
+
+/// The event sink of the command-line tool, and the logger that keeps log lines from
+/// tearing its progress bar
+pub(crate) struct Progress {
+    /// `None` when stderr is not a terminal
+    bars: Option<MultiProgress>,
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl Progress {
+    /// Initializes the logger (at `warn` with `--quiet`, otherwise `info`, unless `RUST_LOG`
+    /// says otherwise) and returns the events for the build.
+    pub(crate) fn init(quiet: bool) -> (Events, Option<Arc<Progress>>) {
+        let env = env_logger::Env::default().default_filter_or(if quiet { "warn" } else { "info" });
+        if quiet {
+            env_logger::Builder::from_env(env).try_init().ok();
+            return (Events::default(), None);
+        }
+
+        let bars = std::io::stderr().is_terminal().then(MultiProgress::new);
+        if let Some(bars) = &bars {
+            BARS.set(bars.clone()).ok();
+        }
+        let logger = env_logger::Builder::from_env(env).build();
+        let level = logger.filter();
+        if log::set_boxed_logger(Box::new(SuspendingLogger { logger, bars: bars.clone() })).is_ok() {
+            log::set_max_level(level);
+        }
+        let progress = Arc::new(Progress { bars, bar: Mutex::new(None) });
+        (Events::new(progress.clone()), Some(progress))
+    }
+
+    /// Removes the bar at the end of the build.
+    pub(crate) fn finish(&self) {
+        if let Some(bar) = self.bar.lock().expect("progress bar lock").take() {
+            bar.finish_and_clear();
+        }
+    }
+
+    fn update(&self, update: impl FnOnce(&ProgressBar)) {
+        if let Some(bar) = self.bar.lock().expect("progress bar lock").as_ref() {
+            update(bar);
+        }
+    }
+}
+
+impl EventSink for Progress {
+    fn on_phase_start(&self, phase: Phase) {
+        let Some(bars) = &self.bars else { return };
+        let bar = bars.add(ProgressBar::new_spinner());
+        bar.set_style(spinner_style());
+        bar.set_prefix(phase_label(phase));
+        bar.enable_steady_tick(Duration::from_millis(100));
+        if let Some(previous) = self.bar.lock().expect("progress bar lock").replace(bar) {
+            previous.finish_and_clear();
+        }
+    }
+
+    fn on_command(&self, program: &str, args: &[String]) {
+        let program = program.rsplit(['/', '\\']).next().unwrap_or(program);
+        let message = match args.first() {
+            // `emmake make`, `emcmake cmake`: name the wrapped tool
+            Some(tool) if matches!(program, "emmake" | "emcmake") => format!("running {}", tool),
+            _ if matches!(program, "emcc" | "em++") && args.iter().any(|arg| arg == "-c") => "compiling with emcc".to_string(),
+            _ if matches!(program, "emcc" | "em++") => "compiling and linking with emcc".to_string(),
+            _ => format!("running {}", program),
+        };
+        self.update(|bar| {
+            bar.set_style(spinner_style());
+            bar.set_message(message);
+        });
+    }
+
+    fn on_stderr_line(&self, _program: &str, line: &str) {
+        // emcc runs wasm-opt after linking; it names it with -v or EMCC_VERBOSE=1
+        if line.contains("wasm-opt") {
+            self.update(|bar| bar.set_message("optimizing with wasm-opt"));
+        }
+    }
+
+    fn on_progress(&self, progress: &BuildProgress) {
+        if self.bars.is_none() {
+            tracing::info!("[{}/{}] {}", progress.completed, progress.total, progress.description);
+            return;
+        }
+        self.update(|bar| {
+            bar.set_style(bar_style(progress.total == 100));
+            bar.set_length(progress.total as u64);
+            bar.set_position(progress.completed as u64);
+            bar.set_message(progress.description.clone());
+        });
+    }
+}
+
+fn phase_label(phase: Phase) -> &'static str {
+    match phase {
+        Phase::Prepare => "Preparing",
+        Phase::Configure => "Configuring",
+        Phase::Build => "Building",
+        Phase::PostProcess => "Post-processing",
+        Phase::Webapp => "Generating webapp",
+        Phase::Test => "Testing",
+        Phase::Run => "Running",
+    }
+}
+
+fn spinner_style() -> ProgressStyle {
+    ProgressStyle::with_template("{spinner:.green} {prefix:.bold} {wide_msg} {elapsed:.dim}").expect("valid template")
+}
+
+/// CMake's Makefiles report a percentage, Ninja a step count
+fn bar_style(percentage: bool) -> ProgressStyle {
+    let template = if percentage {
+        "{prefix:.bold} [{bar:30.cyan/blue}] {percent:>3}% {wide_msg} {elapsed:.dim}"
+    } else {
+        "{prefix:.bold} [{bar:30.cyan/blue}] {pos}/{len} {wide_msg} {elapsed:.dim}"
+    };
+    ProgressStyle::with_template(template).expect("valid template").progress_chars("=> ")
+}
+
+/// Hides the progress bar while a log line is printed
+struct SuspendingLogger {
+    logger: env_logger::Logger,
+    bars: Option<MultiProgress>,
+}
+
+impl log::Log for SuspendingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.logger.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.logger.matches(record) {
+            return;
+        }
+        match &self.bars {
+            Some(bars) => bars.suspend(|| self.logger.log(record)),
+            None => self.logger.log(record),
+        }
+    }
+
+    fn flush(&self) {
+        self.logger.flush();
+    }
+}
@@ -0,0 +1,231 @@
+//! Optional per-project configuration read from a `wasm_compiler.toml` file.
+//!
+//! The file lives in the project root (or is passed explicitly with `--config`) and holds
+//! settings that are awkward to express as command-line flags.
+//!
+//! ```toml
+//! [runtime]
+//! arguments = ["--level", "3"]
+//! locate_file_prefix = "assets/"
+//!
+//! [runtime.env]
+//! LOG_LEVEL = "debug"
+//! ```
+//!
+//! It can also list build-system plugins (see [`crate::plugins`]) and set up the environment
+//! of the build tools:
+//!
+//! ```toml
+//! plugins = ["tools/bazel-plugin.toml"]
+//! path_prefix = ["tools/bin"]
+//!
+//! [env]
+//! EM_CACHE = "/ci/cache/emscripten"
+//! ```
+//!
+//! or pin the tools to exact binaries, when several SDKs are installed (see
+//! [`crate::app_config::AppConfig::tool_path`]):
+//!
+//! ```toml
+//! [tools]
+//! emcc = "/opt/emsdk-3.1.56/upstream/emscripten/emcc"
+//! cmake = "/opt/cmake-3.28/bin/cmake"
+//! ```
+//!
+//! the key the checksums of the build outputs are signed with (see [`crate::signing`]; a
+//! relative path is resolved from the directory of the config file):
+//!
+//! ```toml
+//! [signing]
+//! key = "/ci/secrets/release.key"
+//! ```
+//!
+//! and a matrix of targets that are all built in one invocation (see [`crate::compile_matrix`]):
+//!
+//! ```toml
+//! [targets.web]
+//! target_env = "web"
+//!
+//! [targets.node-debug]
+//! target_env = "node"
+//! build_config = "Debug"
+//! emcc_flags = ["-sASSERTIONS=2"]
+//! ```
+//!
+//! Native libraries the project links can be mapped to emcc flags, or dropped with an empty
+//! list, on top of the built-in mapping (see [`crate::compiler::system_libraries`]):
+//!
+//! ```toml
+//! [link_map]
+//! curl = ["-sFETCH=1"]
+//! asound = []
+//! ```
+//!
+//! Direct builds can give the sources under a path glob extra compile flags (see
+//! [`crate::compiler::emcc_flags`]):
+//!
+//! ```toml
+//! [flags]
+//! "third_party/**" = ["-w"]
+//! "src/math/**" = ["-O3", "-ffast-math"]
+//! ```
+//!
+//! The build phases can be capped in time and temporary disk space (see
+//! [`crate::resource_limits`]):
+//!
+//! ```toml
+//! [limits.build]
+//! timeout = 1800
+//! temp_disk = "4G"
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use crate::events::Phase;
+use crate::resource_limits::parse_size;
+use crate::utils::source_selection::path_glob;
+
+/// Phases that can have `[limits]`; preparing the build runs before the config is loaded
+const LIMITED_PHASES: &[Phase] = &[Phase::Configure, Phase::Build, Phase::PostProcess, Phase::Webapp, Phase::Test, Phase::Run];
+
+/// Default file name looked up in the project root
+pub const PROJECT_CONFIG_FILE_NAME: &str = "wasm_compiler.toml";
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Settings baked into the generated webapp / node launcher
+    pub runtime: RuntimeConfig,
+    /// Plugin files, relative to the directory of the config file
+    pub plugins: Vec<PathBuf>,
+    /// Environment variables set for the build tools (emcc, cmake, make, ...)
+    pub env: BTreeMap<String, String>,
+    /// Directories put in front of PATH for the build tools, relative to the directory of
+    /// the config file
+    pub path_prefix: Vec<PathBuf>,
+    /// Exact binaries of the build tools, overridden by `--emcc-path` and the like
+    pub tools: ToolsConfig,
+    /// Build matrix; each target is built into `<output-dir>/<name>/`
+    pub targets: BTreeMap<String, TargetConfig>,
+    /// Signing of the build outputs
+    pub signing: SigningConfig,
+    /// Native libraries (without `-l`) and the emcc flags that replace them
+    pub link_map: BTreeMap<String, Vec<String>>,
+    /// Extra compile flags for the sources matching each path glob, relative to the project root
+    pub flags: BTreeMap<String, Vec<String>>,
+    /// Caps of the build phases, by phase name (`configure`, `build`, `test`, ...)
+    pub limits: BTreeMap<String, PhaseLimits>,
+}
+
+/// Paths of the build tools, relative to the directory of the config file
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolsConfig {
+    /// em++, emcmake, emmake and the other Emscripten tools are taken from its directory
+    pub emcc: Option<PathBuf>,
+    /// ctest is taken from its directory
+    pub cmake: Option<PathBuf>,
+    pub make: Option<PathBuf>,
+    pub ninja: Option<PathBuf>,
+    pub node: Option<PathBuf>,
+}
+
+/// Settings for signing SHA256SUMS
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SigningConfig {
+    /// Unencrypted minisign secret key, relative to the directory of the config file
+    pub key: Option<PathBuf>,
+}
+
+/// Settings of one matrix target, overriding the command-line options
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TargetConfig {
+    pub build_config: Option<String>,
+    pub target_env: Option<String>,
+    /// Added after the `--emcc-flag` values
+    pub emcc_flags: Vec<String>,
+    /// Added after the `--define` values
+    pub defines: Vec<String>,
+}
+
+/// Caps of one build phase
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhaseLimits {
+    /// Seconds the phase may take
+    pub timeout: Option<u64>,
+    /// How much the build directories may grow during the phase, e.g. `"4G"` or `"512M"`
+    pub temp_disk: Option<String>,
+}
+
+/// Runtime settings passed to the Emscripten `Module` object
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntimeConfig {
+    /// `Module.arguments`: argv passed to `main()`
+    pub arguments: Vec<String>,
+    /// Environment variables set in `Module.ENV` during `preRun`
+    pub env: BTreeMap<String, String>,
+    /// Prefix used by `Module.locateFile` to find `.wasm`/`.data` files
+    pub locate_file_prefix: Option<String>,
+}
+
+impl ProjectConfig {
+    /// Reads and parses a project config file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read project config {:?}: {}", path, e))?;
+        let mut config: Self = toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse project config {:?}: {}", path, e))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let tools = &mut config.tools;
+        let tool_paths = [&mut tools.emcc, &mut tools.cmake, &mut tools.make, &mut tools.ninja, &mut tools.node];
+        for path in config.plugins.iter_mut().chain(&mut config.path_prefix).chain(&mut config.signing.key).chain(tool_paths.into_iter().flatten()) {
+            *path = base_dir.join(&*path);
+        }
+        for pattern in config.flags.keys() {
+            path_glob(pattern, "[flags]").map_err(|e| format!("{} in {:?}", e, path))?;
+        }
+        for (phase, limits) in &config.limits {
+            if !LIMITED_PHASES.iter().any(|limited| limited.name() == phase) {
+                let names: Vec<&str> = LIMITED_PHASES.iter().map(|phase| phase.name()).collect();
+                return Err(format!("Unknown phase '{}' in [limits] of {:?}, expected one of {}", phase, path, names.join(", ")));
+            }
+            if limits.timeout == Some(0) {
+                return Err(format!("The timeout of [limits.{}] in {:?} must be greater than 0", phase, path));
+            }
+            if let Some(size) = &limits.temp_disk {
+                parse_size(size).map_err(|e| format!("{} in [limits.{}] of {:?}", e, phase, path))?;
+            }
+        }
+        if let Some(name) = config.targets.keys().find(|name| !is_valid_target_name(name)) {
+            return Err(format!(
+                "Invalid target name {:?} in {:?}: use letters, digits, '-', '_' and '.'",
+                name, path
+            ));
+        }
+        Ok(config)
+    }
+}
+
+/// Target names become directory names in the output directory
+fn is_valid_target_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+impl RuntimeConfig {
+    /// Serializes the runtime settings as a JavaScript object literal for the generated pages.
+    pub fn to_js_object(&self) -> String {
+        serde_json::json!({
+            "arguments": self.arguments,
+            "env": self.env,
+            "locateFilePrefix": self.locate_file_prefix,
+        })
+        .to_string()
+    }
+}
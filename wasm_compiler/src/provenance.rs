@@ -0,0 +1,143 @@
+//! Build provenance (`--provenance`): a `wasm_compiler.provenance` custom section appended to
+//! the linked `.wasm`, holding the versions of `wasm_compiler` and Emscripten, a hash of the
+//! build settings and the git commit of the project as JSON. Browsers and runtimes ignore
+//! custom sections, so the module behaves the same; `wasm_compiler inspect` reads it back to
+//! tell which build is deployed.
+
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use wasmparser::{Parser, Payload};
+use crate::app_config::AppConfig;
+use crate::build_dir::BUILD_TREE_PREFIX;
+use crate::compiler::compile_commands;
+use crate::header_deps::DEPS_DIR;
+use crate::lockfile::LOCKFILE_NAME;
+use crate::utils::command_runner::{run_build_command, run_command};
+use crate::utils::file_system;
+use crate::utils::wasm_binary::custom_section;
+
+/// Name of the custom section
+pub const SECTION_NAME: &str = "wasm_compiler.provenance";
+
+/// What the custom section records about the build
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// Version of `wasm_compiler`
+    pub tool_version: String,
+    /// First line of `emcc --version`, if it could be run
+    pub emscripten_version: Option<String>,
+    /// SHA-256 of the build configuration, target environment and every flag passed on to
+    /// emcc; equal hashes mean equal build settings
+    pub flags_hash: String,
+    /// `HEAD` of the project's git repository, if it is one
+    pub git_commit: Option<String>,
+    /// Whether the working tree had uncommitted changes, other than the files the build writes
+    /// into it (see [`own_outputs`])
+    pub git_dirty: bool,
+}
+
+impl Provenance {
+    /// Collects the provenance of the build configured by `config`.
+    pub fn collect(config: &AppConfig, project_path_abs: &Path) -> Self {
+        let emscripten_version = run_build_command("emcc", &["--version"], None, config)
+            .ok()
+            .and_then(|output| String::from_utf8_lossy(&output.stdout).lines().next().map(|line| line.trim().to_string()))
+            .filter(|line| !line.is_empty());
+
+        let settings: Vec<String> = [config.build_config.clone(), config.target_env.clone()]
+            .into_iter()
+            .chain(config.emcc_flag.iter().cloned())
+            .chain(config.compile_flags())
+            .chain(config.lib_dir_flags())
+            .chain(config.link_lib_flags())
+            .collect();
+
+        let git = |args: &[&str]| {
+            run_command("git", args, Some(project_path_abs))
+                .ok()
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        let git_commit = git(&["rev-parse", "HEAD"]).filter(|commit| !commit.is_empty());
+        let git_dirty = git_commit.is_some()
+            && git(&["rev-parse", "--show-toplevel"])
+                .and_then(|top| PathBuf::from(top).canonicalize().ok())
+                .and_then(|top| {
+                    // The whole working tree, without the outputs of the build inside it
+                    let excludes: Vec<String> = own_outputs(config, project_path_abs)
+                        .iter()
+                        .filter_map(|path| path.canonicalize().ok()?.strip_prefix(&top).ok().map(Path::to_path_buf))
+                        .filter(|path| !path.as_os_str().is_empty())
+                        .map(|path| format!(":(top,exclude,literal){}", path.to_string_lossy().replace('\\', "/")))
+                        .collect();
+                    let args: Vec<&str> =
+                        ["status", "--porcelain", "--", ":/"].into_iter().chain(excludes.iter().map(String::as_str)).collect();
+                    git(&args)
+                })
+                .is_some_and(|status| !status.is_empty());
+
+        Provenance {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            emscripten_version,
+            flags_hash: file_system::hex_digest(settings.join("\n").as_bytes()),
+            git_commit,
+            git_dirty,
+        }
+    }
+}
+
+/// The files and directories the build writes into the project, which would otherwise make
+/// every build of a clean checkout count as dirty: the output directory, the intermediate build
+/// trees, `compile_commands.json`, the lockfile and the fetched headers
+fn own_outputs(config: &AppConfig, project_path_abs: &Path) -> Vec<PathBuf> {
+    let build_root = config.build_root(project_path_abs);
+    let build_trees = std::fs::read_dir(&build_root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(BUILD_TREE_PREFIX))
+        .map(|entry| entry.path());
+    [
+        config.output_dir.clone(),
+        project_path_abs.join(compile_commands::FILE_NAME),
+        project_path_abs.join(LOCKFILE_NAME),
+        project_path_abs.join(DEPS_DIR),
+    ]
+    .into_iter()
+    .chain(build_trees)
+    .collect()
+}
+
+/// Appends the provenance section to the linked module in the output directory.
+pub fn embed_provenance(config: &AppConfig, project_path_abs: &Path) -> Result<(), String> {
+    let wasm_path = config.output_dir.join(format!("{}.wasm", config.output_name));
+    if !wasm_path.is_file() {
+        crate::strict::fallback(config, "--provenance has no effect with --single-file: there is no .wasm file to add it to")?;
+        return Ok(());
+    }
+    let provenance = Provenance::collect(config, project_path_abs);
+    let payload = serde_json::to_vec(&provenance).map_err(|e| format!("Failed to serialize provenance: {}", e))?;
+
+    let mut module = std::fs::read(&wasm_path).map_err(|e| format!("Failed to read {:?}: {}", wasm_path, e))?;
+    module.extend(custom_section(SECTION_NAME, &payload));
+    std::fs::write(&wasm_path, module).map_err(|e| format!("Failed to write {:?}: {}", wasm_path, e))?;
+    tracing::info!(
+        "Embedded provenance in {:?} (commit {})",
+        wasm_path,
+        provenance.git_commit.as_deref().unwrap_or("unknown")
+    );
+    Ok(())
+}
+
+/// Reads the provenance section of a module, if it has one.
+pub fn read_provenance(module: &[u8]) -> Result<Option<Provenance>, String> {
+    for payload in Parser::new(0).parse_all(module) {
+        if let Payload::CustomSection(section) = payload.map_err(|e| e.to_string())? {
+            if section.name() == SECTION_NAME {
+                return serde_json::from_slice(section.data())
+                    .map(Some)
+                    .map_err(|e| format!("Malformed {} section: {}", SECTION_NAME, e));
+            }
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,241 @@
+//! Projects given to `--project-path` as a git URL or an archive instead of a directory, e.g.
+//! a sample straight from GitHub. Git repositories are cloned into `projects/` in the cache
+//! directory and built there; a ref to check out can be pinned after a `#`
+//! (`https://github.com/owner/repo.git#v1.2`), and GitHub links to a directory of a branch
+//! (`https://github.com/owner/repo/tree/main/examples/demo`) build that directory. As such a
+//! link does not show where the branch name ends, the first part after `tree/` is taken for
+//! the branch unless the branch is also given after a `#` (`.../tree/feature/x/demo#feature/x`).
+//! Checkouts of a branch or the default branch are updated on each build, falling back to the
+//! cached copy when that fails; commits are cloned once. `.tar.gz`, `.tgz` and `.zip` archives,
+//! local or downloaded, are extracted into the cache as well; an archive holding a single
+//! directory (as GitHub's do) builds that directory.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Component, Path, PathBuf};
+use crate::app_config::AppConfig;
+use crate::prefetch::GitContent;
+use crate::utils::command_runner::run_build_command;
+use crate::utils::file_system;
+
+const ARCHIVE_EXTENSIONS: &[&str] = &[".tar.gz", ".tgz", ".zip"];
+
+/// A `--project-path` that is not a local directory
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteProject {
+    Git {
+        repository: String,
+        /// Branch, tag or commit to check out; the default branch when not given
+        reference: Option<String>,
+        /// Directory of the repository to build, relative to its root
+        subdir: Option<PathBuf>,
+    },
+    /// A `.tar.gz`, `.tgz` or `.zip` archive, at a URL or a local path
+    Archive(String),
+}
+
+impl RemoteProject {
+    /// The remote project `project_path` names, or `None` for a local directory
+    pub fn parse(project_path: &Path) -> Option<RemoteProject> {
+        if project_path.is_dir() {
+            return None;
+        }
+        let value = project_path.to_string_lossy();
+        let is_url = ["http://", "https://", "ssh://", "git://", "file://", "git@"].iter().any(|scheme| value.starts_with(scheme));
+        let (location, reference) = match value.split_once('#') {
+            Some((location, reference)) if is_url => (location, Some(reference.to_string()).filter(|reference| !reference.is_empty())),
+            _ => (value.as_ref(), None),
+        };
+        let lower = location.to_ascii_lowercase();
+        if ARCHIVE_EXTENSIONS.iter().any(|extension| lower.ends_with(extension)) && (is_url || project_path.is_file()) {
+            return Some(RemoteProject::Archive(location.to_string()));
+        }
+        if !is_url && !lower.ends_with(".git") {
+            return None;
+        }
+        // https://github.com/owner/repo/tree/<branch>/<directory>; the link does not tell where
+        // a branch with a `/` ends, so such a branch has to be given after a `#` as well
+        if let Some(path) = location.strip_prefix("https://github.com/") {
+            let parts: Vec<&str> = path.trim_end_matches('/').splitn(4, '/').collect();
+            if let [owner, repo, "tree", tree] = parts.as_slice() {
+                let rest = match &reference {
+                    Some(reference) if tree.strip_prefix(reference.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with('/')) => {
+                        &tree[reference.len()..]
+                    }
+                    _ => tree.find('/').map_or("", |index| &tree[index..]),
+                };
+                let branch = tree[..tree.len() - rest.len()].to_string();
+                return Some(RemoteProject::Git {
+                    repository: format!("https://github.com/{}/{}", owner, repo),
+                    reference: reference.or(Some(branch)),
+                    subdir: Some(rest.trim_start_matches('/')).filter(|rest| !rest.is_empty()).map(PathBuf::from),
+                });
+            }
+        }
+        Some(RemoteProject::Git { repository: location.to_string(), reference, subdir: None })
+    }
+
+    /// Name of the project, from the last part of its URL or file name
+    fn name(&self) -> String {
+        let location = match self {
+            RemoteProject::Git { repository, .. } => repository.as_str(),
+            RemoteProject::Archive(archive) => archive.as_str(),
+        };
+        let last = location.trim_end_matches('/').rsplit(['/', '\\', ':']).next().unwrap_or_default();
+        let lower = last.to_ascii_lowercase();
+        let extension = [".git"].iter().chain(ARCHIVE_EXTENSIONS).find(|extension| lower.ends_with(*extension));
+        let name = &last[..last.len() - extension.map_or(0, |extension| extension.len())];
+        if name.is_empty() { "project".to_string() } else { name.to_string() }
+    }
+}
+
+/// Replaces a remote `project_path` of `config` by the local copy of the project, fetching
+/// it first if needed
+pub fn resolve(config: &mut AppConfig) -> Result<(), String> {
+    let Some(remote) = RemoteProject::parse(&config.project_path) else { return Ok(()) };
+    let cache = file_system::cache_dir().ok_or_else(|| format!("There is no cache directory to fetch {:?} into", config.project_path))?;
+    let project_path = match &remote {
+        RemoteProject::Git { repository, reference, subdir } => {
+            if let Some(subdir) = subdir.as_deref().filter(|subdir| subdir.components().any(|component| matches!(component, Component::ParentDir))) {
+                return Err(format!("The directory {:?} of {:?} is outside the repository", subdir, config.project_path));
+            }
+            let key = file_system::hex_digest(format!("{}\n{}", repository, reference.as_deref().unwrap_or_default()).as_bytes());
+            let checkout = cache.join("projects").join(&key[..16]).join(remote.name());
+            let content = GitContent { name: remote.name(), repository: repository.clone(), tag: reference.clone() };
+            if checkout.is_dir() {
+                update(&content, &checkout, config);
+            } else {
+                crate::prefetch::clone(&content, &checkout, config).map_err(|e| e.to_string())?;
+            }
+            match subdir {
+                Some(subdir) => checkout.join(subdir),
+                None => checkout,
+            }
+        }
+        RemoteProject::Archive(archive) => extract(archive, &remote.name(), &cache, config)?,
+    };
+    if !project_path.is_dir() {
+        return Err(format!("{:?} has no directory {:?}", config.project_path, project_path));
+    }
+    tracing::info!("Building {:?} from {:?}", config.project_path, project_path);
+    config.project_path = project_path;
+    Ok(())
+}
+
+/// Updates the cached checkout of a branch or the default branch; a commit does not change
+fn update(content: &GitContent, checkout: &Path, config: &AppConfig) {
+    let reference = content.tag.as_deref().unwrap_or("HEAD");
+    if reference.len() >= 7 && reference.chars().all(|c| c.is_ascii_hexdigit()) {
+        tracing::debug!("Using the cached checkout {:?}", checkout);
+        return;
+    }
+    let dir = checkout.to_string_lossy().into_owned();
+    let updated = run_build_command("git", &["-C", &dir, "fetch", "--depth", "1", "origin", reference], None, config)
+        .and_then(|_| run_build_command("git", &["-C", &dir, "reset", "--hard", "FETCH_HEAD"], None, config))
+        .and_then(|_| run_build_command("git", &["-C", &dir, "submodule", "update", "--init", "--recursive", "--depth", "1"], None, config));
+    if let Err(e) = updated {
+        tracing::warn!("Could not update {} ({}); building the cached checkout {:?}: {}", content.name, content.repository, checkout, e);
+    }
+}
+
+/// Extracts `archive`, downloading it first if it is a URL, and returns the project directory
+fn extract(archive: &str, name: &str, cache: &Path, config: &AppConfig) -> Result<PathBuf, String> {
+    let is_url = archive.contains("://");
+    // A local archive is extracted again when its contents change
+    let key = if is_url {
+        file_system::hex_digest(archive.as_bytes())
+    } else {
+        file_system::hash_file(Path::new(archive))?
+    };
+    let dir = cache.join("projects").join(&key[..16]);
+    let extracted = dir.join(name);
+    if !extracted.is_dir() {
+        file_system::create_dir_atomically(&extracted, |partial| unpack(archive, is_url, partial, config))?;
+    } else {
+        tracing::debug!("Using the extracted {:?}", extracted);
+    }
+
+    // Archives of GitHub and most releases hold a single directory
+    let entries: Vec<PathBuf> = std::fs::read_dir(&extracted)
+        .map_err(|e| format!("Failed to read {:?}: {}", extracted, e))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .collect();
+    match entries.as_slice() {
+        [single] if single.is_dir() => Ok(single.clone()),
+        _ => Ok(extracted),
+    }
+}
+
+fn unpack(archive: &str, is_url: bool, dir: &Path, config: &AppConfig) -> Result<(), String> {
+    file_system::ensure_dir_exists(dir)?;
+    let file = if is_url {
+        let file = dir.with_extension("download");
+        tracing::info!("Downloading {}", archive);
+        let args = ["-fsSL".to_string(), "-o".to_string(), file.to_string_lossy().into_owned(), archive.to_string()];
+        let downloaded = run_build_command("curl", &args, None, config).map(|_| ()).map_err(|e| e.to_string());
+        if let Err(e) = downloaded {
+            let _ = std::fs::remove_file(&file);
+            return Err(e);
+        }
+        file
+    } else {
+        PathBuf::from(archive)
+    };
+
+    tracing::info!("Extracting {} into {:?}", archive, dir);
+    let result = if archive.to_ascii_lowercase().ends_with(".zip") { unpack_zip(&file, dir) } else { unpack_tar_gz(&file, dir) };
+    if is_url {
+        let _ = std::fs::remove_file(&file);
+    }
+    result.map_err(|e| format!("Failed to extract {}: {}", archive, e))
+}
+
+/// Extracts the `.zip` file `file` into `dir`, refusing entries that would land outside it
+fn unpack_zip(file: &Path, dir: &Path) -> Result<(), String> {
+    let file = File::open(file).map_err(|e| format!("Failed to open {:?}: {}", file, e))?;
+    let mut zip = zip::ZipArchive::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(|e| e.to_string())?;
+        let Some(name) = entry.enclosed_name() else {
+            return Err(format!("the entry {:?} is outside the extracted directory", entry.name()));
+        };
+        let path = dir.join(name);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&path).map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+            continue;
+        }
+        // A link could point anywhere; the project is built without it
+        if entry.is_symlink() {
+            tracing::warn!("Leaving out the symbolic link {:?} of the archive", entry.name());
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        let mut out = File::create(&path).map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+        std::io::copy(&mut entry, &mut out).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+        // Keeps scripts like `configure` executable
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode & 0o755))
+                .map_err(|e| format!("Failed to set the permissions of {:?}: {}", path, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Extracts the `.tar.gz` file `file` into `dir`, refusing entries that would land outside it
+fn unpack_tar_gz(file: &Path, dir: &Path) -> Result<(), String> {
+    let file = File::open(file).map_err(|e| format!("Failed to open {:?}: {}", file, e))?;
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(BufReader::new(file)));
+    for entry in tar.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        // `unpack_in` skips paths with `..` and fails on links leading out of `dir`
+        if !entry.unpack_in(dir).map_err(|e| e.to_string())? {
+            return Err(format!("the entry {:?} is outside the extracted directory", String::from_utf8_lossy(&entry.path_bytes())));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+//! Reproduction script of a build (`--emit-script`): the toolchain commands the build runs
+//! (`emcmake`, `emmake`, `emcc`, ...), each in its working directory and with the environment
+//! variables the build sets for it, written as a POSIX shell script or, for a `.ps1` path, a
+//! PowerShell script. Running it outside `wasm_compiler` repeats the build, so flags can be
+//! tried out by editing it. Files `wasm_compiler` writes itself (the webapp, the build
+//! manifest, renamed outputs) are not part of it.
+//!
+//! Commands are added as they run, so after a failed build the script ends with the command
+//! that failed. Like the transcript, the script is written until the guard [`start`] returns
+//! is dropped, so it only holds the commands of the builds it was started for.
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::transcript::env_changes;
+
+static SCRIPT: Mutex<Option<Script>> = Mutex::new(None);
+
+struct Script {
+    path: PathBuf,
+    file: File,
+    powershell: bool,
+    /// Directory the script is in after the last command
+    cwd: Option<PathBuf>,
+}
+
+/// Keeps the script going to its file until it is dropped, then goes back to the one written
+/// before, if any
+#[must_use = "the script stops when the guard is dropped"]
+pub struct ScriptGuard {
+    /// The script [`start`] replaced; `None` when it already went to the file
+    previous: Option<Option<Script>>,
+}
+
+impl Drop for ScriptGuard {
+    fn drop(&mut self) {
+        if let (Some(previous), Ok(mut script)) = (self.previous.take(), SCRIPT.lock()) {
+            *script = previous;
+        }
+    }
+}
+
+/// Starts writing the script to `path`, replacing the file, until the returned guard is
+/// dropped. Does nothing if the script already goes there.
+pub fn start(path: &Path) -> Result<ScriptGuard, String> {
+    let path = std::path::absolute(path).map_err(|e| format!("Invalid script path {:?}: {}", path, e))?;
+    let mut script = SCRIPT.lock().expect("script lock");
+    if script.as_ref().is_some_and(|script| script.path == path) {
+        return Ok(ScriptGuard { previous: None });
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    let powershell = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ps1"));
+    let argv: Vec<String> = std::env::args().collect();
+    let header = if powershell {
+        format!(
+            "# Toolchain commands of a build by wasm_compiler {}\n# {}\n\n$ErrorActionPreference = 'Stop'\n",
+            env!("CARGO_PKG_VERSION"),
+            shell_words::join(&argv)
+        )
+    } else {
+        format!(
+            "#!/bin/sh\n# Toolchain commands of a build by wasm_compiler {}\n# {}\n\nset -e\n",
+            env!("CARGO_PKG_VERSION"),
+            shell_words::join(&argv)
+        )
+    };
+    let mut file = File::create(&path).map_err(|e| format!("Failed to create script {:?}: {}", path, e))?;
+    file.write_all(header.as_bytes()).map_err(|e| format!("Failed to write script {:?}: {}", path, e))?;
+    #[cfg(unix)]
+    if !powershell {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Failed to make {:?} executable: {}", path, e))?;
+    }
+    tracing::info!("Writing the toolchain commands of the build to {:?}", path);
+    let previous = script.replace(Script { path, file, powershell, cwd: None });
+    Ok(ScriptGuard { previous: Some(previous) })
+}
+
+/// Adds a command about to run to the script, if one is being written.
+pub(crate) fn record_command(program: &str, args: &[String], current_dir: Option<&Path>, env: &[(OsString, OsString)]) {
+    let mut script = SCRIPT.lock().expect("script lock");
+    let Some(script) = script.as_mut() else { return };
+
+    let cwd = current_dir
+        .map(|dir| std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf()))
+        .or_else(|| std::env::current_dir().ok());
+    let mut lines = String::from("\n");
+    if cwd.is_some() && cwd != script.cwd {
+        let dir = cwd.as_deref().unwrap_or(Path::new(".")).to_string_lossy();
+        if script.powershell {
+            lines.push_str(&format!("Set-Location -LiteralPath {}\n", powershell_quote(&dir)));
+        } else {
+            lines.push_str(&format!("cd {}\n", shell_words::quote(&dir)));
+        }
+        script.cwd = cwd;
+    }
+
+    let env = env_changes(env);
+    if script.powershell {
+        for (key, value) in &env {
+            lines.push_str(&format!("$env:{} = {}\n", key.to_string_lossy(), powershell_quote(&value.to_string_lossy())));
+        }
+        let command: Vec<String> = std::iter::once(program).chain(args.iter().map(String::as_str)).map(powershell_quote).collect();
+        lines.push_str(&format!("& {}\n", command.join(" ")));
+        lines.push_str("if ($LASTEXITCODE -ne 0) { exit $LASTEXITCODE }\n");
+    } else {
+        // Assignments in front of a command apply to that command only
+        let assignments = env.iter().map(|(key, value)| {
+            format!("{}={}", key.to_string_lossy(), shell_words::quote(&value.to_string_lossy()))
+        });
+        let command = std::iter::once(program).chain(args.iter().map(String::as_str)).map(shell_words::quote);
+        lines.push_str(&assignments.chain(command.map(|word| word.into_owned())).collect::<Vec<_>>().join(" "));
+        lines.push('\n');
+    }
+
+    // A full disk must not fail the build the script is for
+    if let Err(e) = script.file.write_all(lines.as_bytes()) {
+        tracing::debug!("Failed to write to {:?}: {}", script.path, e);
+    }
+}
+
+/// A single-quoted PowerShell string, which only needs its quotes doubled
+fn powershell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
@@ -0,0 +1,194 @@
+//! Caps on the time and temporary disk space of the build phases, from the `[limits]` table
+//! of the project config, so a runaway emcc or ninja cannot hold a shared CI runner:
+//!
+//! ```toml
+//! [limits.configure]
+//! timeout = 300
+//!
+//! [limits.build]
+//! timeout = 1800
+//! temp_disk = "4G"
+//! ```
+//!
+//! `timeout` is the number of seconds the phase may take; a command still running when it
+//! is over is terminated with the tools it started. `temp_disk` caps how much the build
+//! trees of the project (`build_wasm*`, see [`AppConfig::build_root`]) grow during the phase;
+//! the tools of a phase with a disk cap also get `build_wasm_tmp/` next to them as their
+//! temporary directory, so it is counted too, and it is removed when the phase ends. Either breach fails the build with a
+//! [`CommandError`] naming the phase and the limit.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+use crate::app_config::AppConfig;
+use crate::build_dir::BUILD_TREE_PREFIX;
+use crate::events::Phase;
+use crate::utils::command_runner::CommandError;
+
+/// The temporary directory of the tools in a phase with a disk cap, next to the build trees
+pub const TEMP_DIR_NAME: &str = "build_wasm_tmp";
+
+/// How often the disk usage of a running command's phase is measured
+pub(crate) const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The limits of the phase the current thread is in
+#[derive(Debug, Clone)]
+struct ActiveLimits {
+    phase: Phase,
+    /// When the phase has to end, and its limit
+    deadline: Option<(Instant, Duration)>,
+    disk: Option<DiskLimit>,
+}
+
+#[derive(Debug, Clone)]
+struct DiskLimit {
+    build_root: PathBuf,
+    limit: u64,
+    /// Size of the build directories when the phase started
+    baseline: u64,
+}
+
+thread_local! {
+    static ACTIVE: RefCell<Option<ActiveLimits>> = const { RefCell::new(None) };
+}
+
+/// Keeps the limits of a phase in force until it is dropped
+pub(crate) struct LimitsGuard {
+    previous: Option<ActiveLimits>,
+    temp_dir: Option<PathBuf>,
+}
+
+impl Drop for LimitsGuard {
+    fn drop(&mut self) {
+        if let Some(temp_dir) = &self.temp_dir {
+            let _ = std::fs::remove_dir_all(temp_dir);
+        }
+        let previous = self.previous.take();
+        ACTIVE.with(|active| *active.borrow_mut() = previous);
+    }
+}
+
+/// Puts the `[limits]` of `phase` in force for the commands this thread runs
+pub(crate) fn enter(config: &AppConfig, phase: Phase) -> LimitsGuard {
+    let limits = config.project_config.limits.get(phase.name());
+    let project_path = std::path::absolute(&config.project_path).unwrap_or_else(|_| config.project_path.clone());
+    let build_root = config.build_root(&project_path);
+    let deadline = limits.and_then(|limits| limits.timeout).map(|seconds| {
+        let limit = Duration::from_secs(seconds);
+        (Instant::now() + limit, limit)
+    });
+    // Validated when the project config is loaded
+    let disk = limits.and_then(|limits| limits.temp_disk.as_deref()).and_then(|size| parse_size(size).ok()).map(|limit| DiskLimit {
+        baseline: build_dirs_size(&build_root),
+        build_root: build_root.clone(),
+        limit,
+    });
+    let temp_dir = disk.as_ref().map(|_| build_root.join(TEMP_DIR_NAME)).filter(|dir| std::fs::create_dir_all(dir).is_ok());
+
+    let limits = (deadline.is_some() || disk.is_some()).then_some(ActiveLimits { phase, deadline, disk });
+    let previous = ACTIVE.with(|active| std::mem::replace(&mut *active.borrow_mut(), limits));
+    LimitsGuard { previous, temp_dir }
+}
+
+/// The temporary directory of the tools, when the current phase caps disk usage
+pub(crate) fn temp_dir() -> Option<PathBuf> {
+    ACTIVE.with(|active| {
+        let active = active.borrow();
+        let disk = active.as_ref()?.disk.as_ref()?;
+        Some(disk.build_root.join(TEMP_DIR_NAME))
+    })
+}
+
+/// The limits a command started now runs under: its `--command-timeout` and those of the
+/// current phase
+#[derive(Debug, Clone)]
+pub(crate) struct CommandLimits {
+    command_timeout: Option<Duration>,
+    phase: Option<ActiveLimits>,
+    started: Instant,
+}
+
+impl CommandLimits {
+    pub(crate) fn new(command_timeout: Option<Duration>) -> Self {
+        CommandLimits { command_timeout, phase: ACTIVE.with(|active| active.borrow().clone()), started: Instant::now() }
+    }
+
+    /// Whether anything has to be watched while the command runs
+    pub(crate) fn is_limited(&self) -> bool {
+        self.command_timeout.is_some() || self.phase.is_some()
+    }
+
+    /// Time the command may still run, or `None` without a time limit
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        let command = self.command_timeout.map(|limit| limit.saturating_sub(self.started.elapsed()));
+        let phase = self.phase_deadline().map(|(deadline, _)| deadline.saturating_duration_since(Instant::now()));
+        command.into_iter().chain(phase).min()
+    }
+
+    /// The error for a command that ran out of time
+    pub(crate) fn timed_out(&self, tool: &str) -> CommandError {
+        let command_deadline = self.command_timeout.map(|limit| self.started + limit);
+        match (self.phase.as_ref(), self.phase_deadline()) {
+            (Some(active), Some((deadline, limit))) if command_deadline.is_none_or(|command| deadline <= command) => {
+                CommandError::PhaseTimedOut { tool: tool.to_string(), phase: active.phase, seconds: limit.as_secs() }
+            }
+            _ => CommandError::TimedOut { tool: tool.to_string(), seconds: self.command_timeout.map_or(0, |limit| limit.as_secs()) },
+        }
+    }
+
+    /// The error for a command whose phase has used more disk space than it may
+    pub(crate) fn check_disk(&self, tool: &str) -> Result<(), CommandError> {
+        let Some(active) = &self.phase else { return Ok(()) };
+        let Some(disk) = &active.disk else { return Ok(()) };
+        let used = build_dirs_size(&disk.build_root).saturating_sub(disk.baseline);
+        if used <= disk.limit {
+            return Ok(());
+        }
+        Err(CommandError::DiskLimitExceeded { tool: tool.to_string(), phase: active.phase, used, limit: disk.limit })
+    }
+
+    /// Whether the disk usage has to be measured while the command runs
+    pub(crate) fn watches_disk(&self) -> bool {
+        self.phase.as_ref().is_some_and(|active| active.disk.is_some())
+    }
+
+    fn phase_deadline(&self) -> Option<(Instant, Duration)> {
+        self.phase.as_ref().and_then(|active| active.deadline)
+    }
+}
+
+/// Parses a size in bytes, with an optional `K`, `M`, `G` or `T` suffix (powers of 1024,
+/// `KB`/`KiB` accepted too)
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let trimmed = size.trim();
+    let number_end = trimmed.find(|c: char| !c.is_ascii_digit()).unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(number_end);
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(format!("Invalid size '{}', expected bytes or a number with K, M, G or T", size)),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .filter(|bytes| *bytes > 0)
+        .ok_or_else(|| format!("Invalid size '{}', expected a size greater than 0", size))
+}
+
+/// Total size of the files in the `build_wasm*` directories of `build_root`
+fn build_dirs_size(build_root: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(build_root) else { return 0 };
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(BUILD_TREE_PREFIX) && entry.path().is_dir())
+        .flat_map(|entry| WalkDir::new(entry.path()).into_iter().filter_map(Result::ok))
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
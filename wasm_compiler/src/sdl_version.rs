@@ -0,0 +1,91 @@
+//! The major version of SDL a build links (`--sdl-version`), decided once for every handler so
+//! the ImGui backends, the `-sUSE_SDL` flag `--with-imgui` adds and the headers the sources
+//! include agree. Taken from `-sUSE_SDL` or `--use-port` in the emcc flags, otherwise from the
+//! sources and build files: `SDL3/` and `SDL2/` includes, the `imgui_impl_sdl2`/`imgui_impl_sdl3`
+//! backend headers, `find_package(SDL3)` and `SDL2::` targets. A project that includes plain
+//! `<SDL.h>`, or shows no version at all, gets SDL2.
+
+use std::path::Path;
+use crate::app_config::{AppConfig, SdlVersion};
+use crate::utils::source_selection::project_files;
+
+/// The version of projects that show none. ImGui projects used to default to SDL3 in the
+/// ImGui handler while `--with-imgui` linked SDL2; both now use SDL2.
+pub const DEFAULT: SdlVersion = SdlVersion::Sdl2;
+
+/// Text of the sources and build files that only SDL3 projects contain
+const SDL3_MARKERS: &[&str] = &["SDL3/", "imgui_impl_sdl3", "find_package(SDL3", "SDL3::"];
+
+/// Text of the sources and build files that only SDL2 projects contain
+const SDL2_MARKERS: &[&str] = &["SDL2/", "imgui_impl_sdl2", "find_package(SDL2", "SDL2::"];
+
+/// The `--sdl-version`, detected when it was not given.
+pub fn resolve(config: &AppConfig) -> SdlVersion {
+    config.sdl_version
+        .or_else(|| from_flags(&config.emcc_flag).map(|(version, _)| version))
+        .or_else(|| detect(config, &config.project_path).ok().flatten().map(|(version, _)| version))
+        .unwrap_or(DEFAULT)
+}
+
+/// The SDL version an emcc flag (`-sUSE_SDL=3`, `--use-port=sdl2`) asks for, with the flag
+pub fn from_flags(flags: &[String]) -> Option<(SdlVersion, &String)> {
+    flags.iter().find_map(|flag| {
+        let value = flag.strip_prefix("-sUSE_SDL=")
+            .or_else(|| flag.strip_prefix("--use-port=sdl").map(|port| port.split(':').next().unwrap_or(port)))?;
+        match value {
+            "2" => Some((SdlVersion::Sdl2, flag)),
+            "3" => Some((SdlVersion::Sdl3, flag)),
+            _ => None,
+        }
+    })
+}
+
+/// Detects the SDL version of the project, with the reason for the verdict. `None` if the
+/// project shows no particular version. A project using both versions gets the one more files
+/// use, which is an error with `--strict`.
+pub fn detect(config: &AppConfig, project_path: &Path) -> Result<Option<(SdlVersion, String)>, String> {
+    if let Some((version, flag)) = from_flags(&config.emcc_flag) {
+        return Ok(Some((version, format!("emcc flag {}", flag))));
+    }
+
+    let limits = config.detection_limits();
+    let mut sdl2_files = Vec::new();
+    let mut sdl3_files = Vec::new();
+    for file in project_files(project_path, Some(limits.max_depth)) {
+        let name = file.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        let is_build_file = name == "CMakeLists.txt" || name.ends_with(".cmake") || name.eq_ignore_ascii_case("makefile");
+        let is_source = file.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ["cpp", "cxx", "cc", "c", "h", "hpp", "hh"].contains(&ext));
+        // ImGui's own backends name both versions; the project's includes of them count
+        if !(is_build_file || is_source) || name.starts_with("imgui_impl_") {
+            continue;
+        }
+        let Some(content) = limits.read_source(&file) else { continue };
+        let relative = file.strip_prefix(project_path).unwrap_or(&file).to_path_buf();
+        if SDL3_MARKERS.iter().any(|marker| content.contains(marker)) {
+            sdl3_files.push(relative.clone());
+        }
+        if SDL2_MARKERS.iter().any(|marker| content.contains(marker)) {
+            sdl2_files.push(relative);
+        }
+    }
+
+    Ok(match (sdl2_files.first(), sdl3_files.first()) {
+        (None, None) => None,
+        (Some(file), None) => Some((SdlVersion::Sdl2, format!("{} uses SDL2", file.display()))),
+        (None, Some(file)) => Some((SdlVersion::Sdl3, format!("{} uses SDL3", file.display()))),
+        (Some(sdl2_file), Some(sdl3_file)) => {
+            let version = if sdl3_files.len() > sdl2_files.len() { SdlVersion::Sdl3 } else { SdlVersion::Sdl2 };
+            crate::strict::fallback(config, format!(
+                "The project uses both SDL2 ({}, {} file(s)) and SDL3 ({}, {} file(s)); building with SDL{} (pass --sdl-version to choose)",
+                sdl2_file.display(),
+                sdl2_files.len(),
+                sdl3_file.display(),
+                sdl3_files.len(),
+                version.major()
+            ))?;
+            Some((version, "used by more files".to_string()))
+        }
+    })
+}
@@ -0,0 +1,235 @@
+//! Checksums and signatures of the build outputs, for release processes that require signed
+//! artifacts. `--checksums` writes `SHA256SUMS`, in the format of `sha256sum`, listing the
+//! artifacts of the build. With a signing key (`--signing-key`, or `key` in the `[signing]`
+//! table of the project config) the file is also signed into `SHA256SUMS.minisig`, a
+//! detached minisign signature, so the artifacts are covered by the signature through their
+//! checksums. The `verify` subcommand checks both, as do `sha256sum -c` and `minisign -V`.
+//!
+//! Keys are minisign Ed25519 keys. Secret keys have to be unencrypted (`minisign -G -W`),
+//! since the build runs unattended; keep them in the CI secret store, not in the repository.
+
+use std::path::{Path, PathBuf};
+use base64::Engine;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Blake2b512, Digest};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use crate::app_config::VerifyArgs;
+use crate::utils::file_system;
+use crate::BuildArtifacts;
+
+/// File name of the checksum list written next to the artifacts
+pub const CHECKSUMS_FILE_NAME: &str = "SHA256SUMS";
+
+/// File name of the detached signature of [`CHECKSUMS_FILE_NAME`]
+pub const SIGNATURE_FILE_NAME: &str = "SHA256SUMS.minisig";
+
+/// Signature algorithm of minisign keys
+const KEY_ALGORITHM: &[u8; 2] = b"Ed";
+/// Signature over the BLAKE2b-512 hash of the file, which minisign creates by default
+const PREHASHED_ALGORITHM: &[u8; 2] = b"ED";
+
+/// A minisign secret key, read before the build so a bad key fails early
+pub struct SecretKey {
+    key_id: [u8; 8],
+    signing_key: SigningKey,
+}
+
+struct PublicKey {
+    key_id: [u8; 8],
+    verifying_key: VerifyingKey,
+}
+
+impl SecretKey {
+    /// Reads an unencrypted minisign secret key file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read signing key {:?}: {}", path, e))?;
+        let bytes = decode_key_line(&content).ok_or_else(|| format!("{:?} is not a minisign secret key", path))?;
+        // Algorithm (2), KDF (2), checksum algorithm (2), KDF salt (32), KDF limits (16),
+        // key ID (8), Ed25519 keypair (64), BLAKE2b-256 checksum (32)
+        if bytes.len() != 158 || &bytes[..2] != KEY_ALGORITHM {
+            return Err(format!("{:?} is not a minisign Ed25519 secret key", path));
+        }
+        if bytes[2..4] != [0, 0] {
+            return Err(format!(
+                "{:?} is password-protected; signing during the build needs an unencrypted key (minisign -G -W)",
+                path
+            ));
+        }
+        let key_id: [u8; 8] = bytes[54..62].try_into().expect("slice of 8 bytes");
+        let keypair: [u8; 64] = bytes[62..126].try_into().expect("slice of 64 bytes");
+        let checksum = Blake2b::<U32>::new()
+            .chain_update(KEY_ALGORITHM)
+            .chain_update(key_id)
+            .chain_update(keypair)
+            .finalize();
+        if checksum.as_slice() != &bytes[126..] {
+            return Err(format!("{:?} is corrupt: its checksum does not match", path));
+        }
+        let signing_key = SigningKey::from_keypair_bytes(&keypair)
+            .map_err(|e| format!("{:?} holds an invalid Ed25519 key: {}", path, e))?;
+        Ok(SecretKey { key_id, signing_key })
+    }
+}
+
+impl PublicKey {
+    /// Parses a minisign public key file, or the base64 key itself (as passed to `minisign -P`).
+    fn parse(key: &str) -> Result<Self, String> {
+        let path = Path::new(key);
+        let content = if path.is_file() {
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read public key {:?}: {}", path, e))?
+        } else {
+            key.to_string()
+        };
+        let bytes = decode_key_line(&content)
+            .filter(|bytes| bytes.len() == 42 && &bytes[..2] == KEY_ALGORITHM)
+            .ok_or_else(|| format!("'{}' is neither a minisign public key nor a file containing one", key))?;
+        let key_bytes: [u8; 32] = bytes[10..].try_into().expect("slice of 32 bytes");
+        Ok(PublicKey {
+            key_id: bytes[2..10].try_into().expect("slice of 8 bytes"),
+            verifying_key: VerifyingKey::from_bytes(&key_bytes).map_err(|e| format!("Invalid public key: {}", e))?,
+        })
+    }
+}
+
+/// Writes `SHA256SUMS` listing the artifacts of the build, relative to the output directory.
+pub fn write_checksums(artifacts: &BuildArtifacts) -> Result<PathBuf, String> {
+    let optional = [&artifacts.wasm, &artifacts.data, &artifacts.map, &artifacts.component];
+    let mut entries: Vec<(String, &str)> = std::iter::once(&artifacts.js)
+        .chain(optional.into_iter().flatten())
+        .chain(&artifacts.auxiliary)
+        .map(|artifact| {
+            let name = artifact.path.strip_prefix(&artifacts.output_dir).unwrap_or(&artifact.path);
+            (name.to_string_lossy().replace('\\', "/"), artifact.sha256.as_str())
+        })
+        .collect();
+    entries.sort();
+    let content: String = entries.iter().map(|(name, sha256)| format!("{}  {}\n", sha256, name)).collect();
+
+    let path = artifacts.output_dir.join(CHECKSUMS_FILE_NAME);
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    tracing::info!("Wrote checksums of {} file(s) to {:?}", entries.len(), path);
+    Ok(path)
+}
+
+/// Signs `path` with `key` into `<path>.minisig`, in minisign's format.
+pub fn sign_file(path: &Path, key: &SecretKey) -> Result<PathBuf, String> {
+    let content = std::fs::read(path).map_err(|e| format!("Failed to read {:?} for signing: {}", path, e))?;
+    let signature = key.signing_key.sign(&Blake2b512::digest(&content)).to_bytes();
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let trusted_comment = format!("timestamp:{}\tfile:{}\tprehashed", timestamp, file_name);
+    let global_signature = key.signing_key.sign(&[&signature[..], trusted_comment.as_bytes()].concat()).to_bytes();
+
+    let encode = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    let signature_file = format!(
+        "untrusted comment: signature from wasm_compiler secret key {}\n{}\ntrusted comment: {}\n{}\n",
+        key_id_hex(&key.key_id),
+        encode(&[&PREHASHED_ALGORITHM[..], &key.key_id, &signature].concat()),
+        trusted_comment,
+        encode(&global_signature),
+    );
+    let signature_path = PathBuf::from(format!("{}.minisig", path.display()));
+    std::fs::write(&signature_path, signature_file)
+        .map_err(|e| format!("Failed to write signature {:?}: {}", signature_path, e))?;
+    tracing::info!("Signed {:?} with key {}", path, key_id_hex(&key.key_id));
+    Ok(signature_path)
+}
+
+/// The `verify` subcommand: checks the signature of `SHA256SUMS` when a public key is given,
+/// then the checksum of every file it lists.
+pub fn verify(args: &VerifyArgs) -> Result<(), String> {
+    let checksums_path = args.output_dir.join(CHECKSUMS_FILE_NAME);
+    let checksums = std::fs::read(&checksums_path)
+        .map_err(|e| format!("Failed to read {:?}: {}", checksums_path, e))?;
+
+    let signature_path = args.output_dir.join(SIGNATURE_FILE_NAME);
+    match &args.public_key {
+        Some(public_key) => {
+            let trusted_comment = verify_signature(&checksums, &signature_path, &PublicKey::parse(public_key)?)?;
+            tracing::info!("Signature of {} is valid (trusted comment: {})", CHECKSUMS_FILE_NAME, trusted_comment);
+        }
+        None if signature_path.exists() => {
+            tracing::warn!("{} was not checked; pass --public-key to verify it", SIGNATURE_FILE_NAME);
+        }
+        None => {}
+    }
+
+    let checksums = String::from_utf8_lossy(&checksums);
+    let mut failures = Vec::new();
+    let mut count = 0;
+    for line in checksums.lines().filter(|line| !line.trim().is_empty()) {
+        let (expected, name) = line
+            .split_once("  ")
+            .or_else(|| line.split_once(" *"))
+            .ok_or_else(|| format!("Malformed line in {:?}: {}", checksums_path, line))?;
+        count += 1;
+        match file_system::hash_file(&args.output_dir.join(name)) {
+            Ok(actual) if actual.eq_ignore_ascii_case(expected) => tracing::info!("{}: OK", name),
+            Ok(_) => failures.push(format!("{} (checksum mismatch)", name)),
+            Err(_) => failures.push(format!("{} (missing)", name)),
+        }
+    }
+    if count == 0 {
+        return Err(format!("{:?} lists no files", checksums_path));
+    }
+    if !failures.is_empty() {
+        return Err(format!("{} of {} files do not match {}: {}", failures.len(), count, CHECKSUMS_FILE_NAME, failures.join(", ")));
+    }
+    tracing::info!("All {} files match {}", count, CHECKSUMS_FILE_NAME);
+    Ok(())
+}
+
+/// Checks the minisign signature of `content` and returns its trusted comment.
+fn verify_signature(content: &[u8], signature_path: &Path, public_key: &PublicKey) -> Result<String, String> {
+    let signature_file = std::fs::read_to_string(signature_path)
+        .map_err(|e| format!("Failed to read signature {:?}: {}", signature_path, e))?;
+    let malformed = || format!("{:?} is not a minisign signature", signature_path);
+    let mut lines = signature_file.lines().skip(1);
+    let decode = |line: Option<&str>| line.and_then(|line| base64::engine::general_purpose::STANDARD.decode(line.trim()).ok());
+
+    let blob = decode(lines.next()).filter(|blob| blob.len() == 74).ok_or_else(malformed)?;
+    let trusted_comment = lines
+        .next()
+        .and_then(|line| line.strip_prefix("trusted comment: "))
+        .ok_or_else(malformed)?;
+    let global_signature = decode(lines.next()).ok_or_else(malformed)?;
+
+    let key_id: [u8; 8] = blob[2..10].try_into().expect("slice of 8 bytes");
+    if key_id != public_key.key_id {
+        return Err(format!(
+            "{} was signed with key {}, not with the given public key {}",
+            CHECKSUMS_FILE_NAME, key_id_hex(&key_id), key_id_hex(&public_key.key_id)
+        ));
+    }
+    let message = match &blob[..2] {
+        algorithm if algorithm == PREHASHED_ALGORITHM => Blake2b512::digest(content).to_vec(),
+        algorithm if algorithm == KEY_ALGORITHM => content.to_vec(),
+        _ => return Err(malformed()),
+    };
+    let signature = Signature::from_slice(&blob[10..]).map_err(|_| malformed())?;
+    public_key.verifying_key.verify(&message, &signature)
+        .map_err(|_| format!("The signature of {} is invalid; the file was modified after signing", CHECKSUMS_FILE_NAME))?;
+
+    let global_signature = Signature::from_slice(&global_signature).map_err(|_| malformed())?;
+    public_key.verifying_key.verify(&[&blob[10..], trusted_comment.as_bytes()].concat(), &global_signature)
+        .map_err(|_| format!("The trusted comment of {:?} was modified after signing", signature_path))?;
+    Ok(trusted_comment.to_string())
+}
+
+/// Decodes the base64 line of a minisign key file (the line after the untrusted comment), or
+/// a bare base64 key
+fn decode_key_line(content: &str) -> Option<Vec<u8>> {
+    let line = content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))?;
+    base64::engine::general_purpose::STANDARD.decode(line).ok()
+}
+
+/// Key IDs as minisign prints them
+fn key_id_hex(key_id: &[u8; 8]) -> String {
+    format!("{:016X}", u64::from_le_bytes(*key_id))
+}
@@ -0,0 +1,194 @@
+//! Smoke tests of the build output, failing the build when the module does not start:
+//!
+//! - `--run-test node` imports the JS glue with Node.js, instantiates the module and calls
+//!   main() with the `--test-arg`s. A non-zero exit status, or an abort while instantiating
+//!   (e.g. a missing export), fails the build, so broken EXPORT settings show up at build time
+//!   instead of in production.
+//! - `--run-test browser` serves the output directory and loads the generated `index.html` in
+//!   headless Chromium. The page records `onRuntimeInitialized` and `onAbort` in the
+//!   `data-wasm-status` attribute of its root element, which is read from the dumped DOM.
+
+use crate::app_config::{AppConfig, TestRunner};
+use crate::output_assets::AssetNames;
+use crate::utils::command_runner::{is_command_in_path, run_build_command, CommandError};
+use crate::utils::static_server::StaticServer;
+
+/// Browser executables tried, in order, when `CHROME_BIN` is not set
+const BROWSER_CANDIDATES: &[&str] = &["chromium", "chromium-browser", "google-chrome", "google-chrome-stable", "chrome"];
+
+/// Virtual time the page gets to download, compile and start the module
+const BROWSER_TIME_BUDGET_MS: u32 = 10_000;
+
+/// Runs the smoke test selected with `--run-test`, if any.
+pub fn run_smoke_test(config: &AppConfig, assets: &AssetNames) -> Result<(), String> {
+    match config.run_test {
+        Some(TestRunner::Node) => run_node_test(config, assets),
+        Some(TestRunner::Browser) => run_browser_test(config),
+        None => Ok(()),
+    }
+}
+
+fn run_node_test(config: &AppConfig, assets: &AssetNames) -> Result<(), String> {
+    if !supports_environment(config, "node") {
+        crate::strict::fallback(config, "--run-test node skipped: the output is built for the web only (use --target-env node)")?;
+        return Ok(());
+    }
+    if !is_command_in_path("node", config) {
+        return Err("node not found in PATH. Install Node.js to use --run-test node.".to_string());
+    }
+
+    let js_path = std::path::absolute(config.output_dir.join(&assets.js))
+        .map_err(|e| format!("Failed to resolve {:?}: {}", assets.js, e))?;
+    if config.no_entry {
+        tracing::info!("Smoke-testing {}: library build, instantiating the module without calling main()", assets.js);
+    } else {
+        tracing::info!("Smoke-testing {}: calling main({})", assets.js, shell_words::join(&config.test_args));
+    }
+
+    let script = test_script(&js_path.to_string_lossy(), !config.no_entry, &config.test_args);
+    let args = ["--input-type=module", "--eval", script.as_str()];
+    match run_build_command("node", &args, Some(&config.output_dir), config) {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if !stdout.trim().is_empty() {
+                tracing::debug!("Smoke test output:\n{}", stdout.trim_end());
+            }
+            tracing::info!("Smoke test passed");
+            Ok(())
+        }
+        Err(CommandError::Failed { exit_code, stdout, stderr, .. }) => {
+            let status = exit_code.map_or("was terminated by a signal".to_string(), |code| format!("exited with status {}", code));
+            let output = [stdout.trim(), stderr.trim()].into_iter().filter(|s| !s.is_empty()).collect::<Vec<_>>().join("\n");
+            Err(format!("{} {}{}", assets.js, status, if output.is_empty() { String::new() } else { format!(":\n{}", output) }))
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn run_browser_test(config: &AppConfig) -> Result<(), String> {
+    if !supports_environment(config, "web") {
+        crate::strict::fallback(config, "--run-test browser skipped: the output is built for Node.js only")?;
+        return Ok(());
+    }
+    if !config.output_dir.join("index.html").is_file() {
+        return Err("--run-test browser needs the generated index.html; build with --webapp always".to_string());
+    }
+    let browser = find_browser(config).ok_or_else(|| format!(
+        "No Chromium-based browser found in PATH (tried {}). Install Chromium or set CHROME_BIN to use --run-test browser.",
+        BROWSER_CANDIDATES.join(", ")
+    ))?;
+
+    let server = StaticServer::start(&config.output_dir)
+        .map_err(|e| format!("Failed to serve {:?}: {}", config.output_dir, e))?;
+    let profile_dir = std::env::temp_dir().join(format!("wasm_compiler-browser-test-{}", std::process::id()));
+    let url = server.url("index.html");
+    tracing::info!("Smoke-testing {} in headless {}", url, browser);
+
+    let args = [
+        "--headless=new".to_string(),
+        // Sandboxing fails in containers and as root, which is where CI runs this
+        "--no-sandbox".to_string(),
+        // Software WebGL, for GUI applications on machines without a GPU
+        "--enable-unsafe-swiftshader".to_string(),
+        format!("--user-data-dir={}", profile_dir.display()),
+        format!("--virtual-time-budget={}", BROWSER_TIME_BUDGET_MS),
+        "--dump-dom".to_string(),
+        url,
+    ];
+    let result = run_build_command(&browser, &args, None, config);
+    drop(server);
+    let _ = std::fs::remove_dir_all(&profile_dir);
+    let dom = String::from_utf8_lossy(&result.map_err(|e| e.to_string())?.stdout).into_owned();
+
+    match dom_attribute(&dom, "data-wasm-status").as_deref() {
+        Some("initialized") => {
+            tracing::info!("Smoke test passed");
+            Ok(())
+        }
+        Some("aborted") => Err(format!(
+            "index.html aborted: {}",
+            dom_attribute(&dom, "data-wasm-abort").unwrap_or_else(|| "no reason given".to_string())
+        )),
+        _ => Err(format!(
+            "the runtime of index.html did not initialize within {} s",
+            BROWSER_TIME_BUDGET_MS / 1000
+        )),
+    }
+}
+
+/// `CHROME_BIN`, or the first of [`BROWSER_CANDIDATES`] found in PATH
+fn find_browser(config: &AppConfig) -> Option<String> {
+    if let Some(browser) = std::env::var_os("CHROME_BIN") {
+        return Some(browser.to_string_lossy().into_owned());
+    }
+    BROWSER_CANDIDATES
+        .iter()
+        .find(|candidate| is_command_in_path(candidate, config))
+        .map(|candidate| candidate.to_string())
+}
+
+/// The value of the first `name="..."` attribute in serialized HTML, unescaped
+fn dom_attribute(html: &str, name: &str) -> Option<String> {
+    let start = html.find(&format!("{}=\"", name))? + name.len() + 2;
+    let end = start + html[start..].find('"')?;
+    Some(
+        html[start..end]
+            .replace("&quot;", "\"")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+}
+
+/// Whether the glue is built for `environment` (`web` or `node`). A user `-sENVIRONMENT=`
+/// flag decides; otherwise `--target-env web` and `node` each exclude the other and any
+/// other target environment builds for both.
+fn supports_environment(config: &AppConfig, environment: &str) -> bool {
+    match config.emcc_flag.iter().rev().find_map(|flag| flag.strip_prefix("-sENVIRONMENT=")) {
+        Some(environments) => environments.split(',').any(|env| env.trim().trim_matches(['"', '\'']) == environment),
+        None => match config.target_env.to_lowercase().as_str() {
+            "web" | "node" => config.target_env.eq_ignore_ascii_case(environment),
+            _ => true,
+        },
+    }
+}
+
+/// The ES module run by `node --eval`. It mirrors the generated `run.mjs` launcher, but
+/// imports the glue by absolute path and takes the arguments from `--test-arg` only.
+fn test_script(js_path: &str, call_main: bool, args: &[String]) -> String {
+    format!(r#"import {{ pathToFileURL }} from 'node:url';
+
+const imported = await import(pathToFileURL({js_path}).href);
+const createModule = imported.default ?? imported;
+let exitCode = 0;
+
+const instance = await createModule({{
+    noInitialRun: true,
+    onExit: (code) => {{
+        exitCode = code;
+    }},
+}});
+
+if ({call_main}) {{
+    try {{
+        const status = instance.callMain({args});
+        if (typeof status === 'number') {{
+            exitCode = status;
+        }}
+    }} catch (e) {{
+        if (e && e.name === 'ExitStatus') {{
+            exitCode = e.status;
+        }} else {{
+            console.error(e);
+            exitCode = 1;
+        }}
+    }}
+}}
+
+process.exitCode = exitCode;
+"#,
+        js_path = serde_json::Value::from(js_path),
+        call_main = call_main,
+        args = serde_json::Value::from(args.to_vec()),
+    )
+}
@@ -0,0 +1,16 @@
+//! Strict mode (`--strict`) for CI. Where the build would otherwise guess or carry on with a
+//! warning, it fails instead: compiling a source file found in the project root when there
+//! is no build system, guessing ImGui backends or which executable or candidate to build,
+//! deprecated or misspelled emcc flags, and outputs an option asked for that are skipped.
+
+use crate::app_config::AppConfig;
+
+/// Reports a fallback the build takes: a warning, or with `--strict` an error.
+pub fn fallback(config: &AppConfig, message: impl AsRef<str>) -> Result<(), String> {
+    if config.strict {
+        Err(format!("{} (failing because of --strict)", message.as_ref()))
+    } else {
+        tracing::warn!("{}", message.as_ref());
+        Ok(())
+    }
+}
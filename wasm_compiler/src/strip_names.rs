@@ -0,0 +1,176 @@
+//! Stripping of function names from release builds (`--strip-names`). The `name` custom
+//! section, which debuggers and profilers use to show function names, is removed, and the
+//! exports only the JS glue uses are renamed to short names (`a`, `b`, ...), with the glue
+//! rewritten to match. The module's JavaScript API (`Module._foo`, embind bindings) keeps its
+//! names. The original names are written to `<output>.export-map.json` for debugging crash
+//! reports; it is not an output to deploy.
+//!
+//! An export is renamed only when the glue looks it up as `wasmExports["name"]` and every
+//! other occurrence of its name is such a lookup too, so exports the glue uses in any other way
+//! (`Module._name`, a `ccall` name) keep their names.
+//! Standalone modules, whose exports are their interface, and builds with a wasm source map,
+//! whose offsets the shorter export section would invalidate, only lose the name section.
+
+use std::collections::BTreeMap;
+use wasmparser::{Encoding, ExternalKind, Parser, Payload};
+use crate::app_config::AppConfig;
+use crate::utils::wasm_binary::{leb128, section};
+
+/// Exports that runtimes and tools look up by name
+const RESERVED_EXPORTS: &[&str] = &["memory", "__indirect_function_table", "_start", "_initialize"];
+
+/// Glue that iterates over the exports, e.g. with `-sDECLARE_ASM_MODULE_EXPORTS=0`, uses
+/// their names without quoting them
+const EXPORT_ITERATION: &[&str] = &["in wasmExports", "Object.keys(wasmExports", "Object.entries(wasmExports"];
+
+/// ID of the export section in the binary format
+const EXPORT_SECTION_ID: u8 = 7;
+
+/// Original and new name of each renamed export
+type RenamedExports = Vec<(String, String)>;
+
+/// Strips the name section of the module in the output directory and renames its internal
+/// exports.
+pub fn strip_names(config: &AppConfig) -> Result<(), String> {
+    let wasm_path = config.output_dir.join(format!("{}.wasm", config.output_name));
+    if !wasm_path.is_file() {
+        crate::strict::fallback(config, "--strip-names has no effect with --single-file: there is no .wasm file to strip")?;
+        return Ok(());
+    }
+    let module = std::fs::read(&wasm_path).map_err(|e| format!("Failed to read {:?}: {}", wasm_path, e))?;
+    let js_path = config.output_dir.join(format!("{}.js", config.output_name));
+    let map_path = config.output_dir.join(format!("{}.wasm.map", config.output_name));
+
+    let mut glue = None;
+    let keep_exports = if crate::wasm_runtime::is_standalone(config) {
+        Some("the exports of a standalone module are its interface")
+    } else if map_path.exists() {
+        Some("renaming them would invalidate the wasm source map")
+    } else {
+        let js = std::fs::read_to_string(&js_path).map_err(|e| format!("Failed to read JS output {:?}: {}", js_path, e))?;
+        if EXPORT_ITERATION.iter().any(|pattern| js.contains(pattern)) {
+            Some("the JS glue looks them up by iterating over the exports")
+        } else {
+            glue = Some(js);
+            None
+        }
+    };
+    if let Some(reason) = keep_exports {
+        tracing::info!("Keeping the export names of {:?}: {}", wasm_path, reason);
+    }
+
+    let (stripped, renamed) = rewrite_module(&module, |name| {
+        glue.as_deref().is_some_and(|glue| !RESERVED_EXPORTS.contains(&name) && only_looked_up(glue, name))
+    })?;
+
+    if let Some(mut glue) = glue.filter(|_| !renamed.is_empty()) {
+        for (name, new_name) in &renamed {
+            for quote in ['"', '\''] {
+                glue = glue.replace(
+                    &format!("wasmExports[{q}{}{q}]", name, q = quote),
+                    &format!("wasmExports[{q}{}{q}]", new_name, q = quote),
+                );
+            }
+        }
+        std::fs::write(&js_path, glue).map_err(|e| format!("Failed to rewrite JS output {:?}: {}", js_path, e))?;
+
+        let map_path = config.output_dir.join(format!("{}.export-map.json", config.output_name));
+        let mapping: BTreeMap<&str, &str> = renamed.iter().map(|(name, new_name)| (new_name.as_str(), name.as_str())).collect();
+        let json = serde_json::to_string_pretty(&mapping).map_err(|e| format!("Failed to serialize export map: {}", e))?;
+        std::fs::write(&map_path, json).map_err(|e| format!("Failed to write export map {:?}: {}", map_path, e))?;
+        tracing::info!("Renamed {} export(s); original names are in {:?} (do not deploy it)", renamed.len(), map_path);
+    }
+
+    std::fs::write(&wasm_path, &stripped).map_err(|e| format!("Failed to write {:?}: {}", wasm_path, e))?;
+    tracing::info!("Stripped names from {:?}: {} -> {} bytes", wasm_path, module.len(), stripped.len());
+    Ok(())
+}
+
+/// Whether the glue uses `name` only in `wasmExports` lookups, and at least once: every
+/// occurrence of it as a word, quoted or not (`Module._foo`, a `ccall` name), is a lookup
+fn only_looked_up(glue: &str, name: &str) -> bool {
+    let looked_up: usize = ['"', '\''].iter().map(|quote| glue.matches(&format!("wasmExports[{q}{}{q}]", name, q = quote)).count()).sum();
+    looked_up > 0 && word_occurrences(glue, name) == looked_up
+}
+
+/// How often `word` occurs in `text` not as part of a longer identifier
+fn word_occurrences(text: &str, word: &str) -> usize {
+    let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+    text.match_indices(word)
+        .filter(|(start, _)| {
+            let before = text[..*start].chars().next_back();
+            let after = text[start + word.len()..].chars().next();
+            !before.is_some_and(is_identifier) && !after.is_some_and(is_identifier)
+        })
+        .count()
+}
+
+/// Copies `module` without its name section, renaming the exports for which `rename` is true.
+/// Other sections are copied byte for byte. Returns the new module and the renamed exports.
+fn rewrite_module(module: &[u8], rename: impl Fn(&str) -> bool) -> Result<(Vec<u8>, RenamedExports), String> {
+    let mut output = Vec::with_capacity(module.len());
+    let mut renamed = Vec::new();
+    let mut position = 0;
+    for payload in Parser::new(0).parse_all(module) {
+        let payload = payload.map_err(|e| e.to_string())?;
+        if let Payload::Version { encoding: Encoding::Component, .. } = payload {
+            return Err("--strip-names works on core modules, not components".to_string());
+        }
+        if let Payload::Version { range, .. } = &payload {
+            output.extend(&module[..range.end]);
+            position = range.end;
+            continue;
+        }
+        let Some((_, range)) = payload.as_section() else { continue };
+        let original = &module[position..range.end];
+        position = range.end;
+        match payload {
+            Payload::CustomSection(custom) if custom.name() == "name" => {}
+            Payload::ExportSection(reader) => {
+                let exports = reader.into_iter().collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+                let taken: Vec<&str> = exports.iter().filter(|export| !rename(export.name)).map(|export| export.name).collect();
+                let mut short_names = (0..).map(short_name).filter(|name| !taken.contains(&name.as_str()));
+
+                let mut content = leb128(exports.len());
+                for export in &exports {
+                    let name = if rename(export.name) {
+                        let new_name = short_names.next().expect("unbounded name sequence");
+                        renamed.push((export.name.to_string(), new_name.clone()));
+                        new_name
+                    } else {
+                        export.name.to_string()
+                    };
+                    content.extend(leb128(name.len()));
+                    content.extend(name.as_bytes());
+                    content.push(match export.kind {
+                        ExternalKind::Func => 0,
+                        ExternalKind::Table => 1,
+                        ExternalKind::Memory => 2,
+                        ExternalKind::Global => 3,
+                        ExternalKind::Tag => 4,
+                    });
+                    content.extend(leb128(export.index as usize));
+                }
+                output.extend(section(EXPORT_SECTION_ID, &content));
+            }
+            _ => output.extend(original),
+        }
+    }
+    Ok((output, renamed))
+}
+
+/// `a`..`z`, `A`..`Z`, then `aa`, `ab`, ...
+fn short_name(mut index: usize) -> String {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut name = Vec::new();
+    loop {
+        name.push(ALPHABET[index % ALPHABET.len()]);
+        index /= ALPHABET.len();
+        if index == 0 {
+            break;
+        }
+        index -= 1;
+    }
+    name.reverse();
+    String::from_utf8(name).expect("ASCII alphabet")
+}
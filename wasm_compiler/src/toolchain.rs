@@ -0,0 +1,136 @@
+//! A snapshot of the toolchain a build runs with: where emcc, node, Python, CMake, make and
+//! Binaryen are and which versions they are, plus the environment variables that change how
+//! Emscripten behaves. The `doctor` subcommand prints it, so a report of a build that only
+//! fails on one machine says what that machine has. `build-manifest.json` records only the
+//! versions, as the manifest is deployed with the module and paths and variables of the build
+//! machine do not belong there.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use serde::Serialize;
+use crate::app_config::AppConfig;
+use crate::utils::command_runner::{find_in_path, resolve_emscripten_tool};
+
+/// Environment variables recorded in the snapshot
+const RECORDED_VARIABLES: &[&str] = &[
+    "EMSDK",
+    "EM_CONFIG",
+    "EM_CACHE",
+    "EM_NODE_JS",
+    "EMSDK_NODE",
+    "EMSDK_PYTHON",
+    "EMCC_CFLAGS",
+    "EMCC_DEBUG",
+    "EMCC_SKIP_SANITY_CHECK",
+    "CMAKE_GENERATOR",
+    "CFLAGS",
+    "CXXFLAGS",
+    "LDFLAGS",
+];
+
+/// One tool of the toolchain
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolInfo {
+    pub name: String,
+    /// Where it was found; `None` if it is not installed
+    pub path: Option<PathBuf>,
+    /// First line of its `--version` output
+    pub version: Option<String>,
+}
+
+/// The tools and environment variables of a build
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolchainSnapshot {
+    /// `<os>-<arch>` of the machine
+    pub host: String,
+    pub tools: Vec<ToolInfo>,
+    /// The recorded environment variables that are set for the build tools
+    pub env: BTreeMap<String, String>,
+}
+
+/// The versions of the tools of a build, without their paths or the environment
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolchainVersions {
+    /// `<os>-<arch>` of the machine
+    pub host: String,
+    /// First line of the `--version` output of each tool that was found, by tool name
+    pub versions: BTreeMap<String, String>,
+}
+
+impl ToolchainSnapshot {
+    /// Looks up the tools and variables with the tool environment and pinned tools of `config`.
+    pub fn collect(config: &AppConfig) -> Self {
+        let env = config.tool_env();
+        let emcc = locate(config, "emcc", &env);
+        let python = variable(&env, "EMSDK_PYTHON")
+            .map(PathBuf::from)
+            .or_else(|| ["python3", "python"].iter().find_map(|name| find_in_path(name, &env)));
+        // Binaryen ships with the SDK (`upstream/bin` next to `upstream/emscripten`) and is
+        // rarely in PATH
+        let binaryen = find_in_path("wasm-opt", &env).or_else(|| {
+            let emscripten_dir = emcc.as_deref()?.parent()?;
+            let wasm_opt = emscripten_dir.parent()?.join("bin").join(format!("wasm-opt{}", std::env::consts::EXE_SUFFIX));
+            wasm_opt.is_file().then_some(wasm_opt)
+        });
+
+        let tools = vec![
+            tool("emcc", emcc, &env),
+            tool("node", locate(config, "node", &env), &env),
+            tool("python", python, &env),
+            tool("cmake", locate(config, "cmake", &env), &env),
+            tool("make", locate(config, "make", &env), &env),
+            tool("binaryen", binaryen, &env),
+        ];
+        let env = RECORDED_VARIABLES
+            .iter()
+            .filter_map(|name| variable(&env, name).map(|value| (name.to_string(), value)))
+            .collect();
+        ToolchainSnapshot {
+            host: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+            tools,
+            env,
+        }
+    }
+
+    /// The host and the versions of the tools that were found, for `build-manifest.json`
+    pub fn versions(&self) -> ToolchainVersions {
+        ToolchainVersions {
+            host: self.host.clone(),
+            versions: self.tools.iter().filter_map(|tool| Some((tool.name.clone(), tool.version.clone()?))).collect(),
+        }
+    }
+
+    /// Whether `name` was found
+    pub fn has(&self, name: &str) -> bool {
+        self.tools.iter().any(|tool| tool.name == name && tool.path.is_some())
+    }
+}
+
+/// `tool` pinned in `config`, or found in the PATH of `env`
+pub(crate) fn locate(config: &AppConfig, tool: &str, env: &[(OsString, OsString)]) -> Option<PathBuf> {
+    config.tool_path(tool).filter(|path| path.is_file()).or_else(|| find_in_path(&resolve_emscripten_tool(tool), env))
+}
+
+fn tool(name: &str, path: Option<PathBuf>, env: &[(OsString, OsString)]) -> ToolInfo {
+    let version = path.as_deref().and_then(|path| version(path, env));
+    ToolInfo { name: name.to_string(), path, version }
+}
+
+/// First non-empty line `program --version` prints
+fn version(program: &Path, env: &[(OsString, OsString)]) -> Option<String> {
+    let output = Command::new(program).arg("--version").envs(env.iter().map(|(key, value)| (key, value))).output().ok()?;
+    let text = if output.stdout.is_empty() { output.stderr } else { output.stdout };
+    String::from_utf8_lossy(&text).lines().map(str::trim).find(|line| !line.is_empty()).map(str::to_string)
+}
+
+/// The value of `name` for the build tools: set by `env`, or inherited
+fn variable(env: &[(OsString, OsString)], name: &str) -> Option<String> {
+    env.iter()
+        .rev()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.to_string_lossy().into_owned())
+        .or_else(|| std::env::var(name).ok())
+        .filter(|value| !value.is_empty())
+}
@@ -0,0 +1,156 @@
+//! Transcript of the commands a build runs (`--log-file`). Every command is recorded with
+//! its arguments, working directory, the environment variables set for it that differ from
+//! the inherited ones, its exit status and duration, and its full stdout and stderr (without
+//! colors), whatever the log level on the console. Attach the file to a bug report about a failed build.
+//!
+//! The transcript is written until the guard [`start`] returns is dropped: on the command line
+//! at the end of the run, so builds of several projects or matrix targets append to the same
+//! file, and for a library build when `compile` returns.
+
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+static TRANSCRIPT: Mutex<Option<Transcript>> = Mutex::new(None);
+
+struct Transcript {
+    path: PathBuf,
+    file: File,
+    start: Instant,
+}
+
+/// How a recorded command ended
+pub(crate) enum Outcome<'a> {
+    /// It ran and its output was captured
+    Output(&'a Output),
+    /// It ran on the terminal, so there is no output to record
+    Status(ExitStatus),
+    /// It could not be started, or was cancelled
+    Error(&'a dyn Display),
+}
+
+/// Keeps the transcript going to its file until it is dropped, then goes back to the one
+/// written before, if any
+#[must_use = "the transcript stops when the guard is dropped"]
+pub struct TranscriptGuard {
+    /// The transcript [`start`] replaced; `None` when it already went to the file
+    previous: Option<Option<Transcript>>,
+}
+
+impl Drop for TranscriptGuard {
+    fn drop(&mut self) {
+        if let (Some(previous), Ok(mut transcript)) = (self.previous.take(), TRANSCRIPT.lock()) {
+            *transcript = previous;
+        }
+    }
+}
+
+/// Starts writing the transcript to `path`, replacing the file, until the returned guard is
+/// dropped. Does nothing if the transcript already goes there.
+pub fn start(path: &Path) -> Result<TranscriptGuard, String> {
+    let path = std::path::absolute(path).map_err(|e| format!("Invalid log file {:?}: {}", path, e))?;
+    let mut transcript = TRANSCRIPT.lock().expect("transcript lock");
+    if transcript.as_ref().is_some_and(|transcript| transcript.path == path) {
+        return Ok(TranscriptGuard { previous: None });
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    let mut file = File::create(&path).map_err(|e| format!("Failed to create log file {:?}: {}", path, e))?;
+    let started = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let argv: Vec<String> = std::env::args().collect();
+    write!(
+        file,
+        "# wasm_compiler {} on {}-{}, started at {} (seconds since the Unix epoch)\n# {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        started,
+        shell_words::join(&argv)
+    )
+    .map_err(|e| format!("Failed to write log file {:?}: {}", path, e))?;
+    tracing::info!("Recording the commands of the build in {:?}", path);
+    let previous = transcript.replace(Transcript { path, file, start: Instant::now() });
+    Ok(TranscriptGuard { previous: Some(previous) })
+}
+
+/// Adds a line to the transcript, if one is being written.
+pub fn note(message: impl Display) {
+    write_entry(|elapsed| format!("\n# [{:.3}s] {}\n", elapsed.as_secs_f64(), message));
+}
+
+/// Records a command that was run, if a transcript is being written.
+pub(crate) fn record_command(
+    program: &str,
+    args: &[String],
+    current_dir: Option<&Path>,
+    env: &[(OsString, OsString)],
+    duration: Duration,
+    outcome: Outcome,
+) {
+    write_entry(|elapsed| {
+        let command_line = shell_words::join(std::iter::once(program).chain(args.iter().map(String::as_str)));
+        let mut entry = format!("\n==> [{:.3}s] {}\n", elapsed.saturating_sub(duration).as_secs_f64(), command_line);
+        let cwd = current_dir.map(Path::to_path_buf).or_else(|| std::env::current_dir().ok()).unwrap_or_default();
+        entry.push_str(&format!("cwd: {}\n", cwd.display()));
+        for (key, value) in env_changes(env) {
+            match std::env::var_os(key) {
+                Some(previous) => entry.push_str(&format!(
+                    "env: {}={} (was {})\n",
+                    key.to_string_lossy(),
+                    value.to_string_lossy(),
+                    previous.to_string_lossy()
+                )),
+                None => entry.push_str(&format!("env: {}={}\n", key.to_string_lossy(), value.to_string_lossy())),
+            }
+        }
+        let status = match &outcome {
+            Outcome::Output(output) => output.status.to_string(),
+            Outcome::Status(status) => format!("{} (ran on the terminal, output not captured)", status),
+            Outcome::Error(error) => format!("did not complete: {}", error),
+        };
+        entry.push_str(&format!("{} after {:.3}s\n", status, duration.as_secs_f64()));
+        if let Outcome::Output(output) = outcome {
+            for (name, content) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+                if content.is_empty() {
+                    continue;
+                }
+                let text = String::from_utf8_lossy(content);
+                let text = crate::diagnostics::strip_ansi(&text);
+                entry.push_str(&format!("--- {} ---\n{}", name, text));
+                if !text.ends_with('\n') {
+                    entry.push('\n');
+                }
+            }
+        }
+        entry
+    });
+}
+
+/// The variables of `env` whose value differs from the inherited one; for repeated keys the
+/// last value counts, as for [`std::process::Command::envs`]
+pub(crate) fn env_changes(env: &[(OsString, OsString)]) -> Vec<(&OsString, &OsString)> {
+    let mut changes: Vec<(&OsString, &OsString)> = Vec::new();
+    for (key, value) in env {
+        changes.retain(|(previous, _)| *previous != key);
+        if std::env::var_os(key).as_ref() != Some(value) {
+            changes.push((key, value));
+        }
+    }
+    changes
+}
+
+fn write_entry(entry: impl FnOnce(Duration) -> String) {
+    let mut transcript = TRANSCRIPT.lock().expect("transcript lock");
+    let Some(transcript) = transcript.as_mut() else { return };
+    let entry = entry(transcript.start.elapsed());
+    // A full disk must not fail the build it is recording
+    if let Err(e) = transcript.file.write_all(entry.as_bytes()) {
+        tracing::debug!("Failed to write to {:?}: {}", transcript.path, e);
+    }
+}
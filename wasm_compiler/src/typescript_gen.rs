@@ -0,0 +1,209 @@
+//! Generation of a typed JavaScript API for the exported C functions.
+//!
+//! The declarations of the exported functions are looked up in the project headers and
+//! sources with a lightweight parser (no preprocessing; C-style signatures only). From them
+//! `<output>.api.js` wraps each function with `Module.cwrap`, and `<output>.api.d.ts` declares
+//! the matching TypeScript interface.
+
+use std::path::{Path, PathBuf};
+use crate::app_config::AppConfig;
+use crate::exports::{self, ExportKind};
+use crate::output_assets::AssetNames;
+use crate::utils::source_selection::project_files;
+
+/// A C type as seen from JavaScript
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JsType {
+    Number,
+    BigInt,
+    Boolean,
+    String,
+    Void,
+}
+
+impl JsType {
+    fn from_c(c_type: &str) -> Self {
+        let normalized = c_type.replace("const", " ").split_whitespace().collect::<Vec<_>>().join(" ");
+        let normalized = normalized.replace(" *", "*");
+        match normalized.as_str() {
+            "void" => JsType::Void,
+            "bool" | "_Bool" => JsType::Boolean,
+            "char*" => JsType::String,
+            "int64_t" | "uint64_t" | "long long" | "unsigned long long" => JsType::BigInt,
+            _ => JsType::Number, // Integers, floating point and pointers
+        }
+    }
+
+    fn typescript(self) -> &'static str {
+        match self {
+            JsType::Number => "number",
+            JsType::BigInt => "bigint",
+            JsType::Boolean => "boolean",
+            JsType::String => "string",
+            JsType::Void => "void",
+        }
+    }
+
+    /// The type name `cwrap` expects (`null` for void)
+    fn cwrap(self) -> &'static str {
+        match self {
+            JsType::Number | JsType::BigInt => "'number'",
+            JsType::Boolean => "'boolean'",
+            JsType::String => "'string'",
+            JsType::Void => "null",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Signature {
+    name: String,
+    return_type: JsType,
+    /// Parameter names (generated when the declaration has none) and types
+    params: Vec<(String, JsType)>,
+}
+
+/// Writes `<output>.api.js` and `<output>.api.d.ts` for the exported C functions whose
+/// declarations can be found in the project.
+pub fn generate_typescript_api(config: &AppConfig, assets: &AssetNames, project_path: &Path) -> Result<Vec<PathBuf>, String> {
+    let names: Vec<String> = exports::known_exports(config)
+        .into_iter()
+        .filter(|export| export.kind == ExportKind::CFunction)
+        .map(|export| export.name)
+        .collect();
+    if names.is_empty() {
+        crate::strict::fallback(config, "--typescript: no exported C functions are known; export them with --export or EMSCRIPTEN_KEEPALIVE")?;
+        return Ok(Vec::new());
+    }
+
+    let sources: Vec<String> = declaration_files(project_path)
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .map(|content| exports::strip_comments(&content))
+        .collect();
+
+    let mut signatures = Vec::new();
+    for name in names {
+        match sources.iter().find_map(|content| find_signature(content, &name)) {
+            Some(signature) => signatures.push(signature),
+            None => crate::strict::fallback(
+                config,
+                format!("--typescript: no declaration found for exported function {}; it is left out of the API", name),
+            )?,
+        }
+    }
+
+    let api_name = config.output_name.as_str();
+    let mut js = format!(
+        "// Generated by wasm_compiler from the exported C declarations.\n\
+         // Usage: const api = createApi(await createModule());\n\
+         // Module: ./{}\n\n\
+         export function createApi(module) {{\n    return {{\n",
+        assets.js
+    );
+    let mut dts = String::from(
+        "// Generated by wasm_compiler from the exported C declarations.\n\n\
+         export interface Api {\n",
+    );
+    for signature in &signatures {
+        let arg_types: Vec<&str> = signature.params.iter().map(|(_, ty)| ty.cwrap()).collect();
+        js.push_str(&format!(
+            "        {}: module.cwrap('{}', {}, [{}]),\n",
+            signature.name, signature.name, signature.return_type.cwrap(), arg_types.join(", ")
+        ));
+        let params: Vec<String> = signature.params.iter()
+            .map(|(name, ty)| format!("{}: {}", name, ty.typescript()))
+            .collect();
+        dts.push_str(&format!(
+            "    {}({}): {};\n",
+            signature.name, params.join(", "), signature.return_type.typescript()
+        ));
+    }
+    js.push_str("    };\n}\n");
+    dts.push_str("}\n\n/** Wraps the exported functions of an instantiated module */\nexport function createApi(module: object): Api;\n");
+
+    let js_path = config.output_dir.join(format!("{}.api.js", api_name));
+    let dts_path = config.output_dir.join(format!("{}.api.d.ts", api_name));
+    std::fs::write(&js_path, js).map_err(|e| format!("Failed to write {:?}: {}", js_path, e))?;
+    std::fs::write(&dts_path, dts).map_err(|e| format!("Failed to write {:?}: {}", dts_path, e))?;
+    tracing::info!("Generated typed API for {} function(s): {:?}, {:?}", signatures.len(), js_path, dts_path);
+    Ok(vec![js_path, dts_path])
+}
+
+/// Headers first, since they hold the declarations meant for consumers, then sources.
+fn declaration_files(project_path: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = project_files(project_path, Some(4))
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ["h", "hpp", "hh", "c", "cpp", "cc", "cxx"].contains(&ext))
+        })
+        .collect();
+    files.sort_by_key(|path| !path.extension().is_some_and(|ext| ext.to_string_lossy().starts_with('h')));
+    files
+}
+
+/// Finds the declaration or definition of `name` and parses its signature.
+fn find_signature(content: &str, name: &str) -> Option<Signature> {
+    for (index, _) in content.match_indices(name) {
+        let before = &content[..index];
+        if before.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+            continue;
+        }
+        let after = content[index + name.len()..].trim_start();
+        let Some(params_start) = after.strip_prefix('(') else {
+            continue;
+        };
+        let Some(params_end) = params_start.find(')') else {
+            continue;
+        };
+
+        // The return type runs from the previous statement boundary up to the name
+        let statement_start = before.rfind([';', '{', '}', '\n']).map_or(0, |i| i + 1);
+        let return_type = before[statement_start..]
+            .replace("extern \"C\"", " ")
+            .split_whitespace()
+            .filter(|word| !["EMSCRIPTEN_KEEPALIVE", "static", "inline", "extern"].contains(word))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if return_type.is_empty() || return_type.contains(['=', '(', ')', '#', ',']) || return_type == "return" {
+            continue;
+        }
+
+        let params = parse_params(&params_start[..params_end]);
+        return Some(Signature {
+            name: name.to_string(),
+            return_type: JsType::from_c(&return_type),
+            params,
+        });
+    }
+    None
+}
+
+fn parse_params(params: &str) -> Vec<(String, JsType)> {
+    let params = params.trim();
+    if params.is_empty() || params == "void" {
+        return Vec::new();
+    }
+    params
+        .split(',')
+        .enumerate()
+        .map(|(index, param)| {
+            let param = param.trim();
+            // The last identifier is the parameter name unless the declaration is a bare type
+            let start = param.rfind(|c: char| !(c.is_alphanumeric() || c == '_')).map_or(0, |i| i + 1);
+            let (c_type, name) = if start > 0 && start < param.len() && !is_type_keyword(&param[start..]) {
+                (&param[..start], param[start..].to_string())
+            } else {
+                (param, format!("arg{}", index))
+            };
+            (name, JsType::from_c(c_type))
+        })
+        .collect()
+}
+
+fn is_type_keyword(word: &str) -> bool {
+    ["int", "char", "short", "long", "float", "double", "unsigned", "signed", "bool", "size_t", "void"].contains(&word)
+        || word.ends_with("_t")
+}
@@ -0,0 +1,84 @@
+//! Whether a project is a graphical application (`--ui`), which decides whether `--webapp auto`
+//! wraps the build in a page with a canvas. Detected from what the project uses rather than
+//! from its path: `--with-imgui`, emcc flags linking SDL, GLFW or WebGL, the library handlers
+//! that detect the project (all GUI toolkits except OpenCV), SDL, GLFW or OpenGL in the CMake
+//! or Make files, and SDL, GLFW, OpenGL/WebGL, ImGui and canvas APIs in the sources.
+
+use std::path::Path;
+use crate::app_config::{AppConfig, UiKind};
+use crate::utils::source_selection::project_files;
+
+/// Source text only graphical applications contain, with what it reveals
+const SOURCE_MARKERS: &[(&str, &str)] = &[
+    ("#include <SDL", "SDL"),
+    ("#include \"SDL", "SDL"),
+    ("SDL_CreateWindow", "SDL"),
+    ("GLFW/glfw3.h", "GLFW"),
+    ("glfwCreateWindow", "GLFW"),
+    ("#include <GLES", "OpenGL ES"),
+    ("#include <GL/", "OpenGL"),
+    ("emscripten_webgl_create_context", "WebGL"),
+    ("#include <webgpu/", "WebGPU"),
+    ("imgui.h", "ImGui"),
+    ("emscripten_set_canvas_element_size", "the canvas"),
+    ("emscripten_set_main_loop", "a browser main loop"),
+];
+
+/// Text of `CMakeLists.txt` or a `Makefile` that links a graphics library
+const BUILD_FILE_MARKERS: &[(&str, &str)] = &[
+    ("USE_SDL", "SDL"),
+    ("find_package(SDL", "SDL"),
+    ("USE_GLFW", "GLFW"),
+    ("find_package(glfw", "GLFW"),
+    ("find_package(OpenGL", "OpenGL"),
+    ("USE_WEBGL", "WebGL"),
+    ("MAX_WEBGL_VERSION", "WebGL"),
+];
+
+/// The `--ui` kind, detected when it was not given.
+pub fn resolve(config: &AppConfig) -> UiKind {
+    config.ui.unwrap_or_else(|| detect(config, &config.project_path).0)
+}
+
+/// Detects the kind of application, with the reason for the verdict.
+pub fn detect(config: &AppConfig, project_path: &Path) -> (UiKind, String) {
+    if config.with_imgui {
+        return (UiKind::Graphical, "--with-imgui".to_string());
+    }
+    if let Some(flag) = config.emcc_flag.iter().find(|flag| {
+        flag.contains("WEBGL") || flag.contains("USE_SDL") || flag.contains("USE_GLFW")
+    }) {
+        return (UiKind::Graphical, format!("emcc flag {}", flag));
+    }
+
+    let limits = config.detection_limits();
+    if let Some(handler) = config.handlers
+        .detect_libraries_limited(project_path, &limits)
+        .into_iter()
+        .find(|handler| handler.is_graphical())
+    {
+        return (UiKind::Graphical, format!("uses {}", handler.library_name()));
+    }
+
+    for build_file in ["CMakeLists.txt", "Makefile", "makefile"] {
+        let Some(content) = limits.read_source(&project_path.join(build_file)) else { continue };
+        if let Some((_, what)) = BUILD_FILE_MARKERS.iter().find(|(marker, _)| content.contains(marker)) {
+            return (UiKind::Graphical, format!("{} links {}", build_file, what));
+        }
+    }
+
+    let sources = project_files(project_path, Some(limits.max_depth)).into_iter().filter(|path| {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ["cpp", "cxx", "cc", "c", "h", "hpp", "hh"].contains(&ext))
+    });
+    for source in sources {
+        let Some(content) = limits.read_source(&source) else { continue };
+        if let Some((_, what)) = SOURCE_MARKERS.iter().find(|(marker, _)| content.contains(marker)) {
+            let relative = source.strip_prefix(project_path).unwrap_or(&source);
+            return (UiKind::Graphical, format!("{} uses {}", relative.display(), what));
+        }
+    }
+
+    (UiKind::Console, "no SDL, GLFW, OpenGL, ImGui or canvas API usage found".to_string())
+}
@@ -0,0 +1,209 @@
+//! The command invocations of a CMake script (`CMakeLists.txt`, `*.cmake`) with their
+//! arguments, tokenized the way CMake does: line and bracket comments (`#`, `#[[...]]`) are
+//! skipped, quoted arguments (`"a b"`, with `\` escapes) and bracket arguments (`[[a b]]`) are
+//! one argument each and may contain `#` and parentheses, and nested parentheses
+//! (`if((A) AND B)`) do not end the invocation. Variable references are kept as written.
+
+/// A command invocation, e.g. `target_link_libraries(app PRIVATE GL)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    /// The command name in lower case; CMake commands are case-insensitive
+    pub name: String,
+    /// The arguments, unquoted, without the parentheses of nested groups
+    pub arguments: Vec<String>,
+}
+
+/// The command invocations of `content`, in order
+pub fn invocations(content: &str) -> Vec<Invocation> {
+    let mut tokenizer = Tokenizer { chars: content.chars().collect(), index: 0 };
+    let mut invocations = Vec::new();
+    while let Some(c) = tokenizer.peek() {
+        if c == '#' {
+            tokenizer.skip_comment();
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let name = tokenizer.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+            tokenizer.take_while(|c| c == ' ' || c == '\t');
+            if tokenizer.peek() == Some('(') {
+                tokenizer.index += 1;
+                let arguments = tokenizer.arguments();
+                invocations.push(Invocation { name: name.to_ascii_lowercase(), arguments });
+            }
+        } else {
+            tokenizer.index += 1;
+        }
+    }
+    invocations
+}
+
+struct Tokenizer {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Tokenizer {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn take_while(&mut self, accept: impl Fn(char) -> bool) -> String {
+        let start = self.index;
+        while self.peek().is_some_and(&accept) {
+            self.index += 1;
+        }
+        self.chars[start..self.index].iter().collect()
+    }
+
+    /// The arguments up to the `)` closing the invocation, after its `(`
+    fn arguments(&mut self) -> Vec<String> {
+        let mut arguments = Vec::new();
+        let mut depth = 1;
+        while let Some(c) = self.peek() {
+            match c {
+                c if c.is_whitespace() => self.index += 1,
+                '#' => self.skip_comment(),
+                '(' => {
+                    depth += 1;
+                    self.index += 1;
+                }
+                ')' => {
+                    self.index += 1;
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                '"' => {
+                    self.index += 1;
+                    arguments.push(self.quoted());
+                }
+                '[' if self.bracket_level().is_some() => arguments.push(self.bracket()),
+                _ => arguments.push(self.unquoted()),
+            }
+        }
+        arguments
+    }
+
+    /// A quoted argument, after its opening `"`
+    fn quoted(&mut self) -> String {
+        let mut argument = String::new();
+        while let Some(c) = self.peek() {
+            self.index += 1;
+            match c {
+                '"' => break,
+                '\\' => match self.peek() {
+                    // A line continuation
+                    Some('\n') => self.index += 1,
+                    Some(escaped) => {
+                        self.index += 1;
+                        argument.push(match escaped {
+                            'n' => '\n',
+                            't' => '\t',
+                            'r' => '\r',
+                            escaped => escaped,
+                        });
+                    }
+                    None => {}
+                },
+                c => argument.push(c),
+            }
+        }
+        argument
+    }
+
+    /// An unquoted argument, up to whitespace, a parenthesis or a comment
+    fn unquoted(&mut self) -> String {
+        let mut argument = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || matches!(c, '(' | ')' | '#') {
+                break;
+            }
+            self.index += 1;
+            if c == '\\' {
+                argument.extend(self.peek());
+                self.index += 1;
+            } else {
+                argument.push(c);
+            }
+        }
+        argument
+    }
+
+    /// The number of `=` of a bracket opening (`[[`, `[=[`, ...) at the current position
+    fn bracket_level(&self) -> Option<usize> {
+        if self.peek() != Some('[') {
+            return None;
+        }
+        let level = self.chars[self.index + 1..].iter().take_while(|&&c| c == '=').count();
+        (self.chars.get(self.index + 1 + level) == Some(&'[')).then_some(level)
+    }
+
+    /// The content of a bracket argument or comment, consuming it up to its closing bracket
+    fn bracket(&mut self) -> String {
+        let level = self.bracket_level().unwrap_or(0);
+        self.index += level + 2;
+        let close: Vec<char> = std::iter::once(']').chain(std::iter::repeat_n('=', level)).chain(std::iter::once(']')).collect();
+        let start = self.index;
+        while self.index < self.chars.len() && !self.chars[self.index..].starts_with(&close) {
+            self.index += 1;
+        }
+        let content: String = self.chars[start..self.index].iter().collect();
+        self.index = (self.index + close.len()).min(self.chars.len());
+        // A newline right after the opening bracket is not part of the content
+        content.strip_prefix('\n').map(str::to_string).unwrap_or(content)
+    }
+
+    /// Skips a `#` line comment or `#[[...]]` bracket comment
+    fn skip_comment(&mut self) {
+        self.index += 1;
+        if self.bracket_level().is_some() {
+            self.bracket();
+        } else {
+            self.take_while(|c| c != '\n');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(content: &str) -> Vec<Vec<String>> {
+        invocations(content).into_iter().map(|invocation| invocation.arguments).collect()
+    }
+
+    #[test]
+    fn names_are_lower_case() {
+        let names: Vec<String> = invocations("PROJECT(app)\nAdd_Executable(app main.cpp)").into_iter().map(|invocation| invocation.name).collect();
+        assert_eq!(names, ["project", "add_executable"]);
+    }
+
+    #[test]
+    fn quoted_arguments() {
+        assert_eq!(
+            arguments(r#"set(FLAGS "-O2 -g" "a # (b)" "say \"hi\"\n" "one \
+two")"#),
+            [vec!["FLAGS", "-O2 -g", "a # (b)", "say \"hi\"\n", "one two"]]
+        );
+    }
+
+    #[test]
+    fn bracket_arguments() {
+        assert_eq!(arguments("message([[a (b) # c]] [=[x]]y]=] [[\nline]])"), [vec!["a (b) # c", "x]]y", "line"]]);
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let content = "# add_executable(line)\n#[[ add_executable(bracket)\n]]\nadd_executable(app # comment )\n  main.cpp #[[ ) ]] util.cpp)";
+        assert_eq!(arguments(content), [vec!["app", "main.cpp", "util.cpp"]]);
+    }
+
+    #[test]
+    fn nested_parentheses() {
+        assert_eq!(arguments("if((A OR B) AND (C))\nendif()"), [vec!["A", "OR", "B", "AND", "C"], vec![]]);
+    }
+
+    #[test]
+    fn variable_references_are_kept() {
+        assert_eq!(arguments("target_link_libraries(${PROJECT_NAME} PRIVATE ${LIBS})"), [vec!["${PROJECT_NAME}", "PRIVATE", "${LIBS}"]]);
+    }
+}
@@ -1,153 +1,582 @@
-use std::process::{Command, Output, Stdio};
-use std::path::Path;
-use std::ffi::OsStr;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::path::{Path, PathBuf};
+use std::ffi::{OsStr, OsString};
+use std::io::{BufRead, BufReader, Read};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::app_config::AppConfig;
+use crate::cancel::CancelHandle;
+use crate::diagnostics;
+use crate::events::{Events, Phase};
+use crate::resource_limits::{CommandLimits, DISK_CHECK_INTERVAL};
+use super::posix_shell;
+use crate::repro_script;
+use crate::transcript::{self, Outcome};
+
+/// How long a terminated command may take to exit after SIGTERM before it is killed
+#[cfg(unix)]
+pub(crate) const TERMINATE_GRACE: Duration = Duration::from_secs(2);
+
+/// How often a command with limits is checked for having exited
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Most retries of a command that failed with a network error (`--retries`)
+pub const MAX_RETRIES: u32 = 10;
+
+/// Longest wait before retrying a command; the waits double from 2 seconds up to it
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Output of a failed command telling that the network, not the build, failed: Python's
+/// urllib (Emscripten ports, emsdk), curl and git
+const NETWORK_ERRORS: &[&str] = &[
+    "urlopen error",
+    "HTTP Error 5",
+    "Connection reset",
+    "Connection refused",
+    "Connection timed out",
+    "Read timed out",
+    "Remote end closed connection",
+    "IncompleteRead",
+    "Temporary failure in name resolution",
+    "Name or service not known",
+    "Could not resolve host",
+    "Network is unreachable",
+    "Failed to connect to",
+    "unable to access 'http",
+];
+
+/// Failure of an external command, with the output it captured.
+#[derive(thiserror::Error, Debug)]
+pub enum CommandError {
+    #[error("{tool} not found in PATH. Please ensure Emscripten SDK is installed and configured.")]
+    NotFound { tool: String },
+    #[error("Failed to execute command '{tool}': {source}. Is it installed and in your PATH?")]
+    Spawn {
+        tool: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The command was terminated because the build was cancelled
+    #[error("'{tool}' was cancelled")]
+    Cancelled { tool: String },
+    /// The command ran longer than its `--command-timeout` and was terminated
+    #[error("'{tool}' did not finish within {seconds}s and was terminated (see --command-timeout)")]
+    TimedOut { tool: String, seconds: u64 },
+    /// The phase the command ran in took longer than its `[limits]` timeout, and the
+    /// command was terminated
+    #[error("'{tool}' was terminated because the {phase} phase ran longer than its limit of {seconds}s (see [limits.{phase}] in the project config)")]
+    PhaseTimedOut { tool: String, phase: Phase, seconds: u64 },
+    /// The build directories grew by more than the `[limits]` disk cap of the phase the
+    /// command ran in, and the command was terminated
+    #[error("'{tool}' was terminated because the {phase} phase used {used} bytes of disk space, more than its limit of {limit} (see [limits.{phase}] in the project config)")]
+    DiskLimitExceeded { tool: String, phase: Phase, used: u64, limit: u64 },
+    /// The command ran but exited unsuccessfully; `summary` holds the parsed diagnostics.
+    #[error("{summary}")]
+    Failed {
+        tool: String,
+        /// `None` when the process was terminated by a signal
+        exit_code: Option<i32>,
+        stdout: String,
+        stderr: String,
+        summary: String,
+    },
+}
+
+impl CommandError {
+    /// The tool that failed to run.
+    pub fn tool(&self) -> &str {
+        match self {
+            CommandError::NotFound { tool }
+            | CommandError::Spawn { tool, .. }
+            | CommandError::Cancelled { tool }
+            | CommandError::TimedOut { tool, .. }
+            | CommandError::PhaseTimedOut { tool, .. }
+            | CommandError::DiskLimitExceeded { tool, .. }
+            | CommandError::Failed { tool, .. } => tool,
+        }
+    }
+}
 
 pub fn run_command(
     command_name: &str,
     args: &[impl AsRef<OsStr>],
     current_dir: Option<&Path>,
-) -> Result<Output, String> {
-    log::debug!(
+) -> Result<Output, CommandError> {
+    execute(command_name, args, current_dir, None)
+}
+
+/// Like [`run_command`], for the commands of a build: reports the command and each line of
+/// its stderr to the event sink of `config`, terminates it when it runs longer than its
+/// `--command-timeout` or the build is cancelled (see [`crate::cancel`]), and retries it
+/// after network errors (`--retries`).
+pub fn run_build_command(
+    command_name: &str,
+    args: &[impl AsRef<OsStr>],
+    current_dir: Option<&Path>,
+    config: &AppConfig,
+) -> Result<Output, CommandError> {
+    execute(command_name, args, current_dir, Some(config))
+}
+
+/// Runs a program in the foreground: its stdin, stdout and stderr are those of
+/// `wasm_compiler`, so output appears as it is written. Returns the exit status, whether
+/// successful or not. Async builds do not cancel it.
+pub fn run_interactive(
+    command_name: &str,
+    args: &[impl AsRef<OsStr>],
+    config: &AppConfig,
+) -> Result<ExitStatus, CommandError> {
+    let arg_strings: Vec<String> = args.iter().map(|a| a.as_ref().to_string_lossy().into_owned()).collect();
+    tracing::debug!("Running command: {} {}", command_name, arg_strings.join(" "));
+    config.events.command(command_name, &arg_strings);
+
+    let env = config.tool_env();
+    let mut cmd = build_command(command_name, config.tool_path(command_name).as_deref(), args, None);
+    cmd.envs(env.iter().map(|(key, value)| (key, value)));
+    cmd.stdin(Stdio::inherit()).stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    let start = std::time::Instant::now();
+    let status = cmd.status();
+    let outcome = match &status {
+        Ok(status) => Outcome::Status(*status),
+        Err(e) => Outcome::Error(e),
+    };
+    transcript::record_command(command_name, &arg_strings, None, &env, start.elapsed(), outcome);
+    status.map_err(|e| CommandError::Spawn {
+        tool: command_name.to_string(),
+        source: e,
+    })
+}
+
+fn execute(
+    command_name: &str,
+    args: &[impl AsRef<OsStr>],
+    current_dir: Option<&Path>,
+    config: Option<&AppConfig>,
+) -> Result<Output, CommandError> {
+    let arg_strings: Vec<String> = args.iter().map(|a| a.as_ref().to_string_lossy().into_owned()).collect();
+    tracing::debug!(
         "Running command: {} {} (in {:?})",
         command_name,
-        args.iter().map(|a| a.as_ref().to_string_lossy()).collect::<Vec<_>>().join(" "),
+        arg_strings.join(" "),
         current_dir.unwrap_or_else(|| Path::new("."))
     );
+    let no_events = Events::default();
+    let events = config.map_or(&no_events, |config| &config.events);
+    events.command(command_name, &arg_strings);
+    let _span = tracing::info_span!("command", program = command_name).entered();
 
-    let mut cmd = Command::new(resolve_emscripten_tool(command_name));
-    cmd.args(args);
-
-    if let Some(dir) = current_dir {
-        cmd.current_dir(dir);
+    let mut env = config.map(AppConfig::tool_env).unwrap_or_default();
+    if config.is_some() {
+        repro_script::record_command(command_name, &arg_strings, current_dir, &env);
+        // Colors only for this run; the script's output is not piped
+        env.extend(diagnostics::color_env(&env));
     }
+    let program = config.and_then(|config| config.tool_path(command_name));
+    let shell = config.and_then(|config| posix_shell::routed(config, command_name));
+    let timeout = config.and_then(|config| config.command_timeout(command_name));
+    let retries = config.map_or(0, |config| config.retries.min(MAX_RETRIES));
+    let cancel = config.and_then(|config| config.cancel.as_ref());
 
-    // Capture stdio for better error reporting
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
+    let mut attempt = 0;
+    let (output, elapsed) = loop {
+        let start = Instant::now();
+        let limits = CommandLimits::new(timeout);
+        if cancel.is_some_and(CancelHandle::is_cancelled) {
+            return Err(CommandError::Cancelled { tool: command_name.to_string() });
+        }
+        if limits.remaining().is_some_and(|remaining| remaining.is_zero()) {
+            return Err(limits.timed_out(command_name));
+        }
+        let mut cmd = match shell {
+            Some(shell) => posix_shell::command(shell, command_name, &arg_strings, current_dir, &env),
+            None => {
+                let mut cmd = build_command(command_name, program.as_deref(), args, current_dir);
+                cmd.envs(env.iter().map(|(key, value)| (key, value)));
+                cmd
+            }
+        };
 
-    let output = cmd.output().map_err(|e| {
-        format!(
-            "Failed to execute command '{}': {}. Is it installed and in your PATH?",
-            command_name, e
-        )
-    })?;
+        // Capture stdio for better error reporting
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+
+        #[cfg(feature = "async")]
+        let output = match config.and_then(|config| config.cancellation.as_ref()) {
+            Some(token) => crate::async_compile::output_cancellable(cmd, command_name, events, token, &limits),
+            None => wait_for_output(&mut cmd, command_name, events, &limits, cancel),
+        };
+        #[cfg(not(feature = "async"))]
+        let output = wait_for_output(&mut cmd, command_name, events, &limits, cancel);
+
+        let elapsed = start.elapsed();
+        let outcome = match &output {
+            Ok(output) => Outcome::Output(output),
+            Err(e) => Outcome::Error(e),
+        };
+        transcript::record_command(command_name, &arg_strings, current_dir, &env, elapsed, outcome);
+        let output = match (output?, shell) {
+            (output, Some(shell)) => posix_shell::translate_output(shell, output),
+            (output, None) => output,
+        };
+
+        if !output.status.success() && attempt < retries && is_network_failure(&String::from_utf8_lossy(&output.stderr)) {
+            attempt += 1;
+            let delay = Duration::from_secs(1 << attempt.min(5)).min(MAX_RETRY_DELAY);
+            tracing::warn!(
+                "'{}' failed with a network error after {:.1}s; retrying in {}s (retry {} of {})",
+                command_name, elapsed.as_secs_f64(), delay.as_secs(), attempt, retries
+            );
+            wait_before_retry(command_name, delay, config)?;
+            continue;
+        }
+        break (output, elapsed);
+    };
+    let duration_ms = elapsed.as_millis() as u64;
 
     if output.status.success() {
-        log::debug!(
+        if config.is_some() {
+            tracing::info!("'{}' finished in {:.1}s", command_name, elapsed.as_secs_f64());
+        }
+        tracing::debug!(
+            duration_ms,
             "Command '{}' executed successfully. Stout: {}",
             command_name,
             String::from_utf8_lossy(&output.stdout)
         );
+        // Surface compiler warnings from successful runs as structured diagnostics
+        let mut warnings = diagnostics::parse(&String::from_utf8_lossy(&output.stderr));
+        let count = warnings.len();
+        warnings.retain(|warning| !diagnostics::is_noise(warning));
+        if warnings.len() < count {
+            tracing::debug!("Left out {} known-noisy warning(s) of '{}'", count - warnings.len(), command_name);
+        }
+        diagnostics::report_warnings(command_name, warnings);
         Ok(output)
     } else {
-        let err_msg = format!(
-            "Command '{}' failed with status: {}.\nStdout: {}\nStderr: {}",
-            command_name,
-            output.status,
-            String::from_utf8_lossy(&output.stdout),
-            String::from_utf8_lossy(&output.stderr)
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        tracing::debug!(
+            "Command '{}' raw output:\nStdout: {}\nStderr: {}",
+            command_name, stdout, stderr
         );
-        log::error!("{}", err_msg);
-        Err(err_msg)
+        let status = format!("{} after {:.1}s", output.status, elapsed.as_secs_f64());
+        let summary = diagnostics::summarize_failure(command_name, &status, &stdout, &stderr);
+        tracing::error!(duration_ms, exit_code = output.status.code(), "{}", summary);
+        Err(CommandError::Failed {
+            tool: command_name.to_string(),
+            exit_code: output.status.code(),
+            stdout,
+            stderr,
+            summary,
+        })
     }
 }
 
-pub fn is_command_in_path(command_name: &str) -> bool {
-    // For Emscripten tools, use a different approach since they don't all support --version
-    if is_emscripten_tool(command_name) {
-        return is_emscripten_tool_available(command_name);
+/// The command running `command_name`, or the file it is pinned to (see
+/// [`AppConfig::tool_path`]).
+#[cfg(not(windows))]
+fn build_command(command_name: &str, program: Option<&Path>, args: &[impl AsRef<OsStr>], current_dir: Option<&Path>) -> Command {
+    let mut cmd = match program {
+        Some(program) => Command::new(program),
+        None => Command::new(resolve_emscripten_tool(command_name)),
+    };
+    cmd.args(args);
+    if let Some(dir) = current_dir {
+        cmd.current_dir(dir);
     }
+    cmd
+}
 
-    match Command::new(command_name).arg("--version").output() {
-        Ok(_) => true,
-        Err(e) => {
-            if let std::io::ErrorKind::NotFound = e.kind() {
-                log::warn!("Command '{}' not found in PATH.", command_name);
-                false
-            } else {
-                // Command might exist but failed for other reasons (e.g. --version not supported)
-                // For simplicity, we'll assume it exists if it's not a NotFound error.
-                // A more robust check might involve `which` command or PATH environment variable parsing.
-                log::debug!("Command '{}' check resulted in error (assuming it exists): {}", command_name, e);
-                true
-            }
+/// Runs Emscripten wrappers through Python rather than their `.bat` files, or through
+/// `cmd.exe` with arguments quoted for it, and shortens overlong paths; see
+/// [`super::windows_command`].
+#[cfg(windows)]
+fn build_command(command_name: &str, program: Option<&Path>, args: &[impl AsRef<OsStr>], current_dir: Option<&Path>) -> Command {
+    use std::os::windows::process::CommandExt;
+    use super::windows_command::{cmd_command_line, normalize_path_arg, python, shorten_path, wrapper_script};
+
+    let script = match program {
+        Some(program) => Some(program.with_extension("py")).filter(|script| script.is_file()),
+        None => wrapper_script(command_name),
+    };
+    let program = program.map_or_else(|| PathBuf::from(resolve_emscripten_tool(command_name)), Path::to_path_buf);
+    let is_wrapper = program.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("bat") || extension.eq_ignore_ascii_case("cmd"));
+    let normalized = args.iter().map(|arg| match arg.as_ref().to_str() {
+        Some(text) => OsString::from(normalize_path_arg(text, &shorten_path)),
+        None => arg.as_ref().to_os_string(),
+    });
+    let mut cmd = match script.filter(|_| is_emscripten_tool(command_name)) {
+        Some(script) => {
+            tracing::debug!("Running {:?} directly instead of its .bat wrapper", script);
+            let mut cmd = Command::new(python());
+            cmd.arg("-E").arg(shorten_if_too_long(&script));
+            cmd.args(normalized);
+            cmd
         }
+        // cmd.exe runs the wrapper with a command line quoted for it
+        None if is_wrapper => {
+            let args: Vec<String> = normalized.map(|arg| arg.to_string_lossy().into_owned()).collect();
+            let mut cmd = Command::new("cmd.exe");
+            cmd.args(["/d", "/s", "/c"]).raw_arg(cmd_command_line(&program, &args));
+            cmd
+        }
+        None => {
+            let mut cmd = Command::new(program);
+            cmd.args(normalized);
+            cmd
+        }
+    };
+    if let Some(dir) = current_dir {
+        cmd.current_dir(shorten_if_too_long(dir));
     }
+    cmd
 }
 
-fn is_emscripten_tool(command_name: &str) -> bool {
-    matches!(command_name, "emcc" | "em++" | "emmake" | "emcmake" | "emar" | "emranlib" | "emlink" | "emsize" | "emstrip")
-}
-
-fn is_emscripten_tool_available(command_name: &str) -> bool {
-    // For emmake and emcmake, try running them without arguments - they should show usage
-    if matches!(command_name, "emmake" | "emcmake") {
-        let tool_name = resolve_emscripten_tool(command_name);
-        match Command::new(&tool_name).output() {
-            Ok(output) => {
-                // These tools show usage when run without args and exit with non-zero status
-                // But if they run and produce output, they exist
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                let combined_output = format!("{} {}", stdout, stderr);
-                
-                if combined_output.contains("helper") || 
-                   combined_output.contains("usage") ||
-                   combined_output.contains("emscripten") ||
-                   combined_output.contains("make") ||
-                   combined_output.contains("FLAGS") {
-                    log::debug!("Emscripten tool '{}' found and working", command_name);
-                    true
-                } else {
-                    log::warn!("Command '{}' exists but doesn't appear to be working correctly", command_name);
-                    false
-                }
-            }
-            Err(e) => {
-                if let std::io::ErrorKind::NotFound = e.kind() {
-                    log::warn!("Command '{}' not found in PATH.", command_name);
-                    false
-                } else {
-                    log::debug!("Command '{}' check resulted in error (assuming it exists): {}", command_name, e);
-                    true
-                }
-            }
-        }
+#[cfg(windows)]
+fn shorten_if_too_long(path: &Path) -> std::path::PathBuf {
+    use super::windows_command::{is_too_long, shorten_path};
+
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    if is_too_long(&path) { shorten_path(&path) } else { path }
+}
+
+fn wait_for_output(cmd: &mut Command, command_name: &str, events: &Events, limits: &CommandLimits, cancel: Option<&CancelHandle>) -> Result<Output, CommandError> {
+    if limits.is_limited() || cancel.is_some() {
+        return output_with_limits(cmd, command_name, events, limits, cancel);
+    }
+    let output = if events.is_enabled() {
+        output_streaming_stderr(cmd, command_name, events)
     } else {
-        // For other emscripten tools, try --version or help
-        match Command::new(command_name).arg("--version").output() {
-            Ok(_) => true,
-            Err(e) => {
-                if let std::io::ErrorKind::NotFound = e.kind() {
-                    log::warn!("Command '{}' not found in PATH.", command_name);
-                    false
-                } else {
-                    // Try with --help for tools that don't support --version
-                    match Command::new(command_name).arg("--help").output() {
-                        Ok(_) => {
-                            log::debug!("Emscripten tool '{}' found (via --help)", command_name);
-                            true
-                        }
-                        Err(_) => {
-                            log::debug!("Command '{}' check resulted in error (assuming it exists): {}", command_name, e);
-                            true
-                        }
-                    }
-                }
-            }
+        cmd.output()
+    };
+    output.map_err(|e| CommandError::Spawn {
+        tool: command_name.to_string(),
+        source: e,
+    })
+}
+
+/// Runs `cmd` to completion like [`Command::output`], reporting its stderr line by line while
+/// it runs. Stdout is read on a separate thread so neither pipe can fill up and block; the
+/// build tool's status lines on it are reported as progress.
+fn output_streaming_stderr(cmd: &mut Command, command_name: &str, events: &Events) -> std::io::Result<Output> {
+    let mut child = cmd.spawn()?;
+    let stdout_events = events.clone();
+    let stdout_reader = read_lines(child.stdout.take().expect("stdout is piped"), move |line| stdout_events.stdout_line(line));
+
+    let mut stderr = Vec::new();
+    let mut reader = BufReader::new(child.stderr.take().expect("stderr is piped"));
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        events.stderr_line(command_name, String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']));
+        stderr.extend_from_slice(&line);
+        line.clear();
+    }
+
+    let status = child.wait()?;
+    let stdout = stdout_reader.join().expect("stdout reader thread panicked")?;
+    Ok(Output { status, stdout, stderr })
+}
+
+/// Runs `cmd` to completion like [`output_streaming_stderr`], but terminates it and the tools
+/// it started when it runs out of time, its phase uses more disk space than it may (see
+/// [`CommandLimits`]) or `cancel` is cancelled. In its own process group, the command does not
+/// get the Ctrl-C of the terminal and is terminated here instead.
+fn output_with_limits(
+    cmd: &mut Command,
+    command_name: &str,
+    events: &Events,
+    limits: &CommandLimits,
+    cancel: Option<&CancelHandle>,
+) -> Result<Output, CommandError> {
+    let spawn_error = |e| CommandError::Spawn { tool: command_name.to_string(), source: e };
+    // emcc, emcmake and emmake start further tools; give them a process group to signal
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(cmd, 0);
+
+    let deadline = limits.remaining().map(|remaining| Instant::now() + remaining);
+    let mut child = cmd.spawn().map_err(spawn_error)?;
+    // The group has the id of its leader, the command
+    let _running = cancel.map(|cancel| cancel.track(child.id()));
+    let stdout_events = events.clone();
+    let stdout_reader = read_lines(child.stdout.take().expect("stdout is piped"), move |line| stdout_events.stdout_line(line));
+    let stderr_events = events.clone();
+    let tool = command_name.to_string();
+    let stderr_reader = read_lines(child.stderr.take().expect("stderr is piped"), move |line| stderr_events.stderr_line(&tool, line));
+
+    let mut disk_checked = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(spawn_error)? {
+            break status;
+        }
+        let breach = if cancel.is_some_and(CancelHandle::is_cancelled) {
+            Some(CommandError::Cancelled { tool: command_name.to_string() })
+        } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            Some(limits.timed_out(command_name))
+        } else if limits.watches_disk() && disk_checked.elapsed() >= DISK_CHECK_INTERVAL {
+            disk_checked = Instant::now();
+            limits.check_disk(command_name).err()
+        } else {
+            None
+        };
+        if let Some(error) = breach {
+            tracing::info!("Terminating '{}': {}", command_name, error);
+            terminate(&mut child);
+            // The readers finish once the last process holding the pipes is gone
+            return Err(error);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+    let stdout = stdout_reader.join().expect("stdout reader thread panicked").map_err(spawn_error)?;
+    let stderr = stderr_reader.join().expect("stderr reader thread panicked").map_err(spawn_error)?;
+    Ok(Output { status, stdout, stderr })
+}
+
+/// Reads `pipe` on a new thread, passing each line to `on_line`, and returns all of it
+fn read_lines(pipe: impl Read + Send + 'static, on_line: impl Fn(&str) + Send + 'static) -> JoinHandle<std::io::Result<Vec<u8>>> {
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        let mut output = Vec::new();
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line)? > 0 {
+            on_line(String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']));
+            output.extend_from_slice(&line);
+            line.clear();
+        }
+        Ok(output)
+    })
+}
+
+/// Asks the process group of `child` to exit and kills it if it does not within
+/// [`TERMINATE_GRACE`].
+fn terminate(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        let group = -(child.id() as libc::pid_t);
+        // SAFETY: kill(2) has no memory-safety preconditions
+        unsafe { libc::kill(group, libc::SIGTERM) };
+        let deadline = Instant::now() + TERMINATE_GRACE;
+        while Instant::now() < deadline && matches!(child.try_wait(), Ok(None)) {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        // Also reaches tools that ignored SIGTERM after their parent exited
+        unsafe { libc::kill(group, libc::SIGKILL) };
+    }
+    child.kill().ok();
+    child.wait().ok();
+}
+
+/// Waits `delay` before retrying `command_name`, in [`POLL_INTERVAL`] steps so that the build
+/// can be cancelled and its phase run out of time in the meantime
+fn wait_before_retry(command_name: &str, delay: Duration, config: Option<&AppConfig>) -> Result<(), CommandError> {
+    let cancelled = || {
+        let cancelled = config.and_then(|config| config.cancel.as_ref()).is_some_and(CancelHandle::is_cancelled);
+        #[cfg(feature = "async")]
+        let cancelled = cancelled || config.and_then(|config| config.cancellation.as_ref()).is_some_and(|token| token.is_cancelled());
+        cancelled
+    };
+    let limits = CommandLimits::new(None);
+    let end = Instant::now() + delay;
+    while let Some(left) = end.checked_duration_since(Instant::now()).filter(|left| !left.is_zero()) {
+        if cancelled() {
+            return Err(CommandError::Cancelled { tool: command_name.to_string() });
+        }
+        if limits.remaining().is_some_and(|remaining| remaining.is_zero()) {
+            return Err(limits.timed_out(command_name));
         }
+        std::thread::sleep(left.min(POLL_INTERVAL));
+    }
+    Ok(())
+}
+
+/// Whether the stderr of a failed command tells of a network error worth retrying
+fn is_network_failure(stderr: &str) -> bool {
+    NETWORK_ERRORS.iter().any(|error| stderr.contains(error))
+}
+
+/// Whether `command_name` can be run with the tool environment of `config` (see
+/// [`AppConfig::tool_env`]): the file it is pinned to (see [`AppConfig::tool_path`]) is an
+/// executable, or one is found in the PATH of that environment.
+pub fn is_command_in_path(command_name: &str, config: &AppConfig) -> bool {
+    if let Some(shell) = posix_shell::routed(config, command_name) {
+        return posix_shell::is_available(shell);
+    }
+    if let Some(path) = config.tool_path(command_name) {
+        if is_executable(&path) {
+            return true;
+        }
+        tracing::warn!("'{}' is pinned to {}, which is not an executable file.", command_name, path.display());
+        return false;
+    }
+    match find_in_path(&resolve_emscripten_tool(command_name), &config.tool_env()) {
+        Some(path) => {
+            tracing::debug!("Found '{}' at {}", command_name, path.display());
+            true
+        }
+        None => {
+            tracing::warn!("Command '{}' not found in PATH.", command_name);
+            false
+        }
+    }
+}
+
+/// The executable `program` is found as in the PATH of `env` (the inherited PATH if `env`
+/// does not set one), like `which`. A `program` with a directory is only checked for being
+/// executable.
+pub fn find_in_path(program: &str, env: &[(OsString, OsString)]) -> Option<PathBuf> {
+    let program = Path::new(program);
+    if program.components().count() > 1 {
+        return is_executable(program).then(|| program.to_path_buf());
     }
+    let path = env.iter().rev()
+        .find(|(key, _)| key == "PATH")
+        .map(|(_, value)| value.clone())
+        .or_else(|| std::env::var_os("PATH"))?;
+    std::env::split_paths(&path)
+        .flat_map(|dir| executable_names(&dir.join(program)))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// The files `path` may run: itself, and on Windows without an extension, itself with each
+/// extension of PATHEXT
+fn executable_names(path: &Path) -> Vec<PathBuf> {
+    if !cfg!(windows) || path.extension().is_some() {
+        return vec![path.to_path_buf()];
+    }
+    let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    extensions
+        .split(';')
+        .filter(|extension| !extension.is_empty())
+        .map(|extension| {
+            let mut name = path.as_os_str().to_os_string();
+            name.push(extension);
+            PathBuf::from(name)
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
 }
 
 /// Resolves the correct Emscripten tool name for the current platform.
 /// On Windows, appends `.bat` for emscripten wrapper tools (emmake, emcmake, etc).
 pub fn resolve_emscripten_tool(tool: &str) -> String {
-    if cfg!(windows) {
-        match tool {
-            "emmake" | "emcmake" | "emcc" | "em++" | "emar" | "emranlib" | "emlink" | "emsize" | "emstrip" => format!("{}.bat", tool),
-            _ => tool.to_string(),
-        }
+    if cfg!(windows) && is_emscripten_tool(tool) {
+        format!("{}.bat", tool)
     } else {
         tool.to_string()
     }
 }
+
+/// Whether `tool` is one of the Emscripten tools, which are `.bat` wrappers on Windows
+fn is_emscripten_tool(tool: &str) -> bool {
+    matches!(tool, "emmake" | "emcmake" | "emcc" | "em++" | "emar" | "emranlib" | "emlink" | "emsize" | "emstrip" | "embuilder")
+}
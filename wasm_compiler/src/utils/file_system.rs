@@ -1,12 +1,13 @@
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
 pub fn ensure_dir_exists(path: &Path) -> Result<(), String> {
     if !path.exists() {
         fs::create_dir_all(path)
             .map_err(|e| format!("Failed to create directory {:?}: {}", path, e))?;
-        log::info!("Created directory: {:?}", path);
+        tracing::info!("Created directory: {:?}", path);
     }
     Ok(())
 }
@@ -31,8 +32,14 @@ pub fn copy_dir_contents(src: &Path, dest: &Path) -> Result<(), String> {
 
 
 pub fn copy_dir_recursive(src: &Path, dest: &Path) -> Result<(), String> {
+    copy_dir_filtered(src, dest, |_| false)
+}
+
+/// Like [`copy_dir_recursive`], leaving out the files and directories (with their contents)
+/// `skip` returns true for
+pub fn copy_dir_filtered(src: &Path, dest: &Path, skip: impl Fn(&walkdir::DirEntry) -> bool) -> Result<(), String> {
     ensure_dir_exists(dest)?;
-    for entry in WalkDir::new(src).min_depth(1) { // Iterate over all entries recursively
+    for entry in WalkDir::new(src).min_depth(1).into_iter().filter_entry(|entry| !skip(entry)) {
         let entry = entry.map_err(|e| format!("Error reading directory entry: {}", e))?;
         let src_path = entry.path();
         let relative_path = src_path.strip_prefix(src)
@@ -48,23 +55,68 @@ pub fn copy_dir_recursive(src: &Path, dest: &Path) -> Result<(), String> {
             }
             fs::copy(src_path, &dest_path)
                 .map_err(|e| format!("Failed to copy file {:?} to {:?}: {}", src_path, dest_path, e))?;
-            log::trace!("Copied {:?} to {:?}", src_path, dest_path);
+            tracing::trace!("Copied {:?} to {:?}", src_path, dest_path);
         }
     }
     Ok(())
 }
 
+/// Creates `dir` by letting `fill` write a partial directory next to it, which is then moved in
+/// place, so an interrupted download, clone or extraction is not taken for a complete one by
+/// the next build. When `dir` appears while `fill` runs (another build created it in the
+/// meantime), that copy is kept.
+pub fn create_dir_atomically<E: From<String>>(dir: &Path, fill: impl FnOnce(&Path) -> Result<(), E>) -> Result<(), E> {
+    let parent = dir.parent().unwrap_or(dir);
+    fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory {:?}: {}", parent, e))?;
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    let partial = parent.join(format!("{}.partial-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&partial);
+    if let Err(e) = fill(&partial) {
+        let _ = fs::remove_dir_all(&partial);
+        return Err(e);
+    }
+    if let Err(e) = fs::rename(&partial, dir) {
+        let _ = fs::remove_dir_all(&partial);
+        if !dir.is_dir() {
+            return Err(format!("Failed to move {:?} to {:?}: {}", partial, dir, e).into());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the lowercase hex SHA-256 digest of a file's contents.
+pub fn hash_file(path: &Path) -> Result<String, String> {
+    let bytes = fs::read(path)
+        .map_err(|e| format!("Failed to read {:?} for hashing: {}", path, e))?;
+    Ok(hex_digest(&bytes))
+}
+
+/// Returns the lowercase hex SHA-256 digest of a byte slice.
+pub fn hex_digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Cache directory of `wasm_compiler`: `WASM_COMPILER_CACHE`, or `wasm_compiler` in the cache
+/// directory of the user (`XDG_CACHE_HOME` or `~/.cache`, `%LOCALAPPDATA%` on Windows)
+pub fn cache_dir() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    if let Some(dir) = var("WASM_COMPILER_CACHE") {
+        return Some(dir);
+    }
+    let base = if cfg!(windows) {
+        var("LOCALAPPDATA")?
+    } else {
+        var("XDG_CACHE_HOME").or_else(|| var("HOME").map(|home| home.join(".cache")))?
+    };
+    Some(base.join("wasm_compiler"))
+}
+
 // Example of a function that might be needed later
 #[allow(dead_code)]
 pub fn find_file_by_extension(dir: &Path, extension: &str) -> Option<walkdir::DirEntry> {
-    for entry in WalkDir::new(dir)
+    WalkDir::new(dir)
         .into_iter()
         .filter_map(Result::ok)
         .filter(|e| !e.file_type().is_dir())
-    {
-        if entry.path().extension().map_or(false, |ext| ext == extension) {
-            return Some(entry);
-        }
-    }
-    None
+        .find(|entry| entry.path().extension().is_some_and(|ext| ext == extension))
 }
@@ -0,0 +1,90 @@
+//! Compiler and linker flags that are handed to a build tool as a single string, such as
+//! `CMAKE_EXE_LINKER_FLAGS` or make's `LDFLAGS`. The build tool later splits the string with
+//! shell rules, so flags holding paths with spaces, quotes or `$` have to be quoted (and, for
+//! make, `$` escaped) when the set is serialized.
+
+use std::ops::Deref;
+
+/// Characters that make the shell split or reinterpret a flag
+const SHELL_SPECIAL: &[char] = &['"', '$', '`', '\\', ';', '&', '|', '<', '>', '(', ')'];
+
+/// An ordered list of flags, each kept as one word when serialized.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlagSet(Vec<String>);
+
+impl FlagSet {
+    pub fn new() -> Self {
+        FlagSet(Vec::new())
+    }
+
+    pub fn push(&mut self, flag: impl Into<String>) {
+        self.0.push(flag.into());
+    }
+
+    /// Adds `flag` unless the set already contains it
+    pub fn push_unique(&mut self, flag: impl Into<String>) {
+        let flag = flag.into();
+        if !self.0.contains(&flag) {
+            self.0.push(flag);
+        }
+    }
+
+    /// Removes and returns the flag at `index`
+    pub fn remove(&mut self, index: usize) -> String {
+        self.0.remove(index)
+    }
+
+    /// The flags as words of a shell command line. Flags are quoted only when the shell would
+    /// otherwise split or alter them, so ordinary flags appear unchanged.
+    pub fn to_shell_string(&self) -> String {
+        self.0
+            .iter()
+            .map(|flag| {
+                if flag.is_empty() || flag.chars().any(|c| c.is_whitespace() || SHELL_SPECIAL.contains(&c)) {
+                    shell_words::quote(flag).into_owned()
+                } else {
+                    flag.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Value for a `-D<VAR>=` flags variable on the CMake command line. CMake copies it into
+    /// the generated compile and link commands, which are run by a shell.
+    pub fn to_cmake_value(&self) -> String {
+        self.to_shell_string()
+    }
+
+    /// Value for a `VAR=` flags variable on the make command line. Make expands `$` in
+    /// variables before the shell sees them, so it is doubled.
+    pub fn to_make_value(&self) -> String {
+        self.to_shell_string().replace('$', "$$")
+    }
+}
+
+impl Deref for FlagSet {
+    type Target = [String];
+
+    fn deref(&self) -> &[String] {
+        &self.0
+    }
+}
+
+impl From<Vec<String>> for FlagSet {
+    fn from(flags: Vec<String>) -> Self {
+        FlagSet(flags)
+    }
+}
+
+impl FromIterator<String> for FlagSet {
+    fn from_iter<I: IntoIterator<Item = String>>(flags: I) -> Self {
+        FlagSet(flags.into_iter().collect())
+    }
+}
+
+impl Extend<String> for FlagSet {
+    fn extend<I: IntoIterator<Item = String>>(&mut self, flags: I) {
+        self.0.extend(flags);
+    }
+}
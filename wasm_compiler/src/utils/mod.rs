@@ -2,5 +2,14 @@
 //! running external commands and file system operations. These utilities are
 //! used by various parts of the `wasm_compiler` application.
 
+pub mod cmake_script;
 pub mod command_runner;
 pub mod file_system;
+pub mod flag_set;
+pub mod output_name;
+pub mod posix_shell;
+pub mod preprocessor;
+pub mod source_selection;
+pub mod static_server;
+pub mod wasm_binary;
+pub mod windows_command;
@@ -0,0 +1,71 @@
+//! The default output name, used when `--output-name` is not given: the executable target
+//! of the CMake project or the `TARGET` of the Makefile when it can be read from the build
+//! files, otherwise the project directory name.
+
+use std::path::Path;
+
+/// Used when nothing usable can be derived from the project
+const FALLBACK_OUTPUT_NAME: &str = "output";
+
+/// Derives the output name for the project at `project_path` (which should be absolute, so
+/// that `.` has a directory name).
+pub fn default_output_name(project_path: &Path) -> String {
+    let directory_name = project_path.file_name().map(|name| name.to_string_lossy().into_owned());
+    cmake_target(project_path)
+        .or_else(|| make_target(project_path))
+        .into_iter()
+        .chain(directory_name)
+        .map(|name| sanitize(&name))
+        .find(|name| !name.is_empty())
+        .unwrap_or_else(|| FALLBACK_OUTPUT_NAME.to_string())
+}
+
+/// The first `add_executable` target of `CMakeLists.txt`, resolving `${PROJECT_NAME}`
+fn cmake_target(project_path: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(project_path.join("CMakeLists.txt")).ok()?;
+    let target = first_command_argument(&content, "add_executable")?;
+    if target == "${PROJECT_NAME}" {
+        return first_command_argument(&content, "project");
+    }
+    (!target.contains("${")).then_some(target)
+}
+
+/// First argument of the first call of the CMake command `name` (commands are case-insensitive)
+fn first_command_argument(content: &str, name: &str) -> Option<String> {
+    content.lines().map(str::trim_start).find_map(|line| {
+        let open = line.find('(')?;
+        if !line[..open].trim_end().eq_ignore_ascii_case(name) {
+            return None;
+        }
+        line[open + 1..]
+            .split(|c: char| c.is_whitespace() || c == ')')
+            .find(|arg| !arg.is_empty())
+            .map(|arg| arg.trim_matches('"').to_string())
+    })
+}
+
+/// The value of a `TARGET = name` assignment in the Makefile, without a `.js`/`.html` extension
+fn make_target(project_path: &Path) -> Option<String> {
+    let content = ["Makefile", "makefile", "GNUmakefile"]
+        .iter()
+        .find_map(|name| std::fs::read_to_string(project_path.join(name)).ok())?;
+    content.lines().find_map(|line| {
+        let (variable, value) = line.split_once('=')?;
+        let variable = variable.trim_end_matches([':', '?', '+']).trim();
+        let value = value.split('#').next()?.trim();
+        if variable != "TARGET" || value.is_empty() || value.contains("$(") {
+            return None;
+        }
+        let value = value.strip_suffix(".js").or_else(|| value.strip_suffix(".html")).unwrap_or(value);
+        Some(value.to_string())
+    })
+}
+
+/// Replaces characters that are not safe in file names and URLs with `_`
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect::<String>()
+        .trim_start_matches('.')
+        .to_string()
+}
@@ -0,0 +1,272 @@
+//! Makefile builds on Windows through WSL or MSYS2. Makefiles written for Unix (and the
+//! autotools that generate them) run their recipes with a POSIX shell and call `rm -f`,
+//! `mkdir -p`, `sed` and the like, which `cmd.exe` does not have. When the Makefile needs a
+//! POSIX shell and `--shell` is not given, the shells that are installed are offered (see
+//! [`crate::ambiguity`]); with `--shell wsl` or `--shell msys2`, emmake and make run in
+//! `bash -lc` there.
+//!
+//! Windows paths in the arguments are translated to the shell's (`C:\src\app` is
+//! `/mnt/c/src/app` in WSL and `/c/src/app` in MSYS2), and the shell's paths in the output
+//! back, so diagnostics point at files Windows tools can open. WSL needs an Emscripten SDK
+//! installed in the distribution; MSYS2 inherits the Windows PATH, so an SDK installed on
+//! Windows is found.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use crate::app_config::{AppConfig, Shell};
+use crate::compiler::error::CompileError;
+
+/// Commands a Makefile build runs through the shell
+const ROUTED_COMMANDS: &[&str] = &["emmake", "emconfigure", "make"];
+
+/// Recipe commands `cmd.exe` does not have
+const POSIX_COMMANDS: &[&str] = &["rm -", "mkdir -p", "cp ", "ln -s", "sed ", "uname", "touch ", "find ", "test -", "[ -"];
+
+/// Why the Makefile of `project_path` needs a POSIX shell, if it does
+pub fn needs_posix_shell(project_path: &Path) -> Option<String> {
+    if ["configure", "configure.ac"].iter().any(|name| project_path.join(name).is_file()) {
+        return Some("the project is configured with autotools".to_string());
+    }
+    let makefile = ["Makefile", "makefile"].into_iter().map(|name| project_path.join(name)).find(|path| path.is_file())?;
+    let content = std::fs::read_to_string(makefile).ok()?;
+    for line in content.lines() {
+        if let Some(shell) = line.strip_prefix("SHELL").and_then(|rest| rest.trim_start().trim_start_matches([':', '?']).strip_prefix('=')) {
+            return Some(format!("it sets SHELL to {}", shell.trim()));
+        }
+        let recipe = line.strip_prefix('\t');
+        let command = POSIX_COMMANDS.iter().find(|command| {
+            recipe.is_some_and(|recipe| commands(recipe).any(|word| word.starts_with(*command)))
+                || (command.starts_with("uname") && line.contains("$(shell uname"))
+        });
+        if let Some(command) = command {
+            return Some(format!("its recipes run `{}`", command.trim()));
+        }
+    }
+    None
+}
+
+/// The commands of a recipe line: split at `;`, `&&`, `||` and `|`, without make's `@`, `-`
+/// and `+` prefixes and leading shell keywords, so only command words are matched and not
+/// their arguments (`-c main.cpp -o main.o` does not run `cp`)
+fn commands(recipe: &str) -> impl Iterator<Item = &str> {
+    recipe.split([';', '|', '&']).map(|command| {
+        let mut command = command.trim_start().trim_start_matches(['@', '-', '+']).trim_start();
+        while let Some(rest) = ["if ", "then ", "else ", "do ", "! "].iter().find_map(|keyword| command.strip_prefix(keyword)) {
+            command = rest.trim_start();
+        }
+        command
+    })
+}
+
+/// The shell to run the Makefile build of `project_path` in: `--shell`, the one picked from
+/// the installed shells when the Makefile needs a POSIX shell, or the native one
+pub fn choose(config: &AppConfig, project_path: &Path) -> Result<Shell, CompileError> {
+    if !cfg!(windows) {
+        if let Some(shell) = config.shell.filter(|shell| *shell != Shell::Native) {
+            crate::strict::fallback(config, format!("--shell {} only applies on Windows; building natively", name(shell)))?;
+        }
+        return Ok(Shell::Native);
+    }
+    if let Some(shell) = config.shell {
+        return Ok(shell);
+    }
+    let Some(reason) = needs_posix_shell(project_path) else { return Ok(Shell::Native) };
+
+    let mut shells = vec![Shell::Native];
+    shells.extend([Shell::Wsl, Shell::Msys2].into_iter().filter(|shell| is_available(*shell)));
+    if shells.len() == 1 {
+        tracing::warn!("The Makefile needs a POSIX shell ({}), but neither WSL nor MSYS2 is installed; running it with cmd.exe", reason);
+        return Ok(Shell::Native);
+    }
+    let names: Vec<String> = shells.iter().map(|shell| describe(*shell)).collect();
+    let what = format!("shells for a Makefile that needs a POSIX shell ({})", reason);
+    let index = crate::ambiguity::choose(config, project_path, &what, &names)?.unwrap_or(0);
+    Ok(shells[index])
+}
+
+/// The shell `command_name` runs in: the POSIX shell of a Makefile build for the commands
+/// it routes, `None` to run it directly
+pub fn routed(config: &AppConfig, command_name: &str) -> Option<Shell> {
+    let command = command_name.strip_suffix(".bat").unwrap_or(command_name);
+    config.posix_shell.filter(|shell| *shell != Shell::Native && ROUTED_COMMANDS.contains(&command))
+}
+
+/// The command running `command` with `args` in `current_dir` in `shell`, with the tool
+/// environment `env`, whose variables reach WSL through `WSLENV`
+pub fn command(shell: Shell, command: &str, args: &[String], current_dir: Option<&Path>, env: &[(OsString, OsString)]) -> Command {
+    let command = command.strip_suffix(".bat").unwrap_or(command);
+    let mut words = vec![command.to_string()];
+    words.extend(args.iter().map(|arg| to_posix_paths(shell, arg)));
+    let mut script = format!("exec {}", shell_words::join(&words));
+    if let Some(dir) = current_dir {
+        let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+        script = format!("cd {} && {}", shell_words::quote(&to_posix_paths(shell, &dir.to_string_lossy())), script);
+    }
+
+    let mut env = env.to_vec();
+    let arguments = vec!["-lc".to_string(), script];
+    let (program, arguments) = match shell {
+        Shell::Wsl => {
+            // Paths in the values are translated by WSL (`/p`)
+            let mut shared: Vec<String> = env
+                .iter()
+                .filter(|(key, _)| key != "PATH")
+                .map(|(key, value)| {
+                    let key = key.to_string_lossy();
+                    if is_windows_path(&value.to_string_lossy()) { format!("{}/p", key) } else { key.into_owned() }
+                })
+                .collect();
+            if let Some(existing) = std::env::var("WSLENV").ok().filter(|existing| !existing.is_empty()) {
+                shared.insert(0, existing);
+            }
+            env.push(("WSLENV".into(), shared.join(":").into()));
+            let mut wsl_arguments = vec!["-e".to_string(), "bash".to_string()];
+            wsl_arguments.extend(arguments);
+            (PathBuf::from("wsl.exe"), wsl_arguments)
+        }
+        _ => {
+            // Keep the Windows PATH, where the Emscripten SDK usually is
+            env.push(("MSYS2_PATH_TYPE".into(), "inherit".into()));
+            (msys2_bash(), arguments)
+        }
+    };
+
+    let mut cmd = Command::new(program);
+    cmd.args(arguments).envs(env.iter().map(|(key, value)| (key, value)));
+    if let Some(dir) = current_dir {
+        cmd.current_dir(dir);
+    }
+    cmd
+}
+
+/// `output` with the shell's paths in its stdout and stderr turned back into Windows paths
+pub fn translate_output(shell: Shell, output: Output) -> Output {
+    let translate = |bytes: &[u8]| to_windows_paths(shell, &String::from_utf8_lossy(bytes)).into_bytes();
+    Output { status: output.status, stdout: translate(&output.stdout), stderr: translate(&output.stderr) }
+}
+
+/// `text` with the shell's paths (`/mnt/c/...`, `/c/...`) turned back into Windows paths
+pub fn to_windows_paths(shell: Shell, text: &str) -> String {
+    let prefix = match shell {
+        Shell::Wsl => "/mnt/",
+        Shell::Msys2 => "/",
+        Shell::Native => return text.to_string(),
+    };
+    let mut translated = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(prefix) {
+        let after = &rest[index + prefix.len()..];
+        let mut chars = after.chars();
+        let at_start = rest[..index].chars().next_back().is_none_or(is_path_boundary);
+        match (chars.next(), chars.next()) {
+            (Some(drive), Some('/')) if at_start && drive.is_ascii_alphabetic() => {
+                translated.push_str(&rest[..index]);
+                let path = &after[2..];
+                let end = path.find(|c: char| is_path_boundary(c) || c == ':').unwrap_or(path.len());
+                translated.push_str(&format!("{}:\\{}", drive.to_ascii_uppercase(), path[..end].replace('/', "\\")));
+                rest = &path[end..];
+            }
+            _ => {
+                translated.push_str(&rest[..index + prefix.len()]);
+                rest = after;
+            }
+        }
+    }
+    translated.push_str(rest);
+    translated
+}
+
+/// `text` with its Windows paths (`C:\src\app`, also inside flags such as `-IC:\src`) in the
+/// form of `shell`
+pub fn to_posix_paths(shell: Shell, text: &str) -> String {
+    let prefix = match shell {
+        Shell::Wsl => "/mnt/",
+        Shell::Msys2 => "/",
+        Shell::Native => return text.to_string(),
+    };
+    let chars: Vec<char> = text.chars().collect();
+    let mut translated = String::with_capacity(text.len());
+    let mut index = 0;
+    while index < chars.len() {
+        // A path starts a word, or follows the letters of a flag (`-IC:\src`)
+        let word = chars[..index].iter().rposition(|c| is_path_boundary(*c)).map_or(0, |boundary| boundary + 1);
+        let is_drive = chars[index].is_ascii_alphabetic()
+            && chars.get(index + 1) == Some(&':')
+            && matches!(chars.get(index + 2), Some('\\' | '/'))
+            && (word == index || (chars[word] == '-' && chars[word + 1..index].iter().all(char::is_ascii_alphabetic)));
+        if !is_drive {
+            translated.push(chars[index]);
+            index += 1;
+            continue;
+        }
+        translated.push_str(prefix);
+        translated.push(chars[index].to_ascii_lowercase());
+        index += 2;
+        while index < chars.len() && !is_path_boundary(chars[index]) {
+            translated.push(if chars[index] == '\\' { '/' } else { chars[index] });
+            index += 1;
+        }
+    }
+    translated
+}
+
+fn is_path_boundary(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '"' | '\'' | '=' | '(' | ')' | ',' | ';')
+}
+
+fn is_windows_path(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!((chars.next(), chars.next(), chars.next()), (Some(drive), Some(':'), Some('\\' | '/')) if drive.is_ascii_alphabetic())
+}
+
+/// Whether `shell` is installed
+pub fn is_available(shell: Shell) -> bool {
+    match shell {
+        Shell::Native => true,
+        Shell::Wsl => super::command_runner::find_in_path("wsl.exe", &[]).is_some(),
+        Shell::Msys2 => msys2_bash().is_file(),
+    }
+}
+
+/// The bash of the MSYS2 installation in `MSYS2_ROOT`, by default `C:\msys64`
+fn msys2_bash() -> PathBuf {
+    let root = std::env::var_os("MSYS2_ROOT").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(r"C:\msys64"));
+    root.join("usr").join("bin").join("bash.exe")
+}
+
+fn name(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Native => "native",
+        Shell::Wsl => "wsl",
+        Shell::Msys2 => "msys2",
+    }
+}
+
+fn describe(shell: Shell) -> String {
+    match shell {
+        Shell::Native => "native (cmd.exe)".to_string(),
+        Shell::Wsl => "wsl (needs Emscripten installed in WSL)".to_string(),
+        Shell::Msys2 => format!("msys2 ({})", msys2_bash().display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_of_a_recipe() {
+        let commands: Vec<&str> = commands("@if [ -d out ]; then rm -rf out; fi && -mkdir -p out | tee log").map(str::trim).filter(|command| !command.is_empty()).collect();
+        assert_eq!(commands, ["[ -d out ]", "rm -rf out", "fi", "mkdir -p out", "tee log"]);
+    }
+
+    #[test]
+    fn arguments_are_not_commands() {
+        let matches = |recipe: &str| commands(recipe).any(|command| POSIX_COMMANDS.iter().any(|posix| command.starts_with(posix)));
+        assert!(!matches("$(CXX) -c main.cpp -o main.o"));
+        assert!(!matches("emcc -o app.js main.o --preload-file find "));
+        assert!(matches("$(CXX) -o app main.o && cp app bin/"));
+        assert!(matches("@-rm -f *.o"));
+    }
+}
@@ -0,0 +1,66 @@
+//! A minimal view of C/C++ conditional compilation for source analysis: which lines are
+//! compiled for the browser, which are excluded for it (`#ifndef __EMSCRIPTEN__`, the `#else`
+//! of `#ifdef __EMSCRIPTEN__`) and which depend on conditions that cannot be known without
+//! running the preprocessor (`#if 0`, `#ifdef _WIN32`, `__has_include`, ...).
+
+/// Macros defined only for builds running in the browser (raylib uses `PLATFORM_WEB`)
+const WEB_MACROS: &[&str] = &["__EMSCRIPTEN__", "PLATFORM_WEB"];
+
+/// Whether a line is compiled for the browser
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Branch {
+    Compiled,
+    Excluded,
+    /// Inside a conditional that does not test for the browser
+    Unknown,
+}
+
+/// The lines of `code`, each with its line break, and their branch.
+pub fn lines_with_branches(code: &str) -> Vec<(&str, Branch)> {
+    // One entry per open conditional: whether its current branch is compiled for the browser,
+    // or `None` when that is not known
+    let mut conditions: Vec<Option<bool>> = Vec::new();
+    let mut lines = Vec::new();
+    for line in code.split_inclusive('\n') {
+        let directive: String = line
+            .trim_start()
+            .strip_prefix('#')
+            .map(|rest| rest.split_whitespace().collect::<Vec<_>>().join(" "))
+            .unwrap_or_default();
+        let tests = |forms: &[&str]| {
+            WEB_MACROS.iter().any(|name| forms.iter().any(|form| directive == form.replace("{}", name)))
+        };
+        let excluded_before = conditions.contains(&Some(false));
+        if tests(&["ifdef {}", "if defined({})", "if defined {}"]) {
+            conditions.push(Some(true));
+        } else if tests(&["ifndef {}", "if !defined({})", "if !defined {}"]) {
+            conditions.push(Some(false));
+        } else if directive.starts_with("if") {
+            conditions.push(None);
+        } else if directive.starts_with("else") {
+            if let Some(Some(compiled)) = conditions.last_mut() {
+                *compiled = !*compiled;
+            }
+        } else if directive.starts_with("elif") {
+            if let Some(condition) = conditions.last_mut() {
+                // After the browser branch the others are not compiled for it; after a
+                // non-browser branch the next one may be
+                *condition = match condition {
+                    Some(true) => Some(false),
+                    _ => None,
+                };
+            }
+        } else if directive.starts_with("endif") {
+            conditions.pop();
+        }
+        let branch = if excluded_before || conditions.contains(&Some(false)) {
+            Branch::Excluded
+        } else if conditions.contains(&None) {
+            Branch::Unknown
+        } else {
+            Branch::Compiled
+        };
+        lines.push((line, branch));
+    }
+    lines
+}
@@ -0,0 +1,151 @@
+//! Source file selection from the `--source` and `--exclude` glob patterns, and recursive
+//! discovery of all C, C++ (including module interfaces), Objective-C++ and assembly sources
+//! in a project.
+//!
+//! All scanning of project files goes through [`project_files`], which honors `.gitignore`,
+//! `.ignore` and the git exclude files (also outside a git repository), so ignored build
+//! output, vendored checkouts and generated files are neither compiled nor taken as a sign
+//! of a library. Hidden entries and editor backup files (`main.cpp~`, `#main.cpp#`,
+//! `main.cpp.orig`, ...) are skipped as well. Files selected with `--source` are the
+//! exception: the patterns name what to compile, so the ignore rules do not apply to them.
+//!
+//! Patterns are matched against paths relative to the project root using `/` as separator;
+//! `*` does not cross directories, so use `**/` to match at any depth (e.g. `src/**/*.cpp`).
+
+use std::path::{Path, PathBuf};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use crate::app_config::AppConfig;
+
+/// Extensions of the sources picked up by recursive discovery
+const SOURCE_EXTENSIONS: &[&str] = &["cpp", "cxx", "cc", "cppm", "ixx", "c", "mm", "s", "S"];
+
+/// Directories that hold build output or dependencies rather than project sources
+const SKIPPED_DIRS: &[&str] = &["node_modules", "CMakeFiles", "third_party_build", "dist", "out", "target"];
+
+/// Extensions editors and merge tools give backups and swap files
+const BACKUP_EXTENSIONS: &[&str] = &["bak", "orig", "rej", "swp", "swo", "tmp"];
+
+pub struct SourceSelection {
+    /// `None` when no `--source` was given and the handlers' own discovery applies
+    sources: Option<GlobSet>,
+    excludes: GlobSet,
+}
+
+impl SourceSelection {
+    pub fn from_config(config: &AppConfig) -> Result<Self, String> {
+        let sources = if config.sources.is_empty() {
+            None
+        } else {
+            Some(build_glob_set(&config.sources, "--source")?)
+        };
+        let excludes = build_glob_set(&config.excludes, "--exclude")?;
+        Ok(SourceSelection { sources, excludes })
+    }
+
+    /// Whether `--source` patterns replace the automatic source discovery
+    pub fn is_explicit(&self) -> bool {
+        self.sources.is_some()
+    }
+
+    /// Whether `path` (inside `project_path`) matches an `--exclude` pattern
+    pub fn is_excluded(&self, project_path: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(project_path).unwrap_or(path);
+        self.excludes.is_match(relative)
+    }
+
+    /// Returns all files under `project_path` matching a `--source` pattern and no `--exclude`
+    /// pattern, sorted for a stable link order. Hidden files are not searched; ignored files
+    /// and build directories are, since the patterns name what to compile, so generated
+    /// sources can be selected.
+    pub fn collect(&self, project_path: &Path) -> Vec<PathBuf> {
+        let Some(sources) = &self.sources else {
+            return Vec::new();
+        };
+        walk(project_path, None, false, |_| true)
+            .into_iter()
+            .filter(|path| {
+                let relative = path.strip_prefix(project_path).unwrap_or(path);
+                sources.is_match(relative) && !self.excludes.is_match(relative)
+            })
+            .collect()
+    }
+
+    /// Recursively collects every C, C++, Objective-C++ and assembly source under `project_path`,
+    /// skipping `output_dir` and `--exclude` matches besides what [`project_files`] skips.
+    /// Sorted for a stable link order.
+    pub fn discover_recursive(&self, project_path: &Path, output_dir: &Path) -> Vec<PathBuf> {
+        let output_dir = output_dir.canonicalize().ok();
+        walk(project_path, None, true, move |dir| !is_build_dir(dir) && output_dir.as_deref() != dir.canonicalize().ok().as_deref())
+            .into_iter()
+            .filter(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext))
+            })
+            .filter(|path| !self.is_excluded(project_path, path))
+            .collect()
+    }
+}
+
+/// The files under `project_path`, at most `max_depth` levels deep (1 for the project root
+/// only), sorted. Ignored and hidden entries, editor backups and build and dependency
+/// directories (`build*`, `dist`, `node_modules`, ...) are skipped.
+pub fn project_files(project_path: &Path, max_depth: Option<usize>) -> Vec<PathBuf> {
+    walk(project_path, max_depth, true, |dir| !is_build_dir(dir))
+}
+
+fn is_build_dir(dir: &Path) -> bool {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    name.starts_with("build") || SKIPPED_DIRS.contains(&name.as_ref())
+}
+
+fn is_editor_backup(file: &Path) -> bool {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    name.ends_with('~')
+        || (name.starts_with('#') && name.ends_with('#'))
+        || file.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| BACKUP_EXTENSIONS.contains(&ext))
+}
+
+/// The files under `project_path` that are not hidden, and with `ignore_rules` not ignored
+/// either, descending only into the directories `enter_dir` accepts
+fn walk(
+    project_path: &Path,
+    max_depth: Option<usize>,
+    ignore_rules: bool,
+    enter_dir: impl Fn(&Path) -> bool + Send + Sync + 'static,
+) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkBuilder::new(project_path)
+        .max_depth(max_depth)
+        .standard_filters(ignore_rules)
+        .hidden(true)
+        .require_git(false)
+        .filter_entry(move |entry| {
+            entry.depth() == 0 || !entry.file_type().is_some_and(|t| t.is_dir()) || enter_dir(entry.path())
+        })
+        .build()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+        .map(|e| e.into_path())
+        .filter(|path| !is_editor_backup(path))
+        .collect();
+    files.sort();
+    files
+}
+
+/// A glob matched against paths relative to the project root, where `*` does not cross
+/// directories; `option` names where it comes from in the error.
+pub fn path_glob(pattern: &str, option: &str) -> Result<Glob, String> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| format!("Invalid {} pattern {:?}: {}", option, pattern, e))
+}
+
+fn build_glob_set(patterns: &[String], option: &str) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(path_glob(pattern, option)?);
+    }
+    builder.build().map_err(|e| format!("Invalid {} patterns: {}", option, e))
+}
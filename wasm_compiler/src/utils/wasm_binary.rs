@@ -0,0 +1,32 @@
+//! Encoding helpers for the post-processing steps that rewrite `.wasm` files. Parsing is done
+//! with `wasmparser`; these cover the few pieces of the binary format written back.
+
+/// Unsigned LEB128 encoding, as used for sizes and indices in the binary format
+pub fn leb128(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Encodes a section: its id, the size of `content`, then `content`.
+pub fn section(id: u8, content: &[u8]) -> Vec<u8> {
+    let mut section = vec![id];
+    section.extend(leb128(content.len()));
+    section.extend(content);
+    section
+}
+
+/// Encodes a custom section (id 0) with its name followed by `payload`.
+pub fn custom_section(name: &str, payload: &[u8]) -> Vec<u8> {
+    let mut content = leb128(name.len());
+    content.extend(name.as_bytes());
+    content.extend(payload);
+    section(0, &content)
+}
@@ -0,0 +1,165 @@
+//! Building external commands on Windows, where two things break builds that work elsewhere:
+//!
+//! - Paths of `MAX_PATH` (260) characters or more, common with deep project hierarchies, are
+//!   rejected as the working directory of a process and by tools without long-path support.
+//!   Such paths are replaced with their 8.3 short form, or with the extended-length
+//!   (`\\?\`) form when short names are disabled on the volume.
+//! - The Emscripten `.bat` wrappers pass their arguments through `cmd.exe`, which mangles
+//!   quoted `-s` settings such as `-sEXPORTED_FUNCTIONS=["_main","_foo"]`. When the `.py`
+//!   script behind a wrapper is found, it is run with Python directly instead, so the
+//!   arguments reach it with regular command-line quoting. Otherwise the wrapper is run
+//!   through `cmd.exe` with a command line quoted for it (see [`cmd_quote`]).
+//!
+//! The path rewriting itself is platform independent; only the short-name lookup calls into
+//! the Windows API.
+
+use std::path::{Path, PathBuf};
+
+/// Length from which Windows APIs without long-path support reject a path
+pub const MAX_PATH: usize = 260;
+
+/// Flags whose value is a path attached to the flag itself, e.g. `-IC:\deep\include`
+const PATH_FLAG_PREFIXES: &[&str] = &["-I", "-L", "-isystem", "--js-library=", "--pre-js=", "--post-js="];
+
+/// Whether `path` is too long for APIs without long-path support
+pub fn is_too_long(path: &Path) -> bool {
+    path.as_os_str().len() >= MAX_PATH
+}
+
+/// The extended-length form of an absolute path (`\\?\C:\...` or `\\?\UNC\server\...`),
+/// with forward slashes turned into backslashes since the prefix disables normalization.
+pub fn extended_length_path(path: &Path) -> PathBuf {
+    let path = path.to_string_lossy().replace('/', "\\");
+    if path.starts_with(r"\\?\") {
+        PathBuf::from(path)
+    } else if let Some(share) = path.strip_prefix(r"\\") {
+        PathBuf::from(format!(r"\\?\UNC\{}", share))
+    } else {
+        PathBuf::from(format!(r"\\?\{}", path))
+    }
+}
+
+/// Rewrites an argument that is, or ends with (see [`PATH_FLAG_PREFIXES`]), an absolute path
+/// of [`MAX_PATH`] characters or more using `shorten`. Other arguments are returned unchanged.
+pub fn normalize_path_arg(arg: &str, shorten: &dyn Fn(&Path) -> PathBuf) -> String {
+    let (prefix, value) = PATH_FLAG_PREFIXES
+        .iter()
+        .find_map(|prefix| arg.strip_prefix(prefix).map(|value| (*prefix, value)))
+        .unwrap_or(("", arg));
+    let path = Path::new(value);
+    if !path.is_absolute() || !is_too_long(path) {
+        return arg.to_string();
+    }
+    format!("{}{}", prefix, shorten(path).display())
+}
+
+/// The `.py` script next to the `.bat` wrapper of an Emscripten tool found in `PATH`, e.g.
+/// `emcc.py` for `emcc.bat`.
+pub fn wrapper_script(tool: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(format!("{}.bat", tool)))
+        .find(|wrapper| wrapper.is_file())
+        .map(|wrapper| wrapper.with_extension("py"))
+        .filter(|script| script.is_file())
+}
+
+/// Characters `cmd.exe` interprets on a command line, escaped with `^`
+const CMD_SPECIAL: &[char] = &['"', '^', '%', '&', '|', '<', '>', '(', ')'];
+
+/// `arg` quoted for a `.bat` file run by `cmd.exe /c`, so the wrapper hands it on to the tool
+/// unchanged: first quoted the way programs split their command line
+/// (`CommandLineToArgvW`), then with the characters `cmd.exe` interprets escaped.
+pub fn cmd_quote(arg: &str) -> String {
+    let quoted = if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '"']) {
+        arg.to_string()
+    } else {
+        let mut quoted = String::from('"');
+        let mut backslashes = 0;
+        for c in arg.chars() {
+            if c == '\\' {
+                backslashes += 1;
+                continue;
+            }
+            // Backslashes before a quote are escaped, and so is the quote
+            let escapes = if c == '"' { backslashes * 2 + 1 } else { backslashes };
+            quoted.extend(std::iter::repeat_n('\\', escapes));
+            backslashes = 0;
+            quoted.push(c);
+        }
+        // Backslashes before the closing quote are escaped
+        quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+        quoted.push('"');
+        quoted
+    };
+    let mut escaped = String::with_capacity(quoted.len());
+    for c in quoted.chars() {
+        if CMD_SPECIAL.contains(&c) {
+            escaped.push('^');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The command line, passed with `CommandExt::raw_arg`, on which `cmd.exe /d /s /c` runs
+/// `wrapper` (a `.bat` file) with `args`; `/s` strips its outer quotes again
+pub fn cmd_command_line(wrapper: &Path, args: &[String]) -> String {
+    let words: Vec<String> = std::iter::once(format!("\"{}\"", wrapper.display())).chain(args.iter().map(|arg| cmd_quote(arg))).collect();
+    format!("\"{}\"", words.join(" "))
+}
+
+/// The interpreter the Emscripten wrappers use: `EMSDK_PYTHON`, or `python` from `PATH`
+pub fn python() -> PathBuf {
+    std::env::var_os("EMSDK_PYTHON").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("python"))
+}
+
+/// A form of `path` short enough for APIs without long-path support: the 8.3 short path if
+/// the volume has short names, otherwise the extended-length path. A path that does not
+/// exist yet (e.g. an output file) keeps its file name and has its parent shortened.
+#[cfg(windows)]
+pub fn shorten_path(path: &Path) -> PathBuf {
+    if let Some(short) = short_path_name(path).filter(|short| !is_too_long(short)) {
+        return short;
+    }
+    if !path.exists() {
+        if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+            if let Some(short) = short_path_name(parent).map(|parent| parent.join(name)).filter(|short| !is_too_long(short)) {
+                return short;
+            }
+        }
+    }
+    extended_length_path(path)
+}
+
+/// `GetShortPathNameW` for an existing path, without the `\\?\` prefix.
+#[cfg(windows)]
+fn short_path_name(path: &Path) -> Option<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetShortPathNameW(long_path: *const u16, short_path: *mut u16, buffer_len: u32) -> u32;
+    }
+
+    let long_path: Vec<u16> = extended_length_path(path).as_os_str().encode_wide().chain(Some(0)).collect();
+    // SAFETY: `long_path` is NUL-terminated; an empty buffer asks for the required length
+    let required = unsafe { GetShortPathNameW(long_path.as_ptr(), std::ptr::null_mut(), 0) };
+    if required == 0 {
+        return None;
+    }
+    let mut buffer = vec![0u16; required as usize];
+    // SAFETY: `buffer` holds `required` units, including the terminating NUL
+    let written = unsafe { GetShortPathNameW(long_path.as_ptr(), buffer.as_mut_ptr(), required) };
+    if written == 0 || written >= required {
+        return None;
+    }
+    buffer.truncate(written as usize);
+    let short = OsString::from_wide(&buffer).to_string_lossy().into_owned();
+    let short = match short.strip_prefix(r"\\?\UNC\") {
+        Some(share) => format!(r"\\{}", share),
+        None => short.strip_prefix(r"\\?\").unwrap_or(&short).to_string(),
+    };
+    Some(PathBuf::from(short))
+}
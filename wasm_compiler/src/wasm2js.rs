@@ -0,0 +1,80 @@
+//! The asm.js fallback (`--wasm2js-fallback`). After the main build the project is built a
+//! second time with `-sWASM=0`, which makes emcc translate the module to JavaScript with
+//! wasm2js, into `<output>.wasm2js.js`. The generated pages import it instead of the regular
+//! glue when `WebAssembly` is unavailable, e.g. in Safari's Lockdown Mode or on locked-down
+//! enterprise browsers. Both versions export the same factory, so nothing else changes.
+
+use crate::app_config::AppConfig;
+use crate::output_assets::AssetNames;
+use crate::Error;
+
+/// Suffix of the fallback's output name: `<output>.wasm2js.js`
+pub const FALLBACK_SUFFIX: &str = "wasm2js";
+
+/// Whether `config` builds asm.js instead of a WebAssembly module
+pub fn is_wasm2js(config: &AppConfig) -> bool {
+    config.emcc_flag.iter().any(|flag| flag == "-sWASM=0")
+}
+
+/// Checks before the build whether the fallback can be built for this configuration. Node.js
+/// always has WebAssembly, so node builds skip it with a warning.
+pub fn check_fallback(config: &AppConfig) -> Result<bool, Error> {
+    if crate::wasm_runtime::is_standalone(config) || config.component {
+        return Err(Error::Config(
+            "--wasm2js-fallback needs the Emscripten JS glue; it cannot be combined with standalone, WASI or component builds".to_string(),
+        ));
+    }
+    if is_wasm2js(config) {
+        crate::strict::fallback(config, "--wasm2js-fallback has no effect: the build already uses -sWASM=0").map_err(Error::Config)?;
+        return Ok(false);
+    }
+    if config.target_env.eq_ignore_ascii_case("node") {
+        crate::strict::fallback(config, "--wasm2js-fallback has no effect for --target-env node, which always supports WebAssembly").map_err(Error::Config)?;
+        return Ok(false);
+    }
+    if config.single_file {
+        crate::strict::fallback(config, "With --single-file the generated index.html embeds only the WebAssembly build; load the wasm2js fallback yourself").map_err(Error::Config)?;
+    }
+    Ok(true)
+}
+
+/// The configuration of the fallback build: the main configuration writing
+/// `<output>.wasm2js.js` with `-sWASM=0`. The flags enabling WebAssembly features that wasm2js
+/// cannot translate (threads, SIMD, ...) are dropped.
+pub fn fallback_config(config: &AppConfig) -> AppConfig {
+    let mut fallback = config.clone();
+    fallback.output_name = format!("{}.{}", config.output_name, FALLBACK_SUFFIX);
+    fallback.emcc_flag.push("-sWASM=0".to_string());
+    crate::browser_baseline::drop_feature_flags(&mut fallback, |_| false, "the wasm2js fallback");
+    // There is no .wasm file to check for, and the memory initializer stays inside the glue
+    fallback.single_file = true;
+    fallback.matrix_target = Some(match &config.matrix_target {
+        Some(target) => format!("{}-{}", target, FALLBACK_SUFFIX),
+        None => FALLBACK_SUFFIX.to_string(),
+    });
+    fallback.wasm2js_fallback = false;
+    fallback
+}
+
+/// Whether `config` is the configuration of the fallback build (see [`fallback_config`])
+pub fn is_fallback(config: &AppConfig) -> bool {
+    config.matrix_target.as_deref().is_some_and(|target| {
+        target == FALLBACK_SUFFIX || target.strip_suffix(FALLBACK_SUFFIX).is_some_and(|target| target.ends_with('-'))
+    })
+}
+
+/// The name of the CMake target the output is built from: the fallback is built from the
+/// target of the WebAssembly build
+pub fn target_name(config: &AppConfig) -> &str {
+    let suffix = format!(".{}", FALLBACK_SUFFIX);
+    match config.output_name.strip_suffix(suffix.as_str()) {
+        Some(name) if is_fallback(config) => name,
+        _ => &config.output_name,
+    }
+}
+
+/// File name of the fallback glue, if this build produced one
+pub fn fallback_name(config: &AppConfig, assets: &AssetNames) -> Option<String> {
+    let name = format!("{}.{}.js", config.output_name, FALLBACK_SUFFIX);
+    (config.wasm2js_fallback && assets.auxiliary.contains(&name)).then_some(name)
+}
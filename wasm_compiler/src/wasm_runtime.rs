@@ -0,0 +1,88 @@
+//! Running standalone and WASI builds after compiling them (`--run`). The `.wasm` module is
+//! executed under wasmtime or wasmer with the `--map-dir` directories and the arguments after
+//! `--`, and its output goes straight to the terminal, which makes `wasm_compiler` a
+//! compile-and-run tool for non-browser targets.
+
+use std::path::Path;
+use crate::app_config::{AppConfig, DirMapping, WasmRuntime};
+use crate::output_assets::AssetNames;
+use crate::utils::command_runner::{is_command_in_path, run_interactive};
+use crate::Error;
+
+/// Whether the build produces a module that runs without the Emscripten JS glue
+pub fn is_standalone(config: &AppConfig) -> bool {
+    config.target_env.eq_ignore_ascii_case("wasi")
+        || config.emcc_flag.iter().any(|flag| flag == "-sSTANDALONE_WASM" || flag == "-sSTANDALONE_WASM=1")
+}
+
+/// Checks before the build that `--run` can run its output, and picks the runtime.
+pub fn check_runnable(config: &AppConfig) -> Result<WasmRuntime, Error> {
+    if !is_standalone(config) {
+        return Err(Error::Config(
+            "--run needs a standalone module; build with --target-env wasi or -sSTANDALONE_WASM=1".to_string(),
+        ));
+    }
+    if config.no_entry {
+        return Err(Error::Config("--run needs a main() function, but this is a library build (--no-entry)".to_string()));
+    }
+    if config.single_file {
+        return Err(Error::Config("--run cannot be combined with --single-file, which embeds the module in the JS glue".to_string()));
+    }
+    select_runtime(config)
+}
+
+/// Runs the built module under `runtime` (see [`check_runnable`]). Fails with
+/// [`Error::Run`] when the program exits unsuccessfully.
+pub fn run_module(config: &AppConfig, runtime: WasmRuntime, assets: &AssetNames) -> Result<(), Error> {
+    let module = config.output_dir.join(&assets.wasm);
+    let (program, args) = runtime_command(runtime, &module, &config.map_dirs, &config.run_args);
+    tracing::info!("Running {} with {}", assets.wasm, program);
+
+    let status = run_interactive(program, &args, config).map_err(|e| Error::Command(e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::Run { status: status.code() })
+    }
+}
+
+/// The `--runtime`, or the first installed one
+fn select_runtime(config: &AppConfig) -> Result<WasmRuntime, Error> {
+    let installed = |runtime: &WasmRuntime| is_command_in_path(runtime_program(*runtime), config);
+    match config.runtime {
+        Some(runtime) if installed(&runtime) => Ok(runtime),
+        Some(runtime) => Err(Error::Config(format!("{} not found in PATH", runtime_program(runtime)))),
+        None => [WasmRuntime::Wasmtime, WasmRuntime::Wasmer]
+            .into_iter()
+            .find(installed)
+            .ok_or_else(|| Error::Config("--run needs wasmtime or wasmer in PATH".to_string())),
+    }
+}
+
+fn runtime_program(runtime: WasmRuntime) -> &'static str {
+    match runtime {
+        WasmRuntime::Wasmtime => "wasmtime",
+        WasmRuntime::Wasmer => "wasmer",
+    }
+}
+
+/// The command line running `module`. Host directories are made absolute; the runtimes
+/// spell the mapping differently (`--dir HOST::GUEST` vs. `--mapdir GUEST:HOST`).
+fn runtime_command(runtime: WasmRuntime, module: &Path, map_dirs: &[DirMapping], run_args: &[String]) -> (&'static str, Vec<String>) {
+    let host = |mapping: &DirMapping| {
+        std::path::absolute(&mapping.host).unwrap_or_else(|_| mapping.host.clone()).display().to_string()
+    };
+    let mut args = vec!["run".to_string()];
+    for mapping in map_dirs {
+        match runtime {
+            WasmRuntime::Wasmtime => args.push(format!("--dir={}::{}", host(mapping), mapping.guest)),
+            WasmRuntime::Wasmer => args.push(format!("--mapdir={}:{}", mapping.guest, host(mapping))),
+        }
+    }
+    args.push(module.display().to_string());
+    if runtime == WasmRuntime::Wasmer && !run_args.is_empty() {
+        args.push("--".to_string());
+    }
+    args.extend(run_args.iter().cloned());
+    (runtime_program(runtime), args)
+}
@@ -0,0 +1,125 @@
+//! Validation of the linked `.wasm` module. The module is parsed and validated with
+//! `wasmparser`, the post-MVP features it uses (threads, SIMD, memory64, ...) are reported,
+//! and they are checked against what the target environment supports, so that corrupt or
+//! feature-mismatched modules fail the build instead of failing to load for users.
+
+use std::fmt;
+use wasmparser::{Validator, WasmFeatures};
+use crate::app_config::AppConfig;
+
+/// A WebAssembly feature that not every runtime supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmFeature {
+    /// Shared memory and atomics (`-pthread`)
+    Threads,
+    /// Fixed-width and relaxed SIMD (`-msimd128`, `-mrelaxed-simd`)
+    Simd,
+    /// 64-bit memory indices (`-sMEMORY64`)
+    Memory64,
+    /// Native exception handling (`-fwasm-exceptions`)
+    Exceptions,
+    /// Tail calls (`-mtail-call`)
+    TailCall,
+}
+
+impl WasmFeature {
+    pub const ALL: [WasmFeature; 5] = [
+        WasmFeature::Threads,
+        WasmFeature::Simd,
+        WasmFeature::Memory64,
+        WasmFeature::Exceptions,
+        WasmFeature::TailCall,
+    ];
+
+    /// The `wasmparser` proposals making up the feature
+    fn flags(self) -> WasmFeatures {
+        match self {
+            WasmFeature::Threads => WasmFeatures::THREADS | WasmFeatures::SHARED_EVERYTHING_THREADS,
+            WasmFeature::Simd => WasmFeatures::SIMD | WasmFeatures::RELAXED_SIMD,
+            WasmFeature::Memory64 => WasmFeatures::MEMORY64,
+            WasmFeature::Exceptions => WasmFeatures::EXCEPTIONS | WasmFeatures::LEGACY_EXCEPTIONS,
+            WasmFeature::TailCall => WasmFeatures::TAIL_CALL,
+        }
+    }
+}
+
+impl fmt::Display for WasmFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WasmFeature::Threads => "threads",
+            WasmFeature::Simd => "simd",
+            WasmFeature::Memory64 => "memory64",
+            WasmFeature::Exceptions => "exceptions",
+            WasmFeature::TailCall => "tail-call",
+        })
+    }
+}
+
+/// Validates the module in `bytes` and returns the features it uses. A feature is used when
+/// the module no longer validates with that feature disabled.
+pub fn module_features(bytes: &[u8]) -> Result<Vec<WasmFeature>, String> {
+    Validator::new_with_features(WasmFeatures::all())
+        .validate_all(bytes)
+        .map_err(|e| e.to_string())?;
+    Ok(WasmFeature::ALL
+        .into_iter()
+        .filter(|feature| {
+            Validator::new_with_features(WasmFeatures::all().difference(feature.flags()))
+                .validate_all(bytes)
+                .is_err()
+        })
+        .collect())
+}
+
+/// Features the target environment, or the `--browser-baseline` of a web build, does not
+/// support, with the reason
+fn unsupported_features(config: &AppConfig, features: &[WasmFeature]) -> Vec<(WasmFeature, String)> {
+    features
+        .iter()
+        .filter_map(|feature| {
+            let reason = match (config.target_env.to_lowercase().as_str(), config.browser_baseline) {
+                ("node", _) => None,
+                ("wasi", _) => (*feature == WasmFeature::Threads)
+                    .then(|| "WASI runtimes do not run shared-memory modules without wasi-threads".to_string()),
+                (_, Some(baseline)) => (!baseline.supports(*feature))
+                    .then(|| format!("not supported by browser baseline '{}'", baseline.name())),
+                ("web", None) => (*feature == WasmFeature::Memory64)
+                    .then(|| "Safari does not support 64-bit memories".to_string()),
+                (_, None) => None,
+            }?;
+            Some((*feature, reason))
+        })
+        .collect()
+}
+
+/// Validates the linked module in the output directory, if there is one, and fails when it
+/// is invalid or uses features the target environment does not support.
+pub fn validate_output(config: &AppConfig) -> Result<(), String> {
+    let wasm_path = config.output_dir.join(format!("{}.wasm", config.output_name));
+    if !wasm_path.is_file() {
+        return Ok(());
+    }
+    let bytes = std::fs::read(&wasm_path).map_err(|e| format!("Failed to read {:?}: {}", wasm_path, e))?;
+    let features = module_features(&bytes)
+        .map_err(|e| format!("{:?} is not a valid WebAssembly module: {}", wasm_path, e))?;
+    tracing::info!(
+        "Validated {:?}; features used: {}",
+        wasm_path,
+        if features.is_empty() {
+            "none".to_string()
+        } else {
+            features.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+        }
+    );
+
+    let unsupported = unsupported_features(config, &features);
+    if unsupported.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "{:?} uses features unsupported by --target-env {}: {}; use --no-wasm-validation to keep it anyway",
+        wasm_path,
+        config.target_env,
+        unsupported.iter().map(|(feature, reason)| format!("{} ({})", feature, reason)).collect::<Vec<_>>().join(", ")
+    ))
+}
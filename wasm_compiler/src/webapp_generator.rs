@@ -1,62 +1,419 @@
-use std::path::Path;
-use crate::app_config::AppConfig;
-
-/// Determines if the application is a GUI application that needs a webapp wrapper
-pub fn is_gui_application(config: &AppConfig) -> bool {
-    // Check if ImGui is enabled
-    if config.with_imgui {
-        return true;
+use std::path::{Path, PathBuf};
+use crate::app_config::{AppConfig, HtmlMode, ModuleFormat, UiKind, WebappMode};
+use crate::exports::{ExportKind, ExportedFunction};
+use crate::output_assets::AssetNames;
+
+/// Creates a complete webapp in the output directory for GUI applications, and returns the
+/// files it wrote
+pub fn create_webapp(config: &AppConfig, assets: &AssetNames) -> Result<Vec<PathBuf>, std::io::Error> {
+    // Node builds get a command-line launcher instead of a browser page
+    if config.target_env.to_lowercase().as_str() == "node" {
+        if config.electron {
+            crate::strict::fallback(config, "--electron has no effect for node target builds").map_err(std::io::Error::other)?;
+        }
+        if config.no_entry {
+            tracing::info!("Library build: import {} from Node.js directly, no run.mjs launcher is generated", assets.js);
+            return Ok(Vec::new());
+        }
+        if config.webapp == WebappMode::Never {
+            tracing::debug!("run.mjs launcher skipped (mode: {:?})", config.webapp);
+            return Ok(Vec::new());
+        }
+        return Ok(vec![create_node_runner(config, assets)?]);
     }
-    
-    // Check project path for GUI-related keywords
-    let project_path_str = config.project_path.to_string_lossy().to_lowercase();
-    if project_path_str.contains("imgui") || 
-       project_path_str.contains("gui") || 
-       project_path_str.contains("graphics") ||
-       project_path_str.contains("opengl") ||
-       project_path_str.contains("sdl") ||
-       project_path_str.contains("glfw") {
-        return true;
+
+    if config.module_format == ModuleFormat::Cjs {
+        tracing::info!("CommonJS output is meant for Node.js and bundlers; no browser pages are generated");
+        return Ok(Vec::new());
     }
-    
-    // Check if we're using WebGL/OpenGL flags
-    if let Some(flags) = &config.emcc_flags {
-        if flags.contains("WEBGL") || flags.contains("USE_SDL") || flags.contains("USE_GLFW") {
-            return true;
-        }
+
+    let mut written = Vec::new();
+    // The exports test page is useful for libraries too, so it does not depend on GUI detection
+    let exports = crate::exports::known_exports(config);
+    if !exports.is_empty() && config.webapp != WebappMode::Never && !config.single_file {
+        written.push(create_exports_page(config, assets, &exports)?);
     }
-    
-    false
-}
 
-/// Creates a complete webapp in the output directory for GUI applications
-pub fn create_webapp(config: &AppConfig) -> Result<(), std::io::Error> {
-    if !is_gui_application(config) {
-        log::debug!("Not a GUI application, skipping webapp creation");
-        return Ok(());
+    let generate = match config.webapp {
+        WebappMode::Always => true,
+        WebappMode::Never => false,
+        WebappMode::Auto => crate::ui_kind::resolve(config) == UiKind::Graphical,
+    };
+    if !generate {
+        if config.electron {
+            crate::strict::fallback(config, "--electron requires a webapp, but none is being generated; skipping Electron wrapper").map_err(std::io::Error::other)?;
+        }
+        tracing::debug!("Webapp generation skipped (mode: {:?})", config.webapp);
+        return Ok(written);
     }
     
-    log::info!("Creating webapp for GUI application: {}", config.output_name);
+    tracing::info!("Creating webapp for GUI application: {}", config.output_name);
     
-    create_html_file(&config.output_dir, &config.output_name)?;
-    create_css_file(&config.output_dir)?;
-    create_python_server(&config.output_dir, &config.output_name)?;
-    create_readme(&config.output_dir, &config.output_name)?;
+    match config.html {
+        HtmlMode::Full => {
+            written.push(create_html_file(config, assets)?);
+            written.push(create_css_file(&config.output_dir)?);
+        }
+        HtmlMode::Minimal => written.push(create_minimal_html_file(config, assets)?),
+    }
+    if config.single_file {
+        embed_module_in_html(config, assets)?;
+    }
+    if config.pwa {
+        written.extend(create_pwa_files(config, assets)?);
+    }
+    if config.electron {
+        written.extend(create_electron_wrapper(config)?);
+    }
+    if config.single_file {
+        tracing::info!("Webapp created successfully: {:?} is self-contained and can be opened directly", config.output_dir.join("index.html"));
+        return Ok(written);
+    }
+    written.push(create_python_server(&config.output_dir, &config.output_name, assets)?);
+    written.push(create_readme(&config.output_dir, &config.output_name, assets)?);
     
-    log::info!("Webapp created successfully in: {:?}", config.output_dir);
-    log::info!("To serve the webapp, run: python serve.py");
+    tracing::info!("Webapp created successfully in: {:?}", config.output_dir);
+    tracing::info!("To serve the webapp, run: python serve.py");
     
-    Ok(())
+    Ok(written)
+}
+
+/// Creates `exports.html`, a test harness listing each exported function with an input for its
+/// arguments and a button that calls it and logs the result. Arguments are entered as a
+/// comma-separated list of JSON values, e.g. `1, 2.5, "text"`.
+fn create_exports_page(config: &AppConfig, assets: &AssetNames, exports: &[ExportedFunction]) -> Result<PathBuf, std::io::Error> {
+    let rows: String = exports
+        .iter()
+        .map(|export| {
+            let (accessor, kind) = match export.kind {
+                ExportKind::CFunction => (format!("_{}", export.name), "C"),
+                ExportKind::Embind => (export.name.clone(), "embind"),
+            };
+            let name = escape_html(&export.name);
+            format!(
+                r#"        <tr>
+            <td><code>{name}</code></td>
+            <td>{kind}</td>
+            <td><input type="text" id="args-{name}" placeholder="arg1, arg2, ..."></td>
+            <td><button data-export="{accessor}" data-input="args-{name}">Call</button></td>
+        </tr>
+"#,
+                name = name,
+                kind = kind,
+                accessor = escape_html(&accessor),
+            )
+        })
+        .collect();
+
+    let html_content = format!(r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <title>{title} - exports</title>
+    <style>
+        body {{ font-family: sans-serif; margin: 2em; }}
+        table {{ border-collapse: collapse; }}
+        td, th {{ padding: 4px 10px; border-bottom: 1px solid #ddd; text-align: left; }}
+        input {{ width: 20em; }}
+        #log {{ background: #111; color: #0f0; font-family: monospace; padding: 10px; white-space: pre-wrap; min-height: 8em; }}
+    </style>
+</head>
+<body>
+    <h1>{title} - exported functions</h1>
+    <p id="status">Loading module...</p>
+    <table>
+        <tr><th>Function</th><th>Kind</th><th>Arguments (JSON values)</th><th></th></tr>
+{rows}    </table>
+    <h2>Log</h2>
+    <div id="log"></div>
+
+    <script type="module">
+        {module_import}
+
+        const logElement = document.getElementById('log');
+        function log(message) {{
+            logElement.textContent += message + '\n';
+        }}
+
+        const instance = await createModule({{
+            noInitialRun: true,
+            print: (text) => log('stdout: ' + text),
+            printErr: (text) => log('stderr: ' + text),
+        }});
+        window.Module = instance;
+        document.getElementById('status').textContent = 'Module loaded.';
+
+        for (const button of document.querySelectorAll('button[data-export]')) {{
+            button.addEventListener('click', () => {{
+                const name = button.dataset.export;
+                const raw = document.getElementById(button.dataset.input).value.trim();
+                try {{
+                    const args = raw ? JSON.parse('[' + raw + ']') : [];
+                    if (typeof instance[name] !== 'function') {{
+                        throw new Error(name + ' is not exported by the module');
+                    }}
+                    const result = instance[name](...args);
+                    log(name + '(' + args.map((a) => JSON.stringify(a)).join(', ') + ') => ' + JSON.stringify(result));
+                }} catch (e) {{
+                    log(name + ' failed: ' + e);
+                }}
+            }});
+        }}
+    </script>
+</body>
+</html>
+"#, title = escape_html(config.page_title()), rows = rows, module_import = module_import(config, &glue_url(config, assets)));
+
+    let exports_path = config.output_dir.join("exports.html");
+    std::fs::write(&exports_path, html_content)?;
+
+    tracing::info!("Created exports test page with {} function(s) at: {:?}", exports.len(), exports_path);
+    Ok(exports_path)
+}
+
+/// Creates a `run.mjs` launcher for Node.js builds. It forwards the command-line arguments to
+/// `callMain`, wires the module's stdout/stderr to the process, and propagates the exit code.
+fn create_node_runner(config: &AppConfig, assets: &AssetNames) -> Result<PathBuf, std::io::Error> {
+    let output_dir = config.output_dir.as_path();
+    let output_name = config.output_name.as_str();
+    let runner_content = format!(r#"#!/usr/bin/env node
+// Launcher generated by wasm_compiler for: {}
+// Usage: node run.mjs [args...]
+import path from 'node:path';
+import {{ fileURLToPath }} from 'node:url';
+import createModule from './{}';
+
+// Runtime settings from the project config; configured arguments precede command-line ones.
+const runtimeConfig = {};
+const scriptDir = path.dirname(fileURLToPath(import.meta.url));
+
+let exitCode = 0;
+
+const instance = await createModule({{
+    // main() is invoked explicitly below so its return value can be captured
+    noInitialRun: true,
+    preRun: [(mod) => {{
+        if (mod.ENV) {{
+            Object.assign(mod.ENV, runtimeConfig.env);
+        }}
+    }}],
+    locateFile: (file, prefix) => runtimeConfig.locateFilePrefix !== null
+        ? path.join(scriptDir, runtimeConfig.locateFilePrefix, file)
+        : prefix + file,
+    print: (text) => process.stdout.write(text + '\n'),
+    printErr: (text) => process.stderr.write(text + '\n'),
+    onExit: (code) => {{
+        exitCode = code;
+    }},
+}});
+
+try {{
+    const status = instance.callMain([...runtimeConfig.arguments, ...process.argv.slice(2)]);
+    if (typeof status === 'number') {{
+        exitCode = status;
+    }}
+}} catch (e) {{
+    if (e && e.name === 'ExitStatus') {{
+        exitCode = e.status;
+    }} else {{
+        console.error(e);
+        exitCode = 1;
+    }}
+}}
+
+process.exitCode = exitCode;
+"#, output_name, assets.js, config.project_config.runtime.to_js_object());
+
+    let runner_path = output_dir.join("run.mjs");
+    std::fs::write(&runner_path, runner_content)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = std::fs::metadata(&runner_path)?.permissions();
+        perms.set_mode(0o755);
+        std::fs::set_permissions(&runner_path, perms)?;
+    }
+
+    tracing::info!("Created Node.js launcher at: {:?}", runner_path);
+    tracing::info!("To run the program, use: node {:?} [args...]", runner_path);
+    Ok(runner_path)
+}
+
+/// Creates a minimal Electron app around the webapp so it can be run as a desktop application
+/// with `npm install && npm start` from the output directory. The page is served through a
+/// privileged `app://` scheme because ES module imports and wasm fetches are blocked for `file://`.
+fn create_electron_wrapper(config: &AppConfig) -> Result<Vec<PathBuf>, std::io::Error> {
+    let output_dir = config.output_dir.as_path();
+    let output_name = config.output_name.as_str();
+    let package_name: String = output_name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    let package_json = serde_json::json!({
+        "name": package_name,
+        "version": "0.1.0",
+        "private": true,
+        "description": config.app_description.clone()
+            .unwrap_or_else(|| format!("{} - WebAssembly desktop application", config.page_title())),
+        "main": "main.js",
+        "scripts": {
+            "start": "electron ."
+        },
+        "devDependencies": {
+            "electron": "^31.0.0"
+        }
+    });
+    std::fs::write(output_dir.join("package.json"), serde_json::to_string_pretty(&package_json)?)?;
+
+    let main_content = format!(r#"// Electron entry point generated by wasm_compiler for: {}
+const {{ app, BrowserWindow, protocol, net }} = require('electron');
+const path = require('path');
+const {{ pathToFileURL }} = require('url');
+
+protocol.registerSchemesAsPrivileged([
+    {{ scheme: 'app', privileges: {{ standard: true, secure: true, supportFetchAPI: true }} }},
+]);
+
+function createWindow() {{
+    const win = new BrowserWindow({{
+        useContentSize: true,
+        width: {},
+        height: {},
+        title: {},
+        webPreferences: {{
+            preload: path.join(__dirname, 'preload.js'),
+            contextIsolation: true,
+            nodeIntegration: false,
+        }},
+    }});
+    win.setMenuBarVisibility(false);
+    win.loadURL('app://bundle/index.html');
+}}
+
+app.whenReady().then(() => {{
+    protocol.handle('app', (request) => {{
+        const {{ pathname }} = new URL(request.url);
+        const filePath = path.normalize(path.join(__dirname, decodeURIComponent(pathname)));
+        if (!filePath.startsWith(__dirname)) {{
+            return new Response('Forbidden', {{ status: 403 }});
+        }}
+        return net.fetch(pathToFileURL(filePath).toString());
+    }});
+
+    createWindow();
+
+    app.on('activate', () => {{
+        if (BrowserWindow.getAllWindows().length === 0) {{
+            createWindow();
+        }}
+    }});
+}});
+
+app.on('window-all-closed', () => {{
+    if (process.platform !== 'darwin') {{
+        app.quit();
+    }}
+}});
+"#, output_name, config.canvas_size.width, config.canvas_size.height,
+        serde_json::to_string(config.page_title())?);
+    std::fs::write(output_dir.join("main.js"), main_content)?;
+
+    let preload_content = r#"// Preload script generated by wasm_compiler.
+// Expose a minimal, explicit API to the page; the page runs without Node.js integration.
+const { contextBridge } = require('electron');
+
+contextBridge.exposeInMainWorld('desktop', {
+    platform: process.platform,
+    versions: {
+        electron: process.versions.electron,
+        chrome: process.versions.chrome,
+    },
+});
+"#;
+    std::fs::write(output_dir.join("preload.js"), preload_content)?;
+
+    tracing::info!("Created Electron wrapper in: {:?}", output_dir);
+    tracing::info!("To run as a desktop app: cd {:?} && npm install && npm start", output_dir);
+    Ok(["package.json", "main.js", "preload.js"].iter().map(|name| output_dir.join(name)).collect())
+}
+
+/// Creates `index.html` in the output directory of an `--all-executables` build, linking to
+/// the page of each executable: its webapp, else its exports page, else its JS glue. Skipped
+/// when no browser pages are generated. Returns the page, if it was written.
+pub fn create_index_page(config: &AppConfig, built: &[crate::TargetArtifacts]) -> Result<Option<PathBuf>, std::io::Error> {
+    if config.webapp == WebappMode::Never || config.target_env.eq_ignore_ascii_case("node") || config.module_format == ModuleFormat::Cjs {
+        return Ok(None);
+    }
+    let items: String = built
+        .iter()
+        .map(|executable| {
+            let page = ["index.html", "exports.html"]
+                .into_iter()
+                .find(|page| executable.artifacts.output_dir.join(page).is_file())
+                .map(str::to_string)
+                .or_else(|| executable.artifacts.js.path.file_name().map(|name| name.to_string_lossy().into_owned()))
+                .unwrap_or_default();
+            format!(
+                "        <li><a href=\"{name}/{page}\">{name}</a></li>\n",
+                name = escape_html(&executable.name),
+                page = escape_html(&page),
+            )
+        })
+        .collect();
+    let project_name = config.project_path.canonicalize().ok().and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()));
+    let title = escape_html(config.page_title.as_deref().or(project_name.as_deref()).unwrap_or("Executables"));
+
+    let html_content = format!(r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>{title}</title>
+    <style>
+        body {{ font-family: sans-serif; margin: 2em; }}
+        li {{ margin: 0.4em 0; }}
+    </style>
+</head>
+<body>
+    <h1>{title}</h1>
+    <ul>
+{items}    </ul>
+</body>
+</html>
+"#);
+    let html_path = config.output_dir.join("index.html");
+    std::fs::write(&html_path, html_content)?;
+    tracing::info!("Created {:?} listing {} executables", html_path, built.len());
+    Ok(Some(html_path))
+}
+
+/// Escapes text for safe inclusion in HTML content and attribute values
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
 }
 
 /// Creates the main HTML file
-fn create_html_file(output_dir: &Path, output_name: &str) -> Result<(), std::io::Error> {
+fn create_html_file(config: &AppConfig, assets: &AssetNames) -> Result<PathBuf, std::io::Error> {
+    let output_dir = config.output_dir.as_path();
+    let module_import = module_import(config, &glue_url(config, assets));
+    let title = escape_html(config.page_title());
+    let description = escape_html(config.app_description.as_deref().unwrap_or("Compiled with wasm_compiler"));
+    let width = config.canvas_size.width;
+    let height = config.canvas_size.height;
+    let runtime_config = config.project_config.runtime.to_js_object();
     let html_content = format!(r#"<!DOCTYPE html>
 <html lang="en">
 <head>
     <meta charset="UTF-8">
     <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>ImGUI WebAssembly Application</title>
+    <meta name="description" content="{description}">
+    <title>{title}</title>
     <style>
         body {{
             margin: 0;
@@ -131,8 +488,8 @@ fn create_html_file(output_dir: &Path, output_name: &str) -> Result<(), std::io:
 </head>
 <body>
     <div class="header">
-        <h1>🎮 ImGUI WebAssembly Application</h1>
-        <p>Compiled with wasm_compiler</p>
+        <h1>🎮 {title}</h1>
+        <p>{description}</p>
     </div>
     
     <div class="canvas-container">
@@ -140,7 +497,7 @@ fn create_html_file(output_dir: &Path, output_name: &str) -> Result<(), std::io:
             <p>⏳ Loading WebAssembly module...</p>
             <p>This may take a few moments...</p>
         </div>
-        <canvas id="canvas" style="display: none;" width="1280" height="720"></canvas>
+        <canvas id="canvas" style="display: none;" width="{width}" height="{height}"></canvas>
     </div>
     
     <div class="controls">
@@ -198,8 +555,8 @@ fn create_html_file(output_dir: &Path, output_name: &str) -> Result<(), std::io:
             const containerRect = container.getBoundingClientRect();
             
             // Set canvas size to fit container while maintaining aspect ratio
-            const aspectRatio = 16 / 9;
-            let width = Math.min(containerRect.width - 40, 1280);
+            const aspectRatio = {width} / {height};
+            let width = Math.min(containerRect.width - 40, {width});
             let height = width / aspectRatio;
             
             if (height > containerRect.height - 40) {{
@@ -215,7 +572,7 @@ fn create_html_file(output_dir: &Path, output_name: &str) -> Result<(), std::io:
             log(`Canvas resized to ${{width}}x${{height}}`);
             
             // Notify the module about the canvas resize
-            if (typeof Module !== 'undefined' && Module._main) {{
+            if (typeof Module !== 'undefined') {{
                 // Force a redraw
                 try {{
                     if (Module.canvas) {{
@@ -248,8 +605,18 @@ fn create_html_file(output_dir: &Path, output_name: &str) -> Result<(), std::io:
             originalWarn.apply(console, args);
         }};
         
-        // WebAssembly Module configuration
-        var Module = {{
+        // Runtime settings from the project config (wasm_compiler.toml)
+        var runtimeConfig = {runtime_config};
+
+        // WebAssembly Module configuration, passed to the ES6 module factory below.
+        // Once the runtime is ready the instantiated module is exposed as `window.Module`.
+        var moduleConfig = {{
+            arguments: runtimeConfig.arguments,
+            preRun: [function(mod) {{
+                if (mod.ENV) {{
+                    Object.assign(mod.ENV, runtimeConfig.env);
+                }}
+            }}],
             canvas: (function() {{
                 var canvas = document.getElementById('canvas');
                 canvas.addEventListener("webglcontextlost", function(e) {{
@@ -279,11 +646,13 @@ fn create_html_file(output_dir: &Path, output_name: &str) -> Result<(), std::io:
                 const status = left ? 
                     `Preparing... (${{this.totalDependencies-left}}/${{this.totalDependencies}})` : 
                     'All downloads complete.';
-                Module.setStatus(status);
+                moduleConfig.setStatus(status);
             }},
             onRuntimeInitialized: function() {{
+                // Read by `--run-test browser`
+                document.documentElement.dataset.wasmStatus = 'initialized';
                 log('✅ WebAssembly runtime initialized successfully');
-                log('🎮 ImGUI application should now be running');
+                log('🎮 Application should now be running');
                 
                 // Hide loading screen and show canvas
                 const loading = document.getElementById('loading');
@@ -294,18 +663,10 @@ fn create_html_file(output_dir: &Path, output_name: &str) -> Result<(), std::io:
                     canvas.style.display = 'block';
                     resizeCanvas();
                 }}
-                
-                // Try to call main function if it exists
-                try {{
-                    if (typeof Module._main === 'function') {{
-                        log('Calling main function...');
-                        Module._main();
-                    }}
-                }} catch (e) {{
-                    log('Note: main() may be called automatically by Emscripten');
-                }}
             }},
             onAbort: function(what) {{
+                document.documentElement.dataset.wasmStatus = 'aborted';
+                document.documentElement.dataset.wasmAbort = String(what);
                 log('❌ ABORT: ' + what);
                 const loading = document.getElementById('loading');
                 if (loading) {{
@@ -317,7 +678,7 @@ fn create_html_file(output_dir: &Path, output_name: &str) -> Result<(), std::io:
                 if (path.endsWith('.wasm')) {{
                     log('Loading WASM file: ' + path);
                 }}
-                return prefix + path;
+                return (runtimeConfig.locateFilePrefix !== null ? runtimeConfig.locateFilePrefix : prefix) + path;
             }}
         }};
         
@@ -330,7 +691,7 @@ fn create_html_file(output_dir: &Path, output_name: &str) -> Result<(), std::io:
         }});
         
         // Set initial status
-        Module.setStatus('Downloading...');
+        moduleConfig.setStatus('Downloading...');
         
         window.onerror = function(msg, url, lineNo, columnNo, error) {{
             log('❌ JavaScript Error: ' + msg + ' at ' + url + ':' + lineNo + ':' + columnNo);
@@ -338,19 +699,280 @@ fn create_html_file(output_dir: &Path, output_name: &str) -> Result<(), std::io:
         }};
     </script>
     
-    <script async type="text/javascript" src="{}.js"></script>
+    <script type="module">
+        // The output is built with -sMODULARIZE=1, so the glue provides a factory instead
+        // of populating a global `Module`.
+        {module_import}
+
+        createModule(moduleConfig).then(function(instance) {{
+            window.Module = instance;
+        }}).catch(function(err) {{
+            moduleConfig.onAbort(err && err.message ? err.message : String(err));
+        }});
+    </script>
 </body>
-</html>"#, output_name);
+</html>"#);
 
     let html_path = output_dir.join("index.html");
     std::fs::write(&html_path, html_content)?;
     
-    log::debug!("Created HTML file at: {:?}", html_path);
+    tracing::debug!("Created HTML file at: {:?}", html_path);
+    Ok(html_path)
+}
+
+/// Creates a bare, dependency-free HTML file containing only the canvas and the module import
+fn create_minimal_html_file(config: &AppConfig, assets: &AssetNames) -> Result<PathBuf, std::io::Error> {
+    let output_dir = config.output_dir.as_path();
+    let html_content = format!(r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <title>{}</title>
+</head>
+<body>
+    <canvas id="canvas" width="{}" height="{}" oncontextmenu="event.preventDefault()"></canvas>
+    <script type="module">
+        {}
+
+        const runtimeConfig = {};
+        window.Module = await createModule({{
+            canvas: document.getElementById('canvas'),
+            arguments: runtimeConfig.arguments,
+            preRun: [(mod) => mod.ENV && Object.assign(mod.ENV, runtimeConfig.env)],
+            locateFile: (path, prefix) => (runtimeConfig.locateFilePrefix ?? prefix) + path,
+            // The status attributes are read by `--run-test browser`
+            onRuntimeInitialized: () => {{
+                document.documentElement.dataset.wasmStatus = 'initialized';
+            }},
+            onAbort: (what) => {{
+                document.documentElement.dataset.wasmStatus = 'aborted';
+                document.documentElement.dataset.wasmAbort = String(what);
+            }},
+        }});
+    </script>
+</body>
+</html>
+"#, escape_html(config.page_title()), config.canvas_size.width, config.canvas_size.height,
+        module_import(config, &glue_url(config, assets)),
+        config.project_config.runtime.to_js_object());
+
+    let html_path = output_dir.join("index.html");
+    std::fs::write(&html_path, html_content)?;
+
+    tracing::debug!("Created minimal HTML file at: {:?}", html_path);
+    Ok(html_path)
+}
+
+/// The URL of the JS glue a page loads, as a JS expression. With `--wasm2js-fallback`,
+/// browsers without WebAssembly load the asm.js build instead; self-contained pages
+/// (`--single-file`) embed the regular glue only.
+fn glue_url(config: &AppConfig, assets: &AssetNames) -> String {
+    match crate::wasm2js::fallback_name(config, assets) {
+        Some(fallback) if !config.single_file => {
+            format!("typeof WebAssembly === 'object' ? './{}' : './{}'", assets.js, fallback)
+        }
+        _ => format!("'./{}'", assets.js),
+    }
+}
+
+/// The statement that binds `createModule` inside a page's module script, loading the glue
+/// from `url` (a JS expression). ES module glue is imported; UMD glue is loaded as a classic
+/// script, which defines the factory as a global.
+fn module_import(config: &AppConfig, url: &str) -> String {
+    match config.module_format {
+        ModuleFormat::Esm if url.starts_with('\'') => format!("import createModule from {};", url),
+        ModuleFormat::Esm => format!("const {{ default: createModule }} = await import({});", url),
+        ModuleFormat::Cjs | ModuleFormat::Umd => format!(
+            "const createModule = await new Promise((resolve, reject) => {{ \
+             const script = document.createElement('script'); script.src = {}; \
+             script.onload = () => resolve(window['{}']); script.onerror = reject; \
+             document.head.appendChild(script); }});",
+            url,
+            crate::module_format::export_name(config)
+        ),
+    }
+}
+
+/// Replaces the `import` of the JS glue in index.html with an inlined copy, so the page works
+/// without a web server (e.g. opened from disk or sent by email). The glue is base64-encoded
+/// and imported from a blob URL, which avoids having to escape it for an inline script.
+fn embed_module_in_html(config: &AppConfig, assets: &AssetNames) -> Result<(), std::io::Error> {
+    use base64::Engine;
+
+    let output_dir = config.output_dir.as_path();
+    let glue = std::fs::read(output_dir.join(&assets.js))?;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(glue);
+
+    let html_path = output_dir.join("index.html");
+    let html = std::fs::read_to_string(&html_path)?;
+    let import_line = module_import(config, &format!("'./{}'", assets.js));
+    if !html.contains(&import_line) {
+        return Err(std::io::Error::other(format!(
+            "Could not find the module import in {:?} to embed the build output", html_path
+        )));
+    }
+    let inline_import = format!(
+        "const glue = Uint8Array.from(atob('{}'), (c) => c.charCodeAt(0));\n        {}",
+        encoded,
+        module_import(config, "URL.createObjectURL(new Blob([glue], { type: 'text/javascript' }))")
+    );
+    std::fs::write(&html_path, html.replacen(&import_line, &inline_import, 1))?;
+
+    tracing::debug!("Embedded {} into {:?}", assets.js, html_path);
     Ok(())
 }
 
+/// Adds Progressive Web App support: a web app manifest, a placeholder icon, and a service
+/// worker that precaches the build output. The service worker's cache name is derived from the
+/// content hashes of the precached files, so any change to the build invalidates stale caches.
+fn create_pwa_files(config: &AppConfig, assets: &AssetNames) -> Result<Vec<PathBuf>, std::io::Error> {
+    let output_dir = config.output_dir.as_path();
+    let output_name = config.output_name.as_str();
+    let mut manifest = serde_json::json!({
+        "name": config.page_title(),
+        "short_name": output_name,
+        "start_url": "./index.html",
+        "scope": "./",
+        "display": "standalone",
+        "background_color": "#1e1e2e",
+        "theme_color": "#6366f1",
+        "icons": [
+            { "src": "icons/icon.svg", "sizes": "any", "type": "image/svg+xml", "purpose": "any maskable" }
+        ]
+    });
+    if let Some(description) = &config.app_description {
+        manifest["description"] = serde_json::Value::String(description.clone());
+    }
+    let manifest_path = output_dir.join("manifest.webmanifest");
+    std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+
+    let icons_dir = output_dir.join("icons");
+    std::fs::create_dir_all(&icons_dir)?;
+    let icon_path = icons_dir.join("icon.svg");
+    if !icon_path.exists() {
+        // Placeholder only; users are expected to replace it with their own artwork.
+        std::fs::write(&icon_path, r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 512 512">
+    <rect width="512" height="512" rx="96" fill="#6366f1"/>
+    <text x="256" y="300" font-family="sans-serif" font-size="160" font-weight="700" text-anchor="middle" fill="#ffffff">WASM</text>
+</svg>
+"##)?;
+    }
+
+    inject_pwa_tags(&output_dir.join("index.html"))?;
+
+    // Everything the app needs to start offline. Optional Emscripten outputs are only
+    // listed when present so the service worker install step does not fail on a 404.
+    let mut precache = vec![
+        "index.html".to_string(),
+        "manifest.webmanifest".to_string(),
+        "icons/icon.svg".to_string(),
+        assets.js.clone(),
+    ];
+    for optional in [Some(assets.wasm.clone()), Some("style.css".to_string()), assets.data.clone()].into_iter().flatten() {
+        if output_dir.join(&optional).exists() {
+            precache.push(optional);
+        }
+    }
+    // Worker scripts and memory initializers are loaded at startup; debug files are not
+    precache.extend(
+        assets.auxiliary.iter().filter(|name| !name.ends_with(".map") && !name.ends_with("symbols")).cloned(),
+    );
+
+    let mut revisions = Vec::new();
+    for asset in &precache {
+        let asset_path = output_dir.join(asset);
+        let revision = if asset_path.exists() {
+            crate::utils::file_system::hash_file(&asset_path)
+                .map_err(std::io::Error::other)?
+        } else {
+            tracing::warn!("PWA precache asset {:?} does not exist yet", asset_path);
+            String::new()
+        };
+        revisions.push((asset.clone(), revision));
+    }
+    let combined: String = revisions.iter().map(|(asset, rev)| format!("{}:{};", asset, rev)).collect();
+    let cache_version = &crate::utils::file_system::hex_digest(combined.as_bytes())[..16];
+
+    let precache_entries = revisions
+        .iter()
+        .map(|(asset, rev)| format!("    {{ url: './{}', revision: '{}' }}", asset, rev))
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    let sw_content = format!(r#"// Service worker generated by wasm_compiler for: {}
+// The cache name changes whenever any precached file's content hash changes.
+const CACHE_NAME = 'wasm-app-{}';
+const PRECACHE = [
+{}
+];
+
+self.addEventListener('install', (event) => {{
+    event.waitUntil(
+        caches.open(CACHE_NAME)
+            .then((cache) => cache.addAll(PRECACHE.map((entry) => new Request(entry.url, {{ cache: 'reload' }}))))
+            .then(() => self.skipWaiting())
+    );
+}});
+
+self.addEventListener('activate', (event) => {{
+    event.waitUntil(
+        caches.keys()
+            .then((keys) => Promise.all(
+                keys.filter((key) => key.startsWith('wasm-app-') && key !== CACHE_NAME)
+                    .map((key) => caches.delete(key))
+            ))
+            .then(() => self.clients.claim())
+    );
+}});
+
+self.addEventListener('fetch', (event) => {{
+    if (event.request.method !== 'GET') {{
+        return;
+    }}
+    event.respondWith(
+        caches.open(CACHE_NAME).then((cache) =>
+            cache.match(event.request, {{ ignoreSearch: true }}).then((cached) => cached || fetch(event.request))
+        )
+    );
+}});
+"#, output_name, cache_version, precache_entries);
+
+    let sw_path = output_dir.join("sw.js");
+    std::fs::write(&sw_path, sw_content)?;
+
+    tracing::info!("Created PWA manifest and service worker (cache version {})", cache_version);
+    Ok(vec![manifest_path, icon_path, sw_path])
+}
+
+/// Adds the manifest link and service worker registration to an existing index.html
+fn inject_pwa_tags(html_path: &Path) -> Result<(), std::io::Error> {
+    let html = std::fs::read_to_string(html_path)?;
+    if html.contains("manifest.webmanifest") {
+        return Ok(());
+    }
+
+    let html = html.replacen(
+        "</head>",
+        "    <link rel=\"manifest\" href=\"manifest.webmanifest\">\n    <meta name=\"theme-color\" content=\"#6366f1\">\n</head>",
+        1,
+    );
+    let html = html.replacen(
+        "</body>",
+        r#"    <script>
+        if ('serviceWorker' in navigator) {
+            navigator.serviceWorker.register('./sw.js').catch(function(err) {
+                console.warn('Service worker registration failed: ' + err);
+            });
+        }
+    </script>
+</body>"#,
+        1,
+    );
+    std::fs::write(html_path, html)
+}
+
 /// Creates the CSS stylesheet
-fn create_css_file(output_dir: &Path) -> Result<(), std::io::Error> {
+fn create_css_file(output_dir: &Path) -> Result<PathBuf, std::io::Error> {
     let css_content = r#"/* Modern CSS Reset and Base Styles */
 * {
     box-sizing: border-box;
@@ -700,8 +1322,8 @@ canvas:focus {
 
     let css_path = output_dir.join("style.css");
     std::fs::write(&css_path, css_content)?;
-    log::debug!("Created CSS file at: {:?}", css_path);
-    Ok(())
+    tracing::debug!("Created CSS file at: {:?}", css_path);
+    Ok(css_path)
 }
 
 /// Creates the JavaScript module file (DEPRECATED - now embedded in HTML)
@@ -712,7 +1334,7 @@ fn _create_js_module_deprecated(_output_dir: &Path, _output_name: &str) -> Resul
 }
 
 /// Creates a Python server script for serving the webapp
-fn create_python_server(output_dir: &Path, output_name: &str) -> Result<(), std::io::Error> {
+fn create_python_server(output_dir: &Path, output_name: &str, assets: &AssetNames) -> Result<PathBuf, std::io::Error> {
     let python_content = format!(r#"#!/usr/bin/env python3
 """
 Simple HTTP server for serving WebAssembly applications
@@ -748,6 +1370,8 @@ class WAsmHandler(http.server.SimpleHTTPRequestHandler):
             return 'application/javascript'
         elif path.endswith('.json'):
             return 'application/json'
+        elif path.endswith('.webmanifest'):
+            return 'application/manifest+json'
         
         # Use the default implementation for other files
         # The base class returns just the mimetype string
@@ -806,7 +1430,7 @@ def main():
     os.chdir(script_dir)
     
     # Check if required files exist
-    required_files = ['{}.js', '{}.wasm', 'index.html']
+    required_files = ['{}', '{}', 'index.html']
     missing_files = [f for f in required_files if not os.path.exists(f)]
     
     if missing_files:
@@ -850,7 +1474,7 @@ def main():
 
 if __name__ == "__main__":
     main()
-"#, output_name, output_name, output_name, output_name);
+"#, output_name, assets.js, assets.wasm, output_name);
 
     let python_path = output_dir.join("serve.py");
     std::fs::write(&python_path, python_content)?;
@@ -864,20 +1488,20 @@ if __name__ == "__main__":
         std::fs::set_permissions(&python_path, perms)?;
     }
     
-    log::debug!("Created Python server at: {:?}", python_path);
-    Ok(())
+    tracing::debug!("Created Python server at: {:?}", python_path);
+    Ok(python_path)
 }
 
 /// Creates a README file with instructions
-fn create_readme(output_dir: &Path, output_name: &str) -> Result<(), std::io::Error> {
+fn create_readme(output_dir: &Path, output_name: &str, assets: &AssetNames) -> Result<PathBuf, std::io::Error> {
     let readme_content = format!(r#"# {} - WebAssembly Application
 
 This directory contains a complete WebAssembly application compiled from C++ source code.
 
 ## Files
 
-- `{}.js` - Emscripten-generated JavaScript loader
-- `{}.wasm` - Compiled WebAssembly binary
+- `{}` - Emscripten-generated JavaScript loader
+- `{}` - Compiled WebAssembly binary
 - `index.html` - Main HTML page for the web application
 - `style.css` - Stylesheet for the web interface
 - `app.js` - JavaScript module for application logic
@@ -936,7 +1560,7 @@ Make sure your web server:
 
 ### "Failed to fetch dynamically imported module"
 - Ensure you're serving the files through a web server (not opening index.html directly)
-- Check that all files ({}.js, {}.wasm) are in the same directory
+- Check that all files ({}, {}) are in the same directory
 - Verify your web server supports proper MIME types
 
 ### "WebAssembly not supported"
@@ -966,10 +1590,10 @@ Open browser developer tools (F12) to:
 ---
 
 Generated by wasm_compiler
-"#, output_name, output_name, output_name, output_name, output_name);
+"#, output_name, assets.js, assets.wasm, assets.js, assets.wasm);
 
     let readme_path = output_dir.join("README.md");
     std::fs::write(&readme_path, readme_content)?;
-    log::debug!("Created README at: {:?}", readme_path);
-    Ok(())
+    tracing::debug!("Created README at: {:?}", readme_path);
+    Ok(readme_path)
 }